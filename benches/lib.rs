@@ -66,6 +66,30 @@ fn spawn_bench_1(b: &mut Bencher) {
     });
 }
 
+#[bench]
+fn spawn_join_bench(b: &mut Bencher) {
+    b.iter(|| {
+        let wg = mco::std::sync::WaitGroup::new();
+        for _i in 0..1000 {
+            let wg = wg.clone();
+            co!(move || drop(wg));
+        }
+        wg.wait();
+    });
+}
+
+#[bench]
+fn spawn_detached_bench(b: &mut Bencher) {
+    b.iter(|| {
+        let wg = mco::std::sync::WaitGroup::new();
+        for _i in 0..1000 {
+            let wg = wg.clone();
+            mco::co_detached!(move || drop(wg));
+        }
+        wg.wait();
+    });
+}
+
 #[bench]
 fn smoke_bench(b: &mut Bencher) {
     mco::config().set_pool_capacity(10000);
@@ -1,8 +1,9 @@
 #![feature(test)]
 extern crate test;
 
-use mco::std::sync::{Mutex, SyncHashMap};
+use mco::std::sync::{Mutex, ShardedHashMap, SyncHashMap};
 use std::collections::HashMap;
+use std::sync::Arc;
 use test::Bencher;
 
 #[bench]
@@ -46,3 +47,44 @@ fn bench_mutex_hash_map_read(b: &mut Bencher) {
         m.lock().unwrap().get(&0);
     });
 }
+
+// each coroutine writes its own keys, measure the write throughput of the shards
+fn sharded_hash_map_write_co(b: &mut Bencher, coroutines: usize, shards: usize) {
+    const N: usize = 1000;
+    let m = Arc::new(ShardedHashMap::with_shards(shards));
+    b.iter(|| {
+        let handles: Vec<_> = (0..coroutines)
+            .map(|c| {
+                let m = m.clone();
+                mco::co!(move || {
+                    for i in 0..N {
+                        m.insert(c * N + i, i);
+                    }
+                })
+            })
+            .collect();
+        for h in handles {
+            h.join().unwrap();
+        }
+    });
+}
+
+#[bench]
+fn bench_sharded_hash_map_write_8co_1shard(b: &mut Bencher) {
+    sharded_hash_map_write_co(b, 8, 1);
+}
+
+#[bench]
+fn bench_sharded_hash_map_write_8co_16shards(b: &mut Bencher) {
+    sharded_hash_map_write_co(b, 8, 16);
+}
+
+#[bench]
+fn bench_sharded_hash_map_write_16co_1shard(b: &mut Bencher) {
+    sharded_hash_map_write_co(b, 16, 1);
+}
+
+#[bench]
+fn bench_sharded_hash_map_write_16co_16shards(b: &mut Bencher) {
+    sharded_hash_map_write_co(b, 16, 16);
+}
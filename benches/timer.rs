@@ -0,0 +1,35 @@
+#![feature(test)]
+extern crate test;
+
+use mco::std::time::after;
+use std::time::Duration;
+use test::Bencher;
+
+// add a timer while 1M timers are pending
+#[bench]
+fn timer_add_with_1m_pending(b: &mut Bencher) {
+    // expire after the bench is done
+    let pending: Vec<_> = (0..1_000_000)
+        .map(|i| after(Duration::from_millis(60_000 + i * 7 % 60_000)))
+        .collect();
+    let mut i = 0;
+    b.iter(|| {
+        // expire soon, so the number of pending timers stays the same
+        i += 1;
+        after(Duration::from_micros(i % 1000))
+    });
+    drop(pending);
+}
+
+// add 10k timers of different durations and wait for all of them
+#[bench]
+fn timer_expire_10k(b: &mut Bencher) {
+    b.iter(|| {
+        let timers: Vec<_> = (0..10_000)
+            .map(|i| after(Duration::from_micros(i * 7 % 10_000)))
+            .collect();
+        for t in timers {
+            t.recv().unwrap();
+        }
+    });
+}
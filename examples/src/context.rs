@@ -0,0 +1,31 @@
+use mco::std::context::Context;
+use mco::std::time::after;
+use mco::{co, select};
+use std::time::Duration;
+
+fn main() {
+    let ctx = Context::background().with_value("user", "mco".to_string());
+    let (ctx, cancel) = ctx.with_timeout(Duration::from_secs(3));
+
+    for i in 0..3 {
+        let ctx = ctx.clone();
+        co!(move || loop {
+            let id = select! {
+                _ = ctx.done().recv() => {
+                    println!("worker {} stopped: {:?}", i, ctx.err());
+                },
+                _ = after(Duration::from_millis(500)).recv() => {
+                    println!("worker {} working for {:?}", i, ctx.value::<String>("user"));
+                }
+            };
+            if id == 0 {
+                break;
+            }
+        });
+    }
+
+    // the workers are stopped by the timeout, or cancel them earlier
+    mco::coroutine::sleep(Duration::from_secs(2));
+    cancel.cancel();
+    mco::coroutine::sleep(Duration::from_millis(100));
+}
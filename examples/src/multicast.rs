@@ -0,0 +1,47 @@
+#[macro_use]
+extern crate mco;
+
+use std::net::{Ipv4Addr, SocketAddr};
+use std::time::Duration;
+
+use mco::net::UdpSocket;
+
+const GROUP: Ipv4Addr = Ipv4Addr::new(239, 255, 0, 1);
+const PORT: u16 = 30001;
+
+/// receive the multicast datagrams in a coroutine
+/// simple test: echo hello | nc -u 239.255.0.1 30001
+fn main() {
+    let sock = UdpSocket::bind(("0.0.0.0", PORT)).unwrap();
+    sock.join_multicast_v4(&GROUP, &Ipv4Addr::UNSPECIFIED).unwrap();
+    // also receive the announcements sent from this host
+    sock.set_multicast_loop_v4(true).unwrap();
+    println!("joined multicast group {}:{}", GROUP, PORT);
+
+    let receiver = co!(move || {
+        let mut buf = vec![0u8; 1500];
+        loop {
+            let (len, addr) = sock.recv_from(&mut buf).unwrap();
+            println!("{} says: {}", addr, String::from_utf8_lossy(&buf[..len]));
+            if &buf[..len] == b"bye" {
+                break;
+            }
+        }
+        sock.leave_multicast_v4(&GROUP, &Ipv4Addr::UNSPECIFIED).unwrap();
+    });
+
+    let announcer = co!(|| {
+        let sock = UdpSocket::bind("0.0.0.0:0").unwrap();
+        // stay in the local network
+        sock.set_multicast_ttl_v4(1).unwrap();
+        let group = SocketAddr::from((GROUP, PORT));
+        for i in 0..3 {
+            sock.send_to(format!("hello {}", i).as_bytes(), group).unwrap();
+            mco::coroutine::sleep(Duration::from_millis(500));
+        }
+        sock.send_to(b"bye", group).unwrap();
+    });
+
+    announcer.join().unwrap();
+    receiver.join().unwrap();
+}
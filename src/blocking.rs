@@ -0,0 +1,175 @@
+use std::any::Any;
+use std::collections::VecDeque;
+use std::fmt;
+use std::panic;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use crate::config::config;
+use crate::join::Join;
+use crate::scheduler::get_scheduler;
+use crossbeam::atomic::AtomicCell;
+use mco_gen::Error;
+use parking_lot::{Condvar, Mutex};
+
+// idle blocking threads would exit after this duration
+const KEEP_ALIVE: Duration = Duration::from_secs(10);
+
+type Task = Box<dyn FnOnce() + Send>;
+
+struct PoolState {
+    queue: VecDeque<Task>,
+    // number of alive threads
+    threads: usize,
+    // number of threads waiting for tasks
+    idle: usize,
+    // number of idle threads that already signaled
+    notified: usize,
+}
+
+/// elastic thread pool that runs the blocking tasks
+///
+/// threads are created on demand until reaching `Config::get_max_blocking_threads`
+/// and exit after being idle for a while
+pub(crate) struct BlockingPool {
+    state: Mutex<PoolState>,
+    cond: Condvar,
+}
+
+impl BlockingPool {
+    pub fn new() -> Self {
+        BlockingPool {
+            state: Mutex::new(PoolState {
+                queue: VecDeque::new(),
+                threads: 0,
+                idle: 0,
+                notified: 0,
+            }),
+            cond: Condvar::new(),
+        }
+    }
+
+    /// number of alive threads in the pool
+    pub fn threads(&self) -> usize {
+        self.state.lock().threads
+    }
+
+    pub fn execute(&'static self, task: Task) {
+        let mut state = self.state.lock();
+        state.queue.push_back(task);
+        if state.idle > state.notified {
+            state.notified += 1;
+            self.cond.notify_one();
+        } else if state.threads < config().get_max_blocking_threads() {
+            let ret = thread::Builder::new()
+                .name("mco-blocking".to_owned())
+                .spawn(move || self.run());
+            match ret {
+                Ok(_) => state.threads += 1,
+                Err(e) => {
+                    error!("failed to spawn blocking thread, err={}", e);
+                    // the task would be picked up by one of the running threads,
+                    // run it on the calling thread when there is none
+                    if state.threads == 0 {
+                        let tasks: Vec<_> = state.queue.drain(..).collect();
+                        drop(state);
+                        tasks.into_iter().for_each(|task| task());
+                    }
+                }
+            }
+        }
+    }
+
+    fn run(&self) {
+        let mut state = self.state.lock();
+        loop {
+            if let Some(task) = state.queue.pop_front() {
+                drop(state);
+                task();
+                state = self.state.lock();
+                continue;
+            }
+
+            state.idle += 1;
+            let timeout = self.cond.wait_for(&mut state, KEEP_ALIVE).timed_out();
+            state.idle -= 1;
+            if state.notified > 0 {
+                state.notified -= 1;
+            } else if timeout && state.queue.is_empty() {
+                state.threads -= 1;
+                return;
+            }
+        }
+    }
+}
+
+/// A join handle to a blocking task
+pub struct BlockingJoinHandle<T> {
+    join: Arc<Join>,
+    packet: Arc<AtomicCell<Option<T>>>,
+    panic: Arc<AtomicCell<Option<Box<dyn Any + Send>>>>,
+}
+
+impl<T> BlockingJoinHandle<T> {
+    /// return true if the blocking task is finished
+    pub fn is_done(&self) -> bool {
+        self.join.is_done()
+    }
+
+    /// block until the blocking task is done
+    ///
+    /// in coroutine context only the current coroutine is suspended
+    pub fn wait(&self) {
+        self.join.wait();
+    }
+
+    /// Join the blocking task, returning the result it produced.
+    pub fn join(self) -> thread::Result<T> {
+        self.join.wait();
+
+        // take the result
+        self.packet
+            .take()
+            .ok_or_else(|| self.panic.take().unwrap_or_else(|| Box::new(Error::Cancel)))
+    }
+}
+
+impl<T> fmt::Debug for BlockingJoinHandle<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.pad("BlockingJoinHandle { .. }")
+    }
+}
+
+/// run the blocking function `f` on the blocking thread pool
+///
+/// the worker threads are not blocked by `f`, wait the result with the
+/// returned join handle which only suspend the calling coroutine
+pub fn spawn_blocking<F, T>(f: F) -> BlockingJoinHandle<T>
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    let panic = Arc::new(AtomicCell::new(None));
+    let join = Arc::new(Join::new(panic.clone()));
+    let packet = Arc::new(AtomicCell::new(None));
+
+    let their_join = join.clone();
+    let their_packet = packet.clone();
+    let task = Box::new(move || {
+        match panic::catch_unwind(panic::AssertUnwindSafe(f)) {
+            Ok(v) => {
+                their_packet.swap(Some(v));
+            }
+            Err(e) => their_join.set_panic_data(e),
+        }
+        their_join.trigger();
+    });
+    get_scheduler().blocking_pool().execute(task);
+
+    BlockingJoinHandle {
+        join,
+        packet,
+        panic,
+    }
+}
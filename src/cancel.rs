@@ -1,6 +1,7 @@
+use std::fmt;
 use std::io;
-use std::sync::atomic::{AtomicUsize, Ordering};
-use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Weak};
 use std::thread;
 
 use crate::coroutine_impl::CoroutineImpl;
@@ -9,6 +10,7 @@ use crate::scheduler::get_scheduler;
 use crate::std::sync::AtomicOption;
 use crate::yield_now::{get_co_para, set_co_para};
 use mco_gen::Error;
+use parking_lot::Mutex;
 
 // the cancel is implemented by triggering a Cancel panic
 // if drop is called due to a Cancel panic, it's not safe
@@ -34,6 +36,10 @@ pub trait CancelIo {
     fn set(&self, _: Self::Data);
     fn clear(&self);
     fn cancel(&self) -> Result<(), std::io::Error>;
+    /// wake up the io with a `Canceled` error, used by the cancellation token
+    fn interrupt(&self) -> Result<(), std::io::Error> {
+        self.cancel()
+    }
 }
 
 // each coroutine has it's own Cancel data
@@ -47,6 +53,10 @@ pub struct CancelImpl<T: CancelIo> {
     // can't set io and co at the same time!
     // most of the time this is park based API
     co: AtomicOption<Arc<AtomicOption<CoroutineImpl>>>,
+    // set when the cancellation token of the coroutine is canceled
+    interrupted: AtomicBool,
+    // how many nested blocking ops that observe the cancellation token
+    interruptible: AtomicUsize,
 }
 
 impl<T: CancelIo> Default for CancelImpl<T> {
@@ -62,6 +72,8 @@ impl<T: CancelIo> CancelImpl<T> {
             state: AtomicUsize::new(0),
             io: T::new(),
             co: AtomicOption::none(),
+            interrupted: AtomicBool::new(false),
+            interruptible: AtomicUsize::new(0),
         }
     }
 
@@ -105,7 +117,7 @@ impl<T: CancelIo> CancelImpl<T> {
                 co.take()
                     .map(|mut co| {
                         // set the cancel result for the coroutine
-                        set_co_para(&mut co, io::Error::new(io::ErrorKind::Other, "Canceled"));
+                        set_co_para(&mut co, io::Error::other("Canceled"));
                         get_scheduler().schedule(co);
                     })
                     .unwrap_or(());
@@ -115,6 +127,43 @@ impl<T: CancelIo> CancelImpl<T> {
         }
     }
 
+    // return true if the cancellation token is canceled and
+    // the coroutine is in a blocking op that observes the token
+    pub fn is_interrupted(&self) -> bool {
+        self.interruptible.load(Ordering::SeqCst) > 0 && self.interrupted.load(Ordering::SeqCst)
+    }
+
+    // cooperative cancel, unlike `cancel` no panic is triggered
+    // the blocking op that observes the token returns early with a `Canceled` error
+    pub fn interrupt(&self) {
+        self.interrupted.store(true, Ordering::SeqCst);
+        if self.interruptible.load(Ordering::SeqCst) == 0 {
+            return;
+        }
+        match self.co.take() {
+            Some(co) => {
+                if let Some(mut co) = co.take() {
+                    set_co_para(&mut co, io::Error::other("Canceled"));
+                    get_scheduler().schedule(co);
+                }
+            }
+            None => {
+                let _ = self.io.interrupt();
+            }
+        }
+    }
+
+    // forget the cancellation of the previous context of the coroutine
+    pub fn clear_interrupt(&self) {
+        self.interrupted.store(false, Ordering::SeqCst);
+    }
+
+    // mark the following blocking op observes the cancellation token
+    pub fn interruptible(&self) -> InterruptGuard<'_, T> {
+        self.interruptible.fetch_add(1, Ordering::SeqCst);
+        InterruptGuard(self)
+    }
+
     // clear the cancel bit so that we can reuse the cancel
     #[cfg(unix)]
     pub fn clear_cancel_bit(&self) {
@@ -142,4 +191,229 @@ impl<T: CancelIo> CancelImpl<T> {
     }
 }
 
+pub struct InterruptGuard<'a, T: CancelIo>(&'a CancelImpl<T>);
+
+impl<'a, T: CancelIo> Drop for InterruptGuard<'a, T> {
+    fn drop(&mut self) {
+        self.0.interruptible.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
 pub type Cancel = CancelImpl<CancelIoImpl>;
+
+// guard of a blocking op of the current coroutine that observes the cancellation token
+pub type CancelGuard = InterruptGuard<'static, CancelIoImpl>;
+
+type CancelCallback = Box<dyn FnOnce() + Send>;
+
+// an object that is notified when the token is canceled, held weakly
+pub(crate) trait CancelTarget: Send + Sync {
+    fn on_cancel(&self);
+}
+
+#[derive(Default)]
+struct Callbacks {
+    // keyed by the id of the registration
+    fns: Vec<(u64, CancelCallback)>,
+    next_id: u64,
+    // the dropped targets are pruned when the list grows
+    targets: Vec<Weak<dyn CancelTarget>>,
+}
+
+struct TokenInner {
+    canceled: AtomicBool,
+    callbacks: Mutex<Callbacks>,
+    // the registration on the parent token, removed when the child is
+    // dropped or detached
+    parent: Mutex<Option<CancelRegistration>>,
+}
+
+// the handle of a callback registered by `on_cancel`,
+// the callback is removed from the token when it's dropped
+#[must_use]
+pub(crate) struct CancelRegistration {
+    token: Weak<TokenInner>,
+    id: u64,
+}
+
+impl Drop for CancelRegistration {
+    fn drop(&mut self) {
+        let inner = match self.token.upgrade() {
+            Some(inner) => inner,
+            None => return,
+        };
+        let f = {
+            let mut callbacks = inner.callbacks.lock();
+            let idx = callbacks.fns.iter().position(|(id, _)| *id == self.id);
+            idx.map(|i| callbacks.fns.remove(i))
+        };
+        // the callback may drop other tokens, so drop it without the lock
+        drop(f);
+    }
+}
+
+/// A token for cooperative cancellation
+///
+/// unlike `Coroutine::cancel` that panics the coroutine, canceling a token
+/// just sets a flag which can be checked in loops. the blocking ops that
+/// observe the token of the current coroutine, `sleep`, channel `recv`,
+/// socket reads and tcp connects, return early with a `Canceled` error so that the coroutine
+/// can unwind cleanly.
+///
+/// each coroutine has a token, get it by `coroutine::current().cancel_token()`
+#[derive(Clone)]
+pub struct CancellationToken {
+    inner: Arc<TokenInner>,
+}
+
+impl Default for CancellationToken {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CancellationToken {
+    /// create a new token that is not canceled
+    pub fn new() -> Self {
+        CancellationToken {
+            inner: Arc::new(TokenInner {
+                canceled: AtomicBool::new(false),
+                callbacks: Mutex::new(Callbacks::default()),
+                parent: Mutex::new(None),
+            }),
+        }
+    }
+
+    /// cancel the token, the child tokens are canceled as well
+    pub fn cancel(&self) {
+        let callbacks = {
+            let mut callbacks = self.inner.callbacks.lock();
+            if self.inner.canceled.swap(true, Ordering::SeqCst) {
+                return;
+            }
+            std::mem::take(&mut *callbacks)
+        };
+        for (_, f) in callbacks.fns {
+            f();
+        }
+        for t in callbacks.targets.iter().filter_map(Weak::upgrade) {
+            t.on_cancel();
+        }
+    }
+
+    /// return true if the token is canceled
+    pub fn is_canceled(&self) -> bool {
+        self.inner.canceled.load(Ordering::SeqCst)
+    }
+
+    /// return a `Canceled` error if the token is canceled
+    pub fn check(&self) -> io::Result<()> {
+        if self.is_canceled() {
+            return Err(io::Error::other("Canceled"));
+        }
+        Ok(())
+    }
+
+    /// create a token that is canceled when this token is canceled
+    ///
+    /// the child is removed from this token once all its clones are dropped
+    pub fn child_token(&self) -> CancellationToken {
+        let child = CancellationToken::new();
+        let weak = Arc::downgrade(&child.inner);
+        let registration = self.on_cancel(move || {
+            if let Some(inner) = weak.upgrade() {
+                CancellationToken { inner }.cancel();
+            }
+        });
+        *child.inner.parent.lock() = Some(registration);
+        child
+    }
+
+    // remove the token from its parent, it's not canceled with the parent anymore
+    pub(crate) fn detach(&self) {
+        let registration = self.inner.parent.lock().take();
+        // the registration locks the parent, drop it without our lock
+        drop(registration);
+    }
+
+    // register a function that is called when the token is canceled
+    // the function is called immediately if the token is already canceled
+    // the function is removed when the returned registration is dropped
+    pub(crate) fn on_cancel<F: FnOnce() + Send + 'static>(&self, f: F) -> CancelRegistration {
+        {
+            let mut callbacks = self.inner.callbacks.lock();
+            if !self.inner.canceled.load(Ordering::SeqCst) {
+                let id = callbacks.next_id;
+                callbacks.next_id += 1;
+                callbacks.fns.push((id, Box::new(f)));
+                return CancelRegistration {
+                    token: Arc::downgrade(&self.inner),
+                    id,
+                };
+            }
+        }
+        f();
+        CancelRegistration {
+            token: Weak::new(),
+            id: 0,
+        }
+    }
+
+    // same as `on_cancel` but the target is not kept alive by the token,
+    // so a long lived token doesn't pile up the targets
+    pub(crate) fn add_target(&self, target: Weak<dyn CancelTarget>) {
+        {
+            let mut callbacks = self.inner.callbacks.lock();
+            if !self.inner.canceled.load(Ordering::SeqCst) {
+                let targets = &mut callbacks.targets;
+                if targets.len() == targets.capacity() {
+                    targets.retain(|t| t.strong_count() > 0);
+                }
+                targets.push(target);
+                return;
+            }
+        }
+        if let Some(t) = target.upgrade() {
+            t.on_cancel();
+        }
+    }
+}
+
+impl fmt::Debug for CancellationToken {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CancellationToken")
+            .field("canceled", &self.is_canceled())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn registered(token: &CancellationToken) -> usize {
+        token.inner.callbacks.lock().fns.len()
+    }
+
+    #[test]
+    fn dropped_children_are_removed() {
+        let parent = CancellationToken::new();
+        let child = parent.child_token();
+        let grandchild = child.child_token();
+        let other = parent.child_token();
+        assert_eq!(registered(&parent), 2);
+        drop(other);
+        assert_eq!(registered(&parent), 1);
+
+        let r = parent.on_cancel(|| {});
+        assert_eq!(registered(&parent), 2);
+        drop(r);
+        assert_eq!(registered(&parent), 1);
+
+        parent.cancel();
+        assert!(grandchild.is_canceled());
+        drop(child);
+        drop(grandchild);
+        assert_eq!(registered(&parent), 0);
+    }
+}
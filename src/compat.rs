@@ -0,0 +1,120 @@
+//! Bridges to the async ecosystem
+//!
+//! the async-only client libraries can be called from the coroutines with
+//! `block_on`, without a tokio thread per call. the futures that need the
+//! reactor of a specific async runtime still need that runtime running.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll, Wake, Waker};
+
+use crate::std::sync::Blocker;
+
+// wake up the coroutine or the thread that polls the future
+struct BlockerWaker(Blocker);
+
+impl Wake for BlockerWaker {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        let _ = self.0.unpark();
+    }
+}
+
+/// run the future to completion and return its output
+///
+/// in coroutine context only the current coroutine is parked while the
+/// future is pending, the waker schedules it to poll the future again, so
+/// the worker thread keeps running the other coroutines. in thread context
+/// the thread is blocked. the future is polled in the calling coroutine,
+/// so it doesn't need to be `Send`
+/// for example:
+/// ```
+///     let h = mco::co!(|| {
+///         mco::compat::block_on(async { 1 + 1 })
+///     });
+///     assert_eq!(h.join().unwrap(), 2);
+/// ```
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future = future;
+    // the future is not moved anymore
+    let mut future = unsafe { Pin::new_unchecked(&mut future) };
+    let blocker = Arc::new(BlockerWaker(Blocker::new(false)));
+    let waker = Waker::from(blocker.clone());
+    let mut cx = Context::from_waker(&waker);
+    loop {
+        if let Poll::Ready(v) = future.as_mut().poll(&mut cx) {
+            return v;
+        }
+        // a wake before the park makes it return immediately
+        let _ = blocker.0.park(None);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::block_on;
+    use parking_lot::Mutex;
+    use std::future::Future;
+    use std::pin::Pin;
+    use std::sync::Arc;
+    use std::task::{Context, Poll, Waker};
+    use std::thread;
+    use std::time::Duration;
+
+    // a value that is set by another thread
+    #[derive(Default)]
+    struct Shared {
+        value: Option<u32>,
+        waker: Option<Waker>,
+    }
+
+    struct Delayed(Arc<Mutex<Shared>>);
+
+    impl Delayed {
+        fn new(v: u32) -> Self {
+            let shared = Arc::new(Mutex::new(Shared::default()));
+            let s = shared.clone();
+            thread::spawn(move || {
+                thread::sleep(Duration::from_millis(20));
+                let mut s = s.lock();
+                s.value = Some(v);
+                if let Some(w) = s.waker.take() {
+                    w.wake();
+                }
+            });
+            Delayed(shared)
+        }
+    }
+
+    impl Future for Delayed {
+        type Output = u32;
+
+        fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<u32> {
+            let mut s = self.0.lock();
+            match s.value.take() {
+                Some(v) => Poll::Ready(v),
+                None => {
+                    s.waker = Some(cx.waker().clone());
+                    Poll::Pending
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn block_on_thread() {
+        assert_eq!(block_on(async { 1 }), 1);
+        let v = block_on(async { Delayed::new(1).await + Delayed::new(2).await });
+        assert_eq!(v, 3);
+    }
+
+    #[test]
+    fn block_on_coroutine() {
+        let h = co!(|| block_on(async { Delayed::new(1).await + Delayed::new(2).await }));
+        assert_eq!(h.join().unwrap(), 3);
+    }
+}
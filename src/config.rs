@@ -1,14 +1,41 @@
 //! `mco` Configuration interface
 //!
 
-use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::hooks::SchedulerHooks;
+use crate::schedule_policy::{DefaultPolicy, SchedulePolicy};
+
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
 
 // default stack size, in usize
 // windows has a minimal size as 0x4a8!!!!
 pub const DEFAULT_STACK_SIZE: usize = 6 * 1024 * 1024;
 
+// default max threads of the blocking pool
+pub const DEFAULT_MAX_BLOCKING_THREADS: usize = 512;
+
+// default refresh interval of the coarse clock, in us
+pub const DEFAULT_COARSE_CLOCK_RESOLUTION: usize = 1000;
+
 static WORKERS: AtomicUsize = AtomicUsize::new(0);
 static STACK_SIZE: AtomicUsize = AtomicUsize::new(DEFAULT_STACK_SIZE);
+static MAX_WORKERS: AtomicUsize = AtomicUsize::new(0);
+static MAX_BLOCKING_THREADS: AtomicUsize = AtomicUsize::new(DEFAULT_MAX_BLOCKING_THREADS);
+static WORKER_AFFINITY: Lazy<Mutex<Vec<usize>>> = Lazy::new(|| Mutex::new(Vec::new()));
+static TIMER_AFFINITY: AtomicUsize = AtomicUsize::new(usize::MAX);
+static SCHEDULER_HOOKS: Lazy<Mutex<Option<Arc<dyn SchedulerHooks>>>> = Lazy::new(|| Mutex::new(None));
+static WORKER_BLOCK_TIMEOUT: AtomicUsize = AtomicUsize::new(0);
+static MAX_PENDING_COROUTINES: AtomicUsize = AtomicUsize::new(0);
+static STACK_STATS: AtomicBool = AtomicBool::new(false);
+static COROUTINE_BACKTRACE: AtomicBool = AtomicBool::new(false);
+static YIELD_BUDGET: AtomicUsize = AtomicUsize::new(0);
+static COARSE_CLOCK_RESOLUTION: AtomicUsize = AtomicUsize::new(DEFAULT_COARSE_CLOCK_RESOLUTION);
+static SCHEDULE_POLICY: Lazy<Mutex<Arc<dyn SchedulePolicy>>> =
+    Lazy::new(|| Mutex::new(Arc::new(DefaultPolicy)));
 
 /// `mco` Configuration type
 pub struct Config;
@@ -44,6 +71,143 @@ impl Config {
         }
     }
 
+    /// set the max worker thread number that the scheduler can grow to
+    /// at runtime with `Scheduler::add_workers`
+    ///
+    /// if you pass 0 to it, will use 4 times of the normal workers number
+    pub fn set_max_workers(&self, workers: usize) -> &Self {
+        info!("set max workers={:?}", workers);
+        MAX_WORKERS.store(workers, Ordering::Relaxed);
+        self
+    }
+
+    /// get the max workers number, it's never less than the normal workers number
+    pub fn get_max_workers(&self) -> usize {
+        let workers = self.get_workers();
+        let max = MAX_WORKERS.load(Ordering::Relaxed);
+        if max != 0 {
+            std::cmp::max(max, workers)
+        } else {
+            workers * 4
+        }
+    }
+
+    /// set the max thread number of the blocking pool used by `spawn_blocking`
+    ///
+    /// if you pass 0 to it, will use internal default
+    pub fn set_max_blocking_threads(&self, threads: usize) -> &Self {
+        info!("set max blocking threads={:?}", threads);
+        let threads = if threads == 0 {
+            DEFAULT_MAX_BLOCKING_THREADS
+        } else {
+            threads
+        };
+        MAX_BLOCKING_THREADS.store(threads, Ordering::Relaxed);
+        self
+    }
+
+    /// get the max thread number of the blocking pool
+    pub fn get_max_blocking_threads(&self) -> usize {
+        MAX_BLOCKING_THREADS.load(Ordering::Relaxed)
+    }
+
+    /// set the max number of coroutines that are waiting in the ready queues
+    ///
+    /// when the bound is exceeded `Builder::try_spawn` returns `SpawnError::QueueFull`
+    /// and `spawn` blocks the spawner until the queues drain.
+    /// if you pass 0 to it, the queues are unbounded
+    pub fn set_max_pending_coroutines(&self, n: usize) -> &Self {
+        info!("set max pending coroutines={:?}", n);
+        MAX_PENDING_COROUTINES.store(n, Ordering::Relaxed);
+        self
+    }
+
+    /// get the max number of pending coroutines, 0 means unbounded
+    pub fn get_max_pending_coroutines(&self) -> usize {
+        MAX_PENDING_COROUTINES.load(Ordering::Relaxed)
+    }
+
+    /// set the cpu cores that the worker threads are pinned to
+    ///
+    /// the worker with id `i` is pinned to `cores[i % cores.len()]`,
+    /// pass an empty vec to disable the pinning. only works on linux,
+    /// a core that can't be set is logged and the worker is not pinned
+    pub fn set_worker_affinity(&self, cores: Vec<usize>) -> &Self {
+        info!("set worker affinity={:?}", cores);
+        *WORKER_AFFINITY.lock() = cores;
+        self
+    }
+
+    /// get the cpu core that the worker with `id` would be pinned to
+    pub fn get_worker_affinity(&self, id: usize) -> Option<usize> {
+        let cores = WORKER_AFFINITY.lock();
+        if cores.is_empty() {
+            None
+        } else {
+            Some(cores[id % cores.len()])
+        }
+    }
+
+    /// set the cpu core that the timer thread is pinned to, only works on linux
+    pub fn set_timer_affinity(&self, core: usize) -> &Self {
+        info!("set timer affinity={:?}", core);
+        TIMER_AFFINITY.store(core, Ordering::Relaxed);
+        self
+    }
+
+    /// get the cpu core that the timer thread would be pinned to
+    pub fn get_timer_affinity(&self) -> Option<usize> {
+        match TIMER_AFFINITY.load(Ordering::Relaxed) {
+            usize::MAX => None,
+            core => Some(core),
+        }
+    }
+
+    /// enable the blocked worker watchdog
+    ///
+    /// a worker that has not picked up a new coroutine within `timeout` while
+    /// its queue is not empty is considered blocked. the watchdog reports the
+    /// running coroutine, moves the queued coroutines to other workers and
+    /// adds a replacement worker, which is retired once the blocked worker
+    /// returns. pass `Duration::ZERO` to disable it
+    pub fn set_worker_block_timeout(&self, timeout: Duration) -> &Self {
+        info!("set worker block timeout={:?}", timeout);
+        WORKER_BLOCK_TIMEOUT.store(timeout.as_millis() as usize, Ordering::Relaxed);
+        self
+    }
+
+    /// get the blocked worker timeout, `None` if the watchdog is disabled
+    pub fn get_worker_block_timeout(&self) -> Option<Duration> {
+        match WORKER_BLOCK_TIMEOUT.load(Ordering::Relaxed) {
+            0 => None,
+            ms => Some(Duration::from_millis(ms as u64)),
+        }
+    }
+
+    /// set the scheduling policy, see `SchedulePolicy`
+    pub fn set_schedule_policy<P: SchedulePolicy>(&self, policy: P) -> &Self {
+        info!("set schedule policy={:?}", std::any::type_name::<P>());
+        *SCHEDULE_POLICY.lock() = Arc::new(policy);
+        self
+    }
+
+    /// get the scheduling policy
+    pub fn get_schedule_policy(&self) -> Arc<dyn SchedulePolicy> {
+        SCHEDULE_POLICY.lock().clone()
+    }
+
+    /// install the scheduler tracing hooks, see `SchedulerHooks`
+    pub fn set_scheduler_hooks<H: SchedulerHooks>(&self, hooks: H) -> &Self {
+        info!("set scheduler hooks={:?}", std::any::type_name::<H>());
+        *SCHEDULER_HOOKS.lock() = Some(Arc::new(hooks));
+        self
+    }
+
+    /// get the scheduler tracing hooks
+    pub fn get_scheduler_hooks(&self) -> Option<Arc<dyn SchedulerHooks>> {
+        SCHEDULER_HOOKS.lock().clone()
+    }
+
     /// set default coroutine stack size in usize
     ///
     /// if you pass 0 to it, will use internal default
@@ -57,4 +221,72 @@ impl Config {
     pub fn get_stack_size(&self) -> usize {
         STACK_SIZE.load(Ordering::Acquire)
     }
+
+    /// enable the stack usage statistics, see `Coroutine::stack_usage`
+    ///
+    /// the coroutines spawned after this run on their own stacks which are
+    /// painted at spawn and scanned for the used size on demand, this makes
+    /// the spawn slower and uses more memory, better used with a small stack size
+    pub fn set_stack_stats(&self, enable: bool) -> &Self {
+        info!("set stack stats={:?}", enable);
+        STACK_STATS.store(enable, Ordering::Relaxed);
+        self
+    }
+
+    /// return true if the stack usage statistics is enabled
+    pub fn get_stack_stats(&self) -> bool {
+        STACK_STATS.load(Ordering::Relaxed)
+    }
+
+    /// capture the backtrace of the coroutines at each suspension, only on nightly
+    ///
+    /// the backtraces are printed by `coroutine::dump_all` and the blocked worker
+    /// watchdog. capturing a backtrace is expensive, so it's disabled by default
+    pub fn set_coroutine_backtrace(&self, enable: bool) -> &Self {
+        info!("set coroutine backtrace={:?}", enable);
+        COROUTINE_BACKTRACE.store(enable, Ordering::Relaxed);
+        self
+    }
+
+    /// return true if the backtrace of the coroutines is captured
+    pub fn get_coroutine_backtrace(&self) -> bool {
+        COROUTINE_BACKTRACE.load(Ordering::Relaxed)
+    }
+
+    /// set the yield budget of the coroutines
+    ///
+    /// a coroutine is forced to yield after running `budget` channel, lock or
+    /// io operations without being suspended, so a busy coroutine can't starve
+    /// the others on the same worker. if you pass 0 to it, the budget is disabled
+    pub fn set_yield_budget(&self, budget: usize) -> &Self {
+        info!("set yield budget={:?}", budget);
+        YIELD_BUDGET.store(budget, Ordering::Relaxed);
+        self
+    }
+
+    /// get the yield budget of the coroutines
+    pub fn get_yield_budget(&self) -> usize {
+        YIELD_BUDGET.load(Ordering::Relaxed)
+    }
+
+    /// set how often the timer thread refreshes the coarse clock
+    ///
+    /// the coarse clock is used by the timeout bookkeeping of the scheduler
+    /// and the io, so the timeouts may expire later for the resolution at most.
+    /// a smaller value wakes up the timer thread more often, default is 1ms.
+    /// if you pass 0 to it, will use internal default
+    pub fn set_coarse_clock_resolution(&self, resolution: Duration) -> &Self {
+        info!("set coarse clock resolution={:?}", resolution);
+        let us = match resolution.as_micros() as usize {
+            0 => DEFAULT_COARSE_CLOCK_RESOLUTION,
+            us => us,
+        };
+        COARSE_CLOCK_RESOLUTION.store(us, Ordering::Relaxed);
+        self
+    }
+
+    /// get the refresh interval of the coarse clock
+    pub fn get_coarse_clock_resolution(&self) -> Duration {
+        Duration::from_micros(COARSE_CLOCK_RESOLUTION.load(Ordering::Relaxed) as u64)
+    }
 }
@@ -1,13 +1,17 @@
 // re-export coroutine interface
-pub use crate::cancel::trigger_cancel_panic;
+pub use crate::blocking::{spawn_blocking, BlockingJoinHandle};
+pub use crate::cancel::{trigger_cancel_panic, CancellationToken};
 pub use crate::coroutine_impl::{
-    current, is_coroutine, park, park_timeout, spawn, try_current, Builder, Coroutine,
+    current, is_coroutine, park, park_timeout, set_panic_handler, spawn, spawn_detached,
+    spawn_pinned, spawn_with_ctx, take_panic_handler, try_current, Builder, Coroutine, PanicHandler,
+    Priority, SpawnError,
 };
 pub use crate::join::JoinHandle;
 pub use crate::park::ParkError;
-pub use crate::scoped::scope;
-pub use crate::sleep::sleep;
-pub use crate::yield_now::yield_now;
+pub use crate::registry::dump_all;
+pub use crate::scoped::{scope, scope_cancel, CancelScope};
+pub use crate::sleep::{sleep, sleep_until};
+pub use crate::yield_now::{consume_budget, yield_now};
 
 pub trait Spawn {
     /// spawn a new coroutine
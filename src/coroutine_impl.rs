@@ -1,23 +1,74 @@
+use std::any::Any;
 use std::cell::UnsafeCell;
+use std::error;
 use std::fmt;
 use std::io;
 use std::ops::{Deref, DerefMut};
-use std::sync::Arc;
+use std::panic::Location;
+use std::sync::atomic::{AtomicU64, AtomicU8, AtomicUsize, Ordering};
+use std::sync::{Arc, Weak};
 use std::thread::ThreadId;
 use std::time::Duration;
 
-use crate::cancel::Cancel;
+use crate::cancel::{Cancel, CancelRegistration, CancelTarget, CancellationToken};
 use crate::config::{config};
 use crate::err;
 use crate::join::{make_join_handle, Join, JoinHandle};
 use crate::local::get_co_local_data;
-use crate::local::CoroutineLocal;
+use crate::local::{current_context, CoroutineLocal};
 use crate::park::Park;
-use crate::scheduler::get_scheduler;
+use crate::registry::{self, State};
+use crate::scheduler::{current_io_worker, current_worker, get_scheduler};
+use crate::std::context::Context;
 use crossbeam::atomic::AtomicCell;
 use once_cell::sync::Lazy;
 use mco_gen::{Generator, Gn, Stack};
 
+/// the panic handler installed by `set_panic_handler`
+pub type PanicHandler = dyn Fn(&Coroutine, &(dyn Any + Send)) + Send + Sync;
+
+static PANIC_HANDLER: Lazy<parking_lot::RwLock<Option<Arc<PanicHandler>>>> =
+    Lazy::new(|| parking_lot::RwLock::new(None));
+
+/// install a handler that is called when a coroutine panics
+///
+/// the handler receives the coroutine handle and the panic payload, it runs
+/// on the worker thread after the coroutine is unwound and before the panic
+/// is delivered to the `join`. it can be used to log or restart coroutines.
+/// the default panic message is no longer printed for the coroutines once
+/// a handler is installed. the cancel panic is never passed to the handler
+pub fn set_panic_handler<F>(f: F)
+where
+    F: Fn(&Coroutine, &(dyn Any + Send)) + Send + Sync + 'static,
+{
+    *PANIC_HANDLER.write() = Some(Arc::new(f));
+}
+
+/// remove the panic handler installed by `set_panic_handler`
+pub fn take_panic_handler() -> Option<Arc<PanicHandler>> {
+    PANIC_HANDLER.write().take()
+}
+
+// return true if there is a panic handler installed
+pub(crate) fn has_panic_handler() -> bool {
+    PANIC_HANDLER.read().is_some()
+}
+
+// call the panic handler and apply the panic policy of the coroutine
+fn handle_panic(co: &Coroutine, panic: &(dyn Any + Send)) {
+    if let Some(&mco_gen::Error::Cancel) = panic.downcast_ref::<mco_gen::Error>() {
+        return;
+    }
+    let handler = PANIC_HANDLER.read().clone();
+    if let Some(handler) = handler {
+        handler(co, panic);
+    }
+    if co.inner.abort_on_panic {
+        error!("coroutine {:?} panicked, abort the process", co.name());
+        ::std::process::abort();
+    }
+}
+
 /// /////////////////////////////////////////////////////////////////////////////
 /// Coroutine framework types
 /// /////////////////////////////////////////////////////////////////////////////
@@ -41,11 +92,20 @@ impl EventSubscriber {
         let resource = unsafe { &mut *self.resource };
         resource.subscribe(c);
     }
+
+    // the state of the coroutine that waits on the event
+    pub(crate) fn state(&self) -> State {
+        unsafe { &*self.resource }.state()
+    }
 }
 
 pub trait EventSource {
     /// kernel handler of the event
     fn subscribe(&mut self, _c: CoroutineImpl);
+    /// the state of the coroutine that waits on the event, used by the dump
+    fn state(&self) -> State {
+        State::BlockedOnIo
+    }
     /// after yield back process
     fn yield_back(&self, cancel: &'static Cancel) {
         // after return back we should re-check the panic and clear it
@@ -66,7 +126,10 @@ impl Done {
         // destroy the local storage
         let local = unsafe { Box::from_raw(get_co_local(&co)) };
         let name = local.get_co().name();
+        registry::unregister(local.get_co().id());
 
+        // the stack is freed with the coroutine
+        local.get_co().release_stack();
         // recycle the coroutine
         let (size, used) = co.stack_usage();
         if used == size {
@@ -89,6 +152,23 @@ impl EventSource for Done {
     }
 }
 
+// the subscriber that a coroutine returns when finished
+fn done_subscriber() -> EventSubscriber {
+    static DONE: Done = Done {};
+    EventSubscriber {
+        resource: &DONE as &dyn EventSource as *const _ as *mut dyn EventSource,
+    }
+}
+
+/// the scheduling priority of a coroutine
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Priority {
+    Low,
+    #[default]
+    Normal,
+    High,
+}
+
 /// coroutines are static generator
 /// the para type is EventResult, the result type is EventSubscriber
 #[derive(Debug)]
@@ -96,11 +176,16 @@ pub struct CoroutineImpl {
     pub worker_thread_id: Option<ThreadId>,
     pub inner: Generator<'static, EventResult, EventSubscriber>,
     pub reduce: Option<Vec<u8>>,
+    pub priority: Priority,
+    // always run on the worker of `worker_thread_id`
+    pub pinned: bool,
+    // runs on its own stack instead of the stack of the worker
+    pub own_stack: bool,
 }
 
 impl CoroutineImpl {
     pub fn stack_reduce(&mut self) {
-        if self.reduce.is_none() {
+        if self.reduce.is_none() && !self.own_stack {
             let reduce_data = unsafe { &*self.gen.stack.get() }.stack_reduce(crate::config().get_stack_size());
             if reduce_data.len() != 0 {
                 self.reduce = Some(reduce_data);
@@ -138,16 +223,65 @@ fn get_co_local(co: &CoroutineImpl) -> *mut CoroutineLocal {
     co.get_local_data() as *mut CoroutineLocal
 }
 
+// get the id of the coroutine, 0 if it's not a spawned one
+#[inline]
+pub(crate) fn co_id(co: &CoroutineImpl) -> u64 {
+    unsafe { get_co_local(co).as_ref() }.map_or(0, |l| l.get_co().id())
+}
+
 /// /////////////////////////////////////////////////////////////////////////////
 /// Coroutine
 /// /////////////////////////////////////////////////////////////////////////////
 
+// the own stack of a coroutine, painted when it's allocated
+struct PaintedStack(Stack);
+
+// the stack is only scanned before it's freed, see `Coroutine::release_stack`
+unsafe impl Send for PaintedStack {}
+
+impl PaintedStack {
+    // the size from the top down to the first untouched word in bytes
+    fn used(&self) -> usize {
+        self.0.get_used_size() * std::mem::size_of::<usize>()
+    }
+}
+
 /// The internal representation of a `Coroutine` handle
 struct Inner {
+    id: u64,
+    parent_id: Option<u64>,
     name: Option<String>,
+    // where the coroutine is spawned
+    spawn_site: &'static Location<'static>,
     stack_size: usize,
+    priority: Priority,
+    // abort the process when the coroutine panics
+    abort_on_panic: bool,
+    // the scheduling state, see `registry::State`
+    state: AtomicU8,
+    // the stack size saved when the coroutine is suspended
+    saved_stack: AtomicUsize,
+    // the painted stack when the stack stats is enabled, scanned on demand
+    stack: parking_lot::Mutex<Option<PaintedStack>>,
+    // the used stack size found when the coroutine is done
+    stack_hwm: AtomicUsize,
+    // the backtrace captured at the last suspension
+    #[cfg(nightly)]
+    backtrace: parking_lot::Mutex<Option<std::sync::Arc<std::backtrace::Backtrace>>>,
     park: Park,
     cancel: Cancel,
+    // created on the first `cancel_token` call
+    token: once_cell::sync::OnceCell<CancellationToken>,
+    // the registration on the token of the current context
+    context: parking_lot::Mutex<Option<CancelRegistration>>,
+    // bumped each time the current context is replaced
+    context_gen: AtomicUsize,
+}
+
+impl CancelTarget for Inner {
+    fn on_cancel(&self) {
+        self.cancel.interrupt();
+    }
 }
 
 #[derive(Clone)]
@@ -160,23 +294,120 @@ unsafe impl Send for Coroutine {}
 
 impl Coroutine {
     // Used only internally to construct a coroutine object without spawning
-    fn new(name: Option<String>, stack_size: usize) -> Coroutine {
+    fn new(
+        name: Option<String>,
+        spawn_site: &'static Location<'static>,
+        stack_size: usize,
+        priority: Priority,
+        abort_on_panic: bool,
+    ) -> Coroutine {
+        static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+        // the coroutine that spawns this one
+        let parent_id = get_co_local_data().map(|local| unsafe { local.as_ref() }.get_co().id());
         Coroutine {
             inner: Arc::new(Inner {
+                id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+                parent_id,
                 name,
+                spawn_site,
                 stack_size,
+                priority,
+                abort_on_panic,
+                state: AtomicU8::new(State::Ready as u8),
+                saved_stack: AtomicUsize::new(0),
+                stack: parking_lot::Mutex::new(None),
+                stack_hwm: AtomicUsize::new(0),
+                #[cfg(nightly)]
+                backtrace: parking_lot::Mutex::new(None),
                 park: Park::new(),
                 cancel: Cancel::new(),
+                token: once_cell::sync::OnceCell::new(),
+                context: parking_lot::Mutex::new(None),
+                context_gen: AtomicUsize::new(0),
             }),
         }
     }
 
+    /// Gets the unique id of the coroutine.
+    ///
+    /// the id is never reused within the process
+    pub fn id(&self) -> u64 {
+        self.inner.id
+    }
+
+    /// Gets the id of the coroutine that spawned this one,
+    /// `None` if it's spawned from a thread.
+    pub fn parent_id(&self) -> Option<u64> {
+        self.inner.parent_id
+    }
+
+    // the source location that spawns the coroutine
+    pub(crate) fn spawn_site(&self) -> &'static Location<'static> {
+        self.inner.spawn_site
+    }
+
     /// Gets the coroutine stack size.
     pub fn stack_size(&self) -> usize {
         self.inner.stack_size
     }
 
+    /// Gets the (used, total) stack size of the coroutine in bytes.
+    ///
+    /// the used size is the high-water mark of the stack, it's only tracked
+    /// when `config().set_stack_stats(true)` is set before the coroutine is spawned
+    pub fn stack_usage(&self) -> (usize, usize) {
+        let total = self.inner.stack_size * std::mem::size_of::<usize>();
+        let used = match &*self.inner.stack.lock() {
+            Some(s) => s.used(),
+            None => self.inner.stack_hwm.load(Ordering::Relaxed),
+        };
+        (used, total)
+    }
+
+    // keep the final stack usage before the stack is freed
+    fn release_stack(&self) {
+        if let Some(s) = self.inner.stack.lock().take() {
+            self.inner.stack_hwm.store(s.used(), Ordering::Relaxed);
+        }
+    }
+
+    #[inline]
+    pub(crate) fn state(&self) -> State {
+        State::from_u8(self.inner.state.load(Ordering::Relaxed))
+    }
+
+    #[inline]
+    pub(crate) fn set_state(&self, state: State) {
+        self.inner.state.store(state as u8, Ordering::Relaxed);
+    }
+
+    // the size of the stack data saved when the coroutine is suspended
+    pub(crate) fn saved_stack(&self) -> usize {
+        self.inner.saved_stack.load(Ordering::Relaxed)
+    }
+
+    #[cfg(nightly)]
+    pub(crate) fn backtrace(&self) -> Option<std::sync::Arc<std::backtrace::Backtrace>> {
+        self.inner.backtrace.lock().clone()
+    }
+
+    // capture the backtrace of the current coroutine
+    #[cfg(nightly)]
+    pub(crate) fn capture_backtrace(&self) {
+        let bt = std::backtrace::Backtrace::force_capture();
+        *self.inner.backtrace.lock() = Some(std::sync::Arc::new(bt));
+    }
+
+    /// Gets the coroutine scheduling priority.
+    pub fn priority(&self) -> Priority {
+        self.inner.priority
+    }
+
     /// Atomically makes the handle's token available if it is not already.
+    ///
+    /// like `std::thread::Thread::unpark`, the token is consumed by the next
+    /// `park` or `park_timeout` call of the coroutine, so an unpark that
+    /// happens before the park is not lost
     pub fn unpark(&self) {
         self.inner.park.unpark();
     }
@@ -191,6 +422,54 @@ impl Coroutine {
         let _ = self.inner.cancel.cancel();
     }
 
+    /// Gets the cancellation token of the coroutine.
+    ///
+    /// canceling the token doesn't panic the coroutine like `cancel`, the
+    /// blocking ops that observe the token just return early with an error
+    pub fn cancel_token(&self) -> CancellationToken {
+        self.inner
+            .token
+            .get_or_init(|| {
+                let token = CancellationToken::new();
+                self.interrupt_on(&token);
+                token
+            })
+            .clone()
+    }
+
+    // interrupt the blocking ops of the coroutine when the token is canceled
+    pub(crate) fn interrupt_on(&self, token: &CancellationToken) {
+        token.add_target(Arc::downgrade(&self.inner) as Weak<dyn CancelTarget>);
+    }
+
+    // interrupt the blocking ops of the coroutine when the token of its
+    // current context is canceled. the interruption by the previous context
+    // is cleared, so a restored context that is not done doesn't fail the ops
+    pub(crate) fn set_context_token(&self, token: Option<&CancellationToken>) {
+        let inner = &self.inner;
+        let gen = inner.context_gen.fetch_add(1, Ordering::SeqCst) + 1;
+        inner.cancel.clear_interrupt();
+        let own = inner.token.get();
+        if own.is_some_and(CancellationToken::is_canceled) {
+            inner.cancel.interrupt();
+        }
+        let registration = token.map(|t| {
+            let weak = Arc::downgrade(inner);
+            t.on_cancel(move || {
+                // the context may be replaced after the callback is taken
+                match weak.upgrade() {
+                    Some(inner) if inner.context_gen.load(Ordering::SeqCst) == gen => {
+                        inner.cancel.interrupt()
+                    }
+                    _ => {}
+                }
+            })
+        });
+        // the old registration is removed outside of the lock
+        let old = std::mem::replace(&mut *inner.context.lock(), registration);
+        drop(old);
+    }
+
     /// Gets the coroutine name.
     pub fn name(&self) -> Option<&str> {
         self.inner.name.as_deref()
@@ -260,6 +539,16 @@ pub struct Builder {
     name: Option<String>,
     // The size of the stack for the spawned coroutine
     stack_size: Option<usize>,
+    // The scheduling priority for the spawned coroutine
+    priority: Priority,
+    // abort the process instead of delivering the panic to join
+    abort_on_panic: bool,
+    // pin the coroutine to the current worker
+    pinned: bool,
+    // pin the coroutine to the given worker
+    worker: Option<usize>,
+    // the context of the coroutine, inherited from the spawner if not set
+    context: Option<Context>,
 }
 
 impl Builder {
@@ -269,6 +558,11 @@ impl Builder {
         Builder {
             name: None,
             stack_size: None,
+            priority: Priority::Normal,
+            abort_on_panic: false,
+            pinned: false,
+            worker: None,
+            context: None,
         }
     }
 
@@ -285,18 +579,41 @@ impl Builder {
         self
     }
 
+    /// Sets the scheduling priority for the new coroutine.
+    ///
+    /// a high priority coroutine is picked up by the workers before the
+    /// normal ones, and a low priority one runs only when there is spare time
+    pub fn priority(mut self, priority: Priority) -> Builder {
+        self.priority = priority;
+        self
+    }
+
+    /// Sets whether to abort the process when the coroutine panics.
+    ///
+    /// by default the panic is delivered to the `join` of the coroutine
+    pub fn abort_on_panic(mut self, abort: bool) -> Builder {
+        self.abort_on_panic = abort;
+        self
+    }
+
+    /// Sets the context of the new coroutine.
+    ///
+    /// by default the coroutine inherits the context of the spawner, see
+    /// `Context::current`
+    pub fn context(mut self, ctx: Context) -> Builder {
+        self.context = Some(ctx);
+        self
+    }
+
     /// Spawns a new coroutine, and returns a join handle for it.
     /// The join handle can be used to block on
     /// termination of the child coroutine, including recovering its panics.
+    #[track_caller]
     fn spawn_impl<F, T>(self, f: F) -> (CoroutineImpl, JoinHandle<T>)
         where
             F: FnOnce() -> T + Send + 'static,
             T: Send + 'static,
     {
-        static DONE: Done = Done {};
-
-        let stack_size = self.stack_size.unwrap_or_else(|| config().get_stack_size());
-
         // create a join resource, shared by waited coroutine and *this* coroutine
         let panic = Arc::new(AtomicCell::new(None));
         let join = Arc::new(Join::new(panic.clone()));
@@ -304,9 +621,7 @@ impl Builder {
         let their_join = join.clone();
         let their_packet = packet.clone();
 
-        let subscriber = EventSubscriber {
-            resource: &DONE as &dyn EventSource as *const _ as *mut dyn EventSource,
-        };
+        let subscriber = done_subscriber();
 
         let closure = move || {
             // trigger the JoinHandler
@@ -320,17 +635,67 @@ impl Builder {
             their_join.trigger();
             subscriber
         };
+        let (co, handle) = self.build(closure, Some(join.clone()));
+        (co, make_join_handle(handle, join, packet, panic))
+    }
+
+    // create a coroutine without the join resource
+    #[track_caller]
+    fn spawn_detached_impl<F>(self, f: F) -> CoroutineImpl
+        where
+            F: FnOnce() + Send + 'static,
+    {
+        let subscriber = done_subscriber();
+        let closure = move || {
+            f();
+            subscriber
+        };
+        self.build(closure, None).0
+    }
+
+    // create the coroutine and attach the local storage to it
+    #[track_caller]
+    fn build<F>(self, closure: F, join: Option<Arc<Join>>) -> (CoroutineImpl, Coroutine)
+        where
+            F: FnOnce() -> EventSubscriber + Send + 'static,
+    {
+        let stack_size = self.stack_size.unwrap_or_else(|| config().get_stack_size());
         let s = get_scheduler();
         let mut tid = None;
         let mut stack = None;
-        for x in &s.stacks {
-            tid = Some(x.0.clone());
-            stack = Some(x.1.shadow_clone());
-            break;
+        let worker = self.worker.and_then(|id| {
+            s.worker_ids
+                .iter()
+                .find(|(_, v)| **v == id)
+                .map(|(t, _)| *t)
+        });
+        if let Some(t) = worker {
+            // start on the stack of the given worker
+            tid = Some(t);
+            stack = Some(s.get_stack(t));
+        } else if self.pinned && current_worker().is_some() {
+            // start on the stack of the current worker
+            let current = std::thread::current().id();
+            tid = Some(current);
+            stack = Some(s.get_stack(current));
+        } else {
+            for x in &s.stacks {
+                tid = Some(x.0.clone());
+                stack = Some(x.1.shadow_clone());
+                break;
+            }
         }
         let c: fn() -> EventSubscriber = unsafe { std::mem::transmute_copy(&closure) };
-        let mut stack = stack.unwrap();
-        stack.reset();
+        let own_stack = config().get_stack_stats();
+        let stack = if own_stack {
+            // an odd size paints the whole stack so that the used part can be found
+            Stack::new(stack_size | 1)
+        } else {
+            let mut stack = stack.unwrap();
+            stack.reset();
+            stack
+        };
+        let painted = own_stack.then(|| PaintedStack(stack.shadow_clone()));
         // let s = Stack::new(stack_size);
         // let stack_data = s.get_stack_data();
         //
@@ -338,15 +703,28 @@ impl Builder {
             worker_thread_id: tid,
             inner: Gn::new_opt_stack(c, stack),
             reduce: None,
+            priority: self.priority,
+            pinned: self.pinned,
+            own_stack,
         };
         co.init_code(closure);
-        let handle = Coroutine::new(self.name, stack_size);
+        let handle = Coroutine::new(
+            self.name,
+            Location::caller(),
+            stack_size,
+            self.priority,
+            self.abort_on_panic,
+        );
+        *handle.inner.stack.lock() = painted;
+        registry::register(&handle);
         // create the local storage
-        let local = CoroutineLocal::new(handle.clone(), join.clone());
+        let context = self.context.unwrap_or_else(current_context);
+        handle.set_context_token(context.token());
+        let local = CoroutineLocal::new(handle.clone(), join, context);
         // attache the local storage to the coroutine
         co.set_local_data(Box::into_raw(local) as *mut u8);
 
-        (co, make_join_handle(handle, join, packet, panic))
+        (co, handle)
     }
 
     /// Spawns a new coroutine by taking ownership of the `Builder`, and returns an
@@ -388,17 +766,102 @@ impl Builder {
     /// [`TLS`]: ./index.html#TLS
     /// [`go!`]: ../macro.go.html
     /// [`spawn`]: ./fn.spawn.html
+    #[track_caller]
     pub fn spawn<F, T>(self, f: F) -> JoinHandle<T>
         where
             F: FnOnce() -> T + Send + 'static,
             T: Send + 'static,
     {
-        let (co, handle) = self.spawn_impl(f);
         let s = get_scheduler();
-        s.schedule_global(co);
+        // backpressure, wait the ready queues to drain
+        s.wait_drained();
+        let (co, handle) = self.spawn_impl(f);
+        s.on_spawn(&co);
+        s.schedule_spawn(co);
         handle
     }
 
+    /// Spawns a new coroutine like [`spawn`] but fails fast instead of blocking
+    /// when the ready queues exceed `Config::get_max_pending_coroutines`
+    ///
+    /// [`spawn`]: #method.spawn
+    #[track_caller]
+    pub fn try_spawn<F, T>(self, f: F) -> Result<JoinHandle<T>, SpawnError>
+        where
+            F: FnOnce() -> T + Send + 'static,
+            T: Send + 'static,
+    {
+        let s = get_scheduler();
+        if s.is_overloaded() {
+            return Err(SpawnError::QueueFull);
+        }
+        let (co, handle) = self.spawn_impl(f);
+        s.on_spawn(&co);
+        s.schedule_spawn(co);
+        Ok(handle)
+    }
+
+    /// Spawns a new coroutine that can't be joined.
+    ///
+    /// this skips the allocation of the join resource, which makes it cheaper
+    /// than `spawn` for fire-and-forget coroutines. the panic of the coroutine
+    /// is only passed to the panic handler
+    #[track_caller]
+    pub fn spawn_detached<F>(self, f: F)
+        where
+            F: FnOnce() + Send + 'static,
+    {
+        let s = get_scheduler();
+        // backpressure, wait the ready queues to drain
+        s.wait_drained();
+        let co = self.spawn_detached_impl(f);
+        s.on_spawn(&co);
+        s.schedule_spawn(co);
+    }
+
+    /// Spawns a new coroutine that always runs on the current worker.
+    ///
+    /// the coroutine is never stolen by other workers, this gives better cache
+    /// locality for the coroutines that work together, and the closure doesn't
+    /// need to be `Send`. the priority is ignored for the pinned coroutines
+    ///
+    /// # Panics
+    ///
+    /// panics if it's not called on an io worker thread
+    #[track_caller]
+    pub fn spawn_pinned<F, T>(mut self, f: F) -> JoinHandle<T>
+        where
+            F: FnOnce() -> T + 'static,
+            T: Send + 'static,
+    {
+        assert!(
+            current_io_worker().is_some(),
+            "spawn_pinned must be called on an io worker"
+        );
+        self.pinned = true;
+        let f = PinnedFn(f);
+        self.spawn(move || f.call())
+    }
+
+    /// Spawns a new coroutine that always runs on the given worker.
+    ///
+    /// the worker id is in `0..Config::get_workers`, or one of the workers added
+    /// by `Scheduler::add_workers`. return `SpawnError::UnknownWorker` if there
+    /// is no running worker with the id
+    #[track_caller]
+    pub fn spawn_on<F, T>(mut self, worker: usize, f: F) -> Result<JoinHandle<T>, SpawnError>
+        where
+            F: FnOnce() -> T + Send + 'static,
+            T: Send + 'static,
+    {
+        if !get_scheduler().worker_ids.iter().any(|(_, id)| *id == worker) {
+            return Err(SpawnError::UnknownWorker(worker));
+        }
+        self.worker = Some(worker);
+        self.pinned = true;
+        Ok(self.spawn(f))
+    }
+
     /// first run the coroutine in current thread, you should allways use
     /// `spawn` instead of this API.
     ///
@@ -407,6 +870,7 @@ impl Builder {
     /// Cancel would drop all the resource of the coroutine.
     /// Normally this is safe but for some cases you should
     /// take care of the side effect
+    #[track_caller]
     pub fn spawn_local<F, T>(self, f: F) -> JoinHandle<T>
         where
             F: FnOnce() -> T + Send + 'static,
@@ -414,12 +878,44 @@ impl Builder {
     {
         // we will still get optimizations in spawn_impl
         let (co, handle) = self.spawn_impl(f);
+        get_scheduler().on_spawn(&co);
         // first run the coroutine in current thread
         run_coroutine(co);
         handle
     }
 }
 
+// the closure of a pinned coroutine, it never leaves the worker that spawns it
+struct PinnedFn<F>(F);
+
+unsafe impl<F> Send for PinnedFn<F> {}
+
+impl<F: FnOnce() -> T, T> PinnedFn<F> {
+    fn call(self) -> T {
+        (self.0)()
+    }
+}
+
+/// The error returned by `Builder::try_spawn`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpawnError {
+    /// the ready queues exceed `Config::get_max_pending_coroutines`
+    QueueFull,
+    /// there is no running worker with the id passed to `Builder::spawn_on`
+    UnknownWorker(usize),
+}
+
+impl fmt::Display for SpawnError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SpawnError::QueueFull => f.write_str("too many pending coroutines"),
+            SpawnError::UnknownWorker(id) => write!(f, "unknown worker {}", id),
+        }
+    }
+}
+
+impl error::Error for SpawnError {}
+
 ////////////////////////////////////////////////////////////////////////////////
 // Free functions
 ////////////////////////////////////////////////////////////////////////////////
@@ -470,6 +966,7 @@ impl Builder {
 /// [`join`]: struct.JoinHandle.html#method.join
 /// [`Builder::spawn`]: struct.Builder.html#method.spawn
 /// [`Builder`]: struct.Builder.html
+#[track_caller]
 pub fn spawn<F, T>(f: F) -> JoinHandle<T>
     where
         F: FnOnce() -> T + Send + 'static,
@@ -478,6 +975,42 @@ pub fn spawn<F, T>(f: F) -> JoinHandle<T>
     Builder::new().spawn(f)
 }
 
+/// Spawns a new coroutine on the current worker, see [`Builder::spawn_pinned`].
+///
+/// [`Builder::spawn_pinned`]: struct.Builder.html#method.spawn_pinned
+#[track_caller]
+pub fn spawn_pinned<F, T>(f: F) -> JoinHandle<T>
+    where
+        F: FnOnce() -> T + 'static,
+        T: Send + 'static,
+{
+    Builder::new().spawn_pinned(f)
+}
+
+/// Spawns a new coroutine with the given context instead of the current one,
+/// see [`Builder::context`].
+///
+/// [`Builder::context`]: struct.Builder.html#method.context
+#[track_caller]
+pub fn spawn_with_ctx<F, T>(ctx: Context, f: F) -> JoinHandle<T>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+{
+    Builder::new().context(ctx).spawn(f)
+}
+
+/// Spawns a new coroutine that can't be joined, see [`Builder::spawn_detached`].
+///
+/// [`Builder::spawn_detached`]: struct.Builder.html#method.spawn_detached
+#[track_caller]
+pub fn spawn_detached<F>(f: F)
+    where
+        F: FnOnce() + Send + 'static,
+{
+    Builder::new().spawn_detached(f)
+}
+
 /// Gets a handle to the coroutine that invokes it.
 /// it will panic if you call it in a thead context
 #[inline]
@@ -545,11 +1078,20 @@ fn park_timeout_impl(dur: Option<Duration>) {
 }
 
 /// block the current coroutine until it's get unparked
+///
+/// this mirrors `std::thread::park`, it returns immediately if the token
+/// is already available, and it may also return spuriously, e.g. when the
+/// coroutine is interrupted by its cancellation token. so the caller should
+/// re-check its condition in a loop
+///
+/// in thread context it does nothing
 pub fn park() {
     park_timeout_impl(None);
 }
 
 /// timeout block the current coroutine until it's get unparked
+///
+/// the same as `park` but returns after `dur` if no unpark happens
 pub fn park_timeout(dur: Duration) {
     park_timeout_impl(Some(dur));
 }
@@ -558,10 +1100,25 @@ pub fn park_timeout(dur: Duration) {
 #[inline]
 pub(crate) fn run_coroutine(mut co: CoroutineImpl) {
     let s = get_scheduler();
+    let id = s.on_run_start(&co);
+    crate::yield_now::reset_budget();
     co.stack_restore(s.get_stack(std::thread::current().id()));
-    match co.resume() {
+    let local = unsafe { get_co_local(&co).as_ref() };
+    if let Some(l) = local {
+        l.get_co().set_state(State::Running);
+    }
+    let ret = co.resume();
+    s.on_run_end(id);
+    match ret {
         Some(ev) => {
             co.stack_reduce();
+            // the coroutine may be resumed by others once subscribed
+            if let Some(l) = local {
+                let c = l.get_co();
+                let saved = co.reduce.as_ref().map_or(0, |v| v.len());
+                c.inner.saved_stack.store(saved, Ordering::Relaxed);
+                c.set_state(ev.state());
+            }
             ev.subscribe(co);
         }
         None => {
@@ -570,10 +1127,15 @@ pub(crate) fn run_coroutine(mut co: CoroutineImpl) {
             let join = local.get_join();
             // set the panic data
             if let Some(panic) = co.get_panic_data() {
-                join.set_panic_data(panic);
+                handle_panic(local.get_co(), &*panic);
+                if let Some(join) = join.as_ref() {
+                    join.set_panic_data(panic);
+                }
             }
             // trigger the join here
-            join.trigger();
+            if let Some(join) = join {
+                join.trigger();
+            }
             Done::drop_coroutine(co);
         }
     }
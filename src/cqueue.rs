@@ -1,3 +1,6 @@
+use std::cell::Cell;
+use std::collections::hash_map::RandomState;
+use std::hash::BuildHasher;
 use std::panic;
 use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::Arc;
@@ -8,10 +11,11 @@ use crate::coroutine_impl::{
     current_cancel_data, run_coroutine, Coroutine, CoroutineImpl, EventSource,
 };
 use crate::join::JoinHandle;
+use crate::registry::State;
 use crate::scoped::spawn_unsafe;
 use crate::std::sync::Mutex;
 use crate::std::sync::{AtomicOption, Blocker};
-use crate::yield_now::yield_with;
+use crate::yield_now::{yield_now, yield_with};
 
 use crate::std::queue::seg_queue::SegQueue as Queue;
 
@@ -107,6 +111,8 @@ impl<'a> EventSender<'a> {
         let cancel = current_cancel_data();
         cancel.check_cancel();
         self.extra.store(extra, Ordering::Relaxed);
+        // the coroutine looks parked before the event is pushed
+        self.cqueue.sending.fetch_add(1, Ordering::SeqCst);
         yield_with(self);
     }
 }
@@ -120,6 +126,7 @@ impl<'a> EventSource for EventSender<'a> {
             kind: EventKind::Normal,
             co: Some(co),
         });
+        self.cqueue.sending.fetch_sub(1, Ordering::SeqCst);
         if let Some(w) = self.cqueue.to_wake.take() {
             let _ = w.unpark();
         }
@@ -128,6 +135,10 @@ impl<'a> EventSource for EventSender<'a> {
     fn yield_back(&self, _cancel: &'static Cancel) {
         // ignore the cancel to let the bottom half get processed
     }
+
+    fn state(&self) -> State {
+        State::Parked
+    }
 }
 
 impl<'a> Drop for EventSender<'a> {
@@ -161,6 +172,8 @@ pub struct Cqueue {
     total: AtomicUsize,
     // panic status
     is_panicking: AtomicBool,
+    // how many select coroutines are sending events
+    sending: AtomicUsize,
 }
 
 impl Cqueue {
@@ -275,6 +288,57 @@ impl Cqueue {
             }
         }
     }
+
+    /// poll an event without waiting for the blocked select coroutines
+    ///
+    /// the select coroutines that are not blocked yet are given one chance
+    /// to run, if no event is ready after that, or all the unfinished ones
+    /// are blocked, e.g. parked on a channel, sleeping or waiting for io,
+    /// return `PollError::Timeout`. this is the `default` branch of the go select
+    pub fn try_poll(&self) -> Result<Event, PollError> {
+        let mut yielded = false;
+        loop {
+            match self.ev_queue.pop() {
+                Some(mut ev) => {
+                    if ev.kind == EventKind::Done {
+                        self.check_panic(ev.id);
+                        continue;
+                    }
+                    ev.continue_bottom();
+                    return Ok(ev);
+                }
+                None => {
+                    if self.cnt.load(Ordering::Relaxed) == 0 {
+                        return Err(PollError::Finished);
+                    }
+                }
+            }
+
+            if self.all_blocked() && self.sending.load(Ordering::SeqCst) == 0 {
+                // re-check the event that is pushed after the check
+                if self.ev_queue.is_empty() {
+                    return Err(PollError::Timeout);
+                }
+                continue;
+            }
+            if yielded {
+                return Err(PollError::Timeout);
+            }
+            yield_now();
+            yielded = true;
+        }
+    }
+
+    // return true if all the unfinished select coroutines are blocked
+    fn all_blocked(&self) -> bool {
+        self.selectors.lock().unwrap().iter().flatten().all(|j| {
+            j.is_done()
+                || matches!(
+                    j.coroutine().state(),
+                    State::Parked | State::Sleeping | State::BlockedOnIo
+                )
+        })
+    }
 }
 
 impl Drop for Cqueue {
@@ -308,6 +372,27 @@ impl Drop for Cqueue {
     }
 }
 
+thread_local! {
+    // xorshift state for the select order, it doesn't need to be a good one
+    static RNG: Cell<u64> = Cell::new(RandomState::new().hash_one(0u8) | 1);
+}
+
+/// shuffle the list in place, used by `select!` to add the select coroutines
+/// in a random order so that a ready branch is not always preferred
+#[doc(hidden)]
+pub fn shuffle<T>(list: &mut [T]) {
+    RNG.with(|rng| {
+        let mut x = rng.get();
+        for i in (1..list.len()).rev() {
+            x ^= x << 13;
+            x ^= x >> 7;
+            x ^= x << 17;
+            list.swap(i, (x % (i as u64 + 1)) as usize);
+        }
+        rng.set(x);
+    })
+}
+
 /// Create a new `scope`, for select coroutines.
 ///
 /// Scopes, in particular, support scoped select coroutine spawning.
@@ -323,6 +408,7 @@ where
         selectors: Mutex::new(Vec::new()),
         total: AtomicUsize::new(0),
         is_panicking: AtomicBool::new(false),
+        sending: AtomicUsize::new(0),
     };
     f(&cqueue)
 }
@@ -0,0 +1,38 @@
+//! scheduler tracing hooks
+//!
+//! install the hooks with `config().set_scheduler_hooks(...)` before
+//! the scheduler is started
+
+/// the event data passed to the hooks
+#[derive(Debug, Clone, Copy)]
+pub struct HookEvent {
+    /// the coroutine id, 0 if the event is not related to a coroutine
+    pub co_id: u64,
+    /// the worker id, `None` for a non-worker thread
+    pub worker: Option<usize>,
+    /// the time in ns since the runtime started, by the coarse clock
+    pub time: u64,
+}
+
+/// callbacks invoked by the scheduler
+///
+/// the hooks are called in the hot path of the workers, keep them cheap
+pub trait SchedulerHooks: Send + Sync + 'static {
+    /// a coroutine is spawned
+    fn on_spawn(&self, _event: &HookEvent) {}
+
+    /// a coroutine is put into a ready queue
+    fn on_schedule(&self, _event: &HookEvent) {}
+
+    /// a coroutine starts to run on a worker
+    fn on_run_start(&self, _event: &HookEvent) {}
+
+    /// a coroutine yields back or finishes on a worker
+    fn on_run_end(&self, _event: &HookEvent) {}
+
+    /// a worker goes to wait for events
+    fn on_park(&self, _event: &HookEvent) {}
+
+    /// a worker takes a coroutine from the global queue
+    fn on_steal(&self, _event: &HookEvent) {}
+}
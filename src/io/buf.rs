@@ -0,0 +1,422 @@
+use std::fmt;
+use std::io::{self, BufRead, Read, Write};
+use std::mem::ManuallyDrop;
+use std::ptr;
+
+use super::buf_pool::{PooledBuf, BUF_SIZE};
+
+/// Adds buffering to a reader, the buffer is taken from a global pool
+///
+/// same as `std::io::BufReader`, each small read of a mco stream would park
+/// the coroutine and go through the selector, the buffer turns them into one
+/// read of the socket. `read_line` and `read_until` come from `BufRead`
+/// for example:
+/// ```no_run
+///         use mco::io::BufReader;
+///         use mco::net::TcpStream;
+///         use std::io::BufRead;
+///
+///         let stream = TcpStream::connect("127.0.0.1:6379").unwrap();
+///         let mut reader = BufReader::new(stream);
+///         let mut line = String::new();
+///         reader.read_line(&mut line).unwrap();
+/// ```
+pub struct BufReader<R> {
+    inner: R,
+    buf: PooledBuf,
+    pos: usize,
+    filled: usize,
+}
+
+impl<R: Read> BufReader<R> {
+    /// create a reader with the pooled buffer of 8KB
+    pub fn new(inner: R) -> Self {
+        Self::with_capacity(BUF_SIZE, inner)
+    }
+
+    /// create a reader with the buffer of `cap` bytes,
+    /// the buffers of other sizes than the default are not pooled
+    pub fn with_capacity(cap: usize, inner: R) -> Self {
+        BufReader {
+            inner,
+            buf: PooledBuf::with_capacity(cap),
+            pos: 0,
+            filled: 0,
+        }
+    }
+}
+
+impl<R> BufReader<R> {
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// it's inadvisable to read from the inner reader directly
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    /// the buffered data that is not consumed yet
+    pub fn buffer(&self) -> &[u8] {
+        &self.buf[self.pos..self.filled]
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.buf.len()
+    }
+
+    /// unwrap the reader, the buffered data is lost
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R: Read> Read for BufReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        // bypass the buffer for the big reads
+        if self.pos == self.filled && buf.len() >= self.capacity() {
+            return self.inner.read(buf);
+        }
+        let n = self.fill_buf()?.read(buf)?;
+        self.consume(n);
+        Ok(n)
+    }
+}
+
+impl<R: Read> BufRead for BufReader<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        if self.pos >= self.filled {
+            self.filled = self.inner.read(&mut self.buf)?;
+            self.pos = 0;
+        }
+        Ok(self.buffer())
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.pos = std::cmp::min(self.pos + amt, self.filled);
+    }
+}
+
+// so that `BufStream` can write through the reader
+impl<R: Write> Write for BufReader<R> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<R: fmt::Debug> fmt::Debug for BufReader<R> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("BufReader")
+            .field("reader", &self.inner)
+            .field(
+                "buffer",
+                &format_args!("{}/{}", self.filled - self.pos, self.capacity()),
+            )
+            .finish()
+    }
+}
+
+/// Adds buffering to a writer, the buffer is taken from a global pool
+///
+/// same as `std::io::BufWriter`, the buffered data is written when the
+/// buffer is full, `flush` is called or the writer is dropped, the write
+/// errors on drop are ignored, so call `flush` before dropping it
+pub struct BufWriter<W: Write> {
+    inner: W,
+    buf: PooledBuf,
+    len: usize,
+}
+
+impl<W: Write> BufWriter<W> {
+    /// create a writer with the pooled buffer of 8KB
+    pub fn new(inner: W) -> Self {
+        Self::with_capacity(BUF_SIZE, inner)
+    }
+
+    /// create a writer with the buffer of `cap` bytes,
+    /// the buffers of other sizes than the default are not pooled
+    pub fn with_capacity(cap: usize, inner: W) -> Self {
+        BufWriter {
+            inner,
+            buf: PooledBuf::with_capacity(cap),
+            len: 0,
+        }
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    /// it's inadvisable to write to the inner writer directly
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    /// the buffered data that is not written yet
+    pub fn buffer(&self) -> &[u8] {
+        &self.buf[..self.len]
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.buf.len()
+    }
+
+    /// write the buffered data and unwrap the writer
+    pub fn into_inner(mut self) -> io::Result<W> {
+        self.flush_buf()?;
+        let mut this = ManuallyDrop::new(self);
+        // skip the drop of self which would flush again
+        unsafe {
+            ptr::drop_in_place(&mut this.buf);
+            Ok(ptr::read(&this.inner))
+        }
+    }
+
+    // write all the buffered data, the unwritten data is kept on error
+    fn flush_buf(&mut self) -> io::Result<()> {
+        let mut written = 0;
+        let ret = loop {
+            if written >= self.len {
+                break Ok(());
+            }
+            match self.inner.write(&self.buf[written..self.len]) {
+                Ok(0) => {
+                    break Err(io::Error::new(
+                        io::ErrorKind::WriteZero,
+                        "failed to write the buffered data",
+                    ))
+                }
+                Ok(n) => written += n,
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => break Err(e),
+            }
+        };
+        self.buf.copy_within(written..self.len, 0);
+        self.len -= written;
+        ret
+    }
+}
+
+impl<W: Write> Write for BufWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.len + buf.len() > self.capacity() {
+            self.flush_buf()?;
+        }
+        // bypass the buffer for the big writes
+        if buf.len() >= self.capacity() {
+            return self.inner.write(buf);
+        }
+        self.buf[self.len..self.len + buf.len()].copy_from_slice(buf);
+        self.len += buf.len();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.flush_buf()?;
+        self.inner.flush()
+    }
+}
+
+// so that `BufStream` can read through the writer
+impl<W: Write + Read> Read for BufWriter<W> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.inner.read(buf)
+    }
+}
+
+impl<W: Write> Drop for BufWriter<W> {
+    fn drop(&mut self) {
+        if !std::thread::panicking() {
+            let _ = self.flush_buf();
+        }
+    }
+}
+
+impl<W: Write + fmt::Debug> fmt::Debug for BufWriter<W> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("BufWriter")
+            .field("writer", &self.inner)
+            .field("buffer", &format_args!("{}/{}", self.len, self.capacity()))
+            .finish()
+    }
+}
+
+/// Adds buffering to both the read and the write side of a stream
+///
+/// the request/response protocols read and write through the same stream,
+/// remember to `flush` after writing a request before waiting for the reply
+/// for example:
+/// ```no_run
+///         use mco::io::BufStream;
+///         use mco::net::TcpStream;
+///         use std::io::{BufRead, Write};
+///
+///         let stream = TcpStream::connect("127.0.0.1:6379").unwrap();
+///         let mut stream = BufStream::new(stream);
+///         stream.write_all(b"PING\r\n").unwrap();
+///         stream.flush().unwrap();
+///         let mut line = String::new();
+///         stream.read_line(&mut line).unwrap();
+/// ```
+pub struct BufStream<S: Read + Write> {
+    inner: BufReader<BufWriter<S>>,
+}
+
+impl<S: Read + Write> BufStream<S> {
+    /// create a stream with the pooled buffers of 8KB
+    pub fn new(stream: S) -> Self {
+        BufStream {
+            inner: BufReader::new(BufWriter::new(stream)),
+        }
+    }
+
+    /// create a stream with the given buffer sizes,
+    /// the buffers of other sizes than the default are not pooled
+    pub fn with_capacity(read_cap: usize, write_cap: usize, stream: S) -> Self {
+        BufStream {
+            inner: BufReader::with_capacity(read_cap, BufWriter::with_capacity(write_cap, stream)),
+        }
+    }
+
+    pub fn get_ref(&self) -> &S {
+        self.inner.get_ref().get_ref()
+    }
+
+    /// it's inadvisable to read or write the inner stream directly
+    pub fn get_mut(&mut self) -> &mut S {
+        self.inner.get_mut().get_mut()
+    }
+
+    /// write the buffered data and unwrap the stream,
+    /// the buffered read data is lost
+    pub fn into_inner(self) -> io::Result<S> {
+        self.inner.into_inner().into_inner()
+    }
+}
+
+impl<S: Read + Write> Read for BufStream<S> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.inner.read(buf)
+    }
+}
+
+impl<S: Read + Write> BufRead for BufStream<S> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        self.inner.fill_buf()
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.inner.consume(amt)
+    }
+}
+
+impl<S: Read + Write> Write for BufStream<S> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<S: Read + Write + fmt::Debug> fmt::Debug for BufStream<S> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("BufStream")
+            .field("stream", self.get_ref())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a stream that records the number of the calls to the inner io
+    struct Counted {
+        data: io::Cursor<Vec<u8>>,
+        out: Vec<u8>,
+        reads: usize,
+        writes: usize,
+    }
+
+    impl Counted {
+        fn new(data: &[u8]) -> Self {
+            Counted {
+                data: io::Cursor::new(data.to_vec()),
+                out: Vec::new(),
+                reads: 0,
+                writes: 0,
+            }
+        }
+    }
+
+    impl Read for Counted {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.reads += 1;
+            self.data.read(buf)
+        }
+    }
+
+    impl Write for Counted {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.writes += 1;
+            self.out.write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn buf_reader_lines() {
+        let mut r = BufReader::new(Counted::new(b"hello\nworld\nlast"));
+        let mut line = String::new();
+        r.read_line(&mut line).unwrap();
+        assert_eq!(line, "hello\n");
+        assert_eq!(r.buffer(), b"world\nlast");
+        let mut rest = Vec::new();
+        r.read_until(b'\n', &mut rest).unwrap();
+        assert_eq!(rest, b"world\n");
+        let mut byte = [0; 1];
+        assert_eq!(r.read(&mut byte).unwrap(), 1);
+        assert_eq!(&byte, b"l");
+        // the data is read from the stream only once, plus the EOF
+        let mut s = String::new();
+        r.read_to_string(&mut s).unwrap();
+        assert_eq!(s, "ast");
+        assert_eq!(r.get_ref().reads, 2);
+    }
+
+    #[test]
+    fn buf_writer_batches() {
+        let mut w = BufWriter::with_capacity(8, Counted::new(b""));
+        w.write_all(b"abc").unwrap();
+        w.write_all(b"def").unwrap();
+        assert_eq!(w.get_ref().writes, 0);
+        // overflow flushes the buffer, the big write bypasses it
+        w.write_all(b"ghi").unwrap();
+        w.write_all(b"0123456789").unwrap();
+        assert_eq!(w.get_ref().writes, 3);
+        let inner = w.into_inner().unwrap();
+        assert_eq!(inner.out, b"abcdefghi0123456789");
+    }
+
+    #[test]
+    fn buf_stream() {
+        let mut s = BufStream::new(Counted::new(b"+PONG\r\n"));
+        s.write_all(b"PING").unwrap();
+        s.write_all(b"\r\n").unwrap();
+        s.flush().unwrap();
+        let mut line = String::new();
+        s.read_line(&mut line).unwrap();
+        assert_eq!(line, "+PONG\r\n");
+        assert_eq!(s.get_ref().writes, 1);
+        assert_eq!(s.into_inner().unwrap().out, b"PING\r\n");
+    }
+}
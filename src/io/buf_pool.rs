@@ -0,0 +1,62 @@
+use crossbeam::queue::ArrayQueue as Queue;
+use once_cell::sync::Lazy;
+use std::ops::{Deref, DerefMut};
+
+/// the size of the pooled buffers
+pub(crate) const BUF_SIZE: usize = 8 * 1024;
+
+// the idle buffers kept by the pool, the others are freed
+const POOL_CAPACITY: usize = 1024;
+
+static POOL: Lazy<Queue<Box<[u8]>>> = Lazy::new(|| Queue::new(POOL_CAPACITY));
+
+/// a byte buffer that is returned to the global pool when dropped
+///
+/// the io helpers of a lot of coroutines would otherwise allocate and free
+/// a buffer per connection, only the buffers of `BUF_SIZE` are pooled
+pub(crate) struct PooledBuf {
+    buf: Option<Box<[u8]>>,
+}
+
+impl PooledBuf {
+    /// get a buffer of `BUF_SIZE` from the pool
+    pub fn new() -> Self {
+        Self::with_capacity(BUF_SIZE)
+    }
+
+    /// get a buffer of `cap` bytes, the content is not cleared
+    pub fn with_capacity(cap: usize) -> Self {
+        let buf = match cap {
+            BUF_SIZE => POOL.pop(),
+            _ => None,
+        };
+        PooledBuf {
+            buf: Some(buf.unwrap_or_else(|| vec![0; cap].into_boxed_slice())),
+        }
+    }
+}
+
+impl Deref for PooledBuf {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        self.buf.as_ref().unwrap()
+    }
+}
+
+impl DerefMut for PooledBuf {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        self.buf.as_mut().unwrap()
+    }
+}
+
+impl Drop for PooledBuf {
+    fn drop(&mut self) {
+        if let Some(buf) = self.buf.take() {
+            if buf.len() == BUF_SIZE {
+                // discard the buf if the pool is full
+                POOL.push(buf).ok();
+            }
+        }
+    }
+}
@@ -0,0 +1,535 @@
+//! Framing of the byte streams into messages
+//!
+//! a `Decoder` cuts the frames out of the bytes read so far and an `Encoder`
+//! appends a frame to the bytes to write. `Framed` drives them over a stream,
+//! it reads until a whole frame is decoded, so the partial reads are handled
+//! once here instead of in each protocol. the mco streams park the coroutine
+//! when they would block, so does `Framed`
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use mco::io::codec::{Framed, LinesCodec};
+//! use mco::net::TcpListener;
+//!
+//! let listener = TcpListener::bind("127.0.0.1:8080").unwrap();
+//! for stream in listener.incoming() {
+//!     let stream = stream.unwrap();
+//!     mco::co!(move || {
+//!         let mut framed = Framed::new(stream, LinesCodec::new());
+//!         while let Some(Ok(line)) = framed.next() {
+//!             framed.send(format!("echo: {}", line)).unwrap();
+//!         }
+//!     });
+//! }
+//! ```
+
+use std::fmt;
+use std::io::{self, Read, Write};
+
+use super::buf_pool::BUF_SIZE;
+
+/// Decode the frames from the bytes
+pub trait Decoder {
+    type Item;
+    type Error: From<io::Error>;
+
+    /// decode a frame from the head of `buf` and remove its bytes,
+    /// return `Ok(None)` if more bytes are needed
+    fn decode(&mut self, buf: &mut Vec<u8>) -> Result<Option<Self::Item>, Self::Error>;
+
+    /// decode the last frames after the stream reached EOF
+    ///
+    /// by default the bytes left that don't make a frame are an error
+    fn decode_eof(&mut self, buf: &mut Vec<u8>) -> Result<Option<Self::Item>, Self::Error> {
+        match self.decode(buf)? {
+            Some(frame) => Ok(Some(frame)),
+            None if buf.is_empty() => Ok(None),
+            None => Err(
+                io::Error::new(io::ErrorKind::UnexpectedEof, "bytes remaining on stream").into(),
+            ),
+        }
+    }
+}
+
+/// Encode the frames into the bytes
+pub trait Encoder<Item> {
+    type Error: From<io::Error>;
+
+    /// append the encoded `item` to `dst`
+    fn encode(&mut self, item: Item, dst: &mut Vec<u8>) -> Result<(), Self::Error>;
+}
+
+/// A stream of the frames on top of a byte stream
+///
+/// iterate it to read the decoded frames, the iterator ends at EOF or
+/// after an error, except for the read timeouts. `send` encodes a frame
+/// and writes it out, `feed` only buffers it until `flush` to write several
+/// frames at once
+pub struct Framed<T, C> {
+    inner: T,
+    codec: C,
+    rbuf: Vec<u8>,
+    wbuf: Vec<u8>,
+    eof: bool,
+    // the stream is broken by an error, no more frames are read
+    errored: bool,
+}
+
+impl<T, C> Framed<T, C> {
+    pub fn new(inner: T, codec: C) -> Self {
+        Framed {
+            inner,
+            codec,
+            rbuf: Vec::new(),
+            wbuf: Vec::new(),
+            eof: false,
+            errored: false,
+        }
+    }
+
+    pub fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    /// it's inadvisable to read or write the inner stream directly
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+
+    pub fn codec(&self) -> &C {
+        &self.codec
+    }
+
+    pub fn codec_mut(&mut self) -> &mut C {
+        &mut self.codec
+    }
+
+    /// the bytes read that are not decoded yet
+    pub fn read_buffer(&self) -> &[u8] {
+        &self.rbuf
+    }
+
+    /// unwrap the stream, the buffered bytes are lost
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+impl<T: Read, C: Decoder> Framed<T, C> {
+    /// read the next frame, return `Ok(None)` at EOF
+    ///
+    /// after an error other than a read timeout it returns `Ok(None)`,
+    /// the bytes left in the read buffer can't be decoded anymore
+    pub fn next_frame(&mut self) -> Result<Option<C::Item>, C::Error> {
+        if self.errored {
+            return Ok(None);
+        }
+        loop {
+            let ret = if self.eof {
+                self.codec.decode_eof(&mut self.rbuf)
+            } else {
+                self.codec.decode(&mut self.rbuf)
+            };
+            match ret {
+                Ok(None) if !self.eof => {}
+                Err(e) => {
+                    self.errored = true;
+                    return Err(e);
+                }
+                ret => return ret,
+            }
+            let len = self.rbuf.len();
+            self.rbuf.resize(len + BUF_SIZE, 0);
+            let ret = self.inner.read(&mut self.rbuf[len..]);
+            self.rbuf.truncate(len + *ret.as_ref().unwrap_or(&0));
+            match ret {
+                Ok(0) => self.eof = true,
+                Ok(_) => {}
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+                // nothing is lost, the read can be retried
+                Err(e) if is_timeout(&e) => return Err(e.into()),
+                Err(e) => {
+                    self.errored = true;
+                    return Err(e.into());
+                }
+            }
+        }
+    }
+}
+
+fn is_timeout(e: &io::Error) -> bool {
+    matches!(
+        e.kind(),
+        io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock
+    )
+}
+
+impl<T: Read, C: Decoder> Iterator for Framed<T, C> {
+    type Item = Result<C::Item, C::Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_frame().transpose()
+    }
+}
+
+impl<T: Write, C> Framed<T, C> {
+    /// encode the item into the write buffer without writing it
+    pub fn feed<I>(&mut self, item: I) -> Result<(), C::Error>
+    where
+        C: Encoder<I>,
+    {
+        self.codec.encode(item, &mut self.wbuf)
+    }
+
+    /// write all the buffered frames
+    ///
+    /// the bytes not written because of an error are kept in the buffer,
+    /// so the flush can be retried after a write timeout
+    pub fn flush(&mut self) -> io::Result<()> {
+        let mut written = 0;
+        let ret = loop {
+            if written == self.wbuf.len() {
+                break Ok(());
+            }
+            match self.inner.write(&self.wbuf[written..]) {
+                Ok(0) => {
+                    break Err(io::Error::new(
+                        io::ErrorKind::WriteZero,
+                        "failed to write the buffered frames",
+                    ))
+                }
+                Ok(n) => written += n,
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => break Err(e),
+            }
+        };
+        self.wbuf.drain(..written);
+        ret?;
+        self.inner.flush()
+    }
+
+    /// encode the item and write it with the buffered frames
+    pub fn send<I>(&mut self, item: I) -> Result<(), C::Error>
+    where
+        C: Encoder<I>,
+    {
+        self.feed(item)?;
+        Ok(self.flush()?)
+    }
+}
+
+impl<T: fmt::Debug, C: fmt::Debug> fmt::Debug for Framed<T, C> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Framed")
+            .field("inner", &self.inner)
+            .field("codec", &self.codec)
+            .field("read_buffer", &self.rbuf.len())
+            .field("write_buffer", &self.wbuf.len())
+            .finish()
+    }
+}
+
+/// the default max frame length of `LengthDelimitedCodec`, 8MB
+pub const DEFAULT_MAX_FRAME_LENGTH: usize = 8 * 1024 * 1024;
+
+/// A codec for the frames prefixed by their length
+///
+/// the length is a big endian u32 of the payload size, a frame longer than
+/// the max frame length is an `InvalidData` error, so a bad peer can't make
+/// the reader allocate without a limit
+#[derive(Debug, Clone)]
+pub struct LengthDelimitedCodec {
+    max_frame_length: usize,
+}
+
+impl LengthDelimitedCodec {
+    pub fn new() -> Self {
+        Self::with_max_frame_length(DEFAULT_MAX_FRAME_LENGTH)
+    }
+
+    pub fn with_max_frame_length(max_frame_length: usize) -> Self {
+        LengthDelimitedCodec { max_frame_length }
+    }
+
+    pub fn max_frame_length(&self) -> usize {
+        self.max_frame_length
+    }
+
+    fn too_long(&self, len: usize) -> io::Error {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "frame length {} exceeds the max {}",
+                len, self.max_frame_length
+            ),
+        )
+    }
+}
+
+impl Default for LengthDelimitedCodec {
+    fn default() -> Self {
+        LengthDelimitedCodec::new()
+    }
+}
+
+impl Decoder for LengthDelimitedCodec {
+    type Item = Vec<u8>;
+    type Error = io::Error;
+
+    fn decode(&mut self, buf: &mut Vec<u8>) -> io::Result<Option<Vec<u8>>> {
+        if buf.len() < 4 {
+            return Ok(None);
+        }
+        let len = u32::from_be_bytes([buf[0], buf[1], buf[2], buf[3]]) as usize;
+        if len > self.max_frame_length {
+            return Err(self.too_long(len));
+        }
+        if buf.len() < 4 + len {
+            buf.reserve(4 + len - buf.len());
+            return Ok(None);
+        }
+        let frame = buf[4..4 + len].to_vec();
+        buf.drain(..4 + len);
+        Ok(Some(frame))
+    }
+}
+
+impl<T: AsRef<[u8]>> Encoder<T> for LengthDelimitedCodec {
+    type Error = io::Error;
+
+    fn encode(&mut self, item: T, dst: &mut Vec<u8>) -> io::Result<()> {
+        let data = item.as_ref();
+        if data.len() > self.max_frame_length || data.len() > u32::MAX as usize {
+            return Err(self.too_long(data.len()));
+        }
+        dst.reserve(4 + data.len());
+        dst.extend_from_slice(&(data.len() as u32).to_be_bytes());
+        dst.extend_from_slice(data);
+        Ok(())
+    }
+}
+
+/// A codec for the lines of text
+///
+/// the lines are split by `\n`, a trailing `\r` is removed. the last line
+/// without `\n` is returned at EOF. a line longer than the max length or
+/// not in utf-8 is an `InvalidData` error
+#[derive(Debug, Clone)]
+pub struct LinesCodec {
+    max_length: usize,
+    // the bytes before it are checked to have no `\n`
+    next_index: usize,
+}
+
+impl LinesCodec {
+    /// create a codec that doesn't limit the line length
+    pub fn new() -> Self {
+        Self::with_max_length(usize::MAX)
+    }
+
+    /// create a codec that fails on the lines longer than `max_length`
+    /// bytes, the line ending excluded
+    pub fn with_max_length(max_length: usize) -> Self {
+        LinesCodec {
+            max_length,
+            next_index: 0,
+        }
+    }
+
+    pub fn max_length(&self) -> usize {
+        self.max_length
+    }
+
+    fn take_line(&self, buf: &mut Vec<u8>, end: usize, skip: usize) -> io::Result<String> {
+        let mut line: Vec<u8> = buf.drain(..end + skip).take(end).collect();
+        if line.last() == Some(&b'\r') {
+            line.pop();
+        }
+        if line.len() > self.max_length {
+            return Err(self.too_long());
+        }
+        String::from_utf8(line).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    fn too_long(&self) -> io::Error {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("line exceeds the max length {}", self.max_length),
+        )
+    }
+}
+
+impl Default for LinesCodec {
+    fn default() -> Self {
+        LinesCodec::new()
+    }
+}
+
+impl Decoder for LinesCodec {
+    type Item = String;
+    type Error = io::Error;
+
+    fn decode(&mut self, buf: &mut Vec<u8>) -> io::Result<Option<String>> {
+        match buf[self.next_index..].iter().position(|b| *b == b'\n') {
+            Some(i) => {
+                let end = self.next_index + i;
+                self.next_index = 0;
+                self.take_line(buf, end, 1).map(Some)
+            }
+            None => {
+                self.next_index = buf.len();
+                // the `\r` of the line ending is not counted
+                if buf.len() > self.max_length.saturating_add(1) {
+                    return Err(self.too_long());
+                }
+                Ok(None)
+            }
+        }
+    }
+
+    fn decode_eof(&mut self, buf: &mut Vec<u8>) -> io::Result<Option<String>> {
+        match self.decode(buf)? {
+            Some(line) => Ok(Some(line)),
+            None if buf.is_empty() => Ok(None),
+            None => {
+                self.next_index = 0;
+                let end = buf.len();
+                self.take_line(buf, end, 0).map(Some)
+            }
+        }
+    }
+}
+
+impl<T: AsRef<str>> Encoder<T> for LinesCodec {
+    type Error = io::Error;
+
+    fn encode(&mut self, line: T, dst: &mut Vec<u8>) -> io::Result<()> {
+        let line = line.as_ref();
+        dst.reserve(line.len() + 1);
+        dst.extend_from_slice(line.as_bytes());
+        dst.push(b'\n');
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // reads the data in the given pieces and collects the writes
+    struct Mock {
+        reads: Vec<Vec<u8>>,
+        out: Vec<u8>,
+    }
+
+    impl Read for Mock {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.reads.is_empty() {
+                return Ok(0);
+            }
+            let data = self.reads.remove(0);
+            buf[..data.len()].copy_from_slice(&data);
+            Ok(data.len())
+        }
+    }
+
+    impl Write for Mock {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.out.write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn mock(reads: &[&[u8]]) -> Mock {
+        Mock {
+            reads: reads.iter().map(|r| r.to_vec()).collect(),
+            out: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn lines_codec() {
+        let io = mock(&[b"hel", b"lo\r\nwor", b"ld\n\nlast"]);
+        let mut framed = Framed::new(io, LinesCodec::new());
+        let lines: Vec<String> = framed.by_ref().map(|l| l.unwrap()).collect();
+        assert_eq!(lines, ["hello", "world", "", "last"]);
+
+        framed.feed("a").unwrap();
+        framed.send(String::from("b")).unwrap();
+        assert_eq!(framed.get_ref().out, b"a\nb\n");
+
+        let mut framed = Framed::new(mock(&[b"abcdef\n"]), LinesCodec::with_max_length(4));
+        let err = framed.next().unwrap().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        // the iterator ends after the error
+        assert!(framed.next().is_none());
+    }
+
+    // writes 3 bytes at most, the second write would block
+    struct Partial {
+        out: Vec<u8>,
+        writes: usize,
+    }
+
+    impl Write for Partial {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.writes += 1;
+            if self.writes == 2 {
+                return Err(io::ErrorKind::WouldBlock.into());
+            }
+            let n = std::cmp::min(buf.len(), 3);
+            self.out.extend_from_slice(&buf[..n]);
+            Ok(n)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn flush_keeps_unwritten() {
+        let io = Partial {
+            out: Vec::new(),
+            writes: 0,
+        };
+        let mut framed = Framed::new(io, LinesCodec::new());
+        let err = framed.send("hello").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::WouldBlock);
+        framed.flush().unwrap();
+        assert_eq!(framed.get_ref().out, b"hello\n");
+    }
+
+    #[test]
+    fn length_delimited_codec() {
+        let mut codec = LengthDelimitedCodec::new();
+        let mut data = Vec::new();
+        codec.encode(b"hello", &mut data).unwrap();
+        codec.encode(Vec::new(), &mut data).unwrap();
+        codec.encode("world", &mut data).unwrap();
+        assert_eq!(&data[..9], b"\0\0\0\x05hello");
+
+        // one byte per read
+        let reads: Vec<&[u8]> = data.chunks(1).collect();
+        let framed = Framed::new(mock(&reads), LengthDelimitedCodec::new());
+        let frames: Vec<Vec<u8>> = framed.map(|f| f.unwrap()).collect();
+        assert_eq!(frames, [&b"hello"[..], b"", b"world"]);
+
+        // truncated frame
+        let mut framed = Framed::new(mock(&[&data[..7]]), LengthDelimitedCodec::new());
+        let err = framed.next().unwrap().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+
+        let mut framed = Framed::new(
+            mock(&[&data]),
+            LengthDelimitedCodec::with_max_frame_length(4),
+        );
+        let err = framed.next().unwrap().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(framed.send(b"hello").is_err());
+    }
+}
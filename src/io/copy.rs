@@ -0,0 +1,157 @@
+use std::io::{self, Read, Write};
+use std::net::Shutdown;
+
+use super::buf_pool::PooledBuf;
+
+/// A stream that can be cloned and half closed
+///
+/// this is what `copy_bidirectional` needs, each direction is driven by its
+/// own coroutine on a clone of the stream
+pub trait DuplexStream: Read + Write + Send + Sized + 'static {
+    fn try_clone(&self) -> io::Result<Self>;
+    fn shutdown(&self, how: Shutdown) -> io::Result<()>;
+}
+
+impl DuplexStream for crate::net::TcpStream {
+    fn try_clone(&self) -> io::Result<Self> {
+        crate::net::TcpStream::try_clone(self)
+    }
+
+    fn shutdown(&self, how: Shutdown) -> io::Result<()> {
+        crate::net::TcpStream::shutdown(self, how)
+    }
+}
+
+#[cfg(unix)]
+impl DuplexStream for crate::os::unix::net::UnixStream {
+    fn try_clone(&self) -> io::Result<Self> {
+        crate::os::unix::net::UnixStream::try_clone(self)
+    }
+
+    fn shutdown(&self, how: Shutdown) -> io::Result<()> {
+        crate::os::unix::net::UnixStream::shutdown(self, how)
+    }
+}
+
+/// copy the entire content of the reader into the writer,
+/// return the bytes copied
+///
+/// same as `std::io::copy` except that the buffer is taken from a global
+/// pool, instead of a new buffer for each call. the mco streams park the
+/// coroutine when they would block, so the copy doesn't block the worker
+pub fn copy<R, W>(reader: &mut R, writer: &mut W) -> io::Result<u64>
+where
+    R: Read + ?Sized,
+    W: Write + ?Sized,
+{
+    let mut buf = PooledBuf::new();
+    let mut total = 0;
+    loop {
+        let n = match reader.read(&mut buf) {
+            Ok(0) => return Ok(total),
+            Ok(n) => n,
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        writer.write_all(&buf[..n])?;
+        total += n as u64;
+    }
+}
+
+/// copy the data between the two streams in both directions until both of
+/// them reach EOF, return the bytes copied from `a` to `b` and from `b` to `a`
+///
+/// the EOF of one side is passed to the other side with `shutdown(Write)`.
+/// if one direction fails, both streams are shut down to stop the other one.
+/// for the tcp streams `net::copy_bidirectional` moves the data with
+/// `splice(2)` on linux
+pub fn copy_bidirectional<A, B>(a: &A, b: &B) -> io::Result<(u64, u64)>
+where
+    A: DuplexStream,
+    B: DuplexStream,
+{
+    copy_bidirectional_with(a, b, copy, copy)
+}
+
+// each direction runs `copy` in its own coroutine, which waits on its own
+// io data of the cloned stream
+pub(crate) fn copy_bidirectional_with<A, B>(
+    a: &A,
+    b: &B,
+    a_to_b: fn(&mut A, &mut B) -> io::Result<u64>,
+    b_to_a: fn(&mut B, &mut A) -> io::Result<u64>,
+) -> io::Result<(u64, u64)>
+where
+    A: DuplexStream,
+    B: DuplexStream,
+{
+    let (a_r, b_w) = (a.try_clone()?, b.try_clone()?);
+    let (b_r, a_w) = (b.try_clone()?, a.try_clone()?);
+    let a_to_b = crate::coroutine::spawn(move || copy_half(a_r, b_w, a_to_b));
+    let b_to_a = crate::coroutine::spawn(move || copy_half(b_r, a_w, b_to_a));
+    let a_to_b = a_to_b
+        .join()
+        .unwrap_or_else(|e| std::panic::resume_unwind(e));
+    let b_to_a = b_to_a
+        .join()
+        .unwrap_or_else(|e| std::panic::resume_unwind(e));
+    Ok((a_to_b?, b_to_a?))
+}
+
+fn copy_half<R, W>(
+    mut src: R,
+    mut dst: W,
+    copy: fn(&mut R, &mut W) -> io::Result<u64>,
+) -> io::Result<u64>
+where
+    R: DuplexStream,
+    W: DuplexStream,
+{
+    let ret = copy(&mut src, &mut dst);
+    match ret {
+        // the peer may already be gone
+        Ok(_) => drop(dst.shutdown(Shutdown::Write)),
+        Err(_) => {
+            src.shutdown(Shutdown::Both).ok();
+            dst.shutdown(Shutdown::Both).ok();
+        }
+    }
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use super::copy;
+    use std::io::{self, Read};
+
+    // returns the data in small pieces with an interruption in between
+    struct Chunked<'a>(&'a [u8], bool);
+
+    impl Read for Chunked<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.1 = !self.1;
+            if self.1 {
+                return Err(io::ErrorKind::Interrupted.into());
+            }
+            let n = buf.len().min(self.0.len()).min(3);
+            buf[..n].copy_from_slice(&self.0[..n]);
+            self.0 = &self.0[n..];
+            Ok(n)
+        }
+    }
+
+    #[test]
+    fn copy_all() {
+        let data: Vec<u8> = (0..20000u32).map(|i| i as u8).collect();
+        let mut out = Vec::new();
+        assert_eq!(copy(&mut &data[..], &mut out).unwrap(), 20000);
+        assert_eq!(out, data);
+
+        let mut out = Vec::new();
+        assert_eq!(
+            copy(&mut Chunked(b"hello world", false), &mut out).unwrap(),
+            11
+        );
+        assert_eq!(out, b"hello world");
+    }
+}
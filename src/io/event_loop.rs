@@ -1,8 +1,9 @@
+use std::cmp;
 use std::io;
 use std::sync::atomic::Ordering;
 
 use super::sys::{Selector, SysEvent};
-use crate::scheduler::WORKER_ID;
+use crate::scheduler::{get_scheduler, WORKER_ID};
 
 /// Single threaded IO event loop.
 pub struct EventLoop {
@@ -25,11 +26,22 @@ impl EventLoop {
 
         let events_buf: MaybeUninit<[SysEvent; 1024]> = MaybeUninit::uninit();
         let mut events_buf = unsafe { events_buf.assume_init() };
-        // wake up every 1 second
-        let mut next_expire = Some(1_000_000_000);
+        let scheduler = get_scheduler();
+        // wake up periodically, 1 second by default
+        let park_timeout = crate::timeout_list::dur_to_ns(scheduler.policy().park_timeout(id));
+        let mut next_expire = Some(park_timeout);
         loop {
+            scheduler.count_park(id);
             next_expire = match self.selector.select(id, &mut events_buf, next_expire) {
-                Ok(v) => v.or(Some(1_000_000_000)),
+                Ok(v) => {
+                    // the coroutine timers of this worker
+                    let t = scheduler.run_timers(id);
+                    let next = match (v, t) {
+                        (Some(v), Some(t)) => Some(cmp::min(v, t)),
+                        (v, t) => v.or(t),
+                    };
+                    next.or(Some(park_timeout))
+                }
                 Err(e) => {
                     error!("selector error={:?}", e);
                     continue;
@@ -10,8 +10,13 @@ pub(crate) mod sys;
 
 // export the generic IO wrapper
 pub mod co_io_err;
+pub mod codec;
 
+mod buf;
+mod buf_pool;
+mod copy;
 mod event_loop;
+mod timeout;
 
 use std::io;
 use std::ops::Deref;
@@ -19,8 +24,15 @@ use std::sync::atomic::{AtomicBool, Ordering};
 
 use crate::coroutine_impl::is_coroutine;
 
+pub use self::buf::{BufReader, BufStream, BufWriter};
+pub use self::copy::{copy, copy_bidirectional, DuplexStream};
+pub(crate) use self::copy::copy_bidirectional_with;
 pub(crate) use self::event_loop::EventLoop;
 pub use self::sys::co_io::CoIo;
+pub use self::timeout::{SetReadTimeout, SetWriteTimeout};
+// IOCP has no readiness for the raw handles, so no windows version
+#[cfg(unix)]
+pub use self::sys::poll_fd::PollFd;
 #[cfg(unix)]
 pub use self::sys::wait_io::WaitIo;
 pub(crate) use self::sys::{add_socket, cancel, net, IoData, Selector};
@@ -4,6 +4,7 @@ use super::EventData;
 use crate::cancel::CancelIo;
 use crate::scheduler::get_scheduler;
 use crate::std::sync::AtomicOption;
+use crate::yield_now::set_co_para;
 
 pub struct CancelIoImpl(AtomicOption<Arc<EventData>>);
 
@@ -30,4 +31,15 @@ impl CancelIo for CancelIoImpl {
         }
         Ok(())
     }
+
+    fn interrupt(&self) -> Result<(), std::io::Error> {
+        if let Some(e) = self.0.take() {
+            if let Some(mut co) = e.co.take() {
+                let err = std::io::Error::other("Canceled");
+                set_co_para(&mut co, err);
+                get_scheduler().schedule(co);
+            }
+        }
+        Ok(())
+    }
 }
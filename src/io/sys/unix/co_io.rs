@@ -178,6 +178,32 @@ impl<T: AsRawFd + Read> Read for CoIo<T> {
         yield_with(&reader);
         reader.done()
     }
+
+    fn read_vectored(&mut self, bufs: &mut [io::IoSliceMut<'_>]) -> io::Result<usize> {
+        if !self.ctx_check()? {
+            // this can't be nonblocking!!
+            return self.inner.read_vectored(bufs);
+        }
+
+        self.io.reset();
+        // this is an earlier return try for nonblocking read
+        match self.inner.read_vectored(bufs) {
+            Ok(n) => return Ok(n),
+            Err(e) => {
+                // raw_os_error is faster than kind
+                let raw_err = e.raw_os_error();
+                if raw_err == Some(libc::EAGAIN) || raw_err == Some(libc::EWOULDBLOCK) {
+                    // do nothing here
+                } else {
+                    return Err(e);
+                }
+            }
+        }
+
+        let mut reader = net_impl::SocketReadVectored::new(self, bufs, self.read_timeout.get());
+        yield_with(&reader);
+        reader.done()
+    }
 }
 
 impl<T: AsRawFd + Write> Write for CoIo<T> {
@@ -207,6 +233,32 @@ impl<T: AsRawFd + Write> Write for CoIo<T> {
         writer.done()
     }
 
+    fn write_vectored(&mut self, bufs: &[io::IoSlice<'_>]) -> io::Result<usize> {
+        if !self.ctx_check()? {
+            // this can't be nonblocking!!
+            return self.inner.write_vectored(bufs);
+        }
+
+        self.io.reset();
+        // this is an earlier return try for nonblocking write
+        match self.inner.write_vectored(bufs) {
+            Ok(n) => return Ok(n),
+            Err(e) => {
+                // raw_os_error is faster than kind
+                let raw_err = e.raw_os_error();
+                if raw_err == Some(libc::EAGAIN) || raw_err == Some(libc::EWOULDBLOCK) {
+                    // do nothing here
+                } else {
+                    return Err(e);
+                }
+            }
+        }
+
+        let mut writer = net_impl::SocketWriteVectored::new(self, bufs, self.write_timeout.get());
+        yield_with(&writer);
+        writer.done()
+    }
+
     fn flush(&mut self) -> io::Result<()> {
         self.inner.flush()
     }
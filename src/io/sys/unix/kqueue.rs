@@ -8,7 +8,7 @@ use super::{timeout_handler, EventData, IoData, TimerList};
 use crate::coroutine_impl::run_coroutine;
 use crate::scheduler::get_scheduler;
 use crate::std::queue::seg_queue::SegQueue as mpsc;
-use crate::timeout_list::{now, ns_to_dur};
+use crate::timeout_list::{coarse_deadline, now, ns_to_dur};
 
 pub type SysEvent = libc::kevent;
 
@@ -294,7 +294,7 @@ impl Selector {
         // //info!("io timeout = {:?}", dur);
         let (h, b_new) = unsafe { self.vec.get_unchecked(id) }
             .timer_list
-            .add_timer(timeout, io.timer_data());
+            .add_timer_at(coarse_deadline(timeout), io.timer_data());
         if b_new {
             // wakeup the event loop thread to recall the next wait timeout
             self.wakeup(id);
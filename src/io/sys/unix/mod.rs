@@ -17,6 +17,7 @@ mod select;
 pub mod cancel;
 pub mod co_io;
 pub mod net;
+pub mod poll_fd;
 pub mod wait_io;
 
 use std::cell::RefCell;
@@ -28,6 +29,7 @@ use std::{fmt, io, ptr};
 
 use crate::coroutine_impl::{run_coroutine, CoroutineImpl};
 use crate::scheduler::get_scheduler;
+use crate::std::context::interrupted_error;
 use crate::std::sync::AtomicOption;
 use crate::timeout_list::{TimeOutList, TimeoutHandle};
 use crate::yield_now::{get_co_para, set_co_para};
@@ -50,7 +52,7 @@ fn del_socket(io: &IoData) {
 fn co_io_result() -> io::Result<()> {
     match get_co_para() {
         None => Ok(()),
-        Some(err) => Err(err),
+        Some(err) => Err(interrupted_error(err)),
     }
 }
 
@@ -1,4 +1,5 @@
 mod socket_read;
+mod socket_read_vectored;
 mod socket_write;
 mod socket_write_vectored;
 mod tcp_listener_accpet;
@@ -11,6 +12,7 @@ mod unix_send_to;
 mod unix_stream_connect;
 
 pub use self::socket_read::SocketRead;
+pub use self::socket_read_vectored::SocketReadVectored;
 pub use self::socket_write::SocketWrite;
 pub use self::socket_write_vectored::SocketWriteVectored;
 pub use self::tcp_listener_accpet::TcpListenerAccept;
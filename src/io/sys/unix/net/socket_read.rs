@@ -3,7 +3,8 @@ use std::sync::atomic::Ordering;
 use std::time::Duration;
 
 use super::super::{co_io_result, from_nix_error, IoData};
-use crate::coroutine_impl::{co_get_handle, CoroutineImpl, EventSource};
+use crate::cancel::CancelGuard;
+use crate::coroutine_impl::{co_get_handle, current_cancel_data, CoroutineImpl, EventSource};
 use crate::io::AsIoData;
 use crate::scheduler::get_scheduler;
 use crate::yield_now::yield_with;
@@ -13,6 +14,10 @@ pub struct SocketRead<'a> {
     io_data: &'a IoData,
     buf: &'a mut [u8],
     timeout: Option<Duration>,
+    // leave the data in the receive queue
+    peek: bool,
+    // the read observes the cancellation token
+    _interrupt: CancelGuard,
 }
 
 impl<'a> SocketRead<'a> {
@@ -21,6 +26,15 @@ impl<'a> SocketRead<'a> {
             io_data: s.as_io_data(),
             buf,
             timeout,
+            peek: false,
+            _interrupt: current_cancel_data().interruptible(),
+        }
+    }
+
+    pub fn new_peek<T: AsIoData>(s: &'a T, buf: &'a mut [u8], timeout: Option<Duration>) -> Self {
+        SocketRead {
+            peek: true,
+            ..Self::new(s, buf, timeout)
         }
     }
 
@@ -32,7 +46,24 @@ impl<'a> SocketRead<'a> {
             self.io_data.io_flag.store(false, Ordering::Relaxed);
 
             // finish the read operation
-            match read(self.io_data.fd, self.buf) {
+            let ret = if self.peek {
+                let n = unsafe {
+                    libc::recv(
+                        self.io_data.fd,
+                        self.buf.as_mut_ptr() as *mut libc::c_void,
+                        self.buf.len(),
+                        libc::MSG_PEEK,
+                    )
+                };
+                if n < 0 {
+                    Err(nix::errno::Errno::last())
+                } else {
+                    Ok(n as usize)
+                }
+            } else {
+                read(self.io_data.fd, self.buf)
+            };
+            match ret {
                 Ok(n) => return Ok(n),
                 Err(e) => {
                     if e == nix::errno::Errno::EAGAIN {
@@ -81,6 +112,8 @@ impl<'a> EventSource for SocketRead<'a> {
         // re-check the cancel status
         if cancel.is_canceled() {
             let _ = cancel.cancel();
+        } else if cancel.is_interrupted() {
+            cancel.interrupt();
         }
     }
 }
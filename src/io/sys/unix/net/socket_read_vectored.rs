@@ -0,0 +1,97 @@
+use std::io::{self, IoSliceMut};
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+use super::super::{co_io_result, IoData};
+use crate::cancel::CancelGuard;
+use crate::coroutine_impl::{co_get_handle, current_cancel_data, CoroutineImpl, EventSource};
+use crate::io::AsIoData;
+use crate::scheduler::get_scheduler;
+use crate::yield_now::yield_with;
+
+pub struct SocketReadVectored<'a, 'b> {
+    io_data: &'a IoData,
+    bufs: &'a mut [IoSliceMut<'b>],
+    timeout: Option<Duration>,
+    // the read observes the cancellation token
+    _interrupt: CancelGuard,
+}
+
+impl<'a, 'b> SocketReadVectored<'a, 'b> {
+    pub fn new<T: AsIoData>(
+        s: &'a T,
+        bufs: &'a mut [IoSliceMut<'b>],
+        timeout: Option<Duration>,
+    ) -> Self {
+        SocketReadVectored {
+            io_data: s.as_io_data(),
+            bufs,
+            timeout,
+            _interrupt: current_cancel_data().interruptible(),
+        }
+    }
+
+    pub fn done(&mut self) -> io::Result<usize> {
+        loop {
+            co_io_result()?;
+
+            // clear the io_flag
+            self.io_data.io_flag.store(false, Ordering::Relaxed);
+
+            // IoSliceMut is guaranteed to be ABI compatible with iovec
+            let ret = unsafe {
+                libc::readv(
+                    self.io_data.fd,
+                    self.bufs.as_mut_ptr() as *mut libc::iovec as *const libc::iovec,
+                    std::cmp::min(self.bufs.len(), libc::c_int::MAX as usize) as libc::c_int,
+                )
+            };
+            if ret >= 0 {
+                return Ok(ret as usize);
+            }
+            let e = io::Error::last_os_error();
+            let raw_err = e.raw_os_error();
+            if raw_err == Some(libc::EAGAIN) || raw_err == Some(libc::EWOULDBLOCK) {
+                // do nothing here
+            } else {
+                return Err(e);
+            }
+
+            if self.io_data.io_flag.swap(false, Ordering::Relaxed) {
+                continue;
+            }
+
+            // the result is still WouldBlock, need to try again
+            yield_with(self);
+        }
+    }
+}
+
+impl<'a, 'b> EventSource for SocketReadVectored<'a, 'b> {
+    fn subscribe(&mut self, co: CoroutineImpl) {
+        let handle = co_get_handle(&co);
+        let cancel = handle.get_cancel();
+        let io_data = (*self.io_data).clone();
+
+        if let Some(dur) = self.timeout {
+            get_scheduler()
+                .get_selector()
+                .add_io_timer(self.io_data, dur);
+        }
+        self.io_data.co.swap(co);
+
+        // there is event, re-run the coroutine
+        if io_data.io_flag.load(Ordering::Acquire) {
+            return io_data.schedule();
+        }
+
+        // register the cancel io data
+        cancel.set_io(io_data);
+        // re-check the cancel status
+        if cancel.is_canceled() {
+            let _ = cancel.cancel();
+        } else if cancel.is_interrupted() {
+            cancel.interrupt();
+        }
+    }
+}
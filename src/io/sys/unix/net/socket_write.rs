@@ -3,7 +3,8 @@ use std::sync::atomic::Ordering;
 use std::time::Duration;
 
 use super::super::{co_io_result, from_nix_error, IoData};
-use crate::coroutine_impl::{CoroutineImpl, EventSource};
+use crate::cancel::CancelGuard;
+use crate::coroutine_impl::{co_get_handle, current_cancel_data, CoroutineImpl, EventSource};
 use crate::io::AsIoData;
 use crate::scheduler::get_scheduler;
 use crate::yield_now::yield_with;
@@ -13,6 +14,8 @@ pub struct SocketWrite<'a> {
     io_data: &'a IoData,
     buf: &'a [u8],
     timeout: Option<Duration>,
+    // the write observes the cancellation token
+    _interrupt: CancelGuard,
 }
 
 impl<'a> SocketWrite<'a> {
@@ -21,6 +24,7 @@ impl<'a> SocketWrite<'a> {
             io_data: s.as_io_data(),
             buf,
             timeout,
+            _interrupt: current_cancel_data().interruptible(),
         }
     }
 
@@ -54,6 +58,8 @@ impl<'a> SocketWrite<'a> {
 
 impl<'a> EventSource for SocketWrite<'a> {
     fn subscribe(&mut self, co: CoroutineImpl) {
+        let handle = co_get_handle(&co);
+        let cancel = handle.get_cancel();
         let io_data = (*self.io_data).clone();
 
         if let Some(dur) = self.timeout {
@@ -65,7 +71,16 @@ impl<'a> EventSource for SocketWrite<'a> {
 
         // there is event, re-run the coroutine
         if io_data.io_flag.load(Ordering::Acquire) {
-            io_data.schedule();
+            return io_data.schedule();
+        }
+
+        // register the cancel io data
+        cancel.set_io(io_data);
+        // re-check the cancel status
+        if cancel.is_canceled() {
+            let _ = cancel.cancel();
+        } else if cancel.is_interrupted() {
+            cancel.interrupt();
         }
     }
 }
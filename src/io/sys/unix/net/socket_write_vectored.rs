@@ -3,52 +3,54 @@ use std::sync::atomic::Ordering;
 use std::time::Duration;
 
 use super::super::{co_io_result, IoData};
-use crate::coroutine_impl::{CoroutineImpl, EventSource};
+use crate::cancel::CancelGuard;
+use crate::coroutine_impl::{co_get_handle, current_cancel_data, CoroutineImpl, EventSource};
 use crate::io::AsIoData;
 use crate::scheduler::get_scheduler;
 use crate::yield_now::yield_with;
 
-pub struct SocketWriteVectored<'a> {
+pub struct SocketWriteVectored<'a, 'b> {
     io_data: &'a IoData,
-    bufs: &'a [IoSlice<'a>],
-    socket: &'a std::net::TcpStream,
+    bufs: &'a [IoSlice<'b>],
     timeout: Option<Duration>,
+    // the write observes the cancellation token
+    _interrupt: CancelGuard,
 }
 
-impl<'a> SocketWriteVectored<'a> {
-    pub fn new<T: AsIoData>(
-        s: &'a T,
-        socket: &'a std::net::TcpStream,
-        bufs: &'a [IoSlice<'a>],
-        timeout: Option<Duration>,
-    ) -> Self {
+impl<'a, 'b> SocketWriteVectored<'a, 'b> {
+    pub fn new<T: AsIoData>(s: &'a T, bufs: &'a [IoSlice<'b>], timeout: Option<Duration>) -> Self {
         SocketWriteVectored {
             io_data: s.as_io_data(),
             bufs,
-            socket,
             timeout,
+            _interrupt: current_cancel_data().interruptible(),
         }
     }
 
     pub fn done(&mut self) -> io::Result<usize> {
-        use std::io::Write;
-
         loop {
             co_io_result()?;
 
             // clear the io_flag
             self.io_data.io_flag.store(false, Ordering::Relaxed);
 
-            match self.socket.write_vectored(self.bufs) {
-                Ok(n) => return Ok(n),
-                Err(e) => {
-                    let raw_err = e.raw_os_error();
-                    if raw_err == Some(libc::EAGAIN) || raw_err == Some(libc::EWOULDBLOCK) {
-                        // do nothing here
-                    } else {
-                        return Err(e);
-                    }
-                }
+            // IoSlice is guaranteed to be ABI compatible with iovec
+            let ret = unsafe {
+                libc::writev(
+                    self.io_data.fd,
+                    self.bufs.as_ptr() as *const libc::iovec,
+                    std::cmp::min(self.bufs.len(), libc::c_int::MAX as usize) as libc::c_int,
+                )
+            };
+            if ret >= 0 {
+                return Ok(ret as usize);
+            }
+            let e = io::Error::last_os_error();
+            let raw_err = e.raw_os_error();
+            if raw_err == Some(libc::EAGAIN) || raw_err == Some(libc::EWOULDBLOCK) {
+                // do nothing here
+            } else {
+                return Err(e);
             }
 
             if self.io_data.io_flag.swap(false, Ordering::Relaxed) {
@@ -61,8 +63,10 @@ impl<'a> SocketWriteVectored<'a> {
     }
 }
 
-impl<'a> EventSource for SocketWriteVectored<'a> {
+impl<'a, 'b> EventSource for SocketWriteVectored<'a, 'b> {
     fn subscribe(&mut self, co: CoroutineImpl) {
+        let handle = co_get_handle(&co);
+        let cancel = handle.get_cancel();
         let io_data = (*self.io_data).clone();
 
         if let Some(dur) = self.timeout {
@@ -74,7 +78,16 @@ impl<'a> EventSource for SocketWriteVectored<'a> {
 
         // there is event, re-run the coroutine
         if io_data.io_flag.load(Ordering::Acquire) {
-            io_data.schedule();
+            return io_data.schedule();
+        }
+
+        // register the cancel io data
+        cancel.set_io(io_data);
+        // re-check the cancel status
+        if cancel.is_canceled() {
+            let _ = cancel.cancel();
+        } else if cancel.is_interrupted() {
+            cancel.interrupt();
         }
     }
 }
@@ -1,23 +1,28 @@
 use std::net::SocketAddr;
 use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant};
 use std::{self, io};
 
 use super::super::{add_socket, co_io_result, IoData};
 use crate::coroutine_impl::{co_get_handle, CoroutineImpl, EventSource};
 use crate::io::AsIoData;
 use crate::net::{TcpListener, TcpStream};
+use crate::scheduler::get_scheduler;
 use crate::yield_now::yield_with;
 
 pub struct TcpListenerAccept<'a> {
     io_data: &'a IoData,
     socket: &'a std::net::TcpListener,
+    // the accept may be re-registered, the timeout is for the whole accept
+    deadline: Option<Instant>,
 }
 
 impl<'a> TcpListenerAccept<'a> {
-    pub fn new(socket: &'a TcpListener) -> io::Result<Self> {
+    pub fn new(socket: &'a TcpListener, timeout: Option<Duration>) -> io::Result<Self> {
         Ok(TcpListenerAccept {
             io_data: socket.as_io_data(),
             socket: socket.inner(),
+            deadline: timeout.map(|dur| Instant::now() + dur),
         })
     }
 
@@ -59,7 +64,13 @@ impl<'a> EventSource for TcpListenerAccept<'a> {
         let handle = co_get_handle(&co);
         let cancel = handle.get_cancel();
         let io_data = (*self.io_data).clone();
-        // if there is no timer we don't need to call add_io_timer
+
+        if let Some(deadline) = self.deadline {
+            let dur = deadline.saturating_duration_since(Instant::now());
+            get_scheduler()
+                .get_selector()
+                .add_io_timer(self.io_data, dur);
+        }
         self.io_data.co.swap(co);
 
         // there is event happened
@@ -1,10 +1,11 @@
 use std::io;
 use std::net::{SocketAddr, ToSocketAddrs};
 use std::sync::atomic::Ordering;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use super::super::{add_socket, co_io_result, IoData};
-use crate::coroutine_impl::{co_get_handle, CoroutineImpl, EventSource};
+use crate::cancel::CancelGuard;
+use crate::coroutine_impl::{co_get_handle, current_cancel_data, CoroutineImpl, EventSource};
 use crate::io::OptionCell;
 use crate::net::TcpStream;
 use crate::scheduler::get_scheduler;
@@ -14,9 +15,12 @@ use socket2::Socket;
 pub struct TcpStreamConnect {
     io_data: OptionCell<IoData>,
     stream: OptionCell<Socket>,
-    timeout: Option<Duration>,
+    // the connect may be re-registered, the timeout is for the whole connect
+    deadline: Option<Instant>,
     addr: SocketAddr,
     is_connected: bool,
+    // the connect observes the cancellation token
+    _interrupt: CancelGuard,
 }
 
 impl TcpStreamConnect {
@@ -41,9 +45,10 @@ impl TcpStreamConnect {
                 add_socket(&stream).map(|io| TcpStreamConnect {
                     io_data: OptionCell::new(io),
                     stream: OptionCell::new(stream),
-                    timeout,
+                    deadline: timeout.map(|dur| Instant::now() + dur),
                     addr,
                     is_connected: false,
+                    _interrupt: current_cancel_data().interruptible(),
                 })
             })
     }
@@ -106,7 +111,8 @@ impl EventSource for TcpStreamConnect {
         let cancel = handle.get_cancel();
         let io_data = self.io_data.clone();
 
-        if let Some(dur) = self.timeout {
+        if let Some(deadline) = self.deadline {
+            let dur = deadline.saturating_duration_since(Instant::now());
             get_scheduler()
                 .get_selector()
                 .add_io_timer(&self.io_data, dur);
@@ -123,6 +129,8 @@ impl EventSource for TcpStreamConnect {
         // re-check the cancel status
         if cancel.is_canceled() {
             let _ = cancel.cancel();
+        } else if cancel.is_interrupted() {
+            cancel.interrupt();
         }
     }
 }
@@ -4,7 +4,8 @@ use std::time::Duration;
 use std::{self, io};
 
 use super::super::{co_io_result, IoData};
-use crate::coroutine_impl::{co_get_handle, CoroutineImpl, EventSource};
+use crate::cancel::CancelGuard;
+use crate::coroutine_impl::{co_get_handle, current_cancel_data, CoroutineImpl, EventSource};
 use crate::io::AsIoData;
 use crate::net::UdpSocket;
 use crate::scheduler::get_scheduler;
@@ -15,6 +16,10 @@ pub struct UdpRecvFrom<'a> {
     buf: &'a mut [u8],
     socket: &'a std::net::UdpSocket,
     timeout: Option<Duration>,
+    // leave the datagram in the queue
+    peek: bool,
+    // the recv observes the cancellation token
+    _interrupt: CancelGuard,
 }
 
 impl<'a> UdpRecvFrom<'a> {
@@ -24,6 +29,15 @@ impl<'a> UdpRecvFrom<'a> {
             buf,
             socket: socket.inner(),
             timeout: socket.read_timeout().unwrap(),
+            peek: false,
+            _interrupt: current_cancel_data().interruptible(),
+        }
+    }
+
+    pub fn new_peek(socket: &'a UdpSocket, buf: &'a mut [u8]) -> Self {
+        UdpRecvFrom {
+            peek: true,
+            ..Self::new(socket, buf)
         }
     }
 
@@ -34,7 +48,12 @@ impl<'a> UdpRecvFrom<'a> {
             // clear the io_flag
             self.io_data.io_flag.store(false, Ordering::Relaxed);
 
-            match self.socket.recv_from(self.buf) {
+            let ret = if self.peek {
+                self.socket.peek_from(self.buf)
+            } else {
+                self.socket.recv_from(self.buf)
+            };
+            match ret {
                 Ok(n) => return Ok(n),
                 Err(e) => {
                     // raw_os_error is faster than kind
@@ -80,6 +99,8 @@ impl<'a> EventSource for UdpRecvFrom<'a> {
         // re-check the cancel status
         if cancel.is_canceled() {
             let _ = cancel.cancel();
+        } else if cancel.is_interrupted() {
+            cancel.interrupt();
         }
     }
 }
@@ -4,7 +4,8 @@ use std::time::Duration;
 use std::{self, io};
 
 use super::super::{co_io_result, IoData};
-use crate::coroutine_impl::{CoroutineImpl, EventSource};
+use crate::cancel::CancelGuard;
+use crate::coroutine_impl::{co_get_handle, current_cancel_data, CoroutineImpl, EventSource};
 use crate::io::AsIoData;
 use crate::net::UdpSocket;
 use crate::scheduler::get_scheduler;
@@ -16,6 +17,8 @@ pub struct UdpSendTo<'a, A: ToSocketAddrs> {
     socket: &'a std::net::UdpSocket,
     addr: A,
     timeout: Option<Duration>,
+    // the send observes the cancellation token
+    _interrupt: CancelGuard,
 }
 
 impl<'a, A: ToSocketAddrs> UdpSendTo<'a, A> {
@@ -26,6 +29,7 @@ impl<'a, A: ToSocketAddrs> UdpSendTo<'a, A> {
             socket: socket.inner(),
             addr,
             timeout: socket.write_timeout().unwrap(),
+            _interrupt: current_cancel_data().interruptible(),
         })
     }
 
@@ -61,6 +65,8 @@ impl<'a, A: ToSocketAddrs> UdpSendTo<'a, A> {
 
 impl<'a, A: ToSocketAddrs> EventSource for UdpSendTo<'a, A> {
     fn subscribe(&mut self, co: CoroutineImpl) {
+        let handle = co_get_handle(&co);
+        let cancel = handle.get_cancel();
         let io_data = (*self.io_data).clone();
 
         if let Some(dur) = self.timeout {
@@ -72,7 +78,16 @@ impl<'a, A: ToSocketAddrs> EventSource for UdpSendTo<'a, A> {
 
         // there is event, re-run the coroutine
         if io_data.io_flag.load(Ordering::Acquire) {
-            io_data.schedule();
+            return io_data.schedule();
+        }
+
+        // register the cancel io data
+        cancel.set_io(io_data);
+        // re-check the cancel status
+        if cancel.is_canceled() {
+            let _ = cancel.cancel();
+        } else if cancel.is_interrupted() {
+            cancel.interrupt();
         }
     }
 }
@@ -4,7 +4,8 @@ use std::time::Duration;
 use std::{self, io};
 
 use super::super::{co_io_result, IoData};
-use crate::coroutine_impl::{co_get_handle, CoroutineImpl, EventSource};
+use crate::cancel::CancelGuard;
+use crate::coroutine_impl::{co_get_handle, current_cancel_data, CoroutineImpl, EventSource};
 use crate::io::AsIoData;
 use crate::os::unix::net::UnixDatagram;
 use crate::scheduler::get_scheduler;
@@ -15,6 +16,8 @@ pub struct UnixRecvFrom<'a> {
     buf: &'a mut [u8],
     socket: &'a std::os::unix::net::UnixDatagram,
     timeout: Option<Duration>,
+    // the recv observes the cancellation token
+    _interrupt: CancelGuard,
 }
 
 impl<'a> UnixRecvFrom<'a> {
@@ -24,6 +27,7 @@ impl<'a> UnixRecvFrom<'a> {
             buf,
             socket: socket.0.inner(),
             timeout: socket.0.read_timeout().unwrap(),
+            _interrupt: current_cancel_data().interruptible(),
         }
     }
 
@@ -80,6 +84,8 @@ impl<'a> EventSource for UnixRecvFrom<'a> {
         // re-check the cancel status
         if cancel.is_canceled() {
             let _ = cancel.cancel();
+        } else if cancel.is_interrupted() {
+            cancel.interrupt();
         }
     }
 }
@@ -20,7 +20,10 @@ pub struct UnixStreamConnect {
 
 impl UnixStreamConnect {
     pub fn new<P: AsRef<Path>>(path: P) -> io::Result<Self> {
-        let path = SockAddr::unix(path)?;
+        Self::from_addr(SockAddr::unix(path)?)
+    }
+
+    pub fn from_addr(path: SockAddr) -> io::Result<Self> {
         let socket = Socket::new(Domain::UNIX, Type::STREAM, None)?;
         // before yield we must set the socket to nonblocking mode and registe to selector
         socket.set_nonblocking(true)?;
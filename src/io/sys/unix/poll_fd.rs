@@ -0,0 +1,187 @@
+//! # Readiness of raw fds
+//! `PollFd` registers any fd to the selector, so that a coroutine can
+//! wait for it to be readable or writable without blocking the thread
+//!
+//! it's only available on unix. the windows selector is the completion
+//! based IOCP, which has no readiness for an arbitrary `RawSocket` or
+//! handle, use `CoIo` for the sockets there
+//!
+use std::io;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::sync::atomic::Ordering;
+
+use super::{add_socket, co_io_result, IoData};
+use crate::cancel::CancelGuard;
+use crate::coroutine_impl::{
+    co_get_handle, current_cancel_data, is_coroutine, CoroutineImpl, EventSource,
+};
+use crate::io::AsIoData;
+use crate::yield_now::yield_with;
+
+// check the readiness of the fd with `poll(2)`, `-1` timeout blocks the thread
+fn poll(fd: RawFd, events: libc::c_short, timeout: libc::c_int) -> io::Result<bool> {
+    let mut pfd = libc::pollfd {
+        fd,
+        events,
+        revents: 0,
+    };
+    loop {
+        match unsafe { libc::poll(&mut pfd, 1, timeout) } {
+            -1 => {
+                let err = io::Error::last_os_error();
+                if err.kind() != io::ErrorKind::Interrupted {
+                    return Err(err);
+                }
+            }
+            0 => return Ok(false),
+            // an error or hangup also wakes up the waiter, the next io reports it
+            _ => return Ok(true),
+        }
+    }
+}
+
+struct PollWait<'a> {
+    io_data: &'a IoData,
+    // the wait observes the cancellation token
+    _interrupt: CancelGuard,
+}
+
+impl<'a> EventSource for PollWait<'a> {
+    fn subscribe(&mut self, co: CoroutineImpl) {
+        let handle = co_get_handle(&co);
+        let cancel = handle.get_cancel();
+        let io_data = (*self.io_data).clone();
+        self.io_data.co.swap(co);
+
+        // there is event, re-run the coroutine
+        if io_data.io_flag.load(Ordering::Acquire) {
+            return io_data.schedule();
+        }
+
+        // register the cancel io data
+        cancel.set_io(io_data);
+        // re-check the cancel status
+        if cancel.is_canceled() {
+            let _ = cancel.cancel();
+        } else if cancel.is_interrupted() {
+            cancel.interrupt();
+        }
+    }
+}
+
+/// Wrapper that registers a raw fd to the event loop
+///
+/// any fd that works with `epoll`/`kqueue` can be used, e.g. an inotify,
+/// a timerfd or the fd of a C library. the fd should be in non blocking
+/// mode, `PollFd` only waits for the readiness, the io is done by the user
+/// on the inner object. out of coroutine context the waiting blocks the thread.
+/// unix only, there is no windows version.
+///
+/// ```no_run
+/// use std::io::Read;
+/// use std::os::unix::net::UnixStream;
+/// use mco::io::PollFd;
+///
+/// let (a, _b) = UnixStream::pair().unwrap();
+/// a.set_nonblocking(true).unwrap();
+/// let fd = PollFd::new(a).unwrap();
+/// let mut buf = [0u8; 64];
+/// let n = fd.read_with(|mut s| s.read(&mut buf)).unwrap();
+/// ```
+#[derive(Debug)]
+pub struct PollFd<T: AsRawFd> {
+    inner: T,
+    io: IoData,
+}
+
+impl<T: AsRawFd> PollFd<T> {
+    /// register the fd to the selector
+    pub fn new(inner: T) -> io::Result<Self> {
+        let io = add_socket(&inner)?;
+        Ok(PollFd { inner, io })
+    }
+
+    pub fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+
+    /// unregister the fd and return the inner object
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    /// wait until the fd is readable, a hangup or an error also counts
+    pub fn readable(&self) -> io::Result<()> {
+        self.wait(libc::POLLIN)
+    }
+
+    /// wait until the fd is writable, a hangup or an error also counts
+    pub fn writable(&self) -> io::Result<()> {
+        self.wait(libc::POLLOUT)
+    }
+
+    /// run the non blocking read `f` until it doesn't return `WouldBlock`
+    pub fn read_with<R, F>(&self, f: F) -> io::Result<R>
+    where
+        F: FnMut(&T) -> io::Result<R>,
+    {
+        self.io_with(libc::POLLIN, f)
+    }
+
+    /// run the non blocking write `f` until it doesn't return `WouldBlock`
+    pub fn write_with<R, F>(&self, f: F) -> io::Result<R>
+    where
+        F: FnMut(&T) -> io::Result<R>,
+    {
+        self.io_with(libc::POLLOUT, f)
+    }
+
+    fn io_with<R, F>(&self, events: libc::c_short, mut f: F) -> io::Result<R>
+    where
+        F: FnMut(&T) -> io::Result<R>,
+    {
+        loop {
+            match f(&self.inner) {
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => self.wait(events)?,
+                ret => return ret,
+            }
+        }
+    }
+
+    fn wait(&self, events: libc::c_short) -> io::Result<()> {
+        let fd = self.inner.as_raw_fd();
+        if !is_coroutine() {
+            return poll(fd, events, -1).map(|_| ());
+        }
+
+        loop {
+            // the selector wakes up the coroutine on any event of the fd
+            self.io.reset();
+            if poll(fd, events, 0)? {
+                return Ok(());
+            }
+            let waiter = PollWait {
+                io_data: &self.io,
+                _interrupt: current_cancel_data().interruptible(),
+            };
+            yield_with(&waiter);
+            co_io_result()?;
+        }
+    }
+}
+
+impl<T: AsRawFd> AsIoData for PollFd<T> {
+    fn as_io_data(&self) -> &IoData {
+        &self.io
+    }
+}
+
+impl<T: AsRawFd> AsRawFd for PollFd<T> {
+    fn as_raw_fd(&self) -> RawFd {
+        self.inner.as_raw_fd()
+    }
+}
@@ -6,7 +6,7 @@ use std::{io, ptr};
 
 use crate::coroutine_impl::{run_coroutine, CoroutineImpl};
 use crate::scheduler::get_scheduler;
-use crate::timeout_list::{now, ns_to_dur, TimeOutList, TimeoutHandle};
+use crate::timeout_list::{coarse_deadline, now, ns_to_dur, TimeOutList, TimeoutHandle};
 use crate::yield_now::set_co_para;
 use miow::iocp::{CompletionPort, CompletionStatus};
 use windows_sys::Win32::Foundation::*;
@@ -206,6 +206,16 @@ impl Selector {
             .unwrap();
     }
 
+    // post the completion of the io that is done without a packet, so that
+    // the coroutine is resumed by the selector like the other io
+    #[inline]
+    pub fn post_done(&self, io: &mut EventData) -> io::Result<()> {
+        let id = (io.handle as usize % self.vec.len()) >> 2;
+        unsafe { self.vec.get_unchecked(id) }
+            .port
+            .post(CompletionStatus::new(0, 0, io.get_overlapped() as *mut _))
+    }
+
     // register file handle to the iocp
     #[inline]
     pub fn add_socket<T: AsRawSocket + ?Sized>(&self, t: &T) -> io::Result<()> {
@@ -222,7 +232,7 @@ impl Selector {
         // //info!("io timeout = {:?}", dur);
         let (h, b_new) = unsafe { self.vec.get_unchecked(id) }
             .timer_list
-            .add_timer(timeout, io.timer_data());
+            .add_timer_at(coarse_deadline(timeout), io.timer_data());
         if b_new {
             // wakeup the event loop thread to recall the next wait timeout
             self.wakeup(0);
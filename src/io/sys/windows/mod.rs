@@ -16,9 +16,10 @@ pub mod cancel;
 pub mod co_io;
 mod iocp;
 pub mod net;
-mod pipe;
+pub mod pipe;
 
 use crate::scheduler::get_scheduler;
+use crate::std::context::interrupted_error;
 use crate::yield_now::get_co_para;
 use std::os::windows::io::AsRawSocket;
 use std::{fmt, io};
@@ -53,11 +54,28 @@ pub fn add_socket<T: AsRawSocket + ?Sized>(t: &T) -> io::Result<IoData> {
     get_scheduler().get_selector().add_socket(t).map(|_| IoData)
 }
 
+// abort all the pending overlapped io of the socket
+#[inline]
+pub fn cancel_pending_io<T: AsRawSocket + ?Sized>(t: &T) -> io::Result<()> {
+    use windows_sys::Win32::Foundation::{ERROR_NOT_FOUND, HANDLE};
+    use windows_sys::Win32::System::IO::CancelIoEx;
+
+    let ret = unsafe { CancelIoEx(t.as_raw_socket() as HANDLE, std::ptr::null()) };
+    if ret == 0 {
+        let err = io::Error::last_os_error();
+        // nothing is pending
+        if err.raw_os_error() != Some(ERROR_NOT_FOUND as i32) {
+            return Err(err);
+        }
+    }
+    Ok(())
+}
+
 // deal with the io result
 #[inline]
 fn co_io_result(io: &EventData) -> io::Result<usize> {
     match get_co_para() {
-        Some(err) => Err(err),
+        Some(err) => Err(interrupted_error(err)),
         None => Ok(io.get_io_size()),
     }
 }
@@ -1,12 +1,16 @@
 mod socket_read;
+mod socket_read_vectored;
 mod socket_write;
+mod socket_write_vectored;
 mod tcp_listener_accpet;
 mod tcp_stream_connect;
 mod udp_recv_from;
 mod udp_send_to;
 
 pub use self::socket_read::SocketRead;
+pub use self::socket_read_vectored::SocketReadVectored;
 pub use self::socket_write::SocketWrite;
+pub use self::socket_write_vectored::SocketWriteVectored;
 pub use self::tcp_listener_accpet::TcpListenerAccept;
 pub use self::tcp_stream_connect::TcpStreamConnect;
 pub use self::udp_recv_from::UdpRecvFrom;
@@ -1,14 +1,18 @@
 use std::os::windows::io::{AsRawSocket, FromRawSocket, IntoRawSocket, RawSocket};
 use std::time::Duration;
-use std::{self, io};
+use std::{self, io, ptr};
 
 use super::super::{co_io_result, EventData};
-use crate::coroutine_impl::{co_cancel_data, CoroutineImpl, EventSource};
+use crate::cancel::CancelGuard;
+use crate::coroutine_impl::{co_cancel_data, current_cancel_data, CoroutineImpl, EventSource};
 use crate::io::cancel::CancelIoData;
 use crate::scheduler::get_scheduler;
 use crate::std::sync::delay_drop::DelayDrop;
 use miow::net::TcpStreamExt;
 use windows_sys::Win32::Foundation::HANDLE;
+use windows_sys::Win32::Networking::WinSock::{
+    WSAGetLastError, WSARecv, MSG_PEEK, SOCKET, SOCKET_ERROR, WSABUF, WSA_IO_PENDING,
+};
 
 pub struct SocketRead<'a> {
     io_data: EventData,
@@ -16,6 +20,10 @@ pub struct SocketRead<'a> {
     socket: RawSocket,
     timeout: Option<Duration>,
     can_drop: DelayDrop,
+    // the WSARecv flags when peeking the data
+    peek_flags: Option<u32>,
+    // the read observes the cancellation token
+    _interrupt: CancelGuard,
 }
 
 impl<'a> SocketRead<'a> {
@@ -27,6 +35,15 @@ impl<'a> SocketRead<'a> {
             socket,
             timeout,
             can_drop: DelayDrop::new(),
+            peek_flags: None,
+            _interrupt: current_cancel_data().interruptible(),
+        }
+    }
+
+    pub fn new_peek<T: AsRawSocket>(s: &T, buf: &'a mut [u8], timeout: Option<Duration>) -> Self {
+        SocketRead {
+            peek_flags: Some(MSG_PEEK as u32),
+            ..Self::new(s, buf, timeout)
         }
     }
 
@@ -51,19 +68,44 @@ impl<'a> EventSource for SocketRead<'a> {
         self.io_data.co = Some(co);
 
         // call the overlapped read API
-        co_try!(s, self.io_data.co.take().expect("can't get co"), unsafe {
-            let socket: std::net::TcpStream = FromRawSocket::from_raw_socket(self.socket);
-            let ret = socket.read_overlapped(self.buf, self.io_data.get_overlapped());
-            // don't close the socket
-            socket.into_raw_socket();
-            ret
-        });
+        let ret = match self.peek_flags.as_mut() {
+            Some(flags) => unsafe {
+                let buf = WSABUF {
+                    len: self.buf.len() as u32,
+                    buf: self.buf.as_mut_ptr(),
+                };
+                let ret = WSARecv(
+                    self.socket as SOCKET,
+                    &buf,
+                    1,
+                    ptr::null_mut(),
+                    flags,
+                    self.io_data.get_overlapped(),
+                    None,
+                );
+                if ret == SOCKET_ERROR && WSAGetLastError() != WSA_IO_PENDING {
+                    Err(io::Error::last_os_error())
+                } else {
+                    Ok(None)
+                }
+            },
+            None => unsafe {
+                let socket: std::net::TcpStream = FromRawSocket::from_raw_socket(self.socket);
+                let ret = socket.read_overlapped(self.buf, self.io_data.get_overlapped());
+                // don't close the socket
+                socket.into_raw_socket();
+                ret
+            },
+        };
+        co_try!(s, self.io_data.co.take().expect("can't get co"), ret);
 
         // register the cancel io data
         cancel.set_io(CancelIoData::new(&self.io_data));
         // re-check the cancel status
         if cancel.is_canceled() {
             _ = cancel.cancel();
+        } else if cancel.is_interrupted() {
+            cancel.interrupt();
         }
     }
 }
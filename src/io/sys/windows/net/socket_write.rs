@@ -3,8 +3,11 @@ use std::os::windows::io::{AsRawSocket, FromRawSocket, IntoRawSocket, RawSocket}
 use std::time::Duration;
 
 use super::super::{co_io_result, EventData};
-use crate::coroutine_impl::{CoroutineImpl, EventSource};
+use crate::cancel::CancelGuard;
+use crate::coroutine_impl::{co_cancel_data, current_cancel_data, CoroutineImpl, EventSource};
+use crate::io::cancel::CancelIoData;
 use crate::scheduler::get_scheduler;
+use crate::std::sync::delay_drop::DelayDrop;
 use miow::net::TcpStreamExt;
 
 use windows_sys::Win32::Foundation::HANDLE;
@@ -14,6 +17,9 @@ pub struct SocketWrite<'a> {
     buf: &'a [u8],
     socket: RawSocket,
     timeout: Option<Duration>,
+    can_drop: DelayDrop,
+    // the write observes the cancellation token
+    _interrupt: CancelGuard,
 }
 
 impl<'a> SocketWrite<'a> {
@@ -24,6 +30,8 @@ impl<'a> SocketWrite<'a> {
             buf,
             socket,
             timeout,
+            can_drop: DelayDrop::new(),
+            _interrupt: current_cancel_data().interruptible(),
         }
     }
 
@@ -33,9 +41,10 @@ impl<'a> SocketWrite<'a> {
 }
 
 impl<'a> EventSource for SocketWrite<'a> {
-    #[allow(clippy::needless_return)]
     fn subscribe(&mut self, co: CoroutineImpl) {
         let s = get_scheduler();
+        let cancel = co_cancel_data(&co);
+        let _g = self.can_drop.delay_drop();
         if let Some(dur) = self.timeout {
             s.get_selector().add_io_timer(&mut self.io_data, dur);
         }
@@ -50,5 +59,14 @@ impl<'a> EventSource for SocketWrite<'a> {
             socket.into_raw_socket();
             ret
         });
+
+        // register the cancel io data
+        cancel.set_io(CancelIoData::new(&self.io_data));
+        // re-check the cancel status
+        if cancel.is_canceled() {
+            _ = cancel.cancel();
+        } else if cancel.is_interrupted() {
+            cancel.interrupt();
+        }
     }
 }
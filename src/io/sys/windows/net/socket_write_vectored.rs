@@ -0,0 +1,83 @@
+use std::io::{self, IoSlice};
+use std::os::windows::io::{AsRawSocket, RawSocket};
+use std::ptr;
+use std::time::Duration;
+
+use super::super::{co_io_result, EventData};
+use crate::cancel::CancelGuard;
+use crate::coroutine_impl::{co_cancel_data, current_cancel_data, CoroutineImpl, EventSource};
+use crate::io::cancel::CancelIoData;
+use crate::scheduler::get_scheduler;
+use crate::std::sync::delay_drop::DelayDrop;
+use windows_sys::Win32::Foundation::HANDLE;
+use windows_sys::Win32::Networking::WinSock::{
+    WSAGetLastError, WSASend, SOCKET, SOCKET_ERROR, WSABUF, WSA_IO_PENDING,
+};
+
+pub struct SocketWriteVectored<'a, 'b> {
+    io_data: EventData,
+    bufs: &'a [IoSlice<'b>],
+    socket: RawSocket,
+    timeout: Option<Duration>,
+    can_drop: DelayDrop,
+    // the write observes the cancellation token
+    _interrupt: CancelGuard,
+}
+
+impl<'a, 'b> SocketWriteVectored<'a, 'b> {
+    pub fn new<T: AsRawSocket>(s: &T, bufs: &'a [IoSlice<'b>], timeout: Option<Duration>) -> Self {
+        let socket = s.as_raw_socket();
+        SocketWriteVectored {
+            io_data: EventData::new(socket as HANDLE),
+            bufs,
+            socket,
+            timeout,
+            can_drop: DelayDrop::new(),
+            _interrupt: current_cancel_data().interruptible(),
+        }
+    }
+
+    pub fn done(&mut self) -> io::Result<usize> {
+        co_io_result(&self.io_data)
+    }
+}
+
+impl<'a, 'b> EventSource for SocketWriteVectored<'a, 'b> {
+    fn subscribe(&mut self, co: CoroutineImpl) {
+        let s = get_scheduler();
+        let cancel = co_cancel_data(&co);
+        let _g = self.can_drop.delay_drop();
+        if let Some(dur) = self.timeout {
+            s.get_selector().add_io_timer(&mut self.io_data, dur);
+        }
+
+        // prepare the co first
+        self.io_data.co = Some(co);
+        // call the overlapped write API, IoSlice is ABI compatible with WSABUF
+        co_try!(s, self.io_data.co.take().expect("can't get co"), unsafe {
+            let ret = WSASend(
+                self.socket as SOCKET,
+                self.bufs.as_ptr() as *const WSABUF,
+                self.bufs.len() as u32,
+                ptr::null_mut(),
+                0,
+                self.io_data.get_overlapped(),
+                None,
+            );
+            if ret == SOCKET_ERROR && WSAGetLastError() != WSA_IO_PENDING {
+                Err(io::Error::last_os_error())
+            } else {
+                Ok(())
+            }
+        });
+
+        // register the cancel io data
+        cancel.set_io(CancelIoData::new(&self.io_data));
+        // re-check the cancel status
+        if cancel.is_canceled() {
+            _ = cancel.cancel();
+        } else if cancel.is_interrupted() {
+            cancel.interrupt();
+        }
+    }
+}
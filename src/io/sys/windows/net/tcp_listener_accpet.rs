@@ -1,6 +1,7 @@
 use std::io;
 use std::net::SocketAddr;
 use std::os::windows::io::AsRawSocket;
+use std::time::Duration;
 
 use super::super::{add_socket, co_io_result, EventData};
 use crate::coroutine_impl::{co_cancel_data, CoroutineImpl, EventSource};
@@ -18,11 +19,12 @@ pub struct TcpListenerAccept<'a> {
     socket: &'a ::std::net::TcpListener,
     ret: OptionCell<::std::net::TcpStream>,
     addr: AcceptAddrsBuf,
+    timeout: Option<Duration>,
     can_drop: DelayDrop,
 }
 
 impl<'a> TcpListenerAccept<'a> {
-    pub fn new(socket: &'a TcpListener) -> io::Result<Self> {
+    pub fn new(socket: &'a TcpListener, timeout: Option<Duration>) -> io::Result<Self> {
         use socket2::{Domain, Socket, Type};
 
         let local_addr = socket.local_addr()?;
@@ -37,6 +39,7 @@ impl<'a> TcpListenerAccept<'a> {
             socket: socket.inner(),
             ret: OptionCell::new(stream),
             addr: AcceptAddrsBuf::new(),
+            timeout,
             can_drop: DelayDrop::new(),
         })
     }
@@ -65,7 +68,10 @@ impl<'a> EventSource for TcpListenerAccept<'a> {
         let _g = self.can_drop.delay_drop();
         let s = get_scheduler();
         let cancel = co_cancel_data(&co);
-        // we don't need to register the timeout here,
+        // prepare the timer before call the API
+        if let Some(dur) = self.timeout {
+            s.get_selector().add_io_timer(&mut self.io_data, dur);
+        }
         // prepare the co first
         self.io_data.co = Some(co);
 
@@ -4,7 +4,8 @@ use std::os::windows::io::AsRawSocket;
 use std::time::Duration;
 
 use super::super::{add_socket, co_io_result, EventData, IoData};
-use crate::coroutine_impl::{co_cancel_data, CoroutineImpl, EventSource};
+use crate::cancel::CancelGuard;
+use crate::coroutine_impl::{co_cancel_data, current_cancel_data, CoroutineImpl, EventSource};
 use crate::io::cancel::CancelIoData;
 use crate::io::OptionCell;
 use crate::net::TcpStream;
@@ -19,6 +20,8 @@ pub struct TcpStreamConnect {
     timeout: Option<Duration>,
     addr: SocketAddr,
     can_drop: DelayDrop,
+    // the connect observes the cancellation token
+    _interrupt: CancelGuard,
 }
 
 impl TcpStreamConnect {
@@ -64,6 +67,7 @@ impl TcpStreamConnect {
                             stream: OptionCell::new(s),
                             timeout,
                             can_drop: DelayDrop::new(),
+                            _interrupt: current_cancel_data().interruptible(),
                         })
                     },
                 )
@@ -99,6 +103,8 @@ impl EventSource for TcpStreamConnect {
         // re-check the cancel status
         if cancel.is_canceled() {
             _ = cancel.cancel();
+        } else if cancel.is_interrupted() {
+            cancel.interrupt();
         }
     }
 }
@@ -1,16 +1,22 @@
-use std::io;
 use std::net::SocketAddr;
 use std::os::windows::io::AsRawSocket;
 use std::time::Duration;
+use std::{io, mem, ptr};
 
 use super::super::{co_io_result, EventData};
-use crate::coroutine_impl::{co_cancel_data, CoroutineImpl, EventSource};
+use crate::cancel::CancelGuard;
+use crate::coroutine_impl::{co_cancel_data, current_cancel_data, CoroutineImpl, EventSource};
 use crate::io::cancel::CancelIoData;
 use crate::net::UdpSocket;
 use crate::scheduler::get_scheduler;
 use crate::std::sync::delay_drop::DelayDrop;
 use miow::net::{SocketAddrBuf, UdpSocketExt};
+use socket2::SockAddr;
 use windows_sys::Win32::Foundation::HANDLE;
+use windows_sys::Win32::Networking::WinSock::{
+    WSAGetLastError, WSARecvFrom, MSG_PEEK, SOCKADDR, SOCKADDR_STORAGE, SOCKET, SOCKET_ERROR,
+    WSABUF, WSA_IO_PENDING,
+};
 
 pub struct UdpRecvFrom<'a> {
     io_data: EventData,
@@ -19,6 +25,16 @@ pub struct UdpRecvFrom<'a> {
     addr: SocketAddrBuf,
     timeout: Option<Duration>,
     can_drop: DelayDrop,
+    // miow has no flags for recv_from, peek with the raw api
+    peek: Option<PeekAddr>,
+    // the recv observes the cancellation token
+    _interrupt: CancelGuard,
+}
+
+struct PeekAddr {
+    storage: SOCKADDR_STORAGE,
+    len: i32,
+    flags: u32,
 }
 
 impl<'a> UdpRecvFrom<'a> {
@@ -30,11 +46,41 @@ impl<'a> UdpRecvFrom<'a> {
             addr: SocketAddrBuf::new(),
             timeout: socket.read_timeout().unwrap(),
             can_drop: DelayDrop::new(),
+            peek: None,
+            _interrupt: current_cancel_data().interruptible(),
+        }
+    }
+
+    pub fn new_peek(socket: &'a UdpSocket, buf: &'a mut [u8]) -> Self {
+        UdpRecvFrom {
+            peek: Some(PeekAddr {
+                storage: unsafe { mem::zeroed() },
+                len: mem::size_of::<SOCKADDR_STORAGE>() as i32,
+                flags: MSG_PEEK as u32,
+            }),
+            ..Self::new(socket, buf)
         }
     }
 
     pub fn done(&mut self) -> io::Result<(usize, SocketAddr)> {
         let size = co_io_result(&self.io_data)?;
+        if let Some(peek) = self.peek.as_ref() {
+            let (_, addr) = unsafe {
+                SockAddr::try_init(|storage, len| {
+                    ptr::copy_nonoverlapping(
+                        &peek.storage as *const _ as *const u8,
+                        storage as *mut u8,
+                        peek.len as usize,
+                    );
+                    *len = peek.len as _;
+                    Ok(())
+                })
+            }?;
+            let addr = addr.as_socket().ok_or_else(|| {
+                io::Error::new(io::ErrorKind::Other, "could not obtain remote address")
+            })?;
+            return Ok((size, addr));
+        }
         let addr = self.addr.to_socket_addr().ok_or_else(|| {
             io::Error::new(io::ErrorKind::Other, "could not obtain remote address")
         })?;
@@ -53,19 +99,47 @@ impl<'a> EventSource for UdpRecvFrom<'a> {
         // prepare the co first
         self.io_data.co = Some(co);
         // call the overlapped read API
-        co_try!(s, self.io_data.co.take().expect("can't get co"), unsafe {
-            self.socket.recv_from_overlapped(
-                self.buf,
-                &mut self.addr,
-                self.io_data.get_overlapped(),
-            )
-        });
+        let ret = match self.peek.as_mut() {
+            Some(peek) => unsafe {
+                let buf = WSABUF {
+                    len: self.buf.len() as u32,
+                    buf: self.buf.as_mut_ptr(),
+                };
+                let r = WSARecvFrom(
+                    self.socket.as_raw_socket() as SOCKET,
+                    &buf,
+                    1,
+                    ptr::null_mut(),
+                    &mut peek.flags,
+                    &mut peek.storage as *mut _ as *mut SOCKADDR,
+                    &mut peek.len,
+                    self.io_data.get_overlapped(),
+                    None,
+                );
+                if r == SOCKET_ERROR && WSAGetLastError() != WSA_IO_PENDING {
+                    Err(io::Error::last_os_error())
+                } else {
+                    // the result is delivered by the completion port
+                    Ok(true)
+                }
+            },
+            None => unsafe {
+                self.socket.recv_from_overlapped(
+                    self.buf,
+                    &mut self.addr,
+                    self.io_data.get_overlapped(),
+                )
+            },
+        };
+        co_try!(s, self.io_data.co.take().expect("can't get co"), ret);
 
         // register the cancel io data
         cancel.set_io(CancelIoData::new(&self.io_data));
         // re-check the cancel status
         if cancel.is_canceled() {
             _ = cancel.cancel();
+        } else if cancel.is_interrupted() {
+            cancel.interrupt();
         }
     }
 }
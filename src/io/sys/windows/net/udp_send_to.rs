@@ -4,9 +4,12 @@ use std::os::windows::io::AsRawSocket;
 use std::time::Duration;
 
 use super::super::{co_io_result, EventData};
-use crate::coroutine_impl::{CoroutineImpl, EventSource};
+use crate::cancel::CancelGuard;
+use crate::coroutine_impl::{co_cancel_data, current_cancel_data, CoroutineImpl, EventSource};
+use crate::io::cancel::CancelIoData;
 use crate::net::UdpSocket;
 use crate::scheduler::get_scheduler;
+use crate::std::sync::delay_drop::DelayDrop;
 use miow::net::UdpSocketExt;
 use windows_sys::Win32::Foundation::HANDLE;
 
@@ -16,6 +19,9 @@ pub struct UdpSendTo<'a> {
     socket: &'a ::std::net::UdpSocket,
     addr: SocketAddr,
     timeout: Option<Duration>,
+    can_drop: DelayDrop,
+    // the send observes the cancellation token
+    _interrupt: CancelGuard,
 }
 
 impl<'a> UdpSendTo<'a> {
@@ -33,6 +39,8 @@ impl<'a> UdpSendTo<'a> {
                 socket: socket.inner(),
                 addr,
                 timeout: socket.write_timeout().unwrap(),
+                can_drop: DelayDrop::new(),
+                _interrupt: current_cancel_data().interruptible(),
             })
     }
 
@@ -42,9 +50,10 @@ impl<'a> UdpSendTo<'a> {
 }
 
 impl<'a> EventSource for UdpSendTo<'a> {
-    #[allow(clippy::needless_return)]
     fn subscribe(&mut self, co: CoroutineImpl) {
         let s = get_scheduler();
+        let cancel = co_cancel_data(&co);
+        let _g = self.can_drop.delay_drop();
         if let Some(dur) = self.timeout {
             s.get_selector().add_io_timer(&mut self.io_data, dur);
         }
@@ -55,5 +64,14 @@ impl<'a> EventSource for UdpSendTo<'a> {
             self.socket
                 .send_to_overlapped(self.buf, &self.addr, self.io_data.get_overlapped())
         });
+
+        // register the cancel io data
+        cancel.set_io(CancelIoData::new(&self.io_data));
+        // re-check the cancel status
+        if cancel.is_canceled() {
+            _ = cancel.cancel();
+        } else if cancel.is_interrupted() {
+            cancel.interrupt();
+        }
     }
 }
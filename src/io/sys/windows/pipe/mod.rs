@@ -1,5 +1,7 @@
+mod pipe_connect;
 mod pipe_read;
 mod pipe_write;
 
+pub use self::pipe_connect::PipeConnect;
 pub use self::pipe_read::PipeRead;
 pub use self::pipe_write::PipeWrite;
@@ -0,0 +1,76 @@
+use std::io;
+use std::os::windows::io::{AsRawHandle, RawHandle};
+use std::time::Duration;
+
+use super::super::{co_io_result, EventData};
+use crate::cancel::CancelGuard;
+use crate::coroutine_impl::{co_cancel_data, current_cancel_data, CoroutineImpl, EventSource};
+use crate::io::cancel::CancelIoData;
+use crate::scheduler::get_scheduler;
+use crate::std::sync::delay_drop::DelayDrop;
+use windows_sys::Win32::Foundation::{ERROR_IO_PENDING, ERROR_PIPE_CONNECTED, HANDLE};
+use windows_sys::Win32::System::Pipes::ConnectNamedPipe;
+
+// wait for a client to connect the named pipe instance
+pub struct PipeConnect {
+    io_data: EventData,
+    pipe: RawHandle,
+    timeout: Option<Duration>,
+    can_drop: DelayDrop,
+    // the connect observes the cancellation token
+    _interrupt: CancelGuard,
+}
+
+impl PipeConnect {
+    pub fn new<T: AsRawHandle>(s: &T, timeout: Option<Duration>) -> Self {
+        let pipe = s.as_raw_handle();
+        PipeConnect {
+            io_data: EventData::new(pipe as HANDLE),
+            pipe,
+            timeout,
+            can_drop: DelayDrop::new(),
+            _interrupt: current_cancel_data().interruptible(),
+        }
+    }
+
+    pub fn done(&mut self) -> io::Result<()> {
+        co_io_result(&self.io_data).map(|_| ())
+    }
+}
+
+impl EventSource for PipeConnect {
+    fn subscribe(&mut self, co: CoroutineImpl) {
+        let s = get_scheduler();
+        let cancel = co_cancel_data(&co);
+        let _g = self.can_drop.delay_drop();
+        if let Some(dur) = self.timeout {
+            s.get_selector().add_io_timer(&mut self.io_data, dur);
+        }
+        // prepare the co first
+        self.io_data.co = Some(co);
+
+        // call the overlapped connect API
+        co_try!(s, self.io_data.co.take().expect("can't get co"), unsafe {
+            let ret = ConnectNamedPipe(self.pipe as HANDLE, self.io_data.get_overlapped());
+            let err = io::Error::last_os_error();
+            match err.raw_os_error() {
+                _ if ret != 0 => Ok(()),
+                Some(e) if e == ERROR_IO_PENDING as i32 => Ok(()),
+                // the client connected before the call, there is no completion packet
+                Some(e) if e == ERROR_PIPE_CONNECTED as i32 => {
+                    s.get_selector().post_done(&mut self.io_data)
+                }
+                _ => Err(err),
+            }
+        });
+
+        // register the cancel io data
+        cancel.set_io(CancelIoData::new(&self.io_data));
+        // re-check the cancel status
+        if cancel.is_canceled() {
+            let _ = cancel.cancel();
+        } else if cancel.is_interrupted() {
+            cancel.interrupt();
+        }
+    }
+}
@@ -0,0 +1,138 @@
+use std::io;
+#[cfg(unix)]
+use std::os::unix::io::AsRawFd as AsRaw;
+#[cfg(windows)]
+use std::os::windows::io::AsRawHandle as AsRaw;
+use std::time::Duration;
+
+/// A stream whose blocking reads can be bounded by a timeout
+///
+/// in coroutine context the timeout is served by the io timer of the
+/// selector, a read that doesn't complete in time returns `TimedOut`.
+/// the generic protocol code can apply the deadlines through it without
+/// knowing the concrete transport
+pub trait SetReadTimeout {
+    fn set_read_timeout(&self, dur: Option<Duration>) -> io::Result<()>;
+    fn read_timeout(&self) -> io::Result<Option<Duration>>;
+}
+
+/// A stream whose blocking writes can be bounded by a timeout
+///
+/// in coroutine context the timeout is served by the io timer of the
+/// selector, a write that doesn't complete in time returns `TimedOut`
+pub trait SetWriteTimeout {
+    fn set_write_timeout(&self, dur: Option<Duration>) -> io::Result<()>;
+    fn write_timeout(&self) -> io::Result<Option<Duration>>;
+}
+
+macro_rules! impl_timeout {
+    ($($t: ty),* $(,)?) => {
+        $(
+            impl SetReadTimeout for $t {
+                fn set_read_timeout(&self, dur: Option<Duration>) -> io::Result<()> {
+                    <$t>::set_read_timeout(self, dur)
+                }
+
+                fn read_timeout(&self) -> io::Result<Option<Duration>> {
+                    <$t>::read_timeout(self)
+                }
+            }
+
+            impl SetWriteTimeout for $t {
+                fn set_write_timeout(&self, dur: Option<Duration>) -> io::Result<()> {
+                    <$t>::set_write_timeout(self, dur)
+                }
+
+                fn write_timeout(&self) -> io::Result<Option<Duration>> {
+                    <$t>::write_timeout(self)
+                }
+            }
+        )*
+    };
+}
+
+impl_timeout!(
+    crate::net::TcpStream,
+    crate::net::UdpSocket,
+    crate::os::PipeReader,
+    crate::os::PipeWriter,
+);
+
+#[cfg(unix)]
+impl_timeout!(
+    crate::os::unix::net::UnixStream,
+    crate::os::unix::net::UnixDatagram,
+);
+
+#[cfg(windows)]
+impl_timeout!(
+    crate::os::windows::named_pipe::NamedPipeServer,
+    crate::os::windows::named_pipe::NamedPipeClient,
+);
+
+impl<T: AsRaw> SetReadTimeout for super::CoIo<T> {
+    fn set_read_timeout(&self, dur: Option<Duration>) -> io::Result<()> {
+        super::CoIo::set_read_timeout(self, dur)
+    }
+
+    fn read_timeout(&self) -> io::Result<Option<Duration>> {
+        super::CoIo::read_timeout(self)
+    }
+}
+
+impl<T: AsRaw> SetWriteTimeout for super::CoIo<T> {
+    fn set_write_timeout(&self, dur: Option<Duration>) -> io::Result<()> {
+        super::CoIo::set_write_timeout(self, dur)
+    }
+
+    fn write_timeout(&self) -> io::Result<Option<Duration>> {
+        super::CoIo::write_timeout(self)
+    }
+}
+
+// the TLS stream waits on the inner stream
+#[cfg(feature = "tls")]
+impl<S: SetReadTimeout> SetReadTimeout for crate::net::tls::TlsStream<S> {
+    fn set_read_timeout(&self, dur: Option<Duration>) -> io::Result<()> {
+        self.get_ref().set_read_timeout(dur)
+    }
+
+    fn read_timeout(&self) -> io::Result<Option<Duration>> {
+        self.get_ref().read_timeout()
+    }
+}
+
+#[cfg(feature = "tls")]
+impl<S: SetWriteTimeout> SetWriteTimeout for crate::net::tls::TlsStream<S> {
+    fn set_write_timeout(&self, dur: Option<Duration>) -> io::Result<()> {
+        self.get_ref().set_write_timeout(dur)
+    }
+
+    fn write_timeout(&self) -> io::Result<Option<Duration>> {
+        self.get_ref().write_timeout()
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::os::unix::net::UnixStream;
+    use std::io::Read;
+
+    fn set_deadlines<S: SetReadTimeout + SetWriteTimeout>(s: &S, dur: Duration) {
+        s.set_read_timeout(Some(dur)).unwrap();
+        s.set_write_timeout(Some(dur)).unwrap();
+        assert_eq!(s.read_timeout().unwrap(), Some(dur));
+        assert_eq!(s.write_timeout().unwrap(), Some(dur));
+    }
+
+    #[test]
+    fn generic_read_timeout() {
+        let (mut a, _b) = UnixStream::pair().unwrap();
+        let h = co!(move || {
+            set_deadlines(&a, Duration::from_millis(20));
+            a.read(&mut [0; 8]).unwrap_err().kind()
+        });
+        assert_eq!(h.join().unwrap(), io::ErrorKind::TimedOut);
+    }
+}
@@ -45,7 +45,11 @@ impl Join {
         }
     }
 
-    fn wait(&self) {
+    pub fn is_done(&self) -> bool {
+        !self.state.load(Ordering::Acquire)
+    }
+
+    pub fn wait(&self) {
         if self.state.load(Ordering::Acquire) {
             let cur = Blocker::current();
             // register the blocker first
@@ -30,28 +30,40 @@
 extern crate log;
 extern crate core;
 
+mod blocking;
 mod cancel;
+mod hooks;
 mod config;
 mod join;
 mod local;
 mod park;
 mod pool;
+mod registry;
 mod sleep;
 #[macro_use]
 mod macros;
 mod coroutine_impl;
+mod schedule_policy;
 mod scheduler;
 mod scoped;
 mod timeout_list;
+mod timer_wheel;
 mod yield_now;
 pub extern crate mco_gen;
+pub mod compat;
 pub mod coroutine;
 pub mod cqueue;
 pub mod io;
 pub mod net;
 pub mod os;
+pub mod process;
 #[macro_use]
 pub mod std;
 
 pub use crate::config::{config, Config};
 pub use crate::local::LocalKey;
+pub use crate::hooks::{HookEvent, SchedulerHooks};
+pub use crate::schedule_policy::{DefaultPolicy, SchedulePolicy, ScheduleTarget};
+pub use crate::scheduler::{
+    get_scheduler as scheduler, Scheduler, SchedulerStats, WorkerStats,
+};
@@ -7,28 +7,35 @@ use std::sync::Arc;
 
 use crate::coroutine_impl::Coroutine;
 use crate::join::Join;
+use crate::std::context::Context;
 use mco_gen::get_local_data;
 
 // thread local map storage
 thread_local! {static LOCALMAP: LocalMap = RefCell::new(HashMap::default());}
+// the context of the thread, inherited by the coroutines spawned from the thread
+thread_local! {static CONTEXT: RefCell<Context> = RefCell::new(Context::background());}
 
 /// coroutine local storage
 pub struct CoroutineLocal {
     // current coroutine handle
     co: Coroutine,
     // when panic happens, we need to trigger the join here
-    join: Arc<Join>,
+    // `None` for the detached coroutines
+    join: Option<Arc<Join>>,
     // real local data hash map
     local_data: LocalMap,
+    // the current context of the coroutine
+    context: RefCell<Context>,
 }
 
 impl CoroutineLocal {
     /// create coroutine local storage
-    pub fn new(co: Coroutine, join: Arc<Join>) -> Box<Self> {
+    pub fn new(co: Coroutine, join: Option<Arc<Join>>, context: Context) -> Box<Self> {
         Box::new(CoroutineLocal {
             co,
             join,
             local_data: RefCell::new(HashMap::default()),
+            context: RefCell::new(context),
         })
     }
 
@@ -38,7 +45,7 @@ impl CoroutineLocal {
     }
 
     // get the join handle
-    pub fn get_join(&self) -> Arc<Join> {
+    pub fn get_join(&self) -> Option<Arc<Join>> {
         self.join.clone()
     }
 }
@@ -57,6 +64,22 @@ fn with<F: FnOnce(&LocalMap) -> R, R>(f: F) -> R {
     }
 }
 
+// get the context of the current coroutine or thread
+pub(crate) fn current_context() -> Context {
+    match get_co_local_data() {
+        Some(v) => unsafe { v.as_ref() }.context.borrow().clone(),
+        None => CONTEXT.with(|ctx| ctx.borrow().clone()),
+    }
+}
+
+// replace the context of the current coroutine or thread, return the old one
+pub(crate) fn set_current_context(ctx: Context) -> Context {
+    match get_co_local_data() {
+        Some(v) => unsafe { v.as_ref() }.context.replace(ctx),
+        None => CONTEXT.with(|c| c.replace(ctx)),
+    }
+}
+
 pub type LocalMap = RefCell<HashMap<TypeId, Box<dyn Opaque>, BuildHasherDefault<IdHasher>>>;
 
 pub trait Opaque {}
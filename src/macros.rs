@@ -46,6 +46,30 @@ macro_rules! spawn {
     }};
 }
 
+/// macro used to spawn a coroutine that can't be joined
+///
+/// this macro is just a convenient wrapper for [`spawn_detached`].
+///
+/// [`spawn_detached`]: coroutine/fn.spawn_detached.html
+#[macro_export]
+macro_rules! co_detached {
+    ($func:expr) => {{
+        $crate::coroutine::spawn_detached($func)
+    }};
+}
+
+/// macro used to run a blocking function on the blocking thread pool
+///
+/// this macro is just a convenient wrapper for [`spawn_blocking`].
+///
+/// [`spawn_blocking`]: coroutine/fn.spawn_blocking.html
+#[macro_export]
+macro_rules! blocking {
+    ($func:expr) => {{
+        $crate::coroutine::spawn_blocking($func)
+    }};
+}
+
 /// macro used to spawn a coroutine with options such as name, stack_size.
 ///
 /// this macro is just a convenient wrapper for [`spawn`].
@@ -117,6 +141,13 @@ macro_rules! cqueue_add_oneshot {
 
 /// macro used to select for only one event
 /// it will return the index of which event happens first
+///
+/// when more than one branch is ready, one of them is picked at random
+/// like the go select, so that the later branches are not starved. start
+/// with `biased;` to prefer the branches in the source order.
+///
+/// an optional `default` branch at the end runs immediately if all the
+/// other branches are blocked, like the `default` of the go select
 /// for example:
 /// ```rust
 /// use mco::{chan, select};
@@ -131,15 +162,34 @@ macro_rules! cqueue_add_oneshot {
 ///             println!("{}",msg);
 ///         }
 ///     };
+///     // nothing to receive, the default branch runs
+///     select! {
+///         rv = r.recv() => {
+///             println!("{:?}",rv);
+///         },
+///         default => {
+///             println!("no message");
+///         }
+///     };
+///     // always try the first branch first
+///     s.send(2);
+///     select! {
+///         biased;
+///         Ok(msg) = r.try_recv() => {
+///             println!("{}",msg);
+///         },
+///         rv = r.recv() => {
+///             println!("{:?}",rv);
+///         }
+///     };
 /// ```
 #[macro_export]
 macro_rules! select {
-    (
-        $($name:pat = $top:expr => $bottom:expr), +$(,)?
-    ) => ($crate::select_token!($($name = $top => $bottom), +););
+    ($($tt:tt)+) => ($crate::select_token!($($tt)+););
 }
 /// macro used to select for only one event
-/// it will return the index of which event happens first
+/// it will return the index of which event happens first,
+/// the index of the `default` branch is the number of the other branches
 /// for example:
 /// ```rust
 /// use mco::{chan, select_token};
@@ -151,24 +201,86 @@ macro_rules! select {
 ///             println!("{:?}",rv);
 ///         }
 ///     };
+///     assert_eq!(id, 0);
+///     let id = select_token! {
+///         rv = r.recv() => {
+///             println!("{:?}",rv);
+///         },
+///         default => {}
+///     };
+///     assert_eq!(id, 1);
 /// ```
 #[macro_export]
 macro_rules! select_token {
-    (
-        $($name:pat = $top:expr => $bottom:expr), +$(,)?
-    ) => ({
+    // collect the branches until the default one is found
+    (@default [$($biased:ident)?] [$($arms:tt)*] default => $default:expr $(,)?) => (
+        $crate::select_token!(@run [$($biased)?] [$default] $($arms)*)
+    );
+    (@default [$($biased:ident)?] [$($arms:tt)*] $name:pat = $top:expr => $bottom:expr, $($rest:tt)*) => (
+        $crate::select_token!(@default [$($biased)?] [$($arms)* $name = $top => $bottom,] $($rest)*)
+    );
+    (@default [$($biased:ident)?] [$($arms:tt)*] $($rest:tt)*) => (
+        $crate::select_token!(@run [$($biased)?] [] $($arms)* $($rest)*)
+    );
+    // add the select coroutines, return the number of them
+    (@add [biased] $cqueue:ident $($name:pat = $top:expr => $bottom:expr), +$(,)?) => ({
+        let mut _token = 0;
+        $(
+            $crate::cqueue_add_oneshot!($cqueue, _token, $name = $top => $bottom);
+            _token += 1;
+        )+
+        _token
+    });
+    (@add [] $cqueue:ident $($name:pat = $top:expr => $bottom:expr), +$(,)?) => ({
+        let mut _arms: Vec<(usize, Box<dyn FnOnce(&$crate::cqueue::Cqueue, usize) + '_>)> = Vec::new();
+        $(
+            _arms.push((
+                _arms.len(),
+                Box::new(|cqueue: &$crate::cqueue::Cqueue, token: usize| {
+                    $crate::cqueue_add_oneshot!(cqueue, token, $name = $top => $bottom);
+                }),
+            ));
+        )+
+        let _token = _arms.len();
+        $crate::cqueue::shuffle(&mut _arms);
+        for (token, add) in _arms {
+            add($cqueue, token);
+        }
+        _token
+    });
+    (@run [$($biased:ident)?] [] $($arms:tt)+) => ({
         $crate::cqueue::scope(|cqueue| {
-            let mut _token = 0;
-            $(
-                $crate::cqueue_add_oneshot!(cqueue, _token, $name = $top => $bottom);
-                _token += 1;
-            )+
+            $crate::select_token!(@add [$($biased)?] cqueue $($arms)+);
             match cqueue.poll(None) {
                 Ok(ev) => return ev.token,
                 _ => unreachable!("select error"),
             }
         })
     });
+    (@run [$($biased:ident)?] [$default:expr] $($arms:tt)+) => ({
+        // the blocked branches are canceled before the default one runs
+        let ret = $crate::cqueue::scope(|cqueue| {
+            let _token = $crate::select_token!(@add [$($biased)?] cqueue $($arms)+);
+            match cqueue.try_poll() {
+                Ok(ev) => Ok(ev.token),
+                Err($crate::cqueue::PollError::Timeout) => Err(_token),
+                _ => unreachable!("select error"),
+            }
+        });
+        match ret {
+            Ok(token) => token,
+            Err(token) => {
+                $default;
+                token
+            }
+        }
+    });
+    (biased; $($tt:tt)+) => (
+        $crate::select_token!(@default [biased] [] $($tt)+)
+    );
+    ($($tt:tt)+) => (
+        $crate::select_token!(@default [] [] $($tt)+)
+    );
 }
 
 /// macro used to join all scoped sub coroutines
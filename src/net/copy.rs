@@ -0,0 +1,111 @@
+use std::io;
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+use self::splice::copy;
+use super::TcpStream;
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+use crate::io::copy;
+use crate::io::copy_bidirectional_with;
+
+/// copy the data between the two streams in both directions until both of
+/// them reach EOF, return the bytes copied from `a` to `b` and from `b` to `a`
+///
+/// the EOF of one side is passed to the other side with `shutdown(Write)`.
+/// if one direction fails, both streams are shut down to stop the other one.
+/// on linux the data is moved with `splice(2)` through a pipe, so it's not
+/// copied to the user space, the read/write timeouts are ignored there
+///
+/// ```no_run
+/// use mco::net::{copy_bidirectional, TcpListener, TcpStream};
+///
+/// let listener = TcpListener::bind("127.0.0.1:8080").unwrap();
+/// for client in listener.incoming() {
+///     let client = client.unwrap();
+///     mco::co!(move || {
+///         let upstream = TcpStream::connect("127.0.0.1:80").unwrap();
+///         copy_bidirectional(&client, &upstream).ok();
+///     });
+/// }
+/// ```
+pub fn copy_bidirectional(a: &TcpStream, b: &TcpStream) -> io::Result<(u64, u64)> {
+    copy_bidirectional_with(a, b, copy, copy)
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod splice {
+    use std::io;
+    use std::os::unix::io::{AsRawFd, RawFd};
+    use std::ptr;
+
+    use crate::io::WaitIo;
+    use crate::net::TcpStream;
+
+    // the default pipe capacity
+    const PIPE_SIZE: usize = 64 * 1024;
+
+    struct Pipe {
+        r: RawFd,
+        w: RawFd,
+    }
+
+    impl Pipe {
+        fn new() -> io::Result<Self> {
+            let mut fds = [0; 2];
+            if unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_NONBLOCK | libc::O_CLOEXEC) } < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(Pipe {
+                r: fds[0],
+                w: fds[1],
+            })
+        }
+    }
+
+    impl Drop for Pipe {
+        fn drop(&mut self) {
+            unsafe {
+                libc::close(self.r);
+                libc::close(self.w);
+            }
+        }
+    }
+
+    fn splice(fd_in: RawFd, fd_out: RawFd, len: usize) -> io::Result<usize> {
+        let flags = libc::SPLICE_F_MOVE | libc::SPLICE_F_NONBLOCK;
+        let n =
+            unsafe { libc::splice(fd_in, ptr::null_mut(), fd_out, ptr::null_mut(), len, flags) };
+        if n < 0 {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(n as usize)
+        }
+    }
+
+    // run the splice and park on the socket until it doesn't block
+    fn splice_wait(s: &TcpStream, fd_in: RawFd, fd_out: RawFd, len: usize) -> io::Result<usize> {
+        loop {
+            s.reset_io();
+            match splice(fd_in, fd_out, len) {
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => s.wait_io(),
+                ret => return ret,
+            }
+        }
+    }
+
+    pub fn copy(src: &mut TcpStream, dst: &mut TcpStream) -> io::Result<u64> {
+        let pipe = Pipe::new()?;
+        let mut total = 0;
+        loop {
+            // the pipe is always drained, so only the socket can block
+            let n = splice_wait(src, src.as_raw_fd(), pipe.w, PIPE_SIZE)?;
+            if n == 0 {
+                return Ok(total);
+            }
+            let mut left = n;
+            while left > 0 {
+                left -= splice_wait(dst, pipe.r, dst.as_raw_fd(), left)?;
+            }
+            total += n as u64;
+        }
+    }
+}
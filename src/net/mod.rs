@@ -1,8 +1,19 @@
 //! Networking primitives
 //!
 
+mod copy;
+pub mod pool;
+pub mod proxy;
 mod tcp;
+mod throttle;
+#[cfg(feature = "tls")]
+pub mod tls;
 mod udp;
+#[cfg(unix)]
+pub mod unix;
 
-pub use self::tcp::{TcpListener, TcpStream};
+pub use self::copy::copy_bidirectional;
+pub use self::tcp::{TcpListenOptions, TcpListener, TcpStream};
+pub use self::throttle::Throttled;
 pub use self::udp::UdpSocket;
+pub use socket2::TcpKeepalive;
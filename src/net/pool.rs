@@ -0,0 +1,362 @@
+//! Generic client connection pool
+//!
+//! the pool keeps the idle connections for reuse and limits the number of
+//! the connections. when the pool is exhausted `get` parks the coroutine
+//! until a connection is returned.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use std::io::Write;
+//! use std::time::Duration;
+//! use mco::net::pool::ConnPool;
+//! use mco::net::TcpStream;
+//!
+//! let pool = ConnPool::builder()
+//!     .max_size(16)
+//!     .idle_timeout(Duration::from_secs(60))
+//!     .build(|| TcpStream::connect("127.0.0.1:6379"));
+//!
+//! for _ in 0..100 {
+//!     let pool = pool.clone();
+//!     mco::co!(move || {
+//!         let mut conn = pool.get().unwrap();
+//!         conn.write_all(b"PING\r\n").unwrap();
+//!         // the connection is returned to the pool when dropped
+//!     });
+//! }
+//! ```
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::io;
+use std::ops::{Deref, DerefMut};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::std::sync::{Condvar, Mutex};
+
+type ConnectFn<T> = Box<dyn Fn() -> io::Result<T> + Send + Sync>;
+type CheckFn<T> = Box<dyn Fn(&mut T) -> bool + Send + Sync>;
+
+/// Pool configuration
+pub struct Builder<T> {
+    max_size: usize,
+    idle_timeout: Option<Duration>,
+    health_check: Option<CheckFn<T>>,
+}
+
+impl<T> Builder<T> {
+    pub fn new() -> Self {
+        Builder {
+            max_size: 10,
+            idle_timeout: None,
+            health_check: None,
+        }
+    }
+
+    /// the max number of connections, both in use and idle, default is 10
+    pub fn max_size(mut self, max_size: usize) -> Self {
+        assert!(max_size > 0, "max_size must be positive");
+        self.max_size = max_size;
+        self
+    }
+
+    /// close the connections that stay idle longer than `dur`
+    pub fn idle_timeout(mut self, dur: Duration) -> Self {
+        self.idle_timeout = Some(dur);
+        self
+    }
+
+    /// check an idle connection before handing it out, the connection is
+    /// closed if the check returns false
+    pub fn health_check<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&mut T) -> bool + Send + Sync + 'static,
+    {
+        self.health_check = Some(Box::new(f));
+        self
+    }
+
+    /// build the pool, the connections are created lazily by `connect`
+    pub fn build<F>(self, connect: F) -> ConnPool<T>
+    where
+        F: Fn() -> io::Result<T> + Send + Sync + 'static,
+    {
+        ConnPool {
+            inner: Arc::new(Inner {
+                connect: Box::new(connect),
+                health_check: self.health_check,
+                max_size: self.max_size,
+                idle_timeout: self.idle_timeout,
+                state: Mutex::new(State {
+                    idle: VecDeque::new(),
+                    size: 0,
+                }),
+                cond: Condvar::new(),
+            }),
+        }
+    }
+}
+
+impl<T> Default for Builder<T> {
+    fn default() -> Self {
+        Builder::new()
+    }
+}
+
+struct State<T> {
+    // the idle connections with the time they are returned, the newest at the back
+    idle: VecDeque<(T, Instant)>,
+    // the number of the connections, including the ones being created
+    size: usize,
+}
+
+struct Inner<T> {
+    connect: ConnectFn<T>,
+    health_check: Option<CheckFn<T>>,
+    max_size: usize,
+    idle_timeout: Option<Duration>,
+    state: Mutex<State<T>>,
+    cond: Condvar,
+}
+
+enum Slot<T> {
+    Idle(T),
+    New,
+}
+
+impl<T> Inner<T> {
+    // take an idle connection or a slot for a new one
+    fn acquire(&self, deadline: Option<Instant>) -> io::Result<Slot<T>> {
+        let mut state = self.state.lock().unwrap();
+        loop {
+            if let Some(timeout) = self.idle_timeout {
+                while let Some((_, since)) = state.idle.front() {
+                    if since.elapsed() < timeout {
+                        break;
+                    }
+                    state.idle.pop_front();
+                    state.size -= 1;
+                }
+            }
+            if let Some((conn, _)) = state.idle.pop_back() {
+                return Ok(Slot::Idle(conn));
+            }
+            if state.size < self.max_size {
+                state.size += 1;
+                return Ok(Slot::New);
+            }
+
+            state = match deadline {
+                None => self.cond.wait(state).unwrap(),
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        return Err(io::Error::new(
+                            io::ErrorKind::TimedOut,
+                            "get connection timed out",
+                        ));
+                    }
+                    self.cond.wait_timeout(state, deadline - now).unwrap().0
+                }
+            };
+        }
+    }
+
+    // a connection is closed, let a waiter create a new one
+    fn release(&self) {
+        self.state.lock().unwrap().size -= 1;
+        self.cond.notify_one().ok();
+    }
+
+    fn put_idle(&self, conn: T) {
+        self.state
+            .lock()
+            .unwrap()
+            .idle
+            .push_back((conn, Instant::now()));
+        self.cond.notify_one().ok();
+    }
+}
+
+/// A pool of connections, cloning the pool shares the connections
+pub struct ConnPool<T> {
+    inner: Arc<Inner<T>>,
+}
+
+impl<T> Clone for ConnPool<T> {
+    fn clone(&self) -> Self {
+        ConnPool {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<T> fmt::Debug for ConnPool<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ConnPool")
+            .field("max_size", &self.inner.max_size)
+            .field("size", &self.size())
+            .field("idle", &self.idle())
+            .finish()
+    }
+}
+
+impl<T> ConnPool<T> {
+    pub fn builder() -> Builder<T> {
+        Builder::new()
+    }
+
+    /// get a connection, an idle one is reused or a new one is created
+    ///
+    /// parks until a connection is returned when the pool is exhausted
+    pub fn get(&self) -> io::Result<PooledConn<T>> {
+        self.get_impl(None)
+    }
+
+    /// same as `get` but returns a `TimedOut` error after `dur`
+    pub fn get_timeout(&self, dur: Duration) -> io::Result<PooledConn<T>> {
+        self.get_impl(Some(Instant::now() + dur))
+    }
+
+    fn get_impl(&self, deadline: Option<Instant>) -> io::Result<PooledConn<T>> {
+        let inner = &self.inner;
+        loop {
+            let conn = match inner.acquire(deadline)? {
+                Slot::Idle(mut conn) => match &inner.health_check {
+                    Some(check) if !check(&mut conn) => {
+                        drop(conn);
+                        inner.release();
+                        continue;
+                    }
+                    _ => conn,
+                },
+                Slot::New => match (inner.connect)() {
+                    Ok(conn) => conn,
+                    Err(e) => {
+                        inner.release();
+                        return Err(e);
+                    }
+                },
+            };
+            return Ok(PooledConn {
+                conn: Some(conn),
+                pool: inner.clone(),
+            });
+        }
+    }
+
+    /// add a connection to the pool, e.g. a detached one
+    ///
+    /// the connection is closed if the pool is already full
+    pub fn put(&self, conn: T) {
+        let mut state = self.inner.state.lock().unwrap();
+        if state.size < self.inner.max_size {
+            state.size += 1;
+            state.idle.push_back((conn, Instant::now()));
+            drop(state);
+            self.inner.cond.notify_one().ok();
+        }
+    }
+
+    /// the number of the connections, both in use and idle
+    pub fn size(&self) -> usize {
+        self.inner.state.lock().unwrap().size
+    }
+
+    /// the number of the idle connections
+    pub fn idle(&self) -> usize {
+        self.inner.state.lock().unwrap().idle.len()
+    }
+}
+
+/// A connection borrowed from the pool, returned to the pool when dropped
+pub struct PooledConn<T> {
+    conn: Option<T>,
+    pool: Arc<Inner<T>>,
+}
+
+impl<T> PooledConn<T> {
+    /// take the connection out of the pool, it's no longer counted
+    pub fn detach(mut self) -> T {
+        let conn = self.conn.take().unwrap();
+        self.pool.release();
+        conn
+    }
+
+    /// close the connection instead of returning it, e.g. after an io error
+    pub fn discard(self) {
+        drop(self.detach());
+    }
+}
+
+impl<T> Deref for PooledConn<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.conn.as_ref().unwrap()
+    }
+}
+
+impl<T> DerefMut for PooledConn<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.conn.as_mut().unwrap()
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for PooledConn<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("PooledConn").field(&self.conn).finish()
+    }
+}
+
+impl<T> Drop for PooledConn<T> {
+    fn drop(&mut self) {
+        if let Some(conn) = self.conn.take() {
+            self.pool.put_idle(conn);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn counter_pool(max_size: usize) -> ConnPool<usize> {
+        let next = AtomicUsize::new(0);
+        ConnPool::builder()
+            .max_size(max_size)
+            .build(move || Ok(next.fetch_add(1, Ordering::SeqCst)))
+    }
+
+    #[test]
+    fn pool_reuse_and_exhaust() {
+        let pool = counter_pool(1);
+        let conn = pool.get().unwrap();
+        assert_eq!(*conn, 0);
+        let err = pool.get_timeout(Duration::from_millis(10)).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+        drop(conn);
+        assert_eq!(pool.idle(), 1);
+        assert_eq!(*pool.get().unwrap(), 0);
+
+        pool.get().unwrap().discard();
+        assert_eq!(pool.size(), 0);
+        assert_eq!(*pool.get().unwrap(), 1);
+    }
+
+    #[test]
+    fn pool_health_check() {
+        let next = AtomicUsize::new(0);
+        let pool = ConnPool::builder()
+            .health_check(|conn: &mut usize| *conn % 2 == 1)
+            .build(move || Ok(next.fetch_add(1, Ordering::SeqCst)));
+        // the even ones fail the check once idle
+        drop(pool.get().unwrap());
+        assert_eq!(*pool.get().unwrap(), 1);
+        assert_eq!(*pool.get().unwrap(), 1);
+        assert_eq!(pool.size(), 1);
+    }
+}
@@ -0,0 +1,276 @@
+//! Connect to the remote address through a proxy
+//!
+//! both the SOCKS5 and the HTTP `CONNECT` proxies are supported, the result
+//! is a plain [`TcpStream`] tunneled to the target, so it works with the
+//! tls streams and any protocol on top of tcp.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use std::io::Write;
+//! use mco::net::proxy::Socks5Connector;
+//!
+//! let proxy = "127.0.0.1:1080".parse().unwrap();
+//! let connector = Socks5Connector::new(proxy).auth("user", "pass");
+//! let mut stream = connector.connect("example.com", 80).unwrap();
+//! stream.write_all(b"GET / HTTP/1.0\r\n\r\n").unwrap();
+//! ```
+
+use std::io::{self, Read, Write};
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
+
+use super::TcpStream;
+
+fn proxy_err<E>(e: E) -> io::Error
+where
+    E: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    io::Error::other(e)
+}
+
+// connect to the proxy, the timeout also applies to the handshake
+fn connect_proxy(proxy: &SocketAddr, timeout: Option<Duration>) -> io::Result<TcpStream> {
+    let stream = match timeout {
+        Some(dur) => TcpStream::connect_timeout(proxy, dur)?,
+        None => TcpStream::connect(proxy)?,
+    };
+    stream.set_read_timeout(timeout)?;
+    stream.set_write_timeout(timeout)?;
+    Ok(stream)
+}
+
+fn handshake_done(stream: TcpStream) -> io::Result<TcpStream> {
+    stream.set_read_timeout(None)?;
+    stream.set_write_timeout(None)?;
+    Ok(stream)
+}
+
+/// connector through a SOCKS5 proxy, see RFC 1928 and RFC 1929
+#[derive(Debug, Clone)]
+pub struct Socks5Connector {
+    proxy: SocketAddr,
+    auth: Option<(String, String)>,
+    timeout: Option<Duration>,
+}
+
+impl Socks5Connector {
+    pub fn new(proxy: SocketAddr) -> Self {
+        Socks5Connector {
+            proxy,
+            auth: None,
+            timeout: None,
+        }
+    }
+
+    /// authenticate with the username and password
+    pub fn auth(mut self, username: &str, password: &str) -> Self {
+        self.auth = Some((username.to_owned(), password.to_owned()));
+        self
+    }
+
+    /// the timeout of connecting to the proxy and the handshake
+    pub fn timeout(mut self, dur: Duration) -> Self {
+        self.timeout = Some(dur);
+        self
+    }
+
+    /// connect to `host:port` through the proxy
+    ///
+    /// the host name is resolved by the proxy
+    pub fn connect(&self, host: &str, port: u16) -> io::Result<TcpStream> {
+        let mut stream = connect_proxy(&self.proxy, self.timeout)?;
+
+        // the auth methods, 0x02 is username/password and 0x00 is none
+        match self.auth {
+            Some(_) => stream.write_all(&[0x05, 0x02, 0x00, 0x02])?,
+            None => stream.write_all(&[0x05, 0x01, 0x00])?,
+        }
+        let mut rsp = [0u8; 2];
+        stream.read_exact(&mut rsp)?;
+        if rsp[0] != 0x05 {
+            return Err(proxy_err("not a socks5 proxy"));
+        }
+        match (rsp[1], &self.auth) {
+            (0x00, _) => {}
+            (0x02, Some((username, password))) => {
+                if username.len() > 255 || password.len() > 255 {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        "socks5 username or password is too long",
+                    ));
+                }
+                let mut req = vec![0x01, username.len() as u8];
+                req.extend_from_slice(username.as_bytes());
+                req.push(password.len() as u8);
+                req.extend_from_slice(password.as_bytes());
+                stream.write_all(&req)?;
+                stream.read_exact(&mut rsp)?;
+                if rsp[1] != 0x00 {
+                    return Err(io::Error::new(
+                        io::ErrorKind::PermissionDenied,
+                        "socks5 authentication failed",
+                    ));
+                }
+            }
+            _ => return Err(proxy_err("no acceptable socks5 auth method")),
+        }
+
+        let mut req = vec![0x05, 0x01, 0x00];
+        match host.parse::<IpAddr>() {
+            Ok(IpAddr::V4(ip)) => {
+                req.push(0x01);
+                req.extend_from_slice(&ip.octets());
+            }
+            Ok(IpAddr::V6(ip)) => {
+                req.push(0x04);
+                req.extend_from_slice(&ip.octets());
+            }
+            Err(_) => {
+                if host.len() > 255 {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        "socks5 host name is too long",
+                    ));
+                }
+                req.push(0x03);
+                req.push(host.len() as u8);
+                req.extend_from_slice(host.as_bytes());
+            }
+        }
+        req.extend_from_slice(&port.to_be_bytes());
+        stream.write_all(&req)?;
+
+        let mut rsp = [0u8; 4];
+        stream.read_exact(&mut rsp)?;
+        if rsp[1] != 0x00 {
+            return Err(proxy_err(format!("socks5 connect failed, reply={}", rsp[1])));
+        }
+        // skip the bound address
+        let len = match rsp[3] {
+            0x01 => 4,
+            0x04 => 16,
+            0x03 => {
+                let mut len = [0u8; 1];
+                stream.read_exact(&mut len)?;
+                len[0] as usize
+            }
+            _ => return Err(proxy_err("invalid socks5 address type")),
+        };
+        let mut addr = vec![0u8; len + 2];
+        stream.read_exact(&mut addr)?;
+        handshake_done(stream)
+    }
+}
+
+/// connector through a HTTP proxy with the `CONNECT` method
+#[derive(Debug, Clone)]
+pub struct HttpConnectConnector {
+    proxy: SocketAddr,
+    auth: Option<String>,
+    timeout: Option<Duration>,
+}
+
+impl HttpConnectConnector {
+    pub fn new(proxy: SocketAddr) -> Self {
+        HttpConnectConnector {
+            proxy,
+            auth: None,
+            timeout: None,
+        }
+    }
+
+    /// authenticate with the basic `Proxy-Authorization`
+    pub fn auth(mut self, username: &str, password: &str) -> Self {
+        let credential = format!("{}:{}", username, password);
+        self.auth = Some(base64(credential.as_bytes()));
+        self
+    }
+
+    /// the timeout of connecting to the proxy and the handshake
+    pub fn timeout(mut self, dur: Duration) -> Self {
+        self.timeout = Some(dur);
+        self
+    }
+
+    /// connect to `host:port` through the proxy
+    pub fn connect(&self, host: &str, port: u16) -> io::Result<TcpStream> {
+        let mut stream = connect_proxy(&self.proxy, self.timeout)?;
+
+        // the ipv6 address must be in brackets
+        let authority = match host.parse::<IpAddr>() {
+            Ok(IpAddr::V6(_)) => format!("[{}]:{}", host, port),
+            _ => format!("{}:{}", host, port),
+        };
+        let mut req = format!("CONNECT {0} HTTP/1.1\r\nHost: {0}\r\n", authority);
+        if let Some(auth) = &self.auth {
+            req.push_str(&format!("Proxy-Authorization: Basic {}\r\n", auth));
+        }
+        req.push_str("\r\n");
+        stream.write_all(req.as_bytes())?;
+
+        // read byte by byte, the data after the header belongs to the tunnel
+        let mut header = Vec::new();
+        let mut byte = [0u8; 1];
+        while !header.ends_with(b"\r\n\r\n") {
+            if header.len() > 8 * 1024 {
+                return Err(proxy_err("http proxy response header is too large"));
+            }
+            stream.read_exact(&mut byte)?;
+            header.push(byte[0]);
+        }
+
+        // HTTP/1.1 200 Connection established
+        let status_line = header.split(|b| *b == b'\r').next().unwrap_or_default();
+        let status_line = String::from_utf8_lossy(status_line);
+        let mut parts = status_line.split_whitespace();
+        let code = match (parts.next(), parts.next()) {
+            (Some(version), Some(code)) if version.starts_with("HTTP/1.") => code,
+            _ => return Err(proxy_err("invalid http proxy response")),
+        };
+        match code {
+            c if c.starts_with('2') => handshake_done(stream),
+            "407" => Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                "http proxy authentication required",
+            )),
+            _ => Err(proxy_err(format!("http proxy connect failed, {}", status_line))),
+        }
+    }
+}
+
+// the standard base64 with padding
+fn base64(data: &[u8]) -> String {
+    const TABLE: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let b = [
+            chunk[0],
+            chunk.get(1).copied().unwrap_or(0),
+            chunk.get(2).copied().unwrap_or(0),
+        ];
+        let n = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(TABLE[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::base64;
+
+    #[test]
+    fn base64_padding() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(base64(b"user:pass"), "dXNlcjpwYXNz");
+    }
+}
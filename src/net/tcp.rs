@@ -1,12 +1,14 @@
 use std::io::{self, ErrorKind, Read, Write};
 use std::net::{self, Shutdown, SocketAddr, ToSocketAddrs};
-use std::time::Duration;
+use std::time::{Duration, Instant};
+
+use socket2::{SockRef, TcpKeepalive};
 
 use crate::coroutine_impl::is_coroutine;
 use crate::io as io_impl;
 use crate::io::net as net_impl;
 use crate::std::sync::atomic_dur::AtomicDuration;
-use crate::yield_now::yield_with;
+use crate::yield_now::{consume_budget, yield_with};
 
 // ===== TcpStream =====
 //
@@ -44,6 +46,32 @@ impl TcpStream {
         &self.sys
     }
 
+    /// convert a std stream, the timeouts already set on it are kept
+    pub fn from_std(s: net::TcpStream) -> io::Result<TcpStream> {
+        let read_timeout = s.read_timeout()?;
+        let write_timeout = s.write_timeout()?;
+        let s = TcpStream::new(s)?;
+        s.read_timeout.swap(read_timeout);
+        s.write_timeout.swap(write_timeout);
+        Ok(s)
+    }
+
+    /// convert to a std stream in blocking mode, the timeouts are kept
+    ///
+    /// the socket is removed from the selector first. on windows the socket
+    /// can't leave the iocp, which doesn't affect the blocking io
+    pub fn into_std(self) -> io::Result<net::TcpStream> {
+        let sys = self.sys;
+        #[cfg(unix)]
+        drop(self.io);
+        sys.set_nonblocking(false)?;
+        Ok(sys)
+    }
+
+    /// connect to the remote address
+    ///
+    /// in coroutine context the connect returns early with a `Canceled` error
+    /// if the cancellation token of the coroutine is canceled
     pub fn connect<A: ToSocketAddrs>(addr: A) -> io::Result<TcpStream> {
         if !is_coroutine() {
             let s = net::TcpStream::connect(addr)?;
@@ -65,6 +93,8 @@ impl TcpStream {
         c.done()
     }
 
+    /// same as `connect` except that a `TimedOut` error is returned
+    /// if the connection is not established within the timeout
     pub fn connect_timeout(addr: &SocketAddr, timeout: Duration) -> io::Result<TcpStream> {
         if !is_coroutine() {
             let s = net::TcpStream::connect_timeout(addr, timeout)?;
@@ -94,6 +124,41 @@ impl TcpStream {
         self.sys.local_addr()
     }
 
+    /// receive the data without removing it from the queue
+    ///
+    /// the coroutine is parked until some data is available
+    pub fn peek(&self, buf: &mut [u8]) -> io::Result<usize> {
+        if self
+            .ctx
+            .check_nonblocking(|b| self.sys.set_nonblocking(b))?
+            || !self.ctx.check_context(|b| self.sys.set_nonblocking(b))?
+        {
+            return self.sys.peek(buf);
+        }
+
+        #[cfg(unix)]
+        {
+            self.io.reset();
+            // this is an earlier return try for nonblocking read
+            match self.sys.peek(buf) {
+                Ok(n) => return Ok(n),
+                Err(e) => {
+                    // raw_os_error is faster than kind
+                    let raw_err = e.raw_os_error();
+                    if raw_err == Some(libc::EAGAIN) || raw_err == Some(libc::EWOULDBLOCK) {
+                        // do nothing here
+                    } else {
+                        return Err(e);
+                    }
+                }
+            }
+        }
+
+        let mut reader = net_impl::SocketRead::new_peek(self, buf, self.read_timeout.get());
+        yield_with(&reader);
+        reader.done()
+    }
+
     #[cfg(not(windows))]
     pub fn try_clone(&self) -> io::Result<TcpStream> {
         let s = self.sys.try_clone().and_then(TcpStream::new)?;
@@ -120,14 +185,71 @@ impl TcpStream {
         })
     }
 
+    /// shut down the read, write, or both halves of the connection
+    ///
+    /// with `Shutdown::Write` the peer reads EOF, while this side can still
+    /// read the rest of the response. the coroutines parked on the closed
+    /// half are woken up, a read returns `Ok(0)` and a write fails. on
+    /// windows the pending overlapped io is aborted for `Shutdown::Read` and
+    /// `Shutdown::Both`, so the parked read and write both fail
     pub fn shutdown(&self, how: Shutdown) -> io::Result<()> {
-        self.sys.shutdown(how)
+        self.sys.shutdown(how)?;
+        // the pending overlapped recv is not completed by the shutdown
+        #[cfg(windows)]
+        if how != Shutdown::Write {
+            io_impl::sys::cancel_pending_io(&self.sys)?;
+        }
+        Ok(())
     }
 
     pub fn set_nodelay(&self, nodelay: bool) -> io::Result<()> {
         self.sys.set_nodelay(nodelay)
     }
 
+    pub fn nodelay(&self) -> io::Result<bool> {
+        self.sys.nodelay()
+    }
+
+    /// enable `SO_KEEPALIVE` with the given probe params, `None` disables it
+    ///
+    /// the params not set in the `TcpKeepalive` keep the system default
+    pub fn set_keepalive(&self, params: Option<&TcpKeepalive>) -> io::Result<()> {
+        let sock = SockRef::from(&self.sys);
+        match params {
+            Some(params) => sock.set_tcp_keepalive(params),
+            None => sock.set_keepalive(false),
+        }
+    }
+
+    pub fn keepalive(&self) -> io::Result<bool> {
+        SockRef::from(&self.sys).keepalive()
+    }
+
+    /// set `SO_LINGER`, `None` restores the default behavior of `close`
+    pub fn set_linger(&self, linger: Option<Duration>) -> io::Result<()> {
+        SockRef::from(&self.sys).set_linger(linger)
+    }
+
+    pub fn linger(&self) -> io::Result<Option<Duration>> {
+        SockRef::from(&self.sys).linger()
+    }
+
+    pub fn set_recv_buffer_size(&self, size: usize) -> io::Result<()> {
+        SockRef::from(&self.sys).set_recv_buffer_size(size)
+    }
+
+    pub fn recv_buffer_size(&self) -> io::Result<usize> {
+        SockRef::from(&self.sys).recv_buffer_size()
+    }
+
+    pub fn set_send_buffer_size(&self, size: usize) -> io::Result<()> {
+        SockRef::from(&self.sys).set_send_buffer_size(size)
+    }
+
+    pub fn send_buffer_size(&self) -> io::Result<usize> {
+        SockRef::from(&self.sys).send_buffer_size()
+    }
+
     pub fn take_error(&self) -> io::Result<Option<io::Error>> {
         self.sys.take_error()
     }
@@ -180,6 +302,7 @@ impl TcpStream {
 
 impl Read for TcpStream {
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        consume_budget();
         if self
             .ctx
             .check_nonblocking(|b| self.sys.set_nonblocking(b))?
@@ -211,10 +334,44 @@ impl Read for TcpStream {
         yield_with(&reader);
         reader.done()
     }
+
+    fn read_vectored(&mut self, bufs: &mut [io::IoSliceMut<'_>]) -> io::Result<usize> {
+        consume_budget();
+        if self
+            .ctx
+            .check_nonblocking(|b| self.sys.set_nonblocking(b))?
+            || !self.ctx.check_context(|b| self.sys.set_nonblocking(b))?
+        {
+            return self.sys.read_vectored(bufs);
+        }
+
+        #[cfg(unix)]
+        {
+            self.io.reset();
+            // this is an earlier return try for nonblocking read
+            match self.sys.read_vectored(bufs) {
+                Ok(n) => return Ok(n),
+                Err(e) => {
+                    // raw_os_error is faster than kind
+                    let raw_err = e.raw_os_error();
+                    if raw_err == Some(libc::EAGAIN) || raw_err == Some(libc::EWOULDBLOCK) {
+                        // do nothing here
+                    } else {
+                        return Err(e);
+                    }
+                }
+            }
+        }
+
+        let mut reader = net_impl::SocketReadVectored::new(self, bufs, self.read_timeout.get());
+        yield_with(&reader);
+        reader.done()
+    }
 }
 
 impl Write for TcpStream {
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        consume_budget();
         if self
             .ctx
             .check_nonblocking(|b| self.sys.set_nonblocking(b))?
@@ -246,8 +403,8 @@ impl Write for TcpStream {
         writer.done()
     }
 
-    #[cfg(unix)]
     fn write_vectored(&mut self, bufs: &[io::IoSlice<'_>]) -> io::Result<usize> {
+        consume_budget();
         if self
             .ctx
             .check_nonblocking(|b| self.sys.set_nonblocking(b))?
@@ -274,8 +431,7 @@ impl Write for TcpStream {
             }
         }
 
-        let mut writer =
-            net_impl::SocketWriteVectored::new(self, &self.sys, bufs, self.write_timeout.get());
+        let mut writer = net_impl::SocketWriteVectored::new(self, bufs, self.write_timeout.get());
         yield_with(&writer);
         writer.done()
     }
@@ -343,7 +499,36 @@ impl TcpListener {
         &self.sys
     }
 
+    /// convert a std listener
+    pub fn from_std(s: net::TcpListener) -> io::Result<TcpListener> {
+        TcpListener::new(s)
+    }
+
+    /// convert to a std listener in blocking mode, see `TcpStream::into_std`
+    pub fn into_std(self) -> io::Result<net::TcpListener> {
+        let sys = self.sys;
+        #[cfg(unix)]
+        drop(self.io);
+        sys.set_nonblocking(false)?;
+        Ok(sys)
+    }
+
     pub fn bind<A: ToSocketAddrs>(addr: A) -> io::Result<TcpListener> {
+        TcpListener::bind_with(addr, &TcpListenOptions::new())
+    }
+
+    /// bind the listener with the socket options that must be set before `bind`
+    ///
+    /// ```no_run
+    /// use mco::net::{TcpListenOptions, TcpListener};
+    ///
+    /// let opts = TcpListenOptions::new().backlog(1024);
+    /// let listener = TcpListener::bind_with("127.0.0.1:8080", &opts).unwrap();
+    /// ```
+    pub fn bind_with<A: ToSocketAddrs>(
+        addr: A,
+        opts: &TcpListenOptions,
+    ) -> io::Result<TcpListener> {
         use socket2::{Domain, Socket, Type};
         let mut addrs = addr.to_socket_addrs()?;
         let next = addrs.next();
@@ -357,32 +542,117 @@ impl TcpListener {
         };
 
         // windows not have reuset port but reuse address is not safe
-        listener.set_reuse_address(true)?;
+        listener.set_reuse_address(opts.reuse_address)?;
 
         #[cfg(unix)]
-        listener.set_reuse_port(true)?;
+        listener.set_reuse_port(opts.reuse_port)?;
 
         listener.bind(&addr.into())?;
         for addr in addrs {
             listener.bind(&addr.into())?;
         }
-        listener.listen(256)?;
+        listener.listen(opts.backlog)?;
 
         let s = listener.into();
         TcpListener::new(s)
     }
 
+    /// bind the listener with `SO_REUSEPORT`, so that other listeners can
+    /// bind the same address and share the incoming connections
+    #[cfg(unix)]
+    pub fn bind_reuseport<A: ToSocketAddrs>(addr: A) -> io::Result<TcpListener> {
+        TcpListener::bind_with(addr, &TcpListenOptions::new().reuse_port(true))
+    }
+
+    /// bind one `SO_REUSEPORT` listener for each io worker, and run `f` with
+    /// it in a coroutine pinned to that worker
+    ///
+    /// the kernel balances the incoming connections between the listeners,
+    /// so the accept is not serialized on a single queue. with port `0` all
+    /// the listeners share the port picked for the first one
+    ///
+    /// ```no_run
+    /// use std::io::Write;
+    /// use mco::net::TcpListener;
+    ///
+    /// let shards = TcpListener::bind_sharded("127.0.0.1:8080", |listener| {
+    ///     for stream in listener.incoming() {
+    ///         stream.unwrap().write_all(b"hello").ok();
+    ///     }
+    /// })
+    /// .unwrap();
+    /// for h in shards {
+    ///     h.join().unwrap();
+    /// }
+    /// ```
+    #[cfg(unix)]
+    pub fn bind_sharded<A, F>(addr: A, f: F) -> io::Result<Vec<crate::coroutine::JoinHandle<()>>>
+    where
+        A: ToSocketAddrs,
+        F: Fn(TcpListener) + Send + Sync + 'static,
+    {
+        let first = TcpListener::bind_reuseport(addr)?;
+        let addr = first.local_addr()?;
+        let mut listeners = vec![first];
+        for _ in 1..crate::config().get_workers() {
+            listeners.push(TcpListener::bind_reuseport(addr)?);
+        }
+
+        let f = std::sync::Arc::new(f);
+        let shards = listeners
+            .into_iter()
+            .enumerate()
+            .map(|(id, listener)| {
+                let f = f.clone();
+                crate::coroutine::Builder::new().spawn_on(id, move || f(listener))
+            })
+            .collect::<Result<_, _>>()
+            .map_err(io::Error::other)?;
+        Ok(shards)
+    }
+
     pub fn accept(&self) -> io::Result<(TcpStream, SocketAddr)> {
+        self.accept_impl(None)
+    }
+
+    /// accept a connection, return a `TimedOut` error if there is none after `dur`
+    ///
+    /// ```no_run
+    /// use std::io::ErrorKind;
+    /// use std::time::Duration;
+    /// use mco::net::TcpListener;
+    ///
+    /// let listener = TcpListener::bind("127.0.0.1:8080").unwrap();
+    /// loop {
+    ///     match listener.accept_timeout(Duration::from_secs(1)) {
+    ///         Ok((s, _)) => drop(s),
+    ///         // do the periodic work, e.g. check the shutdown flag
+    ///         Err(e) if e.kind() == ErrorKind::TimedOut => {}
+    ///         Err(e) => panic!("{}", e),
+    ///     }
+    /// }
+    /// ```
+    pub fn accept_timeout(&self, dur: Duration) -> io::Result<(TcpStream, SocketAddr)> {
+        self.accept_impl(Some(dur))
+    }
+
+    fn accept_impl(&self, timeout: Option<Duration>) -> io::Result<(TcpStream, SocketAddr)> {
         if self
             .ctx
             .check_nonblocking(|b| self.sys.set_nonblocking(b))?
-            || !self.ctx.check_context(|b| self.sys.set_nonblocking(b))?
         {
             return self
                 .sys
                 .accept()
                 .and_then(|(s, a)| TcpStream::new(s).map(|s| (s, a)));
         }
+        if !self.ctx.check_context(|b| self.sys.set_nonblocking(b))? {
+            let (s, a) = match timeout {
+                Some(dur) => accept_blocking_timeout(&self.sys, dur)?,
+                None => self.sys.accept()?,
+            };
+            return TcpStream::new(s).map(|s| (s, a));
+        }
 
         #[cfg(unix)]
         {
@@ -401,11 +671,13 @@ impl TcpListener {
             }
         }
 
-        let mut a = net_impl::TcpListenerAccept::new(self)?;
+        let mut a = net_impl::TcpListenerAccept::new(self, timeout)?;
         yield_with(&a);
         a.done()
     }
 
+    /// iterate over the accepted connections, each `next` parks the coroutine
+    /// until a connection comes
     pub fn incoming(&self) -> Incoming {
         Incoming { listener: self }
     }
@@ -442,7 +714,79 @@ impl TcpListener {
         self.sys.take_error()
     }
 
-    // TODO: add all std functions
+    pub fn set_ttl(&self, ttl: u32) -> io::Result<()> {
+        self.sys.set_ttl(ttl)
+    }
+
+    pub fn ttl(&self) -> io::Result<u32> {
+        self.sys.ttl()
+    }
+
+    /// the accepted streams inherit the buffer sizes of the listener
+    pub fn set_recv_buffer_size(&self, size: usize) -> io::Result<()> {
+        SockRef::from(&self.sys).set_recv_buffer_size(size)
+    }
+
+    pub fn recv_buffer_size(&self) -> io::Result<usize> {
+        SockRef::from(&self.sys).recv_buffer_size()
+    }
+
+    pub fn set_send_buffer_size(&self, size: usize) -> io::Result<()> {
+        SockRef::from(&self.sys).set_send_buffer_size(size)
+    }
+
+    pub fn send_buffer_size(&self) -> io::Result<usize> {
+        SockRef::from(&self.sys).send_buffer_size()
+    }
+}
+
+/// the options used by [`TcpListener::bind_with`]
+///
+/// the default is what [`TcpListener::bind`] uses: `SO_REUSEADDR` and
+/// `SO_REUSEPORT` (unix only) are on, the backlog is 256
+#[derive(Debug, Clone)]
+pub struct TcpListenOptions {
+    reuse_address: bool,
+    #[cfg(unix)]
+    reuse_port: bool,
+    backlog: i32,
+}
+
+impl TcpListenOptions {
+    pub fn new() -> Self {
+        TcpListenOptions {
+            reuse_address: true,
+            #[cfg(unix)]
+            reuse_port: true,
+            backlog: 256,
+        }
+    }
+
+    /// set `SO_REUSEADDR`
+    pub fn reuse_address(mut self, reuse: bool) -> Self {
+        self.reuse_address = reuse;
+        self
+    }
+
+    /// set `SO_REUSEPORT`, with it several listeners can bind the same address
+    /// and the kernel balances the incoming connections between them
+    #[cfg(unix)]
+    pub fn reuse_port(mut self, reuse: bool) -> Self {
+        self.reuse_port = reuse;
+        self
+    }
+
+    /// the max length of the pending connections queue
+    pub fn backlog(mut self, backlog: i32) -> Self {
+        self.backlog = backlog;
+        self
+    }
+}
+
+impl Default for TcpListenOptions {
+    fn default() -> Self {
+        TcpListenOptions::new()
+    }
 }
 
 #[cfg(unix)]
@@ -456,6 +800,67 @@ impl io_impl::AsIoData for TcpListener {
 //
 //
 
+// accept out of coroutine context within the timeout, the connection that
+// wakes up the poll may be taken by another thread, so the accept after it
+// must not block
+fn accept_blocking_timeout(
+    s: &net::TcpListener,
+    dur: Duration,
+) -> io::Result<(net::TcpStream, SocketAddr)> {
+    let deadline = Instant::now() + dur;
+    loop {
+        wait_readable(s, deadline.saturating_duration_since(Instant::now()))?;
+        s.set_nonblocking(true)?;
+        let ret = s.accept();
+        s.set_nonblocking(false)?;
+        match ret {
+            Err(ref e) if e.kind() == ErrorKind::WouldBlock => {}
+            Err(ref e) if e.kind() == ErrorKind::Interrupted => {}
+            ret => return ret,
+        }
+    }
+}
+
+// block the thread until the listener is readable, used out of coroutine
+// context. an interrupted poll returns as readable, the accept then retries
+#[cfg(unix)]
+fn wait_readable(s: &net::TcpListener, dur: Duration) -> io::Result<()> {
+    let mut pfd = libc::pollfd {
+        fd: s.as_raw_fd(),
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    let ms = dur.as_millis().min(libc::c_int::MAX as u128) as libc::c_int;
+    match unsafe { libc::poll(&mut pfd, 1, ms) } {
+        -1 => match io::Error::last_os_error() {
+            e if e.kind() == ErrorKind::Interrupted => Ok(()),
+            e => Err(e),
+        },
+        0 => Err(io::Error::new(ErrorKind::TimedOut, "accept timed out")),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(windows)]
+fn wait_readable(s: &net::TcpListener, dur: Duration) -> io::Result<()> {
+    use windows_sys::Win32::Networking::WinSock::{WSAPoll, POLLRDNORM, SOCKET, WSAPOLLFD};
+
+    let mut pfd = WSAPOLLFD {
+        fd: s.as_raw_socket() as SOCKET,
+        events: POLLRDNORM,
+        revents: 0,
+    };
+    let ms = dur.as_millis().min(i32::MAX as u128) as i32;
+    match unsafe { WSAPoll(&mut pfd, 1, ms) } {
+        -1 => match io::Error::last_os_error() {
+            e if e.kind() == ErrorKind::Interrupted => Ok(()),
+            e => Err(e),
+        },
+        0 => Err(io::Error::new(ErrorKind::TimedOut, "accept timed out")),
+        _ => Ok(()),
+    }
+}
+
 pub struct Incoming<'a> {
     listener: &'a TcpListener,
 }
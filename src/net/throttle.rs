@@ -0,0 +1,194 @@
+use std::io::{self, Read, Write};
+use std::time::{Duration, Instant};
+
+use crate::coroutine_impl::{current_cancel_data, is_coroutine};
+
+// token bucket of one direction, the burst is one second of the rate
+#[derive(Debug)]
+struct Bucket {
+    // bytes per second, `None` is unlimited
+    rate: Option<u64>,
+    tokens: f64,
+    last: Instant,
+}
+
+impl Bucket {
+    fn new() -> Self {
+        Bucket {
+            rate: None,
+            tokens: 0.0,
+            last: Instant::now(),
+        }
+    }
+
+    fn set_rate(&mut self, rate: Option<u64>) {
+        self.rate = rate.map(|r| r.max(1));
+        self.tokens = self.rate.unwrap_or(0) as f64;
+        self.last = Instant::now();
+    }
+
+    fn refill(&mut self, rate: u64) {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * rate as f64).min(rate as f64);
+        self.last = now;
+    }
+
+    // wait until there is budget, return the max bytes allowed for the io
+    fn acquire(&mut self, want: usize) -> io::Result<usize> {
+        let rate = match self.rate {
+            Some(rate) => rate,
+            None => return Ok(want),
+        };
+        // the wait observes the cancellation token of the coroutine, the
+        // sleep returns early once it is canceled
+        let cancel = is_coroutine().then(current_cancel_data);
+        let _g = cancel.map(|c| c.interruptible());
+        loop {
+            self.refill(rate);
+            if self.tokens >= 1.0 {
+                return Ok(want.min(self.tokens as usize));
+            }
+            if cancel.is_some_and(|c| c.is_interrupted()) {
+                return Err(io::Error::other("Canceled"));
+            }
+            let secs = (1.0 - self.tokens) / rate as f64;
+            crate::coroutine::sleep(Duration::from_secs_f64(secs));
+        }
+    }
+
+    fn consume(&mut self, n: usize) {
+        if self.rate.is_some() {
+            self.tokens -= n as f64;
+        }
+    }
+}
+
+/// Rate limiting wrapper of a stream
+///
+/// the reads and writes are limited separately by token buckets, the burst
+/// is one second worth of bytes. when the budget is exhausted the coroutine
+/// sleeps on the timer, out of coroutine context the thread sleeps
+///
+/// ```no_run
+/// use std::io::Write;
+/// use mco::net::{TcpStream, Throttled};
+///
+/// let stream = TcpStream::connect("127.0.0.1:8080").unwrap();
+/// // upload at 64KB/s at most
+/// let mut stream = Throttled::new(stream).write_limit(64 * 1024);
+/// stream.write_all(&[0u8; 1024 * 1024]).unwrap();
+/// ```
+#[derive(Debug)]
+pub struct Throttled<S> {
+    inner: S,
+    read: Bucket,
+    write: Bucket,
+}
+
+impl<S> Throttled<S> {
+    /// wrap the stream, both directions are unlimited
+    pub fn new(inner: S) -> Self {
+        Throttled {
+            inner,
+            read: Bucket::new(),
+            write: Bucket::new(),
+        }
+    }
+
+    /// limit the reads to `bytes_per_sec`
+    pub fn read_limit(mut self, bytes_per_sec: u64) -> Self {
+        self.set_read_limit(Some(bytes_per_sec));
+        self
+    }
+
+    /// limit the writes to `bytes_per_sec`
+    pub fn write_limit(mut self, bytes_per_sec: u64) -> Self {
+        self.set_write_limit(Some(bytes_per_sec));
+        self
+    }
+
+    /// change the read limit, `None` is unlimited
+    pub fn set_read_limit(&mut self, bytes_per_sec: Option<u64>) {
+        self.read.set_rate(bytes_per_sec);
+    }
+
+    /// change the write limit, `None` is unlimited
+    pub fn set_write_limit(&mut self, bytes_per_sec: Option<u64>) {
+        self.write.set_rate(bytes_per_sec);
+    }
+
+    pub fn get_ref(&self) -> &S {
+        &self.inner
+    }
+
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+}
+
+impl<S: Read> Read for Throttled<S> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return self.inner.read(buf);
+        }
+        let n = self.read.acquire(buf.len())?;
+        let n = self.inner.read(&mut buf[..n])?;
+        self.read.consume(n);
+        Ok(n)
+    }
+}
+
+impl<S: Write> Write for Throttled<S> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return self.inner.write(buf);
+        }
+        let n = self.write.acquire(buf.len())?;
+        let n = self.inner.write(&buf[..n])?;
+        self.write.consume(n);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn throttled_write_rate() {
+        let mut s = Throttled::new(Vec::new()).write_limit(1000);
+        let start = Instant::now();
+        // the first second is the burst
+        s.write_all(&[0u8; 1500]).unwrap();
+        let dur = start.elapsed();
+        assert!(dur >= Duration::from_millis(450), "{:?}", dur);
+        assert_eq!(s.get_ref().len(), 1500);
+
+        s.set_write_limit(None);
+        s.write_all(&[0u8; 100_000]).unwrap();
+    }
+
+    #[test]
+    fn throttled_write_canceled() {
+        let h = co!(|| {
+            let mut s = Throttled::new(Vec::new()).write_limit(1000);
+            // the burst is written at once
+            s.write_all(&[0u8; 1000]).unwrap();
+            crate::coroutine::current().cancel_token().cancel();
+            let start = Instant::now();
+            let err = s.write_all(&[0u8; 1000]).unwrap_err();
+            assert!(start.elapsed() < Duration::from_millis(500));
+            err.to_string()
+        });
+        assert_eq!(h.join().unwrap(), "Canceled");
+    }
+}
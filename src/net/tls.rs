@@ -0,0 +1,212 @@
+//! TLS streams over the coroutine sockets, enabled by the `tls` feature
+//!
+//! the handshake and the record IO are driven by the `Read`/`Write` of the
+//! wrapped stream, so with a [`TcpStream`](crate::net::TcpStream) only the
+//! coroutine is parked while waiting for the peer. the configs are plain
+//! [`rustls`] ones, which is re-exported here.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use std::io::{Read, Write};
+//! use std::sync::Arc;
+//! use mco::net::tls::{rustls, TlsConnector};
+//! use mco::net::TcpStream;
+//!
+//! let ca = std::fs::read("ca.der").unwrap();
+//! let mut roots = rustls::RootCertStore::empty();
+//! roots.add(ca.into()).unwrap();
+//! let config = rustls::ClientConfig::builder()
+//!     .with_root_certificates(roots)
+//!     .with_no_client_auth();
+//! let connector = TlsConnector::new(Arc::new(config));
+//!
+//! let tcp = TcpStream::connect("example.com:443").unwrap();
+//! let mut stream = connector.connect("example.com", tcp).unwrap();
+//! stream.write_all(b"GET / HTTP/1.0\r\n\r\n").unwrap();
+//! let mut rsp = Vec::new();
+//! stream.read_to_end(&mut rsp).unwrap();
+//! ```
+
+use std::convert::TryFrom;
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::sync::Arc;
+
+pub use rustls;
+use rustls::pki_types::ServerName;
+use rustls::{ClientConfig, ClientConnection, Connection, ServerConfig, ServerConnection};
+
+fn tls_err(e: rustls::Error) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e)
+}
+
+/// the client side of the TLS handshake
+#[derive(Clone)]
+pub struct TlsConnector {
+    config: Arc<ClientConfig>,
+}
+
+impl TlsConnector {
+    pub fn new(config: Arc<ClientConfig>) -> Self {
+        TlsConnector { config }
+    }
+
+    /// run the handshake over the connected stream
+    ///
+    /// `domain` is used for SNI and to verify the server certificate
+    pub fn connect<S: Read + Write>(&self, domain: &str, stream: S) -> io::Result<TlsStream<S>> {
+        let name = ServerName::try_from(domain.to_owned())
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        let conn = ClientConnection::new(self.config.clone(), name).map_err(tls_err)?;
+        TlsStream::handshake(conn.into(), stream)
+    }
+}
+
+impl From<Arc<ClientConfig>> for TlsConnector {
+    fn from(config: Arc<ClientConfig>) -> Self {
+        TlsConnector::new(config)
+    }
+}
+
+impl fmt::Debug for TlsConnector {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("TlsConnector").finish()
+    }
+}
+
+/// the server side of the TLS handshake
+#[derive(Clone)]
+pub struct TlsAcceptor {
+    config: Arc<ServerConfig>,
+}
+
+impl TlsAcceptor {
+    pub fn new(config: Arc<ServerConfig>) -> Self {
+        TlsAcceptor { config }
+    }
+
+    /// run the handshake over the accepted stream
+    pub fn accept<S: Read + Write>(&self, stream: S) -> io::Result<TlsStream<S>> {
+        let conn = ServerConnection::new(self.config.clone()).map_err(tls_err)?;
+        TlsStream::handshake(conn.into(), stream)
+    }
+}
+
+impl From<Arc<ServerConfig>> for TlsAcceptor {
+    fn from(config: Arc<ServerConfig>) -> Self {
+        TlsAcceptor::new(config)
+    }
+}
+
+impl fmt::Debug for TlsAcceptor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("TlsAcceptor").finish()
+    }
+}
+
+/// a stream that encrypts the data with TLS
+pub struct TlsStream<S> {
+    conn: Connection,
+    sock: S,
+}
+
+impl<S: Read + Write> TlsStream<S> {
+    fn handshake(mut conn: Connection, mut sock: S) -> io::Result<Self> {
+        while conn.is_handshaking() {
+            let (rd, wr) = conn.complete_io(&mut sock)?;
+            if rd == 0 && wr == 0 && conn.is_handshaking() {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "tls handshake eof",
+                ));
+            }
+        }
+        Ok(TlsStream { conn, sock })
+    }
+
+    // finish the pending handshake and flush the buffered records
+    fn complete_prior_io(&mut self) -> io::Result<()> {
+        if self.conn.is_handshaking() {
+            self.conn.complete_io(&mut self.sock)?;
+        }
+        if self.conn.wants_write() {
+            self.conn.complete_io(&mut self.sock)?;
+        }
+        Ok(())
+    }
+
+    /// send the close_notify alert to the peer
+    ///
+    /// the underlying stream is not shut down
+    pub fn shutdown(&mut self) -> io::Result<()> {
+        self.conn.send_close_notify();
+        while self.conn.wants_write() {
+            self.conn.write_tls(&mut self.sock)?;
+        }
+        self.sock.flush()
+    }
+}
+
+impl<S> TlsStream<S> {
+    pub fn get_ref(&self) -> &S {
+        &self.sock
+    }
+
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.sock
+    }
+
+    /// the TLS state, e.g. the negotiated protocol or the peer certificates
+    pub fn connection(&self) -> &Connection {
+        &self.conn
+    }
+
+    pub fn into_inner(self) -> S {
+        self.sock
+    }
+}
+
+impl<S: Read + Write> Read for TlsStream<S> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.complete_prior_io()?;
+        // a single read may only get part of a record
+        while self.conn.wants_read() {
+            if self.conn.complete_io(&mut self.sock)?.0 == 0 {
+                break;
+            }
+        }
+        self.conn.reader().read(buf)
+    }
+}
+
+impl<S: Read + Write> Write for TlsStream<S> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.complete_prior_io()?;
+        let n = self.conn.writer().write(buf)?;
+        // the data is buffered in the connection, a socket that is not ready
+        // yet gets it on the next call, the other errors are fatal
+        match self.conn.complete_io(&mut self.sock) {
+            Err(e) if e.kind() != io::ErrorKind::WouldBlock => Err(e),
+            _ => Ok(n),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.complete_prior_io()?;
+        self.conn.writer().flush()?;
+        if self.conn.wants_write() {
+            self.conn.complete_io(&mut self.sock)?;
+        }
+        self.sock.flush()
+    }
+}
+
+impl<S: fmt::Debug> fmt::Debug for TlsStream<S> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("TlsStream")
+            .field("sock", &self.sock)
+            .field("protocol", &self.conn.protocol_version())
+            .finish()
+    }
+}
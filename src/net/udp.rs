@@ -110,20 +110,37 @@ impl UdpSocket {
     }
 
     pub fn recv_from(&self, buf: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+        self.recv_from_impl(buf, false)
+    }
+
+    /// receive a datagram without removing it from the queue
+    pub fn peek_from(&self, buf: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+        self.recv_from_impl(buf, true)
+    }
+
+    fn recv_from_impl(&self, buf: &mut [u8], peek: bool) -> io::Result<(usize, SocketAddr)> {
+        let sys_recv_from = |buf: &mut [u8]| {
+            if peek {
+                self.sys.peek_from(buf)
+            } else {
+                self.sys.recv_from(buf)
+            }
+        };
+
         if self
             .ctx
             .check_nonblocking(|b| self.sys.set_nonblocking(b))?
             || !self.ctx.check_context(|b| self.sys.set_nonblocking(b))?
         {
             // this can't be nonblocking!!
-            return self.sys.recv_from(buf);
+            return sys_recv_from(buf);
         }
 
         #[cfg(unix)]
         {
             self.io.reset();
             // this is an earlier return try for nonblocking read
-            match self.sys.recv_from(buf) {
+            match sys_recv_from(buf) {
                 Ok(n) => return Ok(n),
                 Err(e) => {
                     // raw_os_error is faster than kind
@@ -137,7 +154,11 @@ impl UdpSocket {
             }
         }
 
-        let mut reader = net_impl::UdpRecvFrom::new(self, buf);
+        let mut reader = if peek {
+            net_impl::UdpRecvFrom::new_peek(self, buf)
+        } else {
+            net_impl::UdpRecvFrom::new(self, buf)
+        };
         yield_with(&reader);
         reader.done()
     }
@@ -208,6 +229,95 @@ impl UdpSocket {
         reader.done()
     }
 
+    /// send the data gathered from the buffers to the connected peer
+    pub fn send_vectored(&self, bufs: &[io::IoSlice<'_>]) -> io::Result<usize> {
+        let sock = socket2::SockRef::from(&self.sys);
+        if self
+            .ctx
+            .check_nonblocking(|b| self.sys.set_nonblocking(b))?
+            || !self.ctx.check_context(|b| self.sys.set_nonblocking(b))?
+        {
+            // this can't be nonblocking!!
+            return sock.send_vectored(bufs);
+        }
+
+        #[cfg(unix)]
+        {
+            self.io.reset();
+            // this is an earlier return try for nonblocking write
+            match sock.send_vectored(bufs) {
+                Ok(n) => return Ok(n),
+                Err(e) => {
+                    // raw_os_error is faster than kind
+                    let raw_err = e.raw_os_error();
+                    if raw_err == Some(libc::EAGAIN) || raw_err == Some(libc::EWOULDBLOCK) {
+                        // do nothing here
+                    } else {
+                        return Err(e);
+                    }
+                }
+            }
+        }
+
+        let mut writer = net_impl::SocketWriteVectored::new(self, bufs, self.write_timeout.get());
+        yield_with(&writer);
+        writer.done()
+    }
+
+    /// receive a datagram from the connected peer and scatter it into the buffers
+    pub fn recv_vectored(&self, bufs: &mut [io::IoSliceMut<'_>]) -> io::Result<usize> {
+        let sock = socket2::SockRef::from(&self.sys);
+        // both MaybeUninitSlice and IoSliceMut are ABI compatible with iovec/WSABUF
+        let recv = |bufs: &mut [io::IoSliceMut<'_>]| {
+            let bufs = unsafe {
+                std::slice::from_raw_parts_mut(
+                    bufs.as_mut_ptr() as *mut socket2::MaybeUninitSlice<'_>,
+                    bufs.len(),
+                )
+            };
+            sock.recv_vectored(bufs).map(|(n, _)| n)
+        };
+        if self
+            .ctx
+            .check_nonblocking(|b| self.sys.set_nonblocking(b))?
+            || !self.ctx.check_context(|b| self.sys.set_nonblocking(b))?
+        {
+            // this can't be nonblocking!!
+            return recv(bufs);
+        }
+
+        #[cfg(unix)]
+        {
+            self.io.reset();
+            // this is an earlier return try for nonblocking read
+            match recv(bufs) {
+                Ok(n) => return Ok(n),
+                Err(e) => {
+                    // raw_os_error is faster than kind
+                    let raw_err = e.raw_os_error();
+                    if raw_err == Some(libc::EAGAIN) || raw_err == Some(libc::EWOULDBLOCK) {
+                        // do nothing here
+                    } else {
+                        return Err(e);
+                    }
+                }
+            }
+        }
+
+        let mut reader = net_impl::SocketReadVectored::new(self, bufs, self.read_timeout.get());
+        yield_with(&reader);
+        reader.done()
+    }
+
+    /// receive a datagram from the connected peer without removing it from the queue
+    pub fn peek(&self, buf: &mut [u8]) -> io::Result<usize> {
+        self.peek_from(buf).map(|(n, _)| n)
+    }
+
+    pub fn peer_addr(&self) -> io::Result<SocketAddr> {
+        self.sys.peer_addr()
+    }
+
     pub fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
         self.ctx.set_nonblocking(nonblocking);
         Ok(())
@@ -265,6 +375,33 @@ impl UdpSocket {
         self.sys.set_multicast_loop_v6(on)
     }
 
+    /// the hop limit of the outgoing ipv6 multicast packets, the ipv6 `ttl`
+    pub fn multicast_hops_v6(&self) -> io::Result<u32> {
+        socket2::SockRef::from(&self.sys).multicast_hops_v6()
+    }
+
+    pub fn set_multicast_hops_v6(&self, hops: u32) -> io::Result<()> {
+        socket2::SockRef::from(&self.sys).set_multicast_hops_v6(hops)
+    }
+
+    /// the interface of the outgoing ipv4 multicast packets
+    pub fn multicast_if_v4(&self) -> io::Result<Ipv4Addr> {
+        socket2::SockRef::from(&self.sys).multicast_if_v4()
+    }
+
+    pub fn set_multicast_if_v4(&self, interface: &Ipv4Addr) -> io::Result<()> {
+        socket2::SockRef::from(&self.sys).set_multicast_if_v4(interface)
+    }
+
+    /// the interface index of the outgoing ipv6 multicast packets
+    pub fn multicast_if_v6(&self) -> io::Result<u32> {
+        socket2::SockRef::from(&self.sys).multicast_if_v6()
+    }
+
+    pub fn set_multicast_if_v6(&self, interface: u32) -> io::Result<()> {
+        socket2::SockRef::from(&self.sys).set_multicast_if_v6(interface)
+    }
+
     pub fn ttl(&self) -> io::Result<u32> {
         self.sys.ttl()
     }
@@ -273,6 +410,22 @@ impl UdpSocket {
         self.sys.set_ttl(ttl)
     }
 
+    pub fn set_recv_buffer_size(&self, size: usize) -> io::Result<()> {
+        socket2::SockRef::from(&self.sys).set_recv_buffer_size(size)
+    }
+
+    pub fn recv_buffer_size(&self) -> io::Result<usize> {
+        socket2::SockRef::from(&self.sys).recv_buffer_size()
+    }
+
+    pub fn set_send_buffer_size(&self, size: usize) -> io::Result<()> {
+        socket2::SockRef::from(&self.sys).set_send_buffer_size(size)
+    }
+
+    pub fn send_buffer_size(&self) -> io::Result<usize> {
+        socket2::SockRef::from(&self.sys).send_buffer_size()
+    }
+
     #[allow(clippy::trivially_copy_pass_by_ref)]
     pub fn join_multicast_v4(&self, multiaddr: &Ipv4Addr, interface: &Ipv4Addr) -> io::Result<()> {
         self.sys.join_multicast_v4(multiaddr, interface)
@@ -0,0 +1,36 @@
+//! Unix domain sockets
+//!
+//! the sockets park the coroutine instead of blocking the worker thread,
+//! they are the same types as in [`mco::os::unix::net`](crate::os::unix::net).
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use mco::net::unix::{UnixListener, UnixStream};
+//! use std::io::{Read, Write};
+//!
+//! let listener = UnixListener::bind("/tmp/mco.sock").unwrap();
+//! mco::co!(move || {
+//!     for stream in listener.incoming() {
+//!         let mut stream = stream.unwrap();
+//!         stream.write_all(b"hello").unwrap();
+//!     }
+//! });
+//!
+//! let mut stream = UnixStream::connect("/tmp/mco.sock").unwrap();
+//! let mut buf = String::new();
+//! stream.read_to_string(&mut buf).unwrap();
+//! ```
+
+use std::io;
+
+pub use crate::os::unix::net::{Incoming, UnixDatagram, UnixListener, UnixStream};
+pub use std::os::unix::net::SocketAddr;
+
+/// create a pair of connected stream sockets registered to the event loop
+///
+/// it's handy for the self-pipe wakeups, one end can be passed to another
+/// coroutine or thread. use `UnixDatagram::pair` for the datagram sockets.
+pub fn socketpair() -> io::Result<(UnixStream, UnixStream)> {
+    UnixStream::pair()
+}
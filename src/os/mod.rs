@@ -1 +1,7 @@
+mod pipe;
+mod signal;
 pub mod unix;
+pub mod windows;
+
+pub use self::pipe::{pipe, PipeReader, PipeWriter};
+pub use self::signal::{signal, SignalKind, SignalStream};
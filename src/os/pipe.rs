@@ -0,0 +1,156 @@
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::time::Duration;
+
+#[cfg(unix)]
+use std::fs::File;
+#[cfg(unix)]
+use std::os::unix::io::{AsRawFd, FromRawFd, IntoRawFd, RawFd};
+#[cfg(windows)]
+use std::os::windows::io::{AsRawHandle, IntoRawHandle, RawHandle};
+
+#[cfg(unix)]
+use crate::io::CoIo;
+#[cfg(windows)]
+use crate::os::windows::named_pipe::{NamedPipeClient, NamedPipeServer};
+
+/// create an anonymous pipe that can be used in coroutine context
+///
+/// the data written to the `PipeWriter` can be read from the `PipeReader`.
+/// on windows the anonymous pipes don't support the overlapped io, so a
+/// pair of named pipe is used instead
+///
+/// ```no_run
+/// use std::io::{Read, Write};
+///
+/// let (mut reader, mut writer) = mco::os::pipe().unwrap();
+/// mco::co!(move || writer.write_all(b"hello").unwrap());
+/// let mut buf = String::new();
+/// reader.read_to_string(&mut buf).unwrap();
+/// assert_eq!(buf, "hello");
+/// ```
+#[cfg(unix)]
+pub fn pipe() -> io::Result<(PipeReader, PipeWriter)> {
+    let mut fds = [0; 2];
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    let ret = unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC) };
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    let ret = unsafe { libc::pipe(fds.as_mut_ptr()) };
+    if ret < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let (r, w) = unsafe { (File::from_raw_fd(fds[0]), File::from_raw_fd(fds[1])) };
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    for fd in &fds {
+        if unsafe { libc::fcntl(*fd, libc::F_SETFD, libc::FD_CLOEXEC) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok((PipeReader(CoIo::new(r)?), PipeWriter(CoIo::new(w)?)))
+}
+
+/// create an anonymous pipe that can be used in coroutine context
+///
+/// the data written to the `PipeWriter` can be read from the `PipeReader`.
+/// on windows the anonymous pipes don't support the overlapped io, so a
+/// pair of named pipe is used instead
+#[cfg(windows)]
+pub fn pipe() -> io::Result<(PipeReader, PipeWriter)> {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    static ID: AtomicUsize = AtomicUsize::new(0);
+
+    let name = format!(
+        r"\\.\pipe\mco-anonymous-{}-{}",
+        std::process::id(),
+        ID.fetch_add(1, Ordering::Relaxed)
+    );
+    let server = NamedPipeServer::create(&name)?;
+    // the instance is usable once the client is connected
+    let client = NamedPipeClient::connect(&name)?;
+    Ok((PipeReader(server), PipeWriter(client)))
+}
+
+/// The reading end of a pipe, see [`pipe`]
+pub struct PipeReader(#[cfg(unix)] CoIo<File>, #[cfg(windows)] NamedPipeServer);
+
+/// The writing end of a pipe, see [`pipe`]
+pub struct PipeWriter(#[cfg(unix)] CoIo<File>, #[cfg(windows)] NamedPipeClient);
+
+macro_rules! pipe_end {
+    ($name: ident) => {
+        impl $name {
+            pub fn set_read_timeout(&self, dur: Option<Duration>) -> io::Result<()> {
+                self.0.set_read_timeout(dur)
+            }
+
+            pub fn set_write_timeout(&self, dur: Option<Duration>) -> io::Result<()> {
+                self.0.set_write_timeout(dur)
+            }
+
+            pub fn read_timeout(&self) -> io::Result<Option<Duration>> {
+                self.0.read_timeout()
+            }
+
+            pub fn write_timeout(&self) -> io::Result<Option<Duration>> {
+                self.0.write_timeout()
+            }
+        }
+
+        impl fmt::Debug for $name {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                #[cfg(unix)]
+                let raw = self.as_raw_fd();
+                #[cfg(windows)]
+                let raw = self.as_raw_handle();
+                f.debug_tuple(stringify!($name)).field(&raw).finish()
+            }
+        }
+
+        #[cfg(unix)]
+        impl AsRawFd for $name {
+            fn as_raw_fd(&self) -> RawFd {
+                self.0.as_raw_fd()
+            }
+        }
+
+        #[cfg(unix)]
+        impl IntoRawFd for $name {
+            fn into_raw_fd(self) -> RawFd {
+                self.0.into_raw_fd()
+            }
+        }
+
+        #[cfg(windows)]
+        impl AsRawHandle for $name {
+            fn as_raw_handle(&self) -> RawHandle {
+                self.0.as_raw_handle()
+            }
+        }
+
+        #[cfg(windows)]
+        impl IntoRawHandle for $name {
+            fn into_raw_handle(self) -> RawHandle {
+                self.0.into_raw_handle()
+            }
+        }
+    };
+}
+
+pipe_end!(PipeReader);
+pipe_end!(PipeWriter);
+
+impl Read for PipeReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.0.read(buf)
+    }
+}
+
+impl Write for PipeWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
@@ -0,0 +1,424 @@
+use std::fmt;
+use std::io;
+
+#[cfg(unix)]
+use self::unix::Globals;
+#[cfg(windows)]
+use self::windows::Globals;
+
+/// The kind of signal to listen for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SignalKind(i32);
+
+#[cfg(unix)]
+impl SignalKind {
+    /// any signal number, `SIGKILL`, `SIGSTOP` and the fault signals are refused
+    pub const fn from_raw(signum: libc::c_int) -> Self {
+        SignalKind(signum)
+    }
+
+    pub const fn as_raw(&self) -> libc::c_int {
+        self.0
+    }
+
+    /// `SIGINT`, sent by Ctrl-C in the terminal
+    pub const fn interrupt() -> Self {
+        SignalKind(libc::SIGINT)
+    }
+
+    /// `SIGTERM`, the graceful termination request
+    pub const fn terminate() -> Self {
+        SignalKind(libc::SIGTERM)
+    }
+
+    /// `SIGHUP`, usually used to reload the configuration
+    pub const fn hangup() -> Self {
+        SignalKind(libc::SIGHUP)
+    }
+
+    /// `SIGQUIT`
+    pub const fn quit() -> Self {
+        SignalKind(libc::SIGQUIT)
+    }
+
+    /// `SIGUSR1`
+    pub const fn user_defined1() -> Self {
+        SignalKind(libc::SIGUSR1)
+    }
+
+    /// `SIGUSR2`
+    pub const fn user_defined2() -> Self {
+        SignalKind(libc::SIGUSR2)
+    }
+
+    /// `SIGCHLD`
+    pub const fn child() -> Self {
+        SignalKind(libc::SIGCHLD)
+    }
+
+    /// `SIGPIPE`
+    pub const fn pipe() -> Self {
+        SignalKind(libc::SIGPIPE)
+    }
+
+    /// `SIGALRM`
+    pub const fn alarm() -> Self {
+        SignalKind(libc::SIGALRM)
+    }
+
+    /// `SIGWINCH`, the terminal is resized
+    pub const fn window_change() -> Self {
+        SignalKind(libc::SIGWINCH)
+    }
+}
+
+#[cfg(windows)]
+impl SignalKind {
+    /// `CTRL_C_EVENT`, same as `ctrl_c`
+    pub const fn interrupt() -> Self {
+        Self::ctrl_c()
+    }
+
+    /// `CTRL_C_EVENT`
+    pub const fn ctrl_c() -> Self {
+        SignalKind(0)
+    }
+
+    /// `CTRL_BREAK_EVENT`
+    pub const fn ctrl_break() -> Self {
+        SignalKind(1)
+    }
+
+    /// `CTRL_CLOSE_EVENT`, the process is killed shortly after the handling
+    pub const fn ctrl_close() -> Self {
+        SignalKind(2)
+    }
+
+    /// `CTRL_LOGOFF_EVENT`, only received by services
+    pub const fn ctrl_logoff() -> Self {
+        SignalKind(5)
+    }
+
+    /// `CTRL_SHUTDOWN_EVENT`, only received by services
+    pub const fn ctrl_shutdown() -> Self {
+        SignalKind(6)
+    }
+}
+
+/// listen for the signal, return a stream that receives the deliveries
+///
+/// the default action of the signal is replaced by the first call and is
+/// never restored, e.g. a `SIGINT` no longer terminates the process. the
+/// deliveries before the creation of the stream are not observed, and the
+/// ones between two `recv` calls are coalesced into one
+///
+/// on unix the handler only writes to a pipe, a dispatch thread reads it
+/// and wakes up the waiting streams. `signalfd` is not used since it
+/// requires the signal to be blocked in every thread. on windows the
+/// console control events are supported
+///
+/// ```no_run
+/// use mco::os::{signal, SignalKind};
+///
+/// let mut term = signal(SignalKind::terminate()).unwrap();
+/// mco::co!(move || {
+///     term.recv().unwrap();
+///     println!("shutting down");
+/// })
+/// .join()
+/// .unwrap();
+/// ```
+pub fn signal(kind: SignalKind) -> io::Result<SignalStream> {
+    Globals::get()?.listen(kind)
+}
+
+/// Receives the deliveries of a signal, see [`signal`]
+pub struct SignalStream {
+    kind: SignalKind,
+    // the deliveries that are already received
+    seen: usize,
+}
+
+impl fmt::Debug for SignalStream {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SignalStream")
+            .field("kind", &self.kind)
+            .finish()
+    }
+}
+
+impl SignalStream {
+    /// the kind of signal this stream listens for
+    pub fn kind(&self) -> SignalKind {
+        self.kind
+    }
+
+    /// wait for the next delivery of the signal
+    ///
+    /// in coroutine context only the coroutine is parked
+    pub fn recv(&mut self) -> io::Result<()> {
+        let globals = Globals::get()?;
+        self.seen = globals.wait(self)?;
+        Ok(())
+    }
+}
+
+#[cfg(unix)]
+mod unix {
+    use std::fs::File;
+    use std::io::{self, Read};
+    use std::os::unix::io::{FromRawFd, IntoRawFd};
+    use std::sync::atomic::{AtomicI32, AtomicUsize, Ordering};
+    use std::thread;
+
+    use super::{SignalKind, SignalStream};
+    use crate::std::sync::{Condvar, Mutex};
+    use once_cell::sync::OnceCell;
+
+    // large enough for the real time signals on linux
+    const SIG_NUM: usize = 65;
+
+    #[allow(clippy::declare_interior_mutable_const)]
+    const ZERO: AtomicUsize = AtomicUsize::new(0);
+
+    // the deliveries of each signal, only touched by atomics in the handler
+    static COUNTS: [AtomicUsize; SIG_NUM] = [ZERO; SIG_NUM];
+    static WRITE_FD: AtomicI32 = AtomicI32::new(-1);
+    static GLOBALS: OnceCell<Globals> = OnceCell::new();
+
+    pub struct Globals {
+        installed: parking_lot::Mutex<[bool; SIG_NUM]>,
+        // the waiting streams, notified by the dispatch thread
+        lock: Mutex<()>,
+        cond: Condvar,
+    }
+
+    fn cvt(ret: libc::c_int) -> io::Result<libc::c_int> {
+        if ret < 0 {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(ret)
+        }
+    }
+
+    extern "C" fn handler(signum: libc::c_int) {
+        // only the async signal safe functions are allowed here
+        let errno = io::Error::last_os_error().raw_os_error().unwrap_or(0);
+        COUNTS[signum as usize].fetch_add(1, Ordering::SeqCst);
+        let fd = WRITE_FD.load(Ordering::SeqCst);
+        // a full pipe is fine, the readers are already woken up
+        unsafe {
+            libc::write(fd, [1u8].as_ptr() as *const libc::c_void, 1);
+            set_errno(errno);
+        }
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "emscripten"))]
+    unsafe fn set_errno(errno: libc::c_int) {
+        *libc::__errno_location() = errno;
+    }
+
+    #[cfg(any(target_os = "android", target_os = "netbsd", target_os = "openbsd"))]
+    unsafe fn set_errno(errno: libc::c_int) {
+        *libc::__errno() = errno;
+    }
+
+    #[cfg(not(any(
+        target_os = "linux",
+        target_os = "emscripten",
+        target_os = "android",
+        target_os = "netbsd",
+        target_os = "openbsd"
+    )))]
+    unsafe fn set_errno(errno: libc::c_int) {
+        *libc::__error() = errno;
+    }
+
+    fn pipe() -> io::Result<(File, File)> {
+        let mut fds = [0; 2];
+        cvt(unsafe { libc::pipe(fds.as_mut_ptr()) })?;
+        let (r, w) = unsafe { (File::from_raw_fd(fds[0]), File::from_raw_fd(fds[1])) };
+        for fd in &fds {
+            unsafe { cvt(libc::fcntl(*fd, libc::F_SETFD, libc::FD_CLOEXEC))? };
+        }
+        // the handler must never block, the dispatch thread blocks on the read
+        unsafe {
+            let flags = cvt(libc::fcntl(fds[1], libc::F_GETFL))?;
+            cvt(libc::fcntl(fds[1], libc::F_SETFL, flags | libc::O_NONBLOCK))?;
+        }
+        Ok((r, w))
+    }
+
+    // wake up all the waiting streams for each batch of the deliveries, the
+    // count is what tells the deliveries
+    fn dispatch(mut read: File) {
+        let mut buf = [0u8; 64];
+        loop {
+            match read.read(&mut buf) {
+                Ok(n) if n > 0 => {}
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                _ => return,
+            }
+            // no stream exists before the globals are set
+            if let Some(g) = GLOBALS.get() {
+                // notify under the lock so that a waiter can't miss it
+                let _lock = g.lock.lock().unwrap();
+                g.cond.notify_all().ok();
+            }
+        }
+    }
+
+    impl Globals {
+        pub fn get() -> io::Result<&'static Globals> {
+            GLOBALS.get_or_try_init(|| {
+                let (read, write) = pipe()?;
+                thread::Builder::new()
+                    .name("mco-signal".to_owned())
+                    .spawn(move || dispatch(read))?;
+                // the write end lives as long as the process
+                WRITE_FD.store(write.into_raw_fd(), Ordering::SeqCst);
+                Ok(Globals {
+                    installed: parking_lot::Mutex::new([false; SIG_NUM]),
+                    lock: Mutex::new(()),
+                    cond: Condvar::new(),
+                })
+            })
+        }
+
+        pub fn listen(&self, kind: SignalKind) -> io::Result<SignalStream> {
+            let signum = kind.as_raw();
+            let forbidden = [
+                libc::SIGKILL,
+                libc::SIGSTOP,
+                libc::SIGILL,
+                libc::SIGFPE,
+                libc::SIGSEGV,
+                libc::SIGBUS,
+            ];
+            if signum <= 0 || signum as usize >= SIG_NUM || forbidden.contains(&signum) {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("can't listen for signal {}", signum),
+                ));
+            }
+
+            let mut installed = self.installed.lock();
+            if !installed[signum as usize] {
+                unsafe {
+                    let mut action: libc::sigaction = std::mem::zeroed();
+                    action.sa_sigaction = handler as extern "C" fn(libc::c_int) as usize;
+                    action.sa_flags = libc::SA_RESTART;
+                    libc::sigemptyset(&mut action.sa_mask);
+                    cvt(libc::sigaction(signum, &action, std::ptr::null_mut()))?;
+                }
+                installed[signum as usize] = true;
+            }
+            drop(installed);
+
+            Ok(SignalStream {
+                kind,
+                seen: COUNTS[signum as usize].load(Ordering::SeqCst),
+            })
+        }
+
+        pub fn wait(&self, stream: &SignalStream) -> io::Result<usize> {
+            let count = &COUNTS[stream.kind.as_raw() as usize];
+            let mut lock = self.lock.lock().unwrap();
+            loop {
+                // the handler bumps the count before writing the pipe, and
+                // the dispatch thread notifies under the lock after reading
+                // it, so a delivery is either counted here or wakes us up
+                let n = count.load(Ordering::SeqCst);
+                if n != stream.seen {
+                    return Ok(n);
+                }
+                lock = self.cond.wait(lock).unwrap();
+            }
+        }
+    }
+}
+
+#[cfg(windows)]
+mod windows {
+    use std::io;
+
+    use super::{SignalKind, SignalStream};
+    use crate::std::sync::{Condvar, Mutex};
+    use once_cell::sync::Lazy;
+    use windows_sys::Win32::Foundation::BOOL;
+    use windows_sys::Win32::System::Console::SetConsoleCtrlHandler;
+
+    // the max value of the console control events
+    const EVENT_NUM: usize = 7;
+
+    struct State {
+        installed: bool,
+        listening: [bool; EVENT_NUM],
+        counts: [usize; EVENT_NUM],
+    }
+
+    pub struct Globals {
+        state: Mutex<State>,
+        cond: Condvar,
+    }
+
+    static GLOBALS: Lazy<Globals> = Lazy::new(|| Globals {
+        state: Mutex::new(State {
+            installed: false,
+            listening: [false; EVENT_NUM],
+            counts: [0; EVENT_NUM],
+        }),
+        cond: Condvar::new(),
+    });
+
+    // runs in a new thread created by the system
+    unsafe extern "system" fn handler(event: u32) -> BOOL {
+        let event = event as usize;
+        let mut state = GLOBALS.state.lock().unwrap();
+        if event >= EVENT_NUM || !state.listening[event] {
+            // pass to the next handler
+            return 0;
+        }
+        state.counts[event] += 1;
+        drop(state);
+        GLOBALS.cond.notify_all().ok();
+        1
+    }
+
+    impl Globals {
+        pub fn get() -> io::Result<&'static Globals> {
+            Ok(&GLOBALS)
+        }
+
+        pub fn listen(&self, kind: SignalKind) -> io::Result<SignalStream> {
+            let event = kind.0 as usize;
+            if event >= EVENT_NUM {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("can't listen for console event {}", event),
+                ));
+            }
+            let mut state = self.state.lock().unwrap();
+            if !state.installed {
+                if unsafe { SetConsoleCtrlHandler(Some(handler), 1) } == 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                state.installed = true;
+            }
+            state.listening[event] = true;
+            Ok(SignalStream {
+                kind,
+                seen: state.counts[event],
+            })
+        }
+
+        pub fn wait(&self, stream: &SignalStream) -> io::Result<usize> {
+            let event = stream.kind.0 as usize;
+            let mut state = self.state.lock().unwrap();
+            while state.counts[event] == stream.seen {
+                state = self.cond.wait(state).unwrap();
+            }
+            Ok(state.counts[event])
+        }
+    }
+}
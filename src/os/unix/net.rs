@@ -12,6 +12,30 @@ use crate::coroutine_impl::is_coroutine;
 use crate::io::sys::net as net_impl;
 use crate::io::CoIo;
 use crate::yield_now::yield_with;
+use socket2::SockAddr;
+
+// convert the std address to the one that socket2 can connect to
+fn to_sock_addr(addr: &SocketAddr) -> io::Result<SockAddr> {
+    if let Some(path) = addr.as_pathname() {
+        return SockAddr::unix(path);
+    }
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    {
+        use std::os::linux::net::SocketAddrExt;
+        use std::os::unix::ffi::OsStrExt;
+
+        if let Some(name) = addr.as_abstract_name() {
+            // socket2 treats a leading null byte as the abstract namespace
+            let mut bytes = vec![0];
+            bytes.extend_from_slice(name);
+            return SockAddr::unix(std::ffi::OsStr::from_bytes(&bytes));
+        }
+    }
+    Err(io::Error::new(
+        io::ErrorKind::InvalidInput,
+        "can't connect to an unnamed socket address",
+    ))
+}
 
 /// A Unix stream socket.
 ///
@@ -80,6 +104,36 @@ impl UnixStream {
         c.done()
     }
 
+    /// Connects to the socket specified by the address.
+    ///
+    /// The address can be created with `SocketAddr::from_pathname`, or with
+    /// `SocketAddr::from_abstract_name` for the abstract namespace on Linux.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use mco::os::unix::net::{UnixListener, UnixStream};
+    ///
+    /// let listener = UnixListener::bind("/path/to/the/socket").unwrap();
+    /// let addr = listener.local_addr().unwrap();
+    /// let sock = UnixStream::connect_addr(&addr).unwrap();
+    /// ```
+    pub fn connect_addr(addr: &SocketAddr) -> io::Result<UnixStream> {
+        if !is_coroutine() {
+            let stream = net::UnixStream::connect_addr(addr)?;
+            return Ok(UnixStream(CoIo::new(stream)?));
+        }
+
+        let mut c = net_impl::UnixStreamConnect::from_addr(to_sock_addr(addr)?)?;
+
+        if c.check_connected()? {
+            return c.done();
+        }
+
+        yield_with(&c);
+        c.done()
+    }
+
     /// Creates an unnamed pair of connected sockets.
     ///
     /// Returns two `UnixStream`s which are connected to each other.
@@ -258,7 +312,9 @@ impl UnixStream {
     ///
     /// This function will cause all pending and future I/O calls on the
     /// specified portions to immediately return with an appropriate value
-    /// (see the documentation of libstd `Shutdown`).
+    /// (see the documentation of libstd `Shutdown`). The coroutines parked
+    /// on the closed half are woken up, so `Shutdown::Write` can signal EOF
+    /// to the peer while another coroutine still reads the response.
     ///
     /// # Examples
     ///
@@ -278,6 +334,10 @@ impl io::Read for UnixStream {
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
         self.0.read(buf)
     }
+
+    fn read_vectored(&mut self, bufs: &mut [io::IoSliceMut<'_>]) -> io::Result<usize> {
+        self.0.read_vectored(bufs)
+    }
 }
 
 // impl<'a> io::Read for &'a UnixStream {
@@ -291,6 +351,10 @@ impl io::Write for UnixStream {
         self.0.write(buf)
     }
 
+    fn write_vectored(&mut self, bufs: &[io::IoSlice<'_>]) -> io::Result<usize> {
+        self.0.write_vectored(bufs)
+    }
+
     fn flush(&mut self) -> io::Result<()> {
         self.0.flush()
     }
@@ -387,6 +451,26 @@ impl UnixListener {
         Ok(UnixListener(CoIo::new(listener)?))
     }
 
+    /// Creates a new `UnixListener` bound to the specified socket address.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # #[cfg(target_os = "linux")]
+    /// # {
+    /// use mco::os::unix::net::UnixListener;
+    /// use std::os::linux::net::SocketAddrExt;
+    /// use std::os::unix::net::SocketAddr;
+    ///
+    /// let addr = SocketAddr::from_abstract_name(b"hidden").unwrap();
+    /// let listener = UnixListener::bind_addr(&addr).unwrap();
+    /// # }
+    /// ```
+    pub fn bind_addr(addr: &SocketAddr) -> io::Result<UnixListener> {
+        let listener = net::UnixListener::bind_addr(addr)?;
+        Ok(UnixListener(CoIo::new(listener)?))
+    }
+
     /// Accepts a new incoming connection to this listener.
     ///
     /// This function will block the calling thread until a new Unix connection
@@ -652,6 +736,22 @@ impl UnixDatagram {
         Ok(UnixDatagram(CoIo::new(datagram)?))
     }
 
+    /// Creates a Unix datagram socket bound to the given socket address.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use mco::os::unix::net::UnixDatagram;
+    /// use std::os::unix::net::SocketAddr;
+    ///
+    /// let addr = SocketAddr::from_pathname("/path/to/the/socket").unwrap();
+    /// let sock = UnixDatagram::bind_addr(&addr).unwrap();
+    /// ```
+    pub fn bind_addr(addr: &SocketAddr) -> io::Result<UnixDatagram> {
+        let datagram = net::UnixDatagram::bind_addr(addr)?;
+        Ok(UnixDatagram(CoIo::new(datagram)?))
+    }
+
     /// Creates a Unix Datagram socket which is not bound to any address.
     ///
     /// # Examples
@@ -725,6 +825,21 @@ impl UnixDatagram {
         self.0.inner().connect(path)
     }
 
+    /// Connects the socket to the given socket address.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use mco::os::unix::net::UnixDatagram;
+    ///
+    /// let sock = UnixDatagram::unbound().unwrap();
+    /// let peer = UnixDatagram::bind("/path/to/the/socket").unwrap();
+    /// sock.connect_addr(&peer.local_addr().unwrap()).unwrap();
+    /// ```
+    pub fn connect_addr(&self, addr: &SocketAddr) -> io::Result<()> {
+        self.0.inner().connect_addr(addr)
+    }
+
     /// Creates a new independently owned handle to the underlying socket.
     ///
     /// The returned `UnixDatagram` is a reference to the same socket that this
@@ -1402,4 +1517,50 @@ mod test {
     fn abstract_namespace_not_allowed() {
         assert!(UnixStream::connect("\0asdf").is_err());
     }
+
+    #[test]
+    fn connect_addr() {
+        let dir = tmpdir();
+        let socket_path = dir.path().join("sock");
+        let addr = or_panic!(SocketAddr::from_pathname(&socket_path));
+
+        let listener = or_panic!(UnixListener::bind_addr(&addr));
+        let thread = co!(move || {
+            let mut stream = or_panic!(listener.accept()).0;
+            or_panic!(stream.write_all(b"hello"));
+        });
+
+        let client = co!(move || {
+            let mut stream = or_panic!(UnixStream::connect_addr(&addr));
+            let mut buf = vec![];
+            or_panic!(stream.read_to_end(&mut buf));
+            buf
+        });
+        assert_eq!(client.join().unwrap(), b"hello");
+        thread.join().unwrap();
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    #[test]
+    fn abstract_address() {
+        use std::os::linux::net::SocketAddrExt;
+
+        let name = format!("mco-test-{}", std::process::id());
+        let addr = or_panic!(SocketAddr::from_abstract_name(name.as_bytes()));
+        let listener = or_panic!(UnixListener::bind_addr(&addr));
+        assert_eq!(
+            listener.local_addr().unwrap().as_abstract_name(),
+            Some(name.as_bytes())
+        );
+        let thread = co!(move || {
+            let mut stream = or_panic!(listener.accept()).0;
+            or_panic!(stream.write_all(b"hello"));
+        });
+
+        let mut stream = or_panic!(UnixStream::connect_addr(&addr));
+        let mut buf = vec![];
+        or_panic!(stream.read_to_end(&mut buf));
+        assert_eq!(&buf[..], b"hello");
+        thread.join().unwrap();
+    }
 }
@@ -0,0 +1,3 @@
+#![cfg(windows)]
+
+pub mod named_pipe;
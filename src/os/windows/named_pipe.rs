@@ -0,0 +1,193 @@
+//! Windows named pipes that can be used in coroutine context
+//!
+//! the pipe io is done with the overlapped APIs on the IOCP, so only the
+//! coroutine is parked while waiting. each `NamedPipeServer` is one instance
+//! of the pipe that serves one client, create a new instance for the next one
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use std::io::{Read, Write};
+//! use mco::os::windows::named_pipe::{NamedPipeClient, NamedPipeServer};
+//!
+//! const NAME: &str = r"\\.\pipe\mco-example";
+//!
+//! let mut server = NamedPipeServer::create(NAME).unwrap();
+//! mco::co!(|| {
+//!     let mut client = NamedPipeClient::connect(NAME).unwrap();
+//!     client.write_all(b"hello").unwrap();
+//! });
+//! server.connect().unwrap();
+//! let mut buf = [0u8; 5];
+//! server.read_exact(&mut buf).unwrap();
+//! ```
+
+use std::ffi::OsStr;
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::os::windows::fs::OpenOptionsExt;
+use std::os::windows::io::{AsRawHandle, IntoRawHandle, RawHandle};
+use std::time::Duration;
+
+use crate::io::sys::pipe::PipeConnect;
+use crate::io::CoIo;
+use crate::yield_now::yield_with;
+use miow::pipe::{NamedPipe, NamedPipeBuilder};
+use windows_sys::Win32::Storage::FileSystem::FILE_FLAG_OVERLAPPED;
+
+/// The server side instance of a named pipe
+pub struct NamedPipeServer(CoIo<NamedPipe>);
+
+impl fmt::Debug for NamedPipeServer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("NamedPipeServer")
+            .field("handle", &self.as_raw_handle())
+            .finish()
+    }
+}
+
+impl NamedPipeServer {
+    /// create a new instance of the pipe with the given name, e.g. `\\.\pipe\foo`
+    ///
+    /// the pipe is duplex and in byte mode
+    pub fn create<A: AsRef<OsStr>>(name: A) -> io::Result<NamedPipeServer> {
+        let pipe = NamedPipeBuilder::new(name)
+            .inbound(true)
+            .outbound(true)
+            .create()?;
+        Ok(NamedPipeServer(CoIo::new(pipe)?))
+    }
+
+    /// wait for a client to connect to this instance
+    ///
+    /// the read timeout is used as the timeout of the waiting
+    pub fn connect(&self) -> io::Result<()> {
+        if !self.0.ctx_check()? {
+            return self.0.inner().connect();
+        }
+
+        let mut c = PipeConnect::new(self.0.inner(), self.0.read_timeout()?);
+        yield_with(&c);
+        c.done()
+    }
+
+    /// disconnect the client, then the instance can be connected again
+    pub fn disconnect(&self) -> io::Result<()> {
+        self.0.inner().disconnect()
+    }
+
+    pub fn set_read_timeout(&self, dur: Option<Duration>) -> io::Result<()> {
+        self.0.set_read_timeout(dur)
+    }
+
+    pub fn set_write_timeout(&self, dur: Option<Duration>) -> io::Result<()> {
+        self.0.set_write_timeout(dur)
+    }
+
+    pub fn read_timeout(&self) -> io::Result<Option<Duration>> {
+        self.0.read_timeout()
+    }
+
+    pub fn write_timeout(&self) -> io::Result<Option<Duration>> {
+        self.0.write_timeout()
+    }
+}
+
+impl Read for NamedPipeServer {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.0.read(buf)
+    }
+}
+
+impl Write for NamedPipeServer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+impl AsRawHandle for NamedPipeServer {
+    fn as_raw_handle(&self) -> RawHandle {
+        self.0.as_raw_handle()
+    }
+}
+
+impl IntoRawHandle for NamedPipeServer {
+    fn into_raw_handle(self) -> RawHandle {
+        self.0.into_raw_handle()
+    }
+}
+
+/// The client side of a named pipe
+pub struct NamedPipeClient(CoIo<File>);
+
+impl fmt::Debug for NamedPipeClient {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("NamedPipeClient")
+            .field("handle", &self.as_raw_handle())
+            .finish()
+    }
+}
+
+impl NamedPipeClient {
+    /// connect to the pipe with the given name
+    ///
+    /// this fails with `ERROR_PIPE_BUSY` when all the instances of the pipe
+    /// are connected, retry it after the server creates a new instance
+    pub fn connect<A: AsRef<OsStr>>(name: A) -> io::Result<NamedPipeClient> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .custom_flags(FILE_FLAG_OVERLAPPED)
+            .open(name.as_ref())?;
+        Ok(NamedPipeClient(CoIo::new(file)?))
+    }
+
+    pub fn set_read_timeout(&self, dur: Option<Duration>) -> io::Result<()> {
+        self.0.set_read_timeout(dur)
+    }
+
+    pub fn set_write_timeout(&self, dur: Option<Duration>) -> io::Result<()> {
+        self.0.set_write_timeout(dur)
+    }
+
+    pub fn read_timeout(&self) -> io::Result<Option<Duration>> {
+        self.0.read_timeout()
+    }
+
+    pub fn write_timeout(&self) -> io::Result<Option<Duration>> {
+        self.0.write_timeout()
+    }
+}
+
+impl Read for NamedPipeClient {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.0.read(buf)
+    }
+}
+
+impl Write for NamedPipeClient {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+impl AsRawHandle for NamedPipeClient {
+    fn as_raw_handle(&self) -> RawHandle {
+        self.0.as_raw_handle()
+    }
+}
+
+impl IntoRawHandle for NamedPipeClient {
+    fn into_raw_handle(self) -> RawHandle {
+        self.0.into_raw_handle()
+    }
+}
@@ -7,10 +7,10 @@ use std::time::Duration;
 
 use crate::cancel::Cancel;
 use crate::coroutine_impl::{co_cancel_data, run_coroutine, CoroutineImpl, EventSource};
-use crate::scheduler::get_scheduler;
+use crate::registry::State;
+use crate::scheduler::{get_scheduler, TimerHandle};
 use crate::std::sync::atomic_dur::AtomicDuration;
 use crate::std::sync::AtomicOption;
-use crate::timeout_list::TimeoutHandle;
 use crate::yield_now::{get_co_para, yield_now, yield_with};
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
@@ -38,7 +38,7 @@ pub struct Park {
     // timeout settings in ms, 0 is none (park forever)
     timeout: AtomicDuration,
     // timer handle, can be null
-    timeout_handle: AtomicPtr<TimeoutHandle<Arc<AtomicOption<CoroutineImpl>>>>,
+    timeout_handle: AtomicPtr<TimerHandle>,
     // a flag if kernel is entered
     wait_kernel: AtomicBool,
 }
@@ -70,18 +70,18 @@ impl Park {
     #[inline]
     fn set_timeout_handle(
         &self,
-        handle: Option<TimeoutHandle<Arc<AtomicOption<CoroutineImpl>>>>,
-    ) -> Option<TimeoutHandle<Arc<AtomicOption<CoroutineImpl>>>> {
+        handle: Option<TimerHandle>,
+    ) -> Option<TimerHandle> {
         let ptr = match handle {
             None => ptr::null_mut(),
-            Some(h) => h.into_ptr(),
+            Some(h) => Box::into_raw(Box::new(h)),
         };
 
         let old_ptr = self.timeout_handle.swap(ptr, Ordering::Relaxed);
         if old_ptr.is_null() {
             None
         } else {
-            Some(unsafe { TimeoutHandle::from_ptr(old_ptr) })
+            Some(*unsafe { Box::from_raw(old_ptr) })
         }
     }
 
@@ -150,11 +150,8 @@ impl Park {
     #[inline]
     fn remove_timeout_handle(&self) {
         if let Some(h) = self.set_timeout_handle(None) {
-            if h.is_link() {
-                get_scheduler().del_timer(h);
-            }
-            // when timeout the node is unlinked
-            // just drop it to release memory
+            // removing an expired timer does nothing
+            get_scheduler().del_timer(h);
         }
     }
 
@@ -240,6 +237,10 @@ impl Drop for Park {
 }
 
 impl EventSource for Park {
+    fn state(&self) -> State {
+        State::Parked
+    }
+
     // register the coroutine to the park
     fn subscribe(&mut self, co: CoroutineImpl) {
         let cancel = co_cancel_data(&co);
@@ -268,6 +269,8 @@ impl EventSource for Park {
         // re-check the cancel status
         if cancel.is_canceled() {
             let _ = cancel.cancel();
+        } else if cancel.is_interrupted() {
+            cancel.interrupt();
         }
     }
 
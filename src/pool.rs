@@ -1,5 +1,5 @@
 use crate::config::config;
-use crate::coroutine_impl::CoroutineImpl;
+use crate::coroutine_impl::{CoroutineImpl, Priority};
 use crossbeam::queue::ArrayQueue as Queue;
 use mco_gen::Gn;
 
@@ -18,6 +18,9 @@ impl CoroutinePool {
                 unreachable!("dummy coroutine should never be called");
             }),
             reduce: None,
+            priority: Priority::Normal,
+            pinned: false,
+            own_stack: true,
         }
     }
 
@@ -0,0 +1,411 @@
+//! Child processes that can be used in coroutine context
+//!
+//! the [`Command`] is a thin wrapper of `std::process::Command`, the stdio
+//! pipes of the spawned [`Child`] are registered to the event loop, on
+//! windows their io runs in the blocking thread pool, and [`Child::wait`]
+//! parks the coroutine instead of the worker thread.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use mco::process::Command;
+//!
+//! mco::co!(|| {
+//!     let output = Command::new("echo").arg("hello").output().unwrap();
+//!     assert!(output.status.success());
+//!     assert_eq!(output.stdout, b"hello\n");
+//! });
+//! ```
+
+use std::ffi::OsStr;
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::path::Path;
+use std::process;
+
+#[cfg(unix)]
+use std::os::unix::io::{AsRawFd, RawFd};
+#[cfg(windows)]
+use std::os::windows::io::{AsRawHandle, RawHandle};
+
+#[cfg(unix)]
+use crate::io::CoIo;
+
+pub use std::process::{ExitStatus, Output, Stdio};
+
+/// A process builder, see `std::process::Command`
+pub struct Command {
+    inner: process::Command,
+    // the stdin is configured by `stdin`
+    stdin: bool,
+}
+
+impl Command {
+    pub fn new<S: AsRef<OsStr>>(program: S) -> Command {
+        Command {
+            inner: process::Command::new(program),
+            stdin: false,
+        }
+    }
+
+    pub fn arg<S: AsRef<OsStr>>(&mut self, arg: S) -> &mut Command {
+        self.inner.arg(arg);
+        self
+    }
+
+    pub fn args<I, S>(&mut self, args: I) -> &mut Command
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        self.inner.args(args);
+        self
+    }
+
+    pub fn env<K, V>(&mut self, key: K, val: V) -> &mut Command
+    where
+        K: AsRef<OsStr>,
+        V: AsRef<OsStr>,
+    {
+        self.inner.env(key, val);
+        self
+    }
+
+    pub fn envs<I, K, V>(&mut self, vars: I) -> &mut Command
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: AsRef<OsStr>,
+        V: AsRef<OsStr>,
+    {
+        self.inner.envs(vars);
+        self
+    }
+
+    pub fn env_remove<K: AsRef<OsStr>>(&mut self, key: K) -> &mut Command {
+        self.inner.env_remove(key);
+        self
+    }
+
+    pub fn env_clear(&mut self) -> &mut Command {
+        self.inner.env_clear();
+        self
+    }
+
+    pub fn current_dir<P: AsRef<Path>>(&mut self, dir: P) -> &mut Command {
+        self.inner.current_dir(dir);
+        self
+    }
+
+    pub fn stdin<T: Into<Stdio>>(&mut self, cfg: T) -> &mut Command {
+        self.inner.stdin(cfg);
+        self.stdin = true;
+        self
+    }
+
+    pub fn stdout<T: Into<Stdio>>(&mut self, cfg: T) -> &mut Command {
+        self.inner.stdout(cfg);
+        self
+    }
+
+    pub fn stderr<T: Into<Stdio>>(&mut self, cfg: T) -> &mut Command {
+        self.inner.stderr(cfg);
+        self
+    }
+
+    /// get the wrapped std command
+    pub fn as_std(&self) -> &process::Command {
+        &self.inner
+    }
+
+    /// get the wrapped std command, for the platform specific options
+    ///
+    /// the stdin configured here is not seen by `output`
+    pub fn as_std_mut(&mut self) -> &mut process::Command {
+        &mut self.inner
+    }
+
+    /// spawn the command as a child process
+    pub fn spawn(&mut self) -> io::Result<Child> {
+        Child::new(self.inner.spawn()?)
+    }
+
+    /// run the command and wait for it to exit, collecting all of its output
+    ///
+    /// the stdout and stderr are always captured, the stdin is null unless
+    /// it's configured, same as the std version
+    pub fn output(&mut self) -> io::Result<Output> {
+        self.inner.stdout(Stdio::piped());
+        self.inner.stderr(Stdio::piped());
+        if !self.stdin {
+            self.inner.stdin(Stdio::null());
+        }
+        let child = self.spawn();
+        if !self.stdin {
+            // the later `spawn` inherits the stdin by default
+            self.inner.stdin(Stdio::inherit());
+        }
+        child?.wait_with_output()
+    }
+
+    /// run the command and wait for it to exit
+    pub fn status(&mut self) -> io::Result<ExitStatus> {
+        self.spawn()?.wait()
+    }
+}
+
+impl From<process::Command> for Command {
+    fn from(inner: process::Command) -> Command {
+        // the stdin of the std command can't be inspected, keep it
+        Command { inner, stdin: true }
+    }
+}
+
+impl fmt::Debug for Command {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.inner.fmt(f)
+    }
+}
+
+/// A spawned child process
+///
+/// same as the std version, the child is neither killed nor waited when dropped
+#[derive(Debug)]
+pub struct Child {
+    inner: process::Child,
+    /// the handle of the child's stdin, if it's piped
+    pub stdin: Option<ChildStdin>,
+    /// the handle of the child's stdout, if it's piped
+    pub stdout: Option<ChildStdout>,
+    /// the handle of the child's stderr, if it's piped
+    pub stderr: Option<ChildStderr>,
+}
+
+impl Child {
+    fn new(mut inner: process::Child) -> io::Result<Child> {
+        let stdin = inner.stdin.take().map(ChildStdin::new).transpose()?;
+        let stdout = inner.stdout.take().map(ChildStdout::new).transpose()?;
+        let stderr = inner.stderr.take().map(ChildStderr::new).transpose()?;
+        Ok(Child {
+            inner,
+            stdin,
+            stdout,
+            stderr,
+        })
+    }
+
+    /// the os assigned process id
+    pub fn id(&self) -> u32 {
+        self.inner.id()
+    }
+
+    /// force the child process to exit
+    pub fn kill(&mut self) -> io::Result<()> {
+        self.inner.kill()
+    }
+
+    /// return the exit status if the child has exited, never blocks
+    pub fn try_wait(&mut self) -> io::Result<Option<ExitStatus>> {
+        self.inner.try_wait()
+    }
+
+    /// wait for the child to exit
+    ///
+    /// the stdin is closed before waiting. in coroutine context only the
+    /// coroutine is parked, on linux the exit is observed with a pidfd on
+    /// the event loop, other platforms wait in the blocking thread pool
+    pub fn wait(&mut self) -> io::Result<ExitStatus> {
+        drop(self.stdin.take());
+        if let Some(status) = self.inner.try_wait()? {
+            return Ok(status);
+        }
+        if !crate::coroutine_impl::is_coroutine() {
+            return self.inner.wait();
+        }
+
+        #[cfg(target_os = "linux")]
+        {
+            if let Some(fd) = pidfd_open(self.inner.id())? {
+                // the pidfd becomes readable once the child exits
+                crate::io::PollFd::new(fd)?.readable()?;
+                return self.inner.wait();
+            }
+        }
+
+        self.wait_blocking()?;
+        self.inner.wait()
+    }
+
+    /// wait for the child to exit and collect all of its stdout and stderr
+    ///
+    /// the stdout and stderr are read concurrently, so the child won't be
+    /// blocked by a full pipe
+    pub fn wait_with_output(mut self) -> io::Result<Output> {
+        drop(self.stdin.take());
+        let stderr = self.stderr.take().map(|mut err| {
+            crate::coroutine::spawn(move || {
+                let mut buf = Vec::new();
+                err.read_to_end(&mut buf).map(|_| buf)
+            })
+        });
+        let mut stdout = Vec::new();
+        if let Some(mut out) = self.stdout.take() {
+            if let Err(e) = out.read_to_end(&mut stdout) {
+                // don't leave the stderr reader running behind
+                if let Some(h) = stderr {
+                    if !h.is_done() {
+                        h.coroutine().cancel();
+                    }
+                    let _ = h.join();
+                }
+                return Err(e);
+            }
+        }
+        let stderr = match stderr {
+            Some(h) => h.join().unwrap_or_else(|e| std::panic::resume_unwind(e))?,
+            None => Vec::new(),
+        };
+        let status = self.wait()?;
+        Ok(Output {
+            status,
+            stdout,
+            stderr,
+        })
+    }
+
+    // wait in the blocking pool until the child exits, without reaping it
+    #[cfg(unix)]
+    fn wait_blocking(&self) -> io::Result<()> {
+        let pid = self.inner.id() as libc::id_t;
+        crate::coroutine::spawn_blocking(move || loop {
+            let mut info: libc::siginfo_t = unsafe { std::mem::zeroed() };
+            let flags = libc::WEXITED | libc::WNOWAIT;
+            if unsafe { libc::waitid(libc::P_PID, pid, &mut info, flags) } == 0 {
+                return Ok(());
+            }
+            let err = io::Error::last_os_error();
+            if err.kind() != io::ErrorKind::Interrupted {
+                return Err(err);
+            }
+        })
+        .join()
+        .unwrap_or_else(|e| std::panic::resume_unwind(e))
+    }
+
+    // wait in the blocking pool until the process handle is signaled
+    #[cfg(windows)]
+    fn wait_blocking(&self) -> io::Result<()> {
+        use windows_sys::Win32::Foundation::{HANDLE, WAIT_FAILED};
+        use windows_sys::Win32::System::Threading::WaitForSingleObject;
+        use windows_sys::Win32::System::WindowsProgramming::INFINITE;
+
+        // the handle stays valid since the child is borrowed while waiting
+        let handle = self.inner.as_raw_handle() as HANDLE;
+        crate::coroutine::spawn_blocking(move || {
+            if unsafe { WaitForSingleObject(handle, INFINITE) } == WAIT_FAILED {
+                Err(io::Error::last_os_error())
+            } else {
+                Ok(())
+            }
+        })
+        .join()
+        .unwrap_or_else(|e| std::panic::resume_unwind(e))
+    }
+}
+
+// return `None` if the kernel doesn't support pidfd
+#[cfg(target_os = "linux")]
+fn pidfd_open(pid: u32) -> io::Result<Option<std::fs::File>> {
+    use std::os::unix::io::FromRawFd;
+
+    let fd = unsafe { libc::syscall(libc::SYS_pidfd_open, pid as libc::pid_t, 0) };
+    if fd < 0 {
+        let err = io::Error::last_os_error();
+        return match err.raw_os_error() {
+            Some(libc::ENOSYS) => Ok(None),
+            _ => Err(err),
+        };
+    }
+    Ok(Some(unsafe { std::fs::File::from_raw_fd(fd as RawFd) }))
+}
+
+macro_rules! child_stdio {
+    ($name: ident, $std: ident) => {
+        /// A handle to the child's stdio pipe
+        ///
+        /// on windows the anonymous pipe can't be registered to the event
+        /// loop, in coroutine context its io runs in the blocking thread pool
+        pub struct $name {
+            #[cfg(unix)]
+            inner: CoIo<process::$std>,
+            #[cfg(windows)]
+            inner: crate::std::fs::File,
+        }
+
+        impl $name {
+            #[cfg(unix)]
+            fn new(inner: process::$std) -> io::Result<Self> {
+                Ok($name {
+                    inner: CoIo::new(inner)?,
+                })
+            }
+
+            #[cfg(windows)]
+            fn new(inner: process::$std) -> io::Result<Self> {
+                let handle = std::os::windows::io::OwnedHandle::from(inner);
+                Ok($name {
+                    inner: std::fs::File::from(handle).into(),
+                })
+            }
+        }
+
+        impl fmt::Debug for $name {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                #[cfg(unix)]
+                let raw = self.as_raw_fd();
+                #[cfg(windows)]
+                let raw = self.as_raw_handle();
+                f.debug_tuple(stringify!($name)).field(&raw).finish()
+            }
+        }
+
+        #[cfg(unix)]
+        impl AsRawFd for $name {
+            fn as_raw_fd(&self) -> RawFd {
+                self.inner.as_raw_fd()
+            }
+        }
+
+        #[cfg(windows)]
+        impl AsRawHandle for $name {
+            fn as_raw_handle(&self) -> RawHandle {
+                self.inner.as_raw_handle()
+            }
+        }
+    };
+}
+
+child_stdio!(ChildStdin, ChildStdin);
+child_stdio!(ChildStdout, ChildStdout);
+child_stdio!(ChildStderr, ChildStderr);
+
+impl Write for ChildStdin {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl Read for ChildStdout {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.inner.read(buf)
+    }
+}
+
+impl Read for ChildStderr {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.inner.read(buf)
+    }
+}
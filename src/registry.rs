@@ -0,0 +1,136 @@
+//! registry of the live coroutines, used by `coroutine::dump_all`
+
+use std::collections::HashMap;
+use std::fmt::{self, Write};
+
+use crate::coroutine_impl::Coroutine;
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+
+// the ids are sequential, so the coroutines are spread evenly over the shards
+const SHARDS: usize = 64;
+
+static REGISTRY: Lazy<Vec<Mutex<HashMap<u64, Coroutine>>>> =
+    Lazy::new(|| (0..SHARDS).map(|_| Mutex::new(HashMap::new())).collect());
+
+#[inline]
+fn shard(id: u64) -> &'static Mutex<HashMap<u64, Coroutine>> {
+    &REGISTRY[id as usize % SHARDS]
+}
+
+/// the scheduling state of a coroutine
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum State {
+    /// waiting in a ready queue
+    Ready = 0,
+    /// running on a worker
+    Running = 1,
+    /// waiting on a park, e.g. a channel, a lock or a join
+    Parked = 2,
+    /// waiting on a timer
+    Sleeping = 3,
+    /// waiting for an io event
+    BlockedOnIo = 4,
+}
+
+impl State {
+    pub fn from_u8(v: u8) -> State {
+        match v {
+            0 => State::Ready,
+            1 => State::Running,
+            2 => State::Parked,
+            3 => State::Sleeping,
+            _ => State::BlockedOnIo,
+        }
+    }
+}
+
+impl fmt::Display for State {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            State::Ready => "ready",
+            State::Running => "running",
+            State::Parked => "parked",
+            State::Sleeping => "sleeping",
+            State::BlockedOnIo => "blocked-on-io",
+        })
+    }
+}
+
+pub(crate) fn register(co: &Coroutine) {
+    shard(co.id()).lock().insert(co.id(), co.clone());
+}
+
+pub(crate) fn unregister(id: u64) {
+    shard(id).lock().remove(&id);
+}
+
+// get the live coroutine by id
+pub(crate) fn get(id: u64) -> Option<Coroutine> {
+    shard(id).lock().get(&id).cloned()
+}
+
+// format all the live coroutines, ordered by the id
+pub(crate) fn dump() -> String {
+    let mut list: Vec<Coroutine> = Vec::new();
+    for s in REGISTRY.iter() {
+        list.extend(s.lock().values().cloned());
+    }
+    list.sort_by_key(|co| co.id());
+    let mut s = String::new();
+    let _ = writeln!(s, "{} coroutines:", list.len());
+    for co in list {
+        let _ = writeln!(
+            s,
+            "\ncoroutine {} [{}]: name={:?}, parent={:?}, spawned at {}, stack_size={}, saved_stack={}",
+            co.id(),
+            co.state(),
+            co.name().unwrap_or("<unnamed>"),
+            co.parent_id(),
+            co.spawn_site(),
+            co.stack_size(),
+            co.saved_stack()
+        );
+        #[cfg(nightly)]
+        if let Some(bt) = co.backtrace() {
+            let _ = writeln!(s, "{}", bt);
+        }
+    }
+    s
+}
+
+/// print all the live coroutines to stderr, like the goroutine dump of go
+///
+/// for each coroutine the name, id, state, spawn site and stack size are printed.
+/// on nightly the backtrace captured at the last suspension is printed
+/// as well if `Config::set_coroutine_backtrace` is enabled
+pub fn dump_all() {
+    eprintln!("{}", dump());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coroutine_impl::Builder;
+
+    #[test]
+    fn dump_live_coroutine() {
+        let (tx, rx) = crate::std::sync::channel::<()>();
+        let h = Builder::new()
+            .name("dump_test".to_owned())
+            .spawn(move || {
+                let _ = rx.recv();
+            });
+        // wait the coroutine get parked
+        while !dump().contains("[parked]: name=\"dump_test\"") {
+            std::thread::sleep(std::time::Duration::from_millis(1));
+        }
+        assert!(dump().contains(&format!("spawned at {}:", file!())));
+        tx.send(()).unwrap();
+        h.join().unwrap();
+        // unregistered after the join is triggered
+        while dump().contains("dump_test") {
+            std::thread::sleep(std::time::Duration::from_millis(1));
+        }
+    }
+}
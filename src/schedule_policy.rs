@@ -0,0 +1,67 @@
+//! pluggable scheduling policy
+//!
+//! install a policy with `config().set_schedule_policy(...)` before
+//! the scheduler is started, the default policy is `DefaultPolicy`
+
+use std::time::Duration;
+
+/// the queue that a scheduled coroutine is put into
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScheduleTarget {
+    /// the lifo slot of the current worker, it runs next
+    Lifo,
+    /// the back of the local queue of the current worker
+    Local,
+    /// the global queue, any worker can pick it up
+    Global,
+    /// the global queue, but prefer the worker with the given id
+    Worker(usize),
+    /// the queue of the io worker that has the least queued coroutines
+    ///
+    /// the queue lengths of all the io workers are compared for each coroutine,
+    /// and the placed coroutines are not stolen by the other workers
+    LeastLoaded,
+}
+
+/// decide how the scheduler dispatches coroutines
+///
+/// the methods are called in the hot path of the workers, keep them cheap
+pub trait SchedulePolicy: Send + Sync + 'static {
+    /// choose the queue for a coroutine that is woken up,
+    /// `worker` is the current worker id, `None` for a non-worker thread
+    ///
+    /// `Lifo` and `Local` fall back to `Global` for a non-worker thread
+    fn schedule(&self, worker: Option<usize>) -> ScheduleTarget {
+        match worker {
+            Some(_) => ScheduleTarget::Lifo,
+            None => ScheduleTarget::Global,
+        }
+    }
+
+    /// choose the queue for a newly spawned coroutine,
+    /// `worker` is the current worker id, `None` for a non-worker thread
+    ///
+    /// by default all the coroutines are put into the global queue, return
+    /// `LeastLoaded` for `None` to place the coroutines spawned from a
+    /// non-worker thread on the least loaded io worker instead
+    fn spawn(&self, _worker: Option<usize>) -> ScheduleTarget {
+        ScheduleTarget::Global
+    }
+
+    /// whether the worker should steal from the global queue
+    /// when its local queue is empty
+    fn steal_global(&self, _worker: usize) -> bool {
+        true
+    }
+
+    /// the max time an idle worker waits for events before re-check the queues
+    fn park_timeout(&self, _worker: usize) -> Duration {
+        Duration::from_secs(1)
+    }
+}
+
+/// the default scheduling policy
+#[derive(Debug, Default)]
+pub struct DefaultPolicy;
+
+impl SchedulePolicy for DefaultPolicy {}
@@ -1,17 +1,26 @@
+use std::collections::HashMap;
 use std::io;
 use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
 use std::sync::{Arc, Once};
-use std::thread;
-use std::time::Duration;
+use std::thread::{self, Thread};
+use std::time::{Duration, Instant};
 
+use crate::blocking::BlockingPool;
 use crate::config::{config};
-use crate::coroutine_impl::{run_coroutine, CoroutineImpl};
+use crate::coroutine_impl::{
+    co_id, has_panic_handler, is_coroutine, run_coroutine, CoroutineImpl, Priority,
+};
+use crate::hooks::{HookEvent, SchedulerHooks};
 use crate::io::{EventLoop, Selector};
-use crate::std::sync::AtomicOption;
+use crate::schedule_policy::{SchedulePolicy, ScheduleTarget};
+use crate::std::sync::{AtomicOption, Notify};
 use crate::timeout_list;
+use crate::timer_wheel::TimerWheel;
 use crate::yield_now::set_co_para;
 use crossbeam::deque;
+use crossbeam::queue::SegQueue;
 use crossbeam::utils::Backoff;
+use parking_lot::Mutex;
 
 #[cfg(nightly)]
 use std::intrinsics::likely;
@@ -35,7 +44,41 @@ thread_local! { pub static WORKER_ID: AtomicUsize = AtomicUsize::new(!1); }
 // here we use Arc<AtomicOption<>> for that in the select implementation
 // other event may try to consume the coroutine while timer thread consume it
 type TimerData = Arc<AtomicOption<CoroutineImpl>>;
-type TimerThread = timeout_list::TimerThread<TimerData>;
+type TimerThread = timeout_list::TimerThread<TimerEvent>;
+
+// the timer thread wakes up a coroutine or runs a callback
+pub(crate) enum TimerEvent {
+    Co(TimerData),
+    Call(Box<dyn FnOnce() + Send>),
+}
+
+/// handle of a callback timer, remove it to cancel the timer
+pub(crate) type CallbackHandle = timeout_list::TimeoutHandle<TimerEvent>;
+
+/// handle of a coroutine timer, used to cancel the timer
+pub(crate) enum TimerHandle {
+    // timer in the timer thread
+    Global(timeout_list::TimeoutHandle<TimerEvent>),
+    // timer in the wheel of the io worker, (worker id, token)
+    Local(usize, u64),
+}
+
+// the timer wheel of an io worker, the lock is only contended when the
+// watchdog moves the timers of a blocked worker to the timer thread
+struct LocalTimer {
+    wheel: Mutex<TimerWheel<TimerData>>,
+    // the timers moved to the timer thread, token -> handle
+    moved: Mutex<HashMap<u64, CallbackHandle>>,
+}
+
+impl LocalTimer {
+    fn new() -> Self {
+        LocalTimer {
+            wheel: Mutex::new(TimerWheel::new()),
+            moved: Mutex::new(HashMap::new()),
+        }
+    }
+}
 
 // filter out the cancel panic, don't print anything for it
 fn filter_cancel_panic() {
@@ -50,12 +93,61 @@ fn filter_cancel_panic() {
             // this is not an error at all, ignore it
             return;
         }
+        if is_coroutine() && has_panic_handler() {
+            // the panic handler would take care of it
+            return;
+        }
         old(info);
     }));
 }
 
+// pin the current thread to the given cpu core
+fn set_affinity(core: Option<usize>) {
+    if let Some(core) = core {
+        if let Err(e) = try_set_affinity(core) {
+            error!("failed to set thread affinity to core {}, err={}", core, e);
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn try_set_affinity(core: usize) -> io::Result<()> {
+    // `CPU_SET` can't hold a core beyond the fixed size set
+    if core >= libc::CPU_SETSIZE as usize {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("core {} exceeds CPU_SETSIZE {}", core, libc::CPU_SETSIZE),
+        ));
+    }
+    unsafe {
+        let mut set: libc::cpu_set_t = std::mem::zeroed();
+        libc::CPU_SET(core, &mut set);
+        let ret = libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &set);
+        if ret != 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn try_set_affinity(core: usize) -> io::Result<()> {
+    warn!("thread affinity is not supported, core {} ignored", core);
+    Ok(())
+}
+
 static mut SCHED: *const Scheduler = std::ptr::null();
 
+// max times that the lifo slot can be run in a row before
+// giving the local queue a chance, to avoid starvation
+const MAX_LIFO_POLLS: usize = 3;
+
+// the high priority queue is skipped every this many polls so that
+// the normal coroutines are not starved
+const HIGH_PRIORITY_WEIGHT: usize = 4;
+// the low priority queue is polled first every this many polls
+const LOW_PRIORITY_INTERVAL: usize = 16;
+
 pub struct ParkStatus {
     pub parked: AtomicU64,
     workers: u64,
@@ -82,7 +174,7 @@ impl ParkStatus {
             // the worker thread would set it to 1 when idle
             let mask = self.workers + first_thread;
             self.parked.fetch_and(!mask, Ordering::Relaxed);
-            scheduler.get_selector().wakeup(first_thread as usize);
+            scheduler.wakeup_worker(first_thread as usize);
         }
     }
 }
@@ -98,28 +190,36 @@ fn init_scheduler() {
     }
     filter_cancel_panic();
 
+    // the coarse clock must be valid before any timer is added
+    timeout_list::refresh_coarse();
+
     // timer thread
     thread::spawn(move || {
         println!("init timer worker {:?}", std::thread::current().id());
+        set_affinity(config().get_timer_affinity());
         let s = unsafe { &*SCHED };
         // timer function
-        let timer_event_handler = |co: Arc<AtomicOption<CoroutineImpl>>| {
-            // just re-push the co to the visit list
-            if let Some(mut c) = co.take() {
-                // set the timeout result for the coroutine
-                set_co_para(&mut c, io::Error::new(io::ErrorKind::TimedOut, "timeout"));
-                // s.schedule_global(c);
-                // run_coroutine(c);
-                if let Some(t) = &c.worker_thread_id {
-                    let id = s.worker_ids.get(t);
-                    if let Some(id) = id {
-                        s.local_queues[*id].push(c);
-                        s.get_selector().wakeup(*id);
+        let timer_event_handler = |event: TimerEvent| match event {
+            TimerEvent::Co(co) => s.wake_timeout(co),
+            TimerEvent::Call(f) => f(),
+        };
+        // the tick refreshes the coarse clock and runs the watchdog
+        let resolution = config().get_coarse_clock_resolution();
+        let block_timeout = config().get_worker_block_timeout();
+        let check_interval = block_timeout.map(|t| std::cmp::max(t / 2, Duration::from_millis(1)));
+        let interval = check_interval.map_or(resolution, |i| std::cmp::min(i, resolution));
+        let mut watchdog = Vec::new();
+        let mut next_check = 0;
+        s.timer_thread
+            .run_with_tick(&timer_event_handler, Some(interval), || {
+                let now = timeout_list::refresh_coarse();
+                if let (Some(timeout), Some(i)) = (block_timeout, check_interval) {
+                    if now >= next_check {
+                        s.check_blocked_workers(&mut watchdog, timeout);
+                        next_check = now + timeout_list::dur_to_ns(i);
                     }
                 }
-            }
-        };
-        s.timer_thread.run(&timer_event_handler);
+            });
     });
 
     println!("init workers {}", workers);
@@ -129,6 +229,7 @@ fn init_scheduler() {
         let w = wg.clone();
         thread::spawn(move || {
             println!("init worker {:?}", std::thread::current().id());
+            set_affinity(config().get_worker_affinity(id));
             let s = unsafe { &*SCHED };
             s.worker_ids.insert(std::thread::current().id(), id);
             s.stacks.insert(std::thread::current().id(), Stack::new(crate::config().get_stack_size()));
@@ -142,6 +243,36 @@ fn init_scheduler() {
     SCHEDULER_INITED.store(true, Ordering::Relaxed);
 }
 
+// get the current io worker id, `None` for the other threads
+#[inline]
+pub(crate) fn current_io_worker() -> Option<usize> {
+    current_worker().filter(|id| *id < get_scheduler().workers_len)
+}
+
+// get the current worker id, `None` for a non-worker thread
+#[inline]
+pub(crate) fn current_worker() -> Option<usize> {
+    #[cfg(nightly)]
+    let id = WORKER_ID.load(Ordering::Relaxed);
+    #[cfg(not(nightly))]
+    let id = WORKER_ID.with(|id| id.load(Ordering::Relaxed));
+    if id == !1 {
+        None
+    } else {
+        Some(id)
+    }
+}
+
+#[inline]
+fn hook_event(co_id: u64) -> HookEvent {
+    HookEvent {
+        co_id,
+        worker: current_worker(),
+        time: timeout_list::now_coarse(),
+    }
+}
+
+/// get the global scheduler, it would be initialized at the first call
 #[inline]
 pub fn get_scheduler() -> &'static Scheduler {
     unsafe {
@@ -195,23 +326,99 @@ pub fn get_scheduler() -> &'static Scheduler {
 //     }
 // }
 
+// runtime counters of a worker
+#[derive(Default)]
+struct WorkerCounters {
+    steals: AtomicU64,
+    parks: AtomicU64,
+    wakeups: AtomicU64,
+    tasks: AtomicU64,
+    // id of the running coroutine, 0 if not running one
+    running: AtomicU64,
+}
+
+// watchdog state of a worker, only accessed in the timer thread
+#[derive(Default, Clone)]
+struct WatchdogState {
+    tasks: u64,
+    since: u64,
+    blocked: bool,
+    // a replacement worker is added for the blocked one
+    replaced: bool,
+}
+
+/// runtime metrics of a worker thread
+#[derive(Debug, Clone, Default)]
+pub struct WorkerStats {
+    /// the worker id
+    pub id: usize,
+    /// number of coroutines in the local queue
+    pub local_queue: usize,
+    /// number of coroutines stolen from the global queue
+    pub steals: u64,
+    /// number of times the worker went to wait for events
+    pub parks: u64,
+    /// number of wakeup signals sent to the worker
+    pub wakeups: u64,
+    /// number of coroutines run from the queues
+    pub tasks: u64,
+}
+
+/// runtime metrics of the scheduler, see `Scheduler::stats`
+#[derive(Debug, Clone, Default)]
+pub struct SchedulerStats {
+    /// number of coroutines in the global queue
+    pub global_queue: usize,
+    /// stats of the running workers
+    pub workers: Vec<WorkerStats>,
+}
+
+// worker added at runtime, it has no io selector and only runs queued tasks
+#[derive(Default)]
+struct DynWorker {
+    thread: Mutex<Option<Thread>>,
+    retire: AtomicBool,
+}
+
 #[repr(align(128))]
 pub struct Scheduler {
     event_loop: EventLoop,
     global_queue: dark_std::sync::SyncVec<CoroutineImpl>,
+    // queues for the none normal priority coroutines
+    high_queue: SegQueue<CoroutineImpl>,
+    low_queue: SegQueue<CoroutineImpl>,
     local_queues: Vec<deque::Worker<CoroutineImpl>>,
+    // coroutines placed on the workers by other threads
+    remote_queues: Vec<SegQueue<CoroutineImpl>>,
+    place_idx: AtomicUsize,
+    // the spawners that wait for the ready queues to drain
+    drained: Notify,
+    drain_waiters: AtomicUsize,
+    // the last scheduled coroutine of each worker, it runs next
+    lifo_slots: Vec<AtomicOption<CoroutineImpl>>,
     pub(crate) workers: ParkStatus,
+    // the timers of coroutines that not running on io workers
     timer_thread: TimerThread,
+    local_timers: Vec<LocalTimer>,
     // stealers: Vec<Vec<(usize, deque::Stealer<CoroutineImpl>)>>,
     workers_len: usize,
+    // slots for workers added at runtime, the id is `workers_len + index`
+    dyn_workers: Vec<DynWorker>,
+    dyn_workers_len: AtomicUsize,
+    dyn_wake_idx: AtomicUsize,
+    blocking_pool: BlockingPool,
+    policy: Arc<dyn SchedulePolicy>,
+    hooks: Option<Arc<dyn SchedulerHooks>>,
+    counters: Vec<WorkerCounters>,
     pub(crate) worker_ids: dark_std::sync::SyncHashMap<ThreadId, usize>,
     pub(crate) stacks: dark_std::sync::SyncHashMap<ThreadId, Stack>,
 }
 
 impl Scheduler {
-    pub fn new(workers: usize) -> Box<Self> {
-        let mut local_queues = Vec::with_capacity(workers);
-        (0..workers).for_each(|_| local_queues.push(deque::Worker::new_fifo()));
+    pub(crate) fn new(workers: usize) -> Box<Self> {
+        let max_workers = std::cmp::max(config().get_max_workers(), workers);
+        let mut local_queues = Vec::with_capacity(max_workers);
+        (0..max_workers).for_each(|_| local_queues.push(deque::Worker::new_fifo()));
         let mut stealers = Vec::with_capacity(workers);
         for id in 0..workers {
             let mut stealers_l = Vec::with_capacity(workers);
@@ -226,11 +433,26 @@ impl Scheduler {
         Box::new(Scheduler {
             event_loop: EventLoop::new(workers).expect("can't create event_loop"),
             global_queue: dark_std::sync::SyncVec::new(),
+            high_queue: SegQueue::new(),
+            low_queue: SegQueue::new(),
             local_queues,
+            remote_queues: (0..max_workers).map(|_| SegQueue::new()).collect(),
+            place_idx: AtomicUsize::new(0),
+            drained: Notify::new(),
+            drain_waiters: AtomicUsize::new(0),
+            lifo_slots: (0..max_workers).map(|_| AtomicOption::none()).collect(),
             timer_thread: TimerThread::new(),
+            local_timers: (0..workers).map(|_| LocalTimer::new()).collect(),
             workers: ParkStatus::new(workers as u64),
             //stealers,
             workers_len: workers,
+            dyn_workers: (workers..max_workers).map(|_| DynWorker::default()).collect(),
+            dyn_workers_len: AtomicUsize::new(0),
+            dyn_wake_idx: AtomicUsize::new(0),
+            blocking_pool: BlockingPool::new(),
+            policy: config().get_schedule_policy(),
+            hooks: config().get_scheduler_hooks(),
+            counters: (0..max_workers).map(|_| WorkerCounters::default()).collect(),
             worker_ids: {
                 let v = dark_std::sync::SyncHashMap::new();
                 v
@@ -239,40 +461,86 @@ impl Scheduler {
         })
     }
 
-    pub fn run_queued_tasks(&self, id: usize) {
+    pub(crate) fn run_queued_tasks(&self, id: usize) {
+        let steal = self.policy.steal_global(id);
         let local = unsafe { self.local_queues.get_unchecked(id) };
+        let remote = self.remote_queues.get(id);
+        let lifo_slot = unsafe { self.lifo_slots.get_unchecked(id) };
+        let mut lifo_polls = 0;
+        let mut tick = 0usize;
         // let stealers = unsafe { self.stealers.get_unchecked(id) };
         loop {
+            tick = tick.wrapping_add(1);
+            // the high priority ones go first, the low priority ones get a chance periodically
+            let mut co = None;
+            if !tick.is_multiple_of(HIGH_PRIORITY_WEIGHT) {
+                co = self.high_queue.pop();
+            }
+            if co.is_none() && tick.is_multiple_of(LOW_PRIORITY_INTERVAL) {
+                co = self.low_queue.pop();
+            }
+            // the lifo slot goes next, but not forever
+            if co.is_none() {
+                co = lifo_slot.take();
+                if co.is_some() {
+                    lifo_polls += 1;
+                    if lifo_polls > MAX_LIFO_POLLS {
+                        local.push(co.take().unwrap());
+                        lifo_polls = 0;
+                    }
+                } else {
+                    lifo_polls = 0;
+                }
+            }
             // Pop a task from the local queue
-            let co = local.pop().or_else(|| {
-                // Try stealing a of task from other local queues.
-                // let parked_threads = self.workers.parked.load(Ordering::Relaxed);
-                // stealers
-                //     .iter()
-                //     .map(|s| {
-                //         if parked_threads & (self.workers_len + s.0) as u64 != 0 {
-                //             return None;
-                //         }
-                //         steal_local(&s.1, local)
-                //     })
-                //     .find_map(|r| r)
-                //     // Try stealing a batch of tasks from the global queue.
-                //     .or_else(|| {
-                //         if self.global_queue.is_empty() {
-                //             None
-                //         } else {
-                //             steal_global(&self.global_queue, local)
-                //         }
-                //     })
-                let f = self.steal_global();
-                f
-            });
+            let co = co
+                .or_else(|| local.pop())
+                .or_else(|| remote.and_then(|q| q.pop()))
+                .or_else(|| {
+                    // Try stealing a of task from other local queues.
+                    // let parked_threads = self.workers.parked.load(Ordering::Relaxed);
+                    // stealers
+                    //     .iter()
+                    //     .map(|s| {
+                    //         if parked_threads & (self.workers_len + s.0) as u64 != 0 {
+                    //             return None;
+                    //         }
+                    //         steal_local(&s.1, local)
+                    //     })
+                    //     .find_map(|r| r)
+                    //     // Try stealing a batch of tasks from the global queue.
+                    //     .or_else(|| {
+                    //         if self.global_queue.is_empty() {
+                    //             None
+                    //         } else {
+                    //             steal_global(&self.global_queue, local)
+                    //         }
+                    //     })
+                    if !steal {
+                        return None;
+                    }
+                    let f = self.steal_global();
+                    if let Some(co) = f.as_ref() {
+                        self.counters[id].steals.fetch_add(1, Ordering::Relaxed);
+                        if let Some(h) = self.hooks.as_ref() {
+                            h.on_steal(&hook_event(co_id(co)));
+                        }
+                    }
+                    f
+                })
+                .or_else(|| self.high_queue.pop())
+                .or_else(|| self.low_queue.pop());
             if let Some(mut co) = co {
+                self.notify_drained();
+                let counters = &self.counters[id];
+                counters.tasks.fetch_add(1, Ordering::Relaxed);
+                counters.running.store(co_id(&co), Ordering::Relaxed);
                 co.worker_thread_id = Some(std::thread::current().id());
                 run_coroutine(co);
+                counters.running.store(0, Ordering::Relaxed);
             } else {
                 // do a re-check
-                if self.global_queue.is_empty() {
+                if !steal || self.global_queue.is_empty() {
                     break;
                 }
             }
@@ -325,14 +593,92 @@ impl Scheduler {
 
     /// put the coroutine to correct queue so that next time it can be scheduled
     #[inline]
-    pub fn schedule(&self, co: CoroutineImpl) {
+    pub(crate) fn schedule(&self, co: CoroutineImpl) {
+        self.on_schedule(&co);
+        if co.pinned {
+            return self.schedule_pinned(co);
+        }
+        if co.priority != Priority::Normal {
+            return self.schedule_priority(co);
+        }
+        #[cfg(nightly)]
+            let id = WORKER_ID.load(Ordering::Relaxed);
+        #[cfg(not(nightly))]
+            let id = WORKER_ID.with(|id| id.load(Ordering::Relaxed));
+
+        let worker = if id == !1 { None } else { Some(id) };
+        match (self.policy.schedule(worker), worker) {
+            (ScheduleTarget::Lifo, Some(id)) => {
+                if let Some(prev) = unsafe { self.lifo_slots.get_unchecked(id) }.swap(co) {
+                    // the replaced one goes to the local queue
+                    unsafe { self.local_queues.get_unchecked(id) }.push(prev);
+                }
+            }
+            (ScheduleTarget::Local, Some(id)) => {
+                unsafe { self.local_queues.get_unchecked(id) }.push(co);
+            }
+            (ScheduleTarget::Worker(target), _) => self.schedule_to(target, co),
+            (ScheduleTarget::LeastLoaded, _) => self.schedule_least_loaded(co),
+            _ => self.push_global(co),
+        }
+    }
+
+    // put the coroutine to the io worker that has the least queued coroutines
+    fn schedule_least_loaded(&self, co: CoroutineImpl) {
+        if co.priority != Priority::Normal {
+            return self.schedule_priority(co);
+        }
+        // start from a rotating index so that the equally loaded workers take turns
+        let start = self.place_idx.fetch_add(1, Ordering::Relaxed);
+        let id = (0..self.workers_len)
+            .map(|i| (start + i) % self.workers_len)
+            .min_by_key(|&id| self.queued(id))
+            .unwrap();
+        self.remote_queues[id].push(co);
+        self.wakeup_worker(id);
+    }
+
+    // number of coroutines queued on the worker
+    #[inline]
+    fn queued(&self, id: usize) -> usize {
+        let remote = self.remote_queues.get(id).map_or(0, |q| q.len());
+        self.local_queues[id].len() + self.lifo_slots[id].is_some() as usize + remote
+    }
+
+    // put the coroutine to the global queue and mark it for the given worker
+    fn schedule_to(&self, id: usize, mut co: CoroutineImpl) {
+        let thread = self
+            .worker_ids
+            .iter()
+            .find(|(_, v)| **v == id)
+            .map(|(t, _)| *t);
+        match thread {
+            Some(t) => {
+                co.worker_thread_id = Some(t);
+                self.global_queue.push(co);
+                self.wakeup_worker(id);
+            }
+            None => self.push_global(co),
+        }
+    }
+
+    /// put the coroutine to the back of the queue, bypass the lifo slot
+    #[inline]
+    pub(crate) fn schedule_fifo(&self, co: CoroutineImpl) {
+        self.on_schedule(&co);
+        if co.pinned {
+            return self.schedule_pinned(co);
+        }
+        if co.priority != Priority::Normal {
+            return self.schedule_priority(co);
+        }
         #[cfg(nightly)]
             let id = WORKER_ID.load(Ordering::Relaxed);
         #[cfg(not(nightly))]
             let id = WORKER_ID.with(|id| id.load(Ordering::Relaxed));
 
         if id == !1 {
-            self.schedule_global(co);
+            self.push_global(co);
         } else {
             unsafe { self.local_queues.get_unchecked(id) }.push(co);
         }
@@ -340,33 +686,570 @@ impl Scheduler {
 
     /// put the coroutine to global queue so that next time it can be scheduled
     #[inline]
-    pub fn schedule_global(&self, mut co: CoroutineImpl) {
+    pub(crate) fn schedule_global(&self, co: CoroutineImpl) {
+        self.on_schedule(&co);
+        if co.pinned {
+            return self.schedule_pinned(co);
+        }
+        self.push_global(co);
+    }
+
+    /// schedule a newly spawned coroutine with the placement of the policy
+    #[inline]
+    pub(crate) fn schedule_spawn(&self, co: CoroutineImpl) {
+        self.on_schedule(&co);
+        if co.pinned {
+            return self.schedule_pinned(co);
+        }
+        match self.policy.spawn(current_worker()) {
+            ScheduleTarget::LeastLoaded => self.schedule_least_loaded(co),
+            ScheduleTarget::Worker(id) => self.schedule_to(id, co),
+            _ => self.push_global(co),
+        }
+    }
+
+    // put the pinned coroutine back to its own worker
+    fn schedule_pinned(&self, mut co: CoroutineImpl) {
+        let target = co
+            .worker_thread_id
+            .as_ref()
+            .and_then(|t| self.worker_ids.get(t).copied());
+        match target {
+            Some(id) if current_worker() == Some(id) => {
+                unsafe { self.local_queues.get_unchecked(id) }.push(co);
+            }
+            // the remote queue is polled even if the worker doesn't steal
+            Some(id) => {
+                self.remote_queues[id].push(co);
+                self.wakeup_worker(id);
+            }
+            // the worker is retired, only for `spawn_on` an added worker
+            None => {
+                co.worker_thread_id = None;
+                self.push_global(co);
+            }
+        }
+    }
+
+    #[inline]
+    fn push_global(&self, co: CoroutineImpl) {
+        if co.priority != Priority::Normal {
+            return self.schedule_priority(co);
+        }
         self.global_queue.push(co);
         // signal one waiting thread if any
         self.workers.wake_one(self);
+        self.wake_dyn_worker();
+    }
+
+    // put the coroutine to the queue of its priority
+    fn schedule_priority(&self, co: CoroutineImpl) {
+        match co.priority {
+            Priority::High => self.high_queue.push(co),
+            _ => self.low_queue.push(co),
+        }
+        self.workers.wake_one(self);
+        self.wake_dyn_worker();
+    }
+
+    // wake up the worker with the given id, no matter it's an io worker or not
+    #[inline]
+    pub(crate) fn wakeup_worker(&self, id: usize) {
+        self.counters[id].wakeups.fetch_add(1, Ordering::Relaxed);
+        if id < self.workers_len {
+            self.get_selector().wakeup(id);
+        } else if let Some(t) = self.dyn_workers[id - self.workers_len].thread.lock().as_ref() {
+            t.unpark();
+        }
+    }
+
+    // unpark one of the runtime added workers in turn
+    #[inline]
+    fn wake_dyn_worker(&self) {
+        if self.dyn_workers_len.load(Ordering::Relaxed) == 0 {
+            return;
+        }
+        let len = self.dyn_workers.len();
+        let start = self.dyn_wake_idx.fetch_add(1, Ordering::Relaxed);
+        for i in 0..len {
+            let w = &self.dyn_workers[(start + i) % len];
+            if let Some(t) = w.thread.lock().as_ref() {
+                let id = self.workers_len + (start + i) % len;
+                self.counters[id].wakeups.fetch_add(1, Ordering::Relaxed);
+                t.unpark();
+                return;
+            }
+        }
+    }
+
+    #[inline]
+    pub(crate) fn policy(&self) -> &dyn SchedulePolicy {
+        &*self.policy
+    }
+
+    // record that the worker is going to wait for events
+    #[inline]
+    pub(crate) fn count_park(&self, id: usize) {
+        self.counters[id].parks.fetch_add(1, Ordering::Relaxed);
+        if let Some(h) = self.hooks.as_ref() {
+            h.on_park(&hook_event(0));
+        }
     }
 
     #[inline]
-    pub fn add_timer(
+    pub(crate) fn on_spawn(&self, co: &CoroutineImpl) {
+        if let Some(h) = self.hooks.as_ref() {
+            h.on_spawn(&hook_event(co_id(co)));
+        }
+    }
+
+    #[inline]
+    fn on_schedule(&self, co: &CoroutineImpl) {
+        if let Some(h) = self.hooks.as_ref() {
+            h.on_schedule(&hook_event(co_id(co)));
+        }
+    }
+
+    // return the coroutine id for `on_run_end`
+    #[inline]
+    pub(crate) fn on_run_start(&self, co: &CoroutineImpl) -> u64 {
+        match self.hooks.as_ref() {
+            Some(h) => {
+                let id = co_id(co);
+                h.on_run_start(&hook_event(id));
+                id
+            }
+            None => 0,
+        }
+    }
+
+    #[inline]
+    pub(crate) fn on_run_end(&self, co_id: u64) {
+        if let Some(h) = self.hooks.as_ref() {
+            h.on_run_end(&hook_event(co_id));
+        }
+    }
+
+    /// get the runtime metrics of the global queue and the running workers
+    pub fn stats(&self) -> SchedulerStats {
+        let worker_stats = |id: usize| {
+            let c = &self.counters[id];
+            WorkerStats {
+                id,
+                local_queue: self.queued(id),
+                steals: c.steals.load(Ordering::Relaxed),
+                parks: c.parks.load(Ordering::Relaxed),
+                wakeups: c.wakeups.load(Ordering::Relaxed),
+                tasks: c.tasks.load(Ordering::Relaxed),
+            }
+        };
+        let mut workers: Vec<WorkerStats> = (0..self.workers_len).map(worker_stats).collect();
+        for (i, w) in self.dyn_workers.iter().enumerate() {
+            if w.thread.lock().is_some() {
+                workers.push(worker_stats(self.workers_len + i));
+            }
+        }
+        SchedulerStats {
+            global_queue: self.global_queue.len() + self.high_queue.len() + self.low_queue.len(),
+            workers,
+        }
+    }
+
+    /// number of coroutines waiting in the global and local ready queues
+    pub fn pending(&self) -> usize {
+        let local: usize = (0..self.max_workers()).map(|id| self.queued(id)).sum();
+        local + self.global_queue.len() + self.high_queue.len() + self.low_queue.len()
+    }
+
+    // whether the ready queues exceed `Config::get_max_pending_coroutines`
+    #[inline]
+    pub(crate) fn is_overloaded(&self) -> bool {
+        let max = config().get_max_pending_coroutines();
+        max != 0 && self.pending() >= max
+    }
+
+    // wait until the ready queues don't exceed `Config::get_max_pending_coroutines`
+    pub(crate) fn wait_drained(&self) {
+        while self.is_overloaded() {
+            self.drain_waiters.fetch_add(1, Ordering::SeqCst);
+            if self.is_overloaded() {
+                self.drained.notified();
+            }
+            self.drain_waiters.fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+
+    // wake up the spawners once the ready queues drain, called after taking a coroutine
+    #[inline]
+    fn notify_drained(&self) {
+        if self.drain_waiters.load(Ordering::SeqCst) != 0 && !self.is_overloaded() {
+            self.drained.notify_waiters();
+            // store a permit for the waiter that is not parked yet
+            self.drained.notify_one();
+        }
+    }
+
+    // find out the workers that are blocked by a coroutine, called in the timer thread
+    fn check_blocked_workers(&self, state: &mut Vec<WatchdogState>, timeout: Duration) {
+        let timeout = crate::timeout_list::dur_to_ns(timeout);
+        let now = crate::timeout_list::now();
+        state.resize(self.max_workers(), WatchdogState::default());
+        for (id, st) in state.iter_mut().enumerate() {
+            if id >= self.workers_len && self.dyn_workers[id - self.workers_len].thread.lock().is_none() {
+                continue;
+            }
+            let tasks = self.counters[id].tasks.load(Ordering::Relaxed);
+            if st.blocked {
+                if tasks == st.tasks {
+                    self.hand_over_queued(id);
+                    continue;
+                }
+                // the blocking coroutine returned, retire the replacement
+                info!(
+                    "worker {} is unblocked after {:?}",
+                    id,
+                    crate::timeout_list::ns_to_dur(now - st.since)
+                );
+                if st.replaced {
+                    self.remove_workers(1);
+                }
+                *st = WatchdogState { tasks, since: now, ..Default::default() };
+                continue;
+            }
+            let queued = self.queued(id);
+            if tasks != st.tasks || queued == 0 {
+                *st = WatchdogState { tasks, since: now, ..Default::default() };
+                continue;
+            }
+            if now - st.since < timeout {
+                continue;
+            }
+
+            st.blocked = true;
+            // the coroutine may be done at any time, only use the owned handle
+            let co_id = self.counters[id].running.load(Ordering::Relaxed);
+            match crate::registry::get(co_id) {
+                Some(co) => {
+                    error!(
+                        "worker {} is blocked for {:?} by coroutine {} name={:?} spawned at {}, {} coroutines queued",
+                        id,
+                        crate::timeout_list::ns_to_dur(now - st.since),
+                        co_id,
+                        co.name().unwrap_or("<unnamed>"),
+                        co.spawn_site(),
+                        queued
+                    );
+                    // captured at the last suspension, see `Config::set_coroutine_backtrace`
+                    #[cfg(nightly)]
+                    if let Some(bt) = co.backtrace() {
+                        error!("coroutine {} backtrace:\n{}", co_id, bt);
+                    }
+                }
+                None => error!(
+                    "worker {} is blocked for {:?}, {} coroutines queued",
+                    id,
+                    crate::timeout_list::ns_to_dur(now - st.since),
+                    queued
+                ),
+            }
+            st.replaced = self.add_workers(1) == 1;
+            if !st.replaced {
+                warn!("can't add replacement worker, max_workers={}", self.max_workers());
+            }
+            self.hand_over_queued(id);
+        }
+    }
+
+    // wake up the coroutine of an expired timer in the timer thread
+    fn wake_timeout(&self, co: TimerData) {
+        // just re-push the co to the visit list
+        if let Some(mut c) = co.take() {
+            // set the timeout result for the coroutine
+            set_co_para(&mut c, io::Error::new(io::ErrorKind::TimedOut, "timeout"));
+            let id = c.worker_thread_id.as_ref().and_then(|t| self.worker_ids.get(t));
+            match id {
+                Some(id) => {
+                    self.on_schedule(&c);
+                    if c.priority != Priority::Normal && !c.pinned {
+                        self.schedule_priority(c);
+                    } else {
+                        // only the owner can push to the local queue
+                        self.remote_queues[*id].push(c);
+                        self.wakeup_worker(*id);
+                    }
+                }
+                // the worker is already retired
+                None => self.schedule_global(c),
+            }
+        }
+    }
+
+    // move the timers in the wheel of a blocked io worker to the timer thread,
+    // they would never fire before the worker comes back otherwise
+    fn hand_over_timers(&self, id: usize) {
+        let timer = match self.local_timers.get(id) {
+            Some(timer) => timer,
+            None => return,
+        };
+        let mut wheel = timer.wheel.lock();
+        if wheel.is_empty() {
+            return;
+        }
+        let now = timeout_list::now();
+        let mut moved = timer.moved.lock();
+        for (token, deadline, co) in wheel.drain() {
+            let dur = Duration::from_nanos(deadline.saturating_sub(now));
+            let f = Box::new(move || {
+                let s = get_scheduler();
+                s.local_timers[id].moved.lock().remove(&token);
+                s.wake_timeout(co);
+            });
+            moved.insert(token, self.timer_thread.add_timer(dur, TimerEvent::Call(f)));
+        }
+    }
+
+    // move the coroutines queued on the worker to the global queue,
+    // the pinned ones are kept in the remote queue of the worker
+    fn hand_over_queued(&self, id: usize) {
+        self.hand_over_timers(id);
+        let mut pinned = Vec::new();
+        let mut hand_over = |mut co: CoroutineImpl| {
+            if co.pinned {
+                pinned.push(co);
+            } else {
+                co.worker_thread_id = None;
+                self.push_global(co);
+            }
+        };
+        if let Some(co) = self.lifo_slots[id].take() {
+            hand_over(co);
+        }
+        let stealer = self.local_queues[id].stealer();
+        loop {
+            match stealer.steal() {
+                deque::Steal::Success(co) => hand_over(co),
+                deque::Steal::Empty => break,
+                deque::Steal::Retry => {}
+            }
+        }
+        while let Some(co) = self.remote_queues[id].pop() {
+            hand_over(co);
+        }
+        for co in pinned {
+            self.remote_queues[id].push(co);
+        }
+    }
+
+    /// the number of currently running workers
+    pub fn workers(&self) -> usize {
+        self.workers_len + self.dyn_workers_len.load(Ordering::Relaxed)
+    }
+
+    /// the max number of workers, see `Config::set_max_workers`
+    pub fn max_workers(&self) -> usize {
+        self.workers_len + self.dyn_workers.len()
+    }
+
+    /// add `n` worker threads at runtime, return the number of workers actually added
+    ///
+    /// the total workers would not exceed `max_workers`. the added workers don't
+    /// poll io events, they only run the scheduled coroutines
+    pub fn add_workers(&self, n: usize) -> usize {
+        let mut added = 0;
+        for (i, w) in self.dyn_workers.iter().enumerate() {
+            if added == n {
+                break;
+            }
+            let mut thread = w.thread.lock();
+            if thread.is_some() || w.retire.load(Ordering::Acquire) {
+                continue;
+            }
+            let id = self.workers_len + i;
+            let h = thread::Builder::new()
+                .name(format!("mco-worker-{}", id))
+                .spawn(move || {
+                    let s = unsafe { &*SCHED };
+                    s.run_dyn_worker(id)
+                });
+            match h {
+                Ok(h) => {
+                    *thread = Some(h.thread().clone());
+                    self.dyn_workers_len.fetch_add(1, Ordering::Relaxed);
+                    added += 1;
+                }
+                Err(e) => {
+                    error!("failed to spawn worker thread, err={}", e);
+                    break;
+                }
+            }
+        }
+        info!("add workers={}, total={}", added, self.workers());
+        added
+    }
+
+    /// retire `n` workers that added by `add_workers`, return the number of retired workers
+    ///
+    /// the initial workers are never removed. the tasks queued on a retired worker
+    /// are moved to the global queue
+    pub fn remove_workers(&self, n: usize) -> usize {
+        let mut removed = 0;
+        for w in self.dyn_workers.iter().rev() {
+            if removed == n {
+                break;
+            }
+            let thread = w.thread.lock();
+            if let Some(t) = thread.as_ref() {
+                if !w.retire.swap(true, Ordering::AcqRel) {
+                    self.dyn_workers_len.fetch_sub(1, Ordering::Relaxed);
+                    t.unpark();
+                    removed += 1;
+                }
+            }
+        }
+        info!("remove workers={}, total={}", removed, self.workers());
+        removed
+    }
+
+    fn run_dyn_worker(&self, id: usize) {
+        set_affinity(config().get_worker_affinity(id));
+        #[cfg(nightly)]
+        WORKER_ID.store(id, Ordering::Relaxed);
+        #[cfg(not(nightly))]
+        WORKER_ID.with(|worker_id| worker_id.store(id, Ordering::Relaxed));
+
+        let current = thread::current().id();
+        self.worker_ids.insert(current, id);
+        self.stacks
+            .insert(current, Stack::new(config().get_stack_size()));
+
+        let w = &self.dyn_workers[id - self.workers_len];
+        while !w.retire.load(Ordering::Acquire) {
+            self.run_queued_tasks(id);
+            self.count_park(id);
+            // wake up periodically in case of missing the signal
+            thread::park_timeout(self.policy.park_timeout(id));
+        }
+
+        // retire the worker, hand over the queued tasks
+        self.worker_ids.remove(&current);
+        let local = &self.local_queues[id];
+        let remote = &self.remote_queues[id];
+        while let Some(mut co) = self.lifo_slots[id]
+            .take()
+            .or_else(|| local.pop())
+            .or_else(|| remote.pop())
+        {
+            co.worker_thread_id = None;
+            self.global_queue.push(co);
+        }
+        let mut thread = w.thread.lock();
+        *thread = None;
+        w.retire.store(false, Ordering::Release);
+        drop(thread);
+        self.workers.wake_one(self);
+    }
+
+    // the io workers keep the timers in their own wheel by the coarse clock,
+    // other threads fall back to the timer thread
+    #[inline]
+    pub(crate) fn add_timer(&self, dur: Duration, co: Arc<AtomicOption<CoroutineImpl>>) -> TimerHandle {
+        match current_worker() {
+            Some(id) if id < self.workers_len => {
+                let mut wheel = self.local_timers[id].wheel.lock();
+                TimerHandle::Local(id, wheel.insert_at(timeout_list::coarse_deadline(dur), co))
+            }
+            _ => TimerHandle::Global(self.timer_thread.add_timer(dur, TimerEvent::Co(co))),
+        }
+    }
+
+    /// run `f` in the timer thread after `dur`
+    ///
+    /// the callback must be short and never block, it delays the other timers
+    pub(crate) fn add_callback_timer(
         &self,
         dur: Duration,
-        co: Arc<AtomicOption<CoroutineImpl>>,
-    ) -> timeout_list::TimeoutHandle<TimerData> {
-        self.timer_thread.add_timer(dur, co)
+        f: Box<dyn FnOnce() + Send>,
+    ) -> CallbackHandle {
+        self.timer_thread.add_timer(dur, TimerEvent::Call(f))
+    }
+
+    /// same as `add_timer` but the timer expires at the `deadline`
+    ///
+    /// the io worker wheels keep the deadline as is, the timer thread
+    /// converts it to the remaining time
+    #[inline]
+    pub(crate) fn add_timer_at(&self, deadline: Instant, co: Arc<AtomicOption<CoroutineImpl>>) -> TimerHandle {
+        match current_worker() {
+            Some(id) if id < self.workers_len => {
+                let mut wheel = self.local_timers[id].wheel.lock();
+                TimerHandle::Local(id, wheel.insert_at(timeout_list::instant_to_ns(deadline), co))
+            }
+            _ => {
+                let dur = deadline.saturating_duration_since(Instant::now());
+                TimerHandle::Global(self.timer_thread.add_timer(dur, TimerEvent::Co(co)))
+            }
+        }
+    }
+
+    #[inline]
+    pub(crate) fn del_timer(&self, handle: TimerHandle) {
+        match handle {
+            TimerHandle::Global(h) => self.timer_thread.del_timer(h),
+            TimerHandle::Local(id, token) => {
+                let timer = &self.local_timers[id];
+                let mut wheel = timer.wheel.lock();
+                if wheel.remove(token).is_none() {
+                    // the timer may be moved to the timer thread by the watchdog
+                    if let Some(h) = timer.moved.lock().remove(&token) {
+                        self.timer_thread.del_timer(h);
+                    }
+                }
+            }
+        }
+    }
+
+    // expire the timers of the io worker and run the timeout coroutines
+    // return the time in ns for the next expiration
+    pub(crate) fn run_timers(&self, id: usize) -> Option<u64> {
+        let timer = &self.local_timers[id];
+        let mut fired = false;
+        {
+            let mut wheel = timer.wheel.lock();
+            wheel.poll(timeout_list::now(), |co| {
+                if let Some(mut c) = co.take() {
+                    // set the timeout result for the coroutine
+                    set_co_para(&mut c, io::Error::new(io::ErrorKind::TimedOut, "timeout"));
+                    self.on_schedule(&c);
+                    self.local_queues[id].push(c);
+                    fired = true;
+                }
+            });
+        }
+        if fired {
+            // the coroutines may add new timers to the wheel
+            self.run_queued_tasks(id);
+        }
+        let wheel = timer.wheel.lock();
+        wheel.next_expire(timeout_list::now())
     }
 
     #[inline]
-    pub fn del_timer(&self, handle: timeout_list::TimeoutHandle<TimerData>) {
-        self.timer_thread.del_timer(handle);
+    pub(crate) fn blocking_pool(&self) -> &BlockingPool {
+        &self.blocking_pool
+    }
+
+    /// the number of alive threads in the blocking pool
+    pub fn blocking_threads(&self) -> usize {
+        self.blocking_pool.threads()
     }
 
     #[inline]
-    pub fn get_selector(&self) -> &Selector {
+    pub(crate) fn get_selector(&self) -> &Selector {
         self.event_loop.get_selector()
     }
 
     #[inline]
-    pub fn get_stack(&self, key: std::thread::ThreadId) -> Stack {
+    pub(crate) fn get_stack(&self, key: std::thread::ThreadId) -> Stack {
         match self.stacks.get(&key) {
             None => {
                 let v = Stack::new(crate::config().get_stack_size());
@@ -380,3 +1263,14 @@ impl Scheduler {
         }
     }
 }
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn affinity_out_of_range() {
+        let err = try_set_affinity(libc::CPU_SETSIZE as usize).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+}
@@ -1,5 +1,6 @@
 // modified from crossbeam
 
+use std::any::Any;
 use std::cell::RefCell;
 use std::fmt;
 use std::mem;
@@ -8,9 +9,11 @@ use std::rc::Rc;
 use std::sync::Arc;
 use std::thread;
 
-use crate::coroutine_impl::{spawn, Coroutine};
+use crate::cancel::{CancelRegistration, CancellationToken};
+use crate::coroutine_impl::{current, is_coroutine, spawn, Coroutine};
 use crate::join::JoinHandle;
 use crossbeam::atomic::AtomicCell;
+use parking_lot::Mutex;
 
 /// Like `coroutine::spawn`, but without the closure bounds.
 pub unsafe fn spawn_unsafe<'a, F>(f: F) -> JoinHandle<()>
@@ -24,6 +27,8 @@ where
 
 pub struct Scope<'a> {
     dtors: RefCell<Option<DtorChain<'a>>>,
+    // the scope is created in coroutine context
+    in_coroutine: bool,
 }
 
 struct DtorChain<'a> {
@@ -68,6 +73,7 @@ where
 {
     let mut scope = Scope {
         dtors: RefCell::new(None),
+        in_coroutine: is_coroutine(),
     };
     let ret = f(&scope);
     scope.drop_all();
@@ -175,6 +181,28 @@ impl<'a> Scope<'a> {
     {
         self.spawn_impl(f)
     }
+
+    /// Create a scoped coroutine that borrows from the enclosing stack, the safe version of `spawn`.
+    ///
+    /// all the coroutines are joined before `scope` returns, even if the scope panics,
+    /// so the closure can borrow any data that outlives the scope.
+    ///
+    /// # Panics
+    ///
+    /// a coroutine's stack is swapped out while it's suspended, so the borrowed stack
+    /// data is only stable when the scope is created in thread context. this method
+    /// panics if the scope is created in a coroutine, use the unsafe `spawn` there
+    pub fn fork<F, T>(&self, f: F) -> ScopedJoinHandle<T>
+    where
+        F: FnOnce() -> T + Send + 'a,
+        T: Send + 'a,
+    {
+        assert!(
+            !self.in_coroutine,
+            "can't borrow the stack of a coroutine, create the scope in thread context"
+        );
+        self.spawn_impl(f)
+    }
 }
 
 impl<T> ScopedJoinHandle<T> {
@@ -195,3 +223,138 @@ impl<'a> Drop for Scope<'a> {
         self.drop_all()
     }
 }
+
+/// A scope that cancels all the coroutines in it once any of them fails,
+/// created by [`scope_cancel`]
+///
+/// [`scope_cancel`]: fn.scope_cancel.html
+pub struct CancelScope<'a, E> {
+    scope: Scope<'a>,
+    token: CancellationToken,
+    // the coroutines canceled with the scope
+    registrations: RefCell<Vec<CancelRegistration>>,
+    // the first error returned by the coroutines
+    error: Arc<Mutex<Option<E>>>,
+    // the first panic of the coroutines
+    panic: Arc<Mutex<Option<Box<dyn Any + Send>>>>,
+}
+
+/// Create a new cancel `scope`, like `scope` but all the coroutines spawned
+/// in it are canceled once any of them returns an error or panics,
+/// or `CancelScope::cancel` is called.
+///
+/// the coroutines are canceled by their cancellation token, so they can
+/// unwind cleanly. the first error is returned after all the coroutines
+/// are finished, and the first panic is propagated to the caller.
+///
+/// when called in a coroutine, the scope is also canceled with the
+/// cancellation token of the current coroutine
+pub fn scope_cancel<'a, F, R, E>(f: F) -> Result<R, E>
+where
+    F: FnOnce(&CancelScope<'a, E>) -> R,
+    E: Send + 'a,
+{
+    let token = if is_coroutine() {
+        current().cancel_token().child_token()
+    } else {
+        CancellationToken::new()
+    };
+    let mut scope = CancelScope {
+        scope: Scope {
+            dtors: RefCell::new(None),
+            in_coroutine: is_coroutine(),
+        },
+        token,
+        registrations: RefCell::new(Vec::new()),
+        error: Arc::new(Mutex::new(None)),
+        panic: Arc::new(Mutex::new(None)),
+    };
+    let ret = f(&scope);
+    scope.scope.drop_all();
+    // the scope is over, don't leave the callback on the parent token even
+    // if a clone of the scope token is still alive
+    scope.token.detach();
+    if let Some(p) = scope.panic.lock().take() {
+        panic::resume_unwind(p);
+    }
+    let err = scope.error.lock().take();
+    match err {
+        Some(e) => Err(e),
+        None => Ok(ret),
+    }
+}
+
+impl<'a, E> fmt::Debug for CancelScope<'a, E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "CancelScope {{ ... }}")
+    }
+}
+
+impl<'a, E: Send + 'a> CancelScope<'a, E> {
+    /// Create a scoped coroutine that can fail.
+    ///
+    /// an error or a panic of the coroutine cancels all the other coroutines in the scope
+    pub unsafe fn spawn<F>(&self, f: F) -> Coroutine
+    where
+        F: FnOnce() -> Result<(), E> + Send + 'a,
+    {
+        let token = self.token.clone();
+        let error = self.error.clone();
+        let panic = self.panic.clone();
+        let handle = self.scope.spawn_impl(move || {
+            match panic::catch_unwind(panic::AssertUnwindSafe(f)) {
+                Ok(Ok(())) => return,
+                Ok(Err(e)) => {
+                    let mut error = error.lock();
+                    if error.is_none() {
+                        *error = Some(e);
+                    }
+                }
+                Err(p) => {
+                    let mut panic = panic.lock();
+                    if panic.is_none() {
+                        *panic = Some(p);
+                    }
+                }
+            }
+            token.cancel();
+        });
+        // cancel the coroutine with the scope
+        let co_token = handle.coroutine().cancel_token();
+        let registration = self.token.on_cancel(move || co_token.cancel());
+        self.registrations.borrow_mut().push(registration);
+        handle.co
+    }
+
+    /// Schedule code to be executed when exiting the scope.
+    pub fn defer<F>(&self, f: F)
+    where
+        F: FnOnce() + 'a,
+    {
+        self.scope.defer(f)
+    }
+
+    /// cancel all the coroutines in the scope
+    pub fn cancel(&self) {
+        self.token.cancel();
+    }
+
+    /// return true if the scope is canceled
+    pub fn is_canceled(&self) -> bool {
+        self.token.is_canceled()
+    }
+
+    /// the cancellation token of the scope
+    pub fn token(&self) -> &CancellationToken {
+        &self.token
+    }
+}
+
+impl<'a, E> Drop for CancelScope<'a, E> {
+    fn drop(&mut self) {
+        // the scope body panics, no need to wait the coroutines
+        if thread::panicking() {
+            self.token.cancel();
+        }
+    }
+}
@@ -1,14 +1,22 @@
 use crate::std::sync::AtomicOption;
 use std::sync::Arc;
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
-use crate::coroutine_impl::{co_cancel_data, is_coroutine, CoroutineImpl, EventSource};
+use crate::coroutine_impl::{
+    co_cancel_data, current_cancel_data, is_coroutine, CoroutineImpl, EventSource,
+};
+use crate::registry::State;
 use crate::scheduler::get_scheduler;
 use crate::yield_now::{get_co_para, yield_with};
 
+enum Wake {
+    After(Duration),
+    At(Instant),
+}
+
 struct Sleep {
-    dur: Duration,
+    wake: Wake,
 }
 
 impl EventSource for Sleep {
@@ -17,15 +25,24 @@ impl EventSource for Sleep {
         let cancel = co_cancel_data(&co);
         // put the coroutine into the timer list
         let sleep_co = Arc::new(AtomicOption::some(co));
-        get_scheduler().add_timer(self.dur, sleep_co.clone());
+        match self.wake {
+            Wake::After(dur) => get_scheduler().add_timer(dur, sleep_co.clone()),
+            Wake::At(deadline) => get_scheduler().add_timer_at(deadline, sleep_co.clone()),
+        };
 
         // register the cancel data
         cancel.set_co(sleep_co);
         // re-check the cancel status
         if cancel.is_canceled() {
             let _ = cancel.cancel();
+        } else if cancel.is_interrupted() {
+            cancel.interrupt();
         }
     }
+
+    fn state(&self) -> State {
+        State::Sleeping
+    }
 }
 
 /// block the current coroutine until timeout
@@ -33,7 +50,24 @@ pub fn sleep(dur: Duration) {
     if !is_coroutine() {
         return thread::sleep(dur);
     }
-    let sleeper = Sleep { dur };
+    sleep_impl(Wake::After(dur));
+}
+
+/// block the current coroutine until the `deadline`
+///
+/// returns soon if the deadline has passed, handy for the code that works
+/// against one deadline across many steps
+pub fn sleep_until(deadline: Instant) {
+    if !is_coroutine() {
+        return thread::sleep(deadline.saturating_duration_since(Instant::now()));
+    }
+    sleep_impl(Wake::At(deadline));
+}
+
+fn sleep_impl(wake: Wake) {
+    // return early if the cancellation token is canceled
+    let _g = current_cancel_data().interruptible();
+    let sleeper = Sleep { wake };
     yield_with(&sleeper);
     // consume the timeout error
     get_co_para();
@@ -1,8 +1,6 @@
 use crate::std::errors::Result;
-use crate::std::sync::channel;
-use std::panic::set_hook;
 
-/// will spawn a thread to doing and return value by channel
+/// run the task on the blocking thread pool and wait for the return value
 /// for example:
 /// ```rust
 ///     let v = mco::spawn_blocking!(|| {
@@ -22,7 +20,7 @@ macro_rules! spawn_blocking {
     };
 }
 
-/// will spawn a thread to doing and return value by channel
+/// run the task on the blocking thread pool and wait for the return value
 /// for example:
 /// ```rust
 ///     let v = mco::std::blocking::spawn_blocking(|| {
@@ -37,22 +35,16 @@ where
     F: Send + 'static,
     T: Send + 'static,
 {
-    let (s, r) = channel::<Result<T>>();
-    std::thread::Builder::new().spawn(move || {
-        let send_e = s.clone();
-        set_hook(Box::new(move |panic_info| {
-            let e = err!(
-                "{}",
-                panic_info
-                    .payload()
-                    .downcast_ref::<&str>()
-                    .unwrap_or(&"spawn_blocking panic!")
-            );
-            let _ = send_e.send(Err(e));
-        }));
-        let _ = s.send(Ok(f()));
-    })?;
-    return r.recv()?;
+    crate::coroutine::spawn_blocking(f).join().map_err(|panic| {
+        let msg = match panic.downcast_ref::<&str>() {
+            Some(e) => e.to_string(),
+            None => match panic.downcast_ref::<String>() {
+                Some(e) => e.clone(),
+                None => "spawn_blocking panic!".to_string(),
+            },
+        };
+        err!("{}", msg)
+    })
 }
 
 #[cfg(test)]
@@ -0,0 +1,469 @@
+//! Go like context
+//!
+//! a `Context` carries the cancellation, the deadline and the request scoped
+//! values across the api boundaries and the coroutines. the contexts form a
+//! tree, canceling a context cancels all the contexts derived from it.
+//!
+//! each coroutine has a current context, which is inherited from the spawner
+//! by default, so the deadline and the cancellation follow the call tree
+//! without passing the context through every closure.
+//!
+//! once the current context is done, the blocking ops of the coroutine return
+//! early, like when the cancellation token of the coroutine is canceled.
+//! the socket reads, writes and connects return an `io::Error` made from the
+//! `ContextError`, `sleep` just returns, the channel `send` and `recv` and
+//! `Mutex::lock_interruptible` return a `Canceled` error, `Context::err` tells
+//! the reason. the plain `Mutex::lock` doesn't observe the context. setting
+//! a context that is not done clears the interruption.
+//!
+//! # Examples
+//!
+//! ```
+//! use mco::co;
+//! use mco::std::context::{Context, ContextError};
+//! use std::time::Duration;
+//!
+//! let ctx = Context::background().with_value("request_id", 42u64);
+//! let (ctx, cancel) = ctx.with_timeout(Duration::from_secs(10));
+//!
+//! let child = ctx.clone();
+//! let h = co!(move || {
+//!     // `done` can also be used as an arm of `select!`
+//!     let _ = child.done().recv();
+//!     assert_eq!(child.value::<u64>("request_id"), Some(&42));
+//!     child.err()
+//! });
+//! cancel.cancel();
+//! assert_eq!(h.join().unwrap(), Some(ContextError::Canceled));
+//! ```
+
+use std::any::Any;
+use std::fmt;
+use std::io;
+use std::sync::{Arc, Weak};
+use std::time::{Duration, Instant};
+
+use crate::cancel::{CancelTarget, CancellationToken};
+use crate::coroutine_impl::{current_cancel_data, try_current};
+use crate::local::{current_context, set_current_context};
+use crate::std::sync::channel::{Receiver, Sender};
+use crate::std::time::Timer;
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+
+/// The reason why a context is done
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ContextError {
+    /// the context is canceled by the `CancelFunc`
+    Canceled,
+    /// the deadline of the context is passed
+    DeadlineExceeded,
+}
+
+impl fmt::Display for ContextError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ContextError::Canceled => f.write_str("context canceled"),
+            ContextError::DeadlineExceeded => f.write_str("context deadline exceeded"),
+        }
+    }
+}
+
+impl std::error::Error for ContextError {}
+
+impl From<ContextError> for io::Error {
+    fn from(e: ContextError) -> Self {
+        match e {
+            ContextError::Canceled => io::Error::other(e),
+            ContextError::DeadlineExceeded => io::Error::new(io::ErrorKind::TimedOut, e),
+        }
+    }
+}
+
+// the done channel of the contexts that are never canceled
+static NEVER: Lazy<(Sender<()>, Receiver<()>)> = Lazy::new(|| chan!(1));
+
+static BACKGROUND: Lazy<Context> = Lazy::new(|| Context {
+    inner: Arc::new(Inner {
+        parent: None,
+        cancel: None,
+        deadline: None,
+        value: None,
+    }),
+});
+
+struct CancelState {
+    // the parent to get the reason from when the parent is canceled
+    parent: Option<Weak<CancelState>>,
+    token: CancellationToken,
+    err: Mutex<Option<ContextError>>,
+    // closed when the context is done
+    done: (Sender<()>, Receiver<()>),
+    // the deadline timer, stopped when the context is done
+    timer: Mutex<Option<Timer>>,
+}
+
+impl CancelState {
+    fn new(parent: Option<Weak<CancelState>>) -> Arc<Self> {
+        Arc::new(CancelState {
+            parent,
+            token: CancellationToken::new(),
+            err: Mutex::new(None),
+            done: chan!(1),
+            timer: Mutex::new(None),
+        })
+    }
+
+    fn cancel(&self, err: ContextError) {
+        {
+            let mut e = self.err.lock();
+            if e.is_some() {
+                return;
+            }
+            *e = Some(err);
+        }
+        self.done.0.close();
+        // the children are canceled by the token callbacks
+        self.token.cancel();
+        drop(self.timer.lock().take());
+    }
+}
+
+// the parent is done, cancel the child with the same reason
+impl CancelTarget for CancelState {
+    fn on_cancel(&self) {
+        let parent = self.parent.as_ref().and_then(Weak::upgrade);
+        let err = parent.and_then(|p| *p.err.lock());
+        self.cancel(err.unwrap_or(ContextError::Canceled));
+    }
+}
+
+struct Inner {
+    parent: Option<Context>,
+    // the nearest cancelable state, `None` if the context is never canceled
+    cancel: Option<Arc<CancelState>>,
+    deadline: Option<Instant>,
+    value: Option<(&'static str, Box<dyn Any + Send + Sync>)>,
+}
+
+/// A context that carries the cancellation, the deadline and the values
+///
+/// cloning the context is cheap, the clones share the same state
+#[derive(Clone)]
+pub struct Context {
+    inner: Arc<Inner>,
+}
+
+impl Context {
+    /// the root context that is never canceled, has no deadline and no values
+    pub fn background() -> Context {
+        BACKGROUND.clone()
+    }
+
+    /// the context of the current coroutine or thread
+    ///
+    /// the background context if it's never set
+    pub fn current() -> Context {
+        current_context()
+    }
+
+    /// make the context the current one of the coroutine or thread, return
+    /// the old one
+    ///
+    /// the coroutines spawned later inherit the context
+    pub fn set_current(&self) -> Context {
+        if let Ok(co) = try_current() {
+            co.set_context_token(self.token());
+        }
+        set_current_context(self.clone())
+    }
+
+    /// derive a context that is canceled by the returned `CancelFunc`
+    ///
+    /// the context is also canceled when the parent is done
+    pub fn with_cancel(&self) -> (Context, CancelFunc) {
+        self.with_cancel_state(self.inner.deadline)
+    }
+
+    /// derive a context that is canceled after `dur`
+    pub fn with_timeout(&self, dur: Duration) -> (Context, CancelFunc) {
+        self.with_deadline(Instant::now() + dur)
+    }
+
+    /// derive a context that is canceled at the `deadline`
+    ///
+    /// the parent deadline is kept if it's earlier
+    pub fn with_deadline(&self, deadline: Instant) -> (Context, CancelFunc) {
+        if self.inner.deadline.is_some_and(|d| d <= deadline) {
+            return self.with_cancel();
+        }
+        let (ctx, cancel) = self.with_cancel_state(Some(deadline));
+        let dur = deadline.saturating_duration_since(Instant::now());
+        if dur == Duration::ZERO {
+            cancel.state.cancel(ContextError::DeadlineExceeded);
+            return (ctx, cancel);
+        }
+        let weak = Arc::downgrade(&cancel.state);
+        let timer = Timer::new(dur, move || {
+            if let Some(state) = weak.upgrade() {
+                state.cancel(ContextError::DeadlineExceeded);
+            }
+        });
+        let mut slot = cancel.state.timer.lock();
+        // the parent may cancel the context in the meantime
+        if cancel.state.err.lock().is_none() {
+            *slot = Some(timer);
+        }
+        drop(slot);
+        (ctx, cancel)
+    }
+
+    /// derive a context that carries the `value` for the `key`
+    pub fn with_value<T: Any + Send + Sync>(&self, key: &'static str, value: T) -> Context {
+        Context {
+            inner: Arc::new(Inner {
+                parent: Some(self.clone()),
+                cancel: self.inner.cancel.clone(),
+                deadline: self.inner.deadline,
+                value: Some((key, Box::new(value))),
+            }),
+        }
+    }
+
+    /// get the value for the `key` from the context or its ancestors
+    ///
+    /// returns `None` if the key is not found or the value is not a `T`
+    pub fn value<T: Any>(&self, key: &str) -> Option<&T> {
+        let mut ctx = self;
+        loop {
+            if let Some((k, v)) = &ctx.inner.value {
+                if *k == key {
+                    return v.downcast_ref();
+                }
+            }
+            ctx = ctx.inner.parent.as_ref()?;
+        }
+    }
+
+    /// the time when the context will be canceled, if any
+    pub fn deadline(&self) -> Option<Instant> {
+        self.inner.deadline
+    }
+
+    /// returns a channel that is closed when the context is done
+    ///
+    /// the `recv` returns an error once the context is canceled or the
+    /// deadline is passed, so it can be used as an arm of `select!`
+    pub fn done(&self) -> Receiver<()> {
+        match &self.inner.cancel {
+            Some(state) => state.done.1.clone(),
+            None => NEVER.1.clone(),
+        }
+    }
+
+    /// the reason why the context is done, `None` if it's not done yet
+    pub fn err(&self) -> Option<ContextError> {
+        *self.inner.cancel.as_ref()?.err.lock()
+    }
+
+    /// return true if the context is canceled or the deadline is passed
+    pub fn is_done(&self) -> bool {
+        self.err().is_some()
+    }
+
+    // the token that is canceled when the context is done
+    pub(crate) fn token(&self) -> Option<&CancellationToken> {
+        self.inner.cancel.as_ref().map(|s| &s.token)
+    }
+
+    fn with_cancel_state(&self, deadline: Option<Instant>) -> (Context, CancelFunc) {
+        let parent = self.inner.cancel.as_ref();
+        let state = CancelState::new(parent.map(Arc::downgrade));
+        if let Some(parent) = parent {
+            parent
+                .token
+                .add_target(Arc::downgrade(&state) as Weak<dyn CancelTarget>);
+        }
+        let ctx = Context {
+            inner: Arc::new(Inner {
+                parent: Some(self.clone()),
+                cancel: Some(state.clone()),
+                deadline,
+                value: None,
+            }),
+        };
+        (ctx, CancelFunc { state })
+    }
+}
+
+// the interrupted io op of the current coroutine returns the reason of the
+// context instead of the `Canceled` error of the cancellation token
+pub(crate) fn interrupted_error(err: io::Error) -> io::Error {
+    if err.kind() != io::ErrorKind::Other || !current_cancel_data().is_interrupted() {
+        return err;
+    }
+    match Context::current().err() {
+        Some(e) => e.into(),
+        None => err,
+    }
+}
+
+impl Default for Context {
+    fn default() -> Self {
+        Context::background()
+    }
+}
+
+impl fmt::Debug for Context {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Context")
+            .field("deadline", &self.inner.deadline)
+            .field("err", &self.err())
+            .finish()
+    }
+}
+
+/// Cancels the context returned by `with_cancel`, `with_timeout` or `with_deadline`
+///
+/// the contexts derived from the context are canceled as well, the later
+/// calls do nothing
+#[derive(Clone)]
+pub struct CancelFunc {
+    state: Arc<CancelState>,
+}
+
+impl CancelFunc {
+    pub fn cancel(&self) {
+        self.state.cancel(ContextError::Canceled);
+    }
+}
+
+impl fmt::Debug for CancelFunc {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CancelFunc").finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cancel_propagates_to_children() {
+        let (parent, cancel) = Context::background().with_cancel();
+        let (child, _) = parent.with_value("k", 1i32).with_cancel();
+        let (other, cancel_other) = parent.with_cancel();
+        assert_eq!(child.value::<i32>("k"), Some(&1));
+        assert_eq!(child.value::<u8>("k"), None);
+
+        cancel_other.cancel();
+        assert_eq!(other.err(), Some(ContextError::Canceled));
+        assert!(!parent.is_done());
+
+        cancel.cancel();
+        assert_eq!(parent.err(), Some(ContextError::Canceled));
+        assert_eq!(child.err(), Some(ContextError::Canceled));
+        assert!(child.done().recv().is_err());
+        // derived from a done context
+        assert!(parent.with_cancel().0.is_done());
+        assert!(!Context::background().is_done());
+    }
+
+    #[test]
+    fn deadline_exceeded() {
+        let (parent, _) = Context::background().with_timeout(Duration::from_millis(50));
+        // the earlier parent deadline is kept
+        let (child, cancel) = parent.with_timeout(Duration::from_secs(60));
+        assert_eq!(child.deadline(), parent.deadline());
+        assert!(child.done().recv_timeout(Duration::from_secs(5)).is_err());
+        assert_eq!(parent.err(), Some(ContextError::DeadlineExceeded));
+        assert_eq!(child.err(), Some(ContextError::DeadlineExceeded));
+        // canceled already
+        cancel.cancel();
+        assert_eq!(child.err(), Some(ContextError::DeadlineExceeded));
+
+        let (ctx, _) = Context::background().with_deadline(Instant::now());
+        assert_eq!(ctx.err(), Some(ContextError::DeadlineExceeded));
+    }
+
+    #[test]
+    fn spawn_inherits_context() {
+        let (ctx, cancel) = Context::background().with_value("k", 1u32).with_cancel();
+        let old = ctx.set_current();
+        let h = co!(move || {
+            cancel.cancel();
+            let ctx = Context::current();
+            (ctx.value::<u32>("k").copied(), ctx.is_done())
+        });
+        assert_eq!(h.join().unwrap(), (Some(1), true));
+
+        let h = crate::coroutine::spawn_with_ctx(Context::background(), || {
+            Context::current().value::<u32>("k").copied()
+        });
+        assert_eq!(h.join().unwrap(), None);
+        old.set_current();
+        assert!(Context::current().value::<u32>("k").is_none());
+    }
+
+    #[test]
+    fn blocking_ops_observe_context() {
+        use crate::net::TcpStream;
+        use crate::std::sync::{
+            LockError, Mutex, RecvError, RecvTimeoutError, SendError, SendTimeoutError,
+        };
+        use std::io::Read;
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let h = crate::coroutine::spawn_with_ctx(Context::background(), move || {
+            let mut conn = TcpStream::connect(addr).unwrap();
+            let start = Instant::now();
+            let (ctx, _) = Context::current().with_timeout(Duration::from_millis(50));
+            let old = ctx.set_current();
+            crate::coroutine::sleep(Duration::from_secs(10));
+            assert_eq!(
+                Context::current().err(),
+                Some(ContextError::DeadlineExceeded)
+            );
+
+            let (s, r) = chan!(i32, 1);
+            s.send(1).unwrap();
+            assert_eq!(s.send(2), Err(SendError::Canceled(2)));
+            assert_eq!(
+                s.send_timeout(2, Duration::from_secs(10)),
+                Err(SendTimeoutError::Canceled(2))
+            );
+            let (_s, r2) = chan!(i32, 1);
+            assert_eq!(r2.recv(), Err(RecvError::Canceled));
+            assert_eq!(
+                r2.recv_timeout(Duration::from_secs(10)),
+                Err(RecvTimeoutError::Canceled)
+            );
+
+            let m = Mutex::new(0);
+            let g = m.lock().unwrap();
+            assert!(matches!(
+                m.lock_interruptible(None),
+                Err(LockError::Canceled)
+            ));
+            drop(g);
+
+            let err = conn.read(&mut [0; 8]).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+            let elapsed = start.elapsed();
+
+            // the restored context doesn't interrupt the ops any more
+            old.set_current();
+            let now = Instant::now();
+            crate::coroutine::sleep(Duration::from_millis(20));
+            assert!(now.elapsed() >= Duration::from_millis(20));
+            assert_eq!(
+                r2.recv_timeout(Duration::from_millis(20)),
+                Err(RecvTimeoutError::Timeout)
+            );
+            drop(r);
+            elapsed
+        });
+        assert!(h.join().unwrap() < Duration::from_secs(5));
+    }
+}
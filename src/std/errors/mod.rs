@@ -129,3 +129,15 @@ impl<T> From<std::sync::mpsc::SendError<T>> for Error {
         return new(e.to_string());
     }
 }
+
+impl From<crate::std::sync::channel::RecvError> for Error {
+    fn from(e: crate::std::sync::channel::RecvError) -> Self {
+        new(e.to_string())
+    }
+}
+
+impl<T> From<crate::std::sync::channel::SendError<T>> for Error {
+    fn from(e: crate::std::sync::channel::SendError<T>) -> Self {
+        new(e.to_string())
+    }
+}
@@ -0,0 +1,535 @@
+//! Filesystem manipulation operations for the coroutines
+//!
+//! the file apis of the os are blocking, calling them in a coroutine stalls
+//! the worker thread and all the coroutines scheduled on it. this module
+//! mirrors `std::fs`, in coroutine context each call is run on the blocking
+//! thread pool and only parks the calling coroutine, in thread context the
+//! call is made directly.
+//!
+//! # Examples
+//!
+//! ```
+//! use mco::std::fs::{self, File};
+//! use std::io::{Read, Write};
+//!
+//! let path = std::env::temp_dir().join("mco_fs_doc.txt");
+//! let h = mco::co!(move || {
+//!     let mut f = File::create(&path).unwrap();
+//!     f.write_all(b"hello mco").unwrap();
+//!     drop(f);
+//!
+//!     let mut s = String::new();
+//!     File::open(&path).unwrap().read_to_string(&mut s).unwrap();
+//!     fs::remove_file(&path).unwrap();
+//!     s
+//! });
+//! assert_eq!(h.join().unwrap(), "hello mco");
+//! ```
+
+use crate::coroutine_impl::is_coroutine;
+use std::cmp;
+use std::collections::VecDeque;
+use std::ffi::OsString;
+use std::fmt;
+use std::fs::{FileType, Metadata, Permissions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::panic;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+// the max bytes that one `read` or `write` moves through the blocking pool
+const MAX_BUF: usize = 2 * 1024 * 1024;
+// the number of the directory entries read in one trip to the blocking pool
+const DIR_BATCH: usize = 32;
+
+// run the blocking file op without stalling the worker thread
+fn asyncify<F, T>(f: F) -> io::Result<T>
+where
+    F: FnOnce() -> io::Result<T> + Send + 'static,
+    T: Send + 'static,
+{
+    if !is_coroutine() {
+        return f();
+    }
+    match crate::coroutine::spawn_blocking(f).join() {
+        Ok(ret) => ret,
+        Err(panic) => panic::resume_unwind(panic),
+    }
+}
+
+/// A reference to an open file on the filesystem
+///
+/// same as `std::fs::File` except that the ops don't block the worker thread,
+/// each read or write is one trip to the blocking thread pool, so prefer
+/// the big buffers or `read_to_end`/`write_all` over many small calls
+pub struct File {
+    inner: Arc<std::fs::File>,
+}
+
+impl File {
+    /// open a file in read-only mode
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<File> {
+        OpenOptions::new().read(true).open(path)
+    }
+
+    /// open a file in write-only mode, create it if it doesn't exist
+    /// and truncate it if it does
+    pub fn create<P: AsRef<Path>>(path: P) -> io::Result<File> {
+        OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)
+    }
+
+    pub fn options() -> OpenOptions {
+        OpenOptions::new()
+    }
+
+    /// wrap an opened `std::fs::File`
+    pub fn from_std(file: std::fs::File) -> File {
+        File {
+            inner: Arc::new(file),
+        }
+    }
+
+    /// unwrap the `std::fs::File`
+    pub fn into_std(self) -> std::fs::File {
+        // the ops wait for the blocking tasks, no other reference is alive
+        Arc::try_unwrap(self.inner).expect("the file is still in use")
+    }
+
+    pub fn metadata(&self) -> io::Result<Metadata> {
+        self.spawn(|f| f.metadata())
+    }
+
+    /// flush the data and the metadata to the disk
+    pub fn sync_all(&self) -> io::Result<()> {
+        self.spawn(|f| f.sync_all())
+    }
+
+    /// flush the data to the disk, the metadata may not be synced
+    pub fn sync_data(&self) -> io::Result<()> {
+        self.spawn(|f| f.sync_data())
+    }
+
+    /// truncate or extend the file to `size` bytes
+    pub fn set_len(&self, size: u64) -> io::Result<()> {
+        self.spawn(move |f| f.set_len(size))
+    }
+
+    pub fn set_permissions(&self, perm: Permissions) -> io::Result<()> {
+        self.spawn(move |f| f.set_permissions(perm))
+    }
+
+    /// create a new `File` that shares the same underlying file handle
+    pub fn try_clone(&self) -> io::Result<File> {
+        self.spawn(|f| f.try_clone()).map(File::from_std)
+    }
+
+    fn spawn<F, T>(&self, f: F) -> io::Result<T>
+    where
+        F: FnOnce(&std::fs::File) -> io::Result<T> + Send + 'static,
+        T: Send + 'static,
+    {
+        let file = self.inner.clone();
+        asyncify(move || f(&file))
+    }
+}
+
+impl Read for &File {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if !is_coroutine() {
+            return (&*self.inner).read(buf);
+        }
+        let len = cmp::min(buf.len(), MAX_BUF);
+        let data = self.spawn(move |mut f| {
+            let mut data = vec![0; len];
+            let n = f.read(&mut data)?;
+            data.truncate(n);
+            Ok(data)
+        })?;
+        buf[..data.len()].copy_from_slice(&data);
+        Ok(data.len())
+    }
+
+    fn read_to_end(&mut self, buf: &mut Vec<u8>) -> io::Result<usize> {
+        let data = self.spawn(|mut f| {
+            let mut data = Vec::new();
+            f.read_to_end(&mut data)?;
+            Ok(data)
+        })?;
+        buf.extend_from_slice(&data);
+        Ok(data.len())
+    }
+
+    fn read_to_string(&mut self, buf: &mut String) -> io::Result<usize> {
+        let data = self.spawn(|mut f| {
+            let mut data = String::new();
+            f.read_to_string(&mut data)?;
+            Ok(data)
+        })?;
+        buf.push_str(&data);
+        Ok(data.len())
+    }
+}
+
+impl Write for &File {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if !is_coroutine() {
+            return (&*self.inner).write(buf);
+        }
+        let data = buf[..cmp::min(buf.len(), MAX_BUF)].to_vec();
+        self.spawn(move |mut f| f.write(&data))
+    }
+
+    fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+        if !is_coroutine() {
+            return (&*self.inner).write_all(buf);
+        }
+        let data = buf.to_vec();
+        self.spawn(move |mut f| f.write_all(&data))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.spawn(|mut f| f.flush())
+    }
+}
+
+impl Seek for &File {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.spawn(move |mut f| f.seek(pos))
+    }
+}
+
+impl Read for File {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        (&*self).read(buf)
+    }
+
+    fn read_to_end(&mut self, buf: &mut Vec<u8>) -> io::Result<usize> {
+        (&*self).read_to_end(buf)
+    }
+
+    fn read_to_string(&mut self, buf: &mut String) -> io::Result<usize> {
+        (&*self).read_to_string(buf)
+    }
+}
+
+impl Write for File {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        (&*self).write(buf)
+    }
+
+    fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+        (&*self).write_all(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        (&*self).flush()
+    }
+}
+
+impl Seek for File {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        (&*self).seek(pos)
+    }
+}
+
+impl From<std::fs::File> for File {
+    fn from(file: std::fs::File) -> File {
+        File::from_std(file)
+    }
+}
+
+impl fmt::Debug for File {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.inner.fmt(f)
+    }
+}
+
+#[cfg(unix)]
+impl std::os::unix::io::AsRawFd for File {
+    fn as_raw_fd(&self) -> std::os::unix::io::RawFd {
+        self.inner.as_raw_fd()
+    }
+}
+
+#[cfg(windows)]
+impl std::os::windows::io::AsRawHandle for File {
+    fn as_raw_handle(&self) -> std::os::windows::io::RawHandle {
+        self.inner.as_raw_handle()
+    }
+}
+
+/// Options and flags to configure how a file is opened
+///
+/// same as `std::fs::OpenOptions` except that `open` returns a mco `File`
+#[derive(Clone, Debug)]
+pub struct OpenOptions(std::fs::OpenOptions);
+
+impl OpenOptions {
+    pub fn new() -> Self {
+        OpenOptions(std::fs::OpenOptions::new())
+    }
+
+    pub fn read(&mut self, read: bool) -> &mut Self {
+        self.0.read(read);
+        self
+    }
+
+    pub fn write(&mut self, write: bool) -> &mut Self {
+        self.0.write(write);
+        self
+    }
+
+    pub fn append(&mut self, append: bool) -> &mut Self {
+        self.0.append(append);
+        self
+    }
+
+    pub fn truncate(&mut self, truncate: bool) -> &mut Self {
+        self.0.truncate(truncate);
+        self
+    }
+
+    pub fn create(&mut self, create: bool) -> &mut Self {
+        self.0.create(create);
+        self
+    }
+
+    pub fn create_new(&mut self, create_new: bool) -> &mut Self {
+        self.0.create_new(create_new);
+        self
+    }
+
+    pub fn open<P: AsRef<Path>>(&self, path: P) -> io::Result<File> {
+        let opts = self.0.clone();
+        let path = path.as_ref().to_owned();
+        asyncify(move || opts.open(path)).map(File::from_std)
+    }
+}
+
+impl Default for OpenOptions {
+    fn default() -> Self {
+        OpenOptions::new()
+    }
+}
+
+/// read the entire contents of a file into a bytes vector
+pub fn read<P: AsRef<Path>>(path: P) -> io::Result<Vec<u8>> {
+    let path = path.as_ref().to_owned();
+    asyncify(move || std::fs::read(path))
+}
+
+/// read the entire contents of a file into a string
+pub fn read_to_string<P: AsRef<Path>>(path: P) -> io::Result<String> {
+    let path = path.as_ref().to_owned();
+    asyncify(move || std::fs::read_to_string(path))
+}
+
+/// write a slice as the entire contents of a file
+pub fn write<P: AsRef<Path>, C: AsRef<[u8]>>(path: P, contents: C) -> io::Result<()> {
+    let path = path.as_ref().to_owned();
+    let contents = contents.as_ref().to_owned();
+    asyncify(move || std::fs::write(path, contents))
+}
+
+pub fn metadata<P: AsRef<Path>>(path: P) -> io::Result<Metadata> {
+    let path = path.as_ref().to_owned();
+    asyncify(move || std::fs::metadata(path))
+}
+
+pub fn canonicalize<P: AsRef<Path>>(path: P) -> io::Result<PathBuf> {
+    let path = path.as_ref().to_owned();
+    asyncify(move || std::fs::canonicalize(path))
+}
+
+/// copy the contents and the permissions of one file to another,
+/// returns the number of the bytes copied
+pub fn copy<P: AsRef<Path>, Q: AsRef<Path>>(from: P, to: Q) -> io::Result<u64> {
+    let (from, to) = (from.as_ref().to_owned(), to.as_ref().to_owned());
+    asyncify(move || std::fs::copy(from, to))
+}
+
+pub fn rename<P: AsRef<Path>, Q: AsRef<Path>>(from: P, to: Q) -> io::Result<()> {
+    let (from, to) = (from.as_ref().to_owned(), to.as_ref().to_owned());
+    asyncify(move || std::fs::rename(from, to))
+}
+
+pub fn remove_file<P: AsRef<Path>>(path: P) -> io::Result<()> {
+    let path = path.as_ref().to_owned();
+    asyncify(move || std::fs::remove_file(path))
+}
+
+pub fn create_dir<P: AsRef<Path>>(path: P) -> io::Result<()> {
+    let path = path.as_ref().to_owned();
+    asyncify(move || std::fs::create_dir(path))
+}
+
+pub fn create_dir_all<P: AsRef<Path>>(path: P) -> io::Result<()> {
+    let path = path.as_ref().to_owned();
+    asyncify(move || std::fs::create_dir_all(path))
+}
+
+pub fn remove_dir<P: AsRef<Path>>(path: P) -> io::Result<()> {
+    let path = path.as_ref().to_owned();
+    asyncify(move || std::fs::remove_dir(path))
+}
+
+pub fn remove_dir_all<P: AsRef<Path>>(path: P) -> io::Result<()> {
+    let path = path.as_ref().to_owned();
+    asyncify(move || std::fs::remove_dir_all(path))
+}
+
+/// return an iterator over the entries in a directory
+pub fn read_dir<P: AsRef<Path>>(path: P) -> io::Result<ReadDir> {
+    let path = path.as_ref().to_owned();
+    let inner = asyncify(move || std::fs::read_dir(path))?;
+    Ok(ReadDir {
+        inner: Some(inner),
+        buf: VecDeque::new(),
+    })
+}
+
+/// Iterator over the entries in a directory, returned by `read_dir`
+///
+/// same as `std::fs::ReadDir` except that the entries are read in batches
+/// on the blocking thread pool
+pub struct ReadDir {
+    // taken while a batch is read, `None` once all the entries are read
+    inner: Option<std::fs::ReadDir>,
+    buf: VecDeque<io::Result<std::fs::DirEntry>>,
+}
+
+impl Iterator for ReadDir {
+    type Item = io::Result<DirEntry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.buf.is_empty() {
+            let mut inner = self.inner.take()?;
+            let batch = asyncify(move || {
+                let buf: VecDeque<_> = inner.by_ref().take(DIR_BATCH).collect();
+                let more = buf.len() == DIR_BATCH;
+                Ok((buf, if more { Some(inner) } else { None }))
+            });
+            let (buf, inner) = match batch {
+                Ok(batch) => batch,
+                Err(e) => return Some(Err(e)),
+            };
+            self.buf = buf;
+            self.inner = inner;
+        }
+        let entry = self.buf.pop_front()?;
+        Some(entry.map(|e| DirEntry { inner: Arc::new(e) }))
+    }
+}
+
+impl fmt::Debug for ReadDir {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.pad("ReadDir { .. }")
+    }
+}
+
+/// An entry returned by the `ReadDir` iterator
+pub struct DirEntry {
+    inner: Arc<std::fs::DirEntry>,
+}
+
+impl DirEntry {
+    /// the full path of the entry
+    pub fn path(&self) -> PathBuf {
+        self.inner.path()
+    }
+
+    /// the file name of the entry without the leading path
+    pub fn file_name(&self) -> OsString {
+        self.inner.file_name()
+    }
+
+    pub fn metadata(&self) -> io::Result<Metadata> {
+        let entry = self.inner.clone();
+        asyncify(move || entry.metadata())
+    }
+
+    pub fn file_type(&self) -> io::Result<FileType> {
+        let entry = self.inner.clone();
+        asyncify(move || entry.file_type())
+    }
+}
+
+impl fmt::Debug for DirEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.inner.fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("mco_fs_{}_{}", std::process::id(), name))
+    }
+
+    #[test]
+    fn file_ops_in_thread() {
+        let path = temp_path("thread");
+        write(&path, b"hello").unwrap();
+        let mut f = OpenOptions::new().append(true).open(&path).unwrap();
+        f.write_all(b" world").unwrap();
+        assert_eq!(f.metadata().unwrap().len(), 11);
+        drop(f);
+        assert_eq!(read_to_string(&path).unwrap(), "hello world");
+        remove_file(&path).unwrap();
+        assert!(metadata(&path).is_err());
+    }
+
+    #[test]
+    fn read_dir_entries() {
+        let dir = temp_path("dir");
+        create_dir_all(&dir).unwrap();
+        // more than one batch
+        for i in 0..DIR_BATCH + 2 {
+            write(dir.join(i.to_string()), b"").unwrap();
+        }
+        let h = co!(move || {
+            let mut names: Vec<usize> = read_dir(&dir)
+                .unwrap()
+                .map(|e| {
+                    let e = e.unwrap();
+                    assert!(e.file_type().unwrap().is_file());
+                    e.file_name().to_str().unwrap().parse().unwrap()
+                })
+                .collect();
+            names.sort_unstable();
+            remove_dir_all(&dir).unwrap();
+            names
+        });
+        assert_eq!(h.join().unwrap(), (0..DIR_BATCH + 2).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn file_ops_in_coroutine() {
+        let path = temp_path("co");
+        let h = co!(move || {
+            let mut f = File::create(&path).unwrap();
+            f.write_all(b"0123456789").unwrap();
+            f.sync_all().unwrap();
+            drop(f);
+
+            let mut f = File::open(&path).unwrap();
+            f.seek(SeekFrom::Start(4)).unwrap();
+            let mut buf = [0; 3];
+            assert_eq!(f.read(&mut buf).unwrap(), 3);
+            assert_eq!(&buf, b"456");
+            let mut rest = String::new();
+            f.read_to_string(&mut rest).unwrap();
+            assert_eq!(rest, "789");
+            assert_eq!(read(&path).unwrap().len(), 10);
+            remove_file(&path).unwrap();
+            File::open(&path).unwrap_err().kind()
+        });
+        assert_eq!(h.join().unwrap(), io::ErrorKind::NotFound);
+    }
+}
@@ -0,0 +1,1048 @@
+//! A HTTP/1.1 client that parks the coroutine instead of the thread
+//!
+//! the connections are pooled per origin and reused with keep-alive, the
+//! redirects are followed and the chunked bodies are decoded. the whole
+//! response body is read into memory. `https` needs the `tls` feature and
+//! a tls config, see [`Builder::tls_config`]. with the `compression` feature
+//! the gzip and deflate bodies are decompressed, see [`Builder::decompress`].
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use std::time::Duration;
+//! use mco::std::http::HttpClient;
+//!
+//! let client = HttpClient::builder()
+//!     .connect_timeout(Duration::from_secs(3))
+//!     .timeout(Duration::from_secs(10))
+//!     .build();
+//! mco::co!(move || {
+//!     let rsp = client.get("http://127.0.0.1:8080/hello").unwrap();
+//!     println!("{} {}", rsp.status(), rsp.text());
+//! });
+//! ```
+
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::net::ToSocketAddrs;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::net::pool::ConnPool;
+#[cfg(feature = "tls")]
+use crate::net::tls::{rustls::ClientConfig, TlsConnector, TlsStream};
+use crate::net::TcpStream;
+use crate::std::sync::Mutex;
+
+// the limit of the status line and headers
+const MAX_HEADER_SIZE: usize = 64 * 1024;
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_owned())
+}
+
+fn invalid_input(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, msg.to_owned())
+}
+
+/// The configuration of a [`HttpClient`]
+pub struct Builder {
+    connect_timeout: Option<Duration>,
+    read_timeout: Option<Duration>,
+    timeout: Option<Duration>,
+    max_redirects: usize,
+    max_connections_per_host: usize,
+    idle_timeout: Duration,
+    #[cfg(feature = "tls")]
+    tls: Option<TlsConnector>,
+    #[cfg(feature = "compression")]
+    decompress: bool,
+}
+
+impl Builder {
+    pub fn new() -> Self {
+        Builder {
+            connect_timeout: None,
+            read_timeout: None,
+            timeout: None,
+            max_redirects: 10,
+            max_connections_per_host: 32,
+            idle_timeout: Duration::from_secs(90),
+            #[cfg(feature = "tls")]
+            tls: None,
+            #[cfg(feature = "compression")]
+            decompress: true,
+        }
+    }
+
+    /// the timeout of connecting to the server, including the tls handshake
+    pub fn connect_timeout(mut self, dur: Duration) -> Self {
+        self.connect_timeout = Some(dur);
+        self
+    }
+
+    /// the timeout of each read and write on the connection
+    pub fn read_timeout(mut self, dur: Duration) -> Self {
+        self.read_timeout = Some(dur);
+        self
+    }
+
+    /// the timeout of the whole request, including the redirects
+    ///
+    /// it's checked before each io, the connecting is bounded by the
+    /// `connect_timeout`
+    pub fn timeout(mut self, dur: Duration) -> Self {
+        self.timeout = Some(dur);
+        self
+    }
+
+    /// the max number of redirects to follow, `0` disables it, default is 10
+    pub fn max_redirects(mut self, max: usize) -> Self {
+        self.max_redirects = max;
+        self
+    }
+
+    /// the max number of connections to one origin, default is 32
+    ///
+    /// more requests to the origin wait for a free connection
+    pub fn max_connections_per_host(mut self, max: usize) -> Self {
+        self.max_connections_per_host = max;
+        self
+    }
+
+    /// close the connections that stay idle longer than `dur`, default is 90s
+    pub fn idle_timeout(mut self, dur: Duration) -> Self {
+        self.idle_timeout = dur;
+        self
+    }
+
+    /// the tls config used by the `https` requests
+    #[cfg(feature = "tls")]
+    pub fn tls_config(mut self, config: Arc<ClientConfig>) -> Self {
+        self.tls = Some(TlsConnector::new(config));
+        self
+    }
+
+    /// ask for the gzip or deflate body and decompress it, default is true
+    ///
+    /// an `Accept-Encoding` header is added unless the request has one, the
+    /// `Content-Encoding` and `Content-Length` headers are removed from the
+    /// decompressed response. the other encodings are left as is
+    #[cfg(feature = "compression")]
+    pub fn decompress(mut self, enable: bool) -> Self {
+        self.decompress = enable;
+        self
+    }
+
+    pub fn build(self) -> HttpClient {
+        HttpClient {
+            inner: Arc::new(Inner {
+                config: self,
+                pools: Mutex::new(HashMap::new()),
+            }),
+        }
+    }
+}
+
+impl Default for Builder {
+    fn default() -> Self {
+        Builder::new()
+    }
+}
+
+struct Inner {
+    config: Builder,
+    pools: Mutex<HashMap<Origin, ConnPool<Conn>>>,
+}
+
+/// A HTTP client, cloning the client shares the connection pools
+#[derive(Clone)]
+pub struct HttpClient {
+    inner: Arc<Inner>,
+}
+
+impl fmt::Debug for HttpClient {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("HttpClient").finish()
+    }
+}
+
+impl Default for HttpClient {
+    fn default() -> Self {
+        HttpClient::new()
+    }
+}
+
+impl HttpClient {
+    /// create a client with the default config
+    pub fn new() -> Self {
+        Builder::new().build()
+    }
+
+    pub fn builder() -> Builder {
+        Builder::new()
+    }
+
+    /// send a `GET` request
+    pub fn get(&self, url: &str) -> io::Result<Response> {
+        self.request(Request::get(url))
+    }
+
+    /// send a `POST` request with the body
+    pub fn post<B: Into<Vec<u8>>>(&self, url: &str, body: B) -> io::Result<Response> {
+        self.request(Request::post(url).body(body))
+    }
+
+    /// send the request and read the whole response, following the redirects
+    pub fn request(&self, req: Request) -> io::Result<Response> {
+        let config = &self.inner.config;
+        let timeouts = Timeouts {
+            io: config.read_timeout,
+            deadline: config.timeout.map(|dur| Instant::now() + dur),
+        };
+        let Request {
+            mut method,
+            url,
+            mut headers,
+            mut body,
+        } = req;
+        let mut url = Url::parse(&url)?;
+        #[cfg(feature = "compression")]
+        {
+            if config.decompress
+                && !headers
+                    .iter()
+                    .any(|(k, _)| k.eq_ignore_ascii_case("accept-encoding"))
+            {
+                headers.push(("Accept-Encoding".to_owned(), "gzip, deflate".to_owned()));
+            }
+        }
+        let mut redirects = 0;
+        loop {
+            let rsp = self.send(&method, &url, &headers, &body, &timeouts)?;
+            let location = match rsp.status {
+                301 | 302 | 303 | 307 | 308 if config.max_redirects > 0 => rsp.header("location"),
+                _ => None,
+            };
+            let location = match location {
+                Some(location) => location,
+                None => {
+                    #[cfg(feature = "compression")]
+                    {
+                        if config.decompress {
+                            return decompress(rsp);
+                        }
+                    }
+                    return Ok(rsp);
+                }
+            };
+            if redirects == config.max_redirects {
+                return Err(io::Error::other("too many redirects"));
+            }
+            redirects += 1;
+
+            let next = url.join(location)?;
+            // only 307 and 308 keep the method and body
+            let to_get =
+                rsp.status == 303 || (rsp.status != 307 && rsp.status != 308 && method == "POST");
+            if to_get && method != "HEAD" {
+                method = "GET".to_owned();
+                body.clear();
+                headers.retain(|(k, _)| !k.eq_ignore_ascii_case("content-type"));
+            }
+            // don't leak the credentials to another origin
+            if next.origin != url.origin {
+                headers.retain(|(k, _)| {
+                    !k.eq_ignore_ascii_case("authorization") && !k.eq_ignore_ascii_case("cookie")
+                });
+            }
+            url = next;
+        }
+    }
+
+    fn pool(&self, origin: &Origin) -> io::Result<ConnPool<Conn>> {
+        let mut pools = self.inner.pools.lock().unwrap();
+        if let Some(pool) = pools.get(origin) {
+            return Ok(pool.clone());
+        }
+
+        let config = &self.inner.config;
+        let connect_timeout = config.connect_timeout;
+        #[cfg(feature = "tls")]
+        let tls = match (origin.https, &config.tls) {
+            (true, Some(tls)) => Some(tls.clone()),
+            (true, None) => return Err(invalid_input("https requires a tls config")),
+            (false, _) => None,
+        };
+        #[cfg(not(feature = "tls"))]
+        {
+            if origin.https {
+                return Err(invalid_input("https requires the `tls` feature"));
+            }
+        }
+
+        let target = origin.clone();
+        let pool = ConnPool::builder()
+            .max_size(config.max_connections_per_host.max(1))
+            .idle_timeout(config.idle_timeout)
+            .build(move || {
+                let tcp = connect(&target, connect_timeout)?;
+                #[cfg(feature = "tls")]
+                {
+                    if let Some(tls) = &tls {
+                        tcp.set_read_timeout(connect_timeout)?;
+                        tcp.set_write_timeout(connect_timeout)?;
+                        let stream = tls.connect(&target.host, tcp)?;
+                        return Ok(Conn::new(Stream::Tls(Box::new(stream))));
+                    }
+                }
+                Ok(Conn::new(Stream::Plain(tcp)))
+            });
+        pools.insert(origin.clone(), pool.clone());
+        Ok(pool)
+    }
+
+    // send one request, retry once if a reused connection turns out to be closed
+    fn send(
+        &self,
+        method: &str,
+        url: &Url,
+        headers: &[(String, String)],
+        body: &[u8],
+        timeouts: &Timeouts,
+    ) -> io::Result<Response> {
+        let pool = self.pool(&url.origin)?;
+        let idempotent = matches!(
+            method,
+            "GET" | "HEAD" | "OPTIONS" | "PUT" | "DELETE" | "TRACE"
+        );
+        loop {
+            let mut conn = match timeouts.deadline {
+                Some(deadline) => {
+                    pool.get_timeout(deadline.saturating_duration_since(Instant::now()))?
+                }
+                None => pool.get()?,
+            };
+            match exchange(&mut conn, method, url, headers, body, timeouts) {
+                Ok((rsp, keep_alive)) => {
+                    if keep_alive {
+                        conn.reused = true;
+                    } else {
+                        conn.discard();
+                    }
+                    return Ok(rsp);
+                }
+                Err(e) => {
+                    // the server may close an idle connection at any time
+                    let retry = conn.reused
+                        && (e.stage == Stage::Write
+                            || (e.stage == Stage::NoResponse && idempotent));
+                    conn.discard();
+                    if !retry {
+                        return Err(e.err);
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn connect(origin: &Origin, timeout: Option<Duration>) -> io::Result<TcpStream> {
+    let mut last_err = None;
+    for addr in (origin.host.as_str(), origin.port).to_socket_addrs()? {
+        let ret = match timeout {
+            Some(dur) => TcpStream::connect_timeout(&addr, dur),
+            None => TcpStream::connect(addr),
+        };
+        match ret {
+            Ok(s) => {
+                s.set_nodelay(true)?;
+                return Ok(s);
+            }
+            Err(e) => last_err = Some(e),
+        }
+    }
+    Err(last_err.unwrap_or_else(|| invalid_input("no socket addresses resolved")))
+}
+
+/// A HTTP request
+#[derive(Debug, Clone)]
+pub struct Request {
+    method: String,
+    url: String,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl Request {
+    pub fn new(method: &str, url: &str) -> Self {
+        Request {
+            method: method.to_ascii_uppercase(),
+            url: url.to_owned(),
+            headers: Vec::new(),
+            body: Vec::new(),
+        }
+    }
+
+    pub fn get(url: &str) -> Self {
+        Request::new("GET", url)
+    }
+
+    pub fn post(url: &str) -> Self {
+        Request::new("POST", url)
+    }
+
+    /// add a header, the `Host`, `Content-Length` and `Transfer-Encoding`
+    /// are always set by the client
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_owned(), value.to_owned()));
+        self
+    }
+
+    pub fn body<B: Into<Vec<u8>>>(mut self, body: B) -> Self {
+        self.body = body.into();
+        self
+    }
+
+    pub fn method(&self) -> &str {
+        &self.method
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    pub fn headers(&self) -> &[(String, String)] {
+        &self.headers
+    }
+}
+
+/// A HTTP response with the whole body
+#[derive(Debug, Clone)]
+pub struct Response {
+    status: u16,
+    reason: String,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+    url: String,
+}
+
+impl Response {
+    pub fn status(&self) -> u16 {
+        self.status
+    }
+
+    pub fn reason(&self) -> &str {
+        &self.reason
+    }
+
+    /// the headers in the received order, the trailers of a chunked body
+    /// are appended
+    pub fn headers(&self) -> &[(String, String)] {
+        &self.headers
+    }
+
+    /// the first value of the header, the name is case insensitive
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    pub fn body(&self) -> &[u8] {
+        &self.body
+    }
+
+    pub fn into_body(self) -> Vec<u8> {
+        self.body
+    }
+
+    /// the body as a string, the invalid utf8 is replaced
+    pub fn text(&self) -> String {
+        String::from_utf8_lossy(&self.body).into_owned()
+    }
+
+    /// the url of the final request after the redirects
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct Origin {
+    https: bool,
+    host: String,
+    port: u16,
+}
+
+impl fmt::Display for Origin {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let scheme = if self.https { "https" } else { "http" };
+        write!(f, "{}://{}", scheme, self.authority())
+    }
+}
+
+impl Origin {
+    // the value of the `Host` header
+    fn authority(&self) -> String {
+        let host = if self.host.contains(':') {
+            format!("[{}]", self.host)
+        } else {
+            self.host.clone()
+        };
+        match (self.https, self.port) {
+            (true, 443) | (false, 80) => host,
+            _ => format!("{}:{}", host, self.port),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Url {
+    origin: Origin,
+    // the path and the query, starts with `/`
+    path: String,
+}
+
+impl fmt::Display for Url {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}{}", self.origin, self.path)
+    }
+}
+
+impl Url {
+    fn parse(url: &str) -> io::Result<Url> {
+        let (scheme, rest) = url
+            .split_once("://")
+            .ok_or_else(|| invalid_input("invalid url, no scheme"))?;
+        let https = match scheme.to_ascii_lowercase().as_str() {
+            "http" => false,
+            "https" => true,
+            _ => return Err(invalid_input("invalid url, unsupported scheme")),
+        };
+        // the fragment is never sent
+        let rest = rest.split('#').next().unwrap_or_default();
+        let end = rest.find(['/', '?']).unwrap_or(rest.len());
+        let (authority, path) = rest.split_at(end);
+        if authority.contains('@') {
+            return Err(invalid_input("invalid url, userinfo is not supported"));
+        }
+
+        let (host, port) = match authority.strip_prefix('[') {
+            Some(v6) => {
+                let (host, rest) = v6
+                    .split_once(']')
+                    .ok_or_else(|| invalid_input("invalid url, bad ipv6 host"))?;
+                (host, rest.strip_prefix(':'))
+            }
+            None => match authority.rsplit_once(':') {
+                Some((host, port)) => (host, Some(port)),
+                None => (authority, None),
+            },
+        };
+        if host.is_empty() {
+            return Err(invalid_input("invalid url, empty host"));
+        }
+        let port = match port {
+            Some(port) => port
+                .parse()
+                .map_err(|_| invalid_input("invalid url, bad port"))?,
+            None if https => 443,
+            None => 80,
+        };
+        let path = match path {
+            "" => "/".to_owned(),
+            p if p.starts_with('?') => format!("/{}", p),
+            p => p.to_owned(),
+        };
+        Ok(Url {
+            origin: Origin {
+                https,
+                host: host.to_ascii_lowercase(),
+                port,
+            },
+            path,
+        })
+    }
+
+    // resolve the `Location` of a redirect
+    fn join(&self, location: &str) -> io::Result<Url> {
+        if location.contains("://") {
+            return Url::parse(location);
+        }
+        if location.starts_with("//") {
+            let scheme = if self.origin.https { "https:" } else { "http:" };
+            return Url::parse(&format!("{}{}", scheme, location));
+        }
+        let location = location.split('#').next().unwrap_or_default();
+        let path = if location.starts_with('/') {
+            location.to_owned()
+        } else {
+            let base = self.path.split('?').next().unwrap_or_default();
+            let dir = &base[..base.rfind('/').map_or(0, |i| i + 1)];
+            format!("{}{}", dir, location)
+        };
+        Ok(Url {
+            origin: self.origin.clone(),
+            path,
+        })
+    }
+}
+
+enum Stream {
+    Plain(TcpStream),
+    #[cfg(feature = "tls")]
+    Tls(Box<TlsStream<TcpStream>>),
+}
+
+struct Conn {
+    stream: Stream,
+    // returned to the pool at least once, so it may be closed by the server
+    reused: bool,
+}
+
+impl Conn {
+    fn new(stream: Stream) -> Self {
+        Conn {
+            stream,
+            reused: false,
+        }
+    }
+
+    fn tcp(&self) -> &TcpStream {
+        match &self.stream {
+            Stream::Plain(s) => s,
+            #[cfg(feature = "tls")]
+            Stream::Tls(s) => s.get_ref(),
+        }
+    }
+
+    fn set_timeout(&self, dur: Option<Duration>) -> io::Result<()> {
+        let tcp = self.tcp();
+        tcp.set_read_timeout(dur)?;
+        tcp.set_write_timeout(dur)
+    }
+}
+
+impl Read for Conn {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match &mut self.stream {
+            Stream::Plain(s) => s.read(buf),
+            #[cfg(feature = "tls")]
+            Stream::Tls(s) => s.read(buf),
+        }
+    }
+}
+
+impl Write for Conn {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match &mut self.stream {
+            Stream::Plain(s) => s.write(buf),
+            #[cfg(feature = "tls")]
+            Stream::Tls(s) => s.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.stream {
+            Stream::Plain(s) => s.flush(),
+            #[cfg(feature = "tls")]
+            Stream::Tls(s) => s.flush(),
+        }
+    }
+}
+
+struct Timeouts {
+    // the timeout of each io
+    io: Option<Duration>,
+    // the end of the whole request
+    deadline: Option<Instant>,
+}
+
+impl Timeouts {
+    // the timeout of the next io
+    fn next(&self) -> io::Result<Option<Duration>> {
+        let deadline = match self.deadline {
+            Some(deadline) => deadline,
+            None => return Ok(self.io),
+        };
+        let left = deadline.saturating_duration_since(Instant::now());
+        if left.is_zero() {
+            return Err(io::Error::new(io::ErrorKind::TimedOut, "request timed out"));
+        }
+        Ok(Some(self.io.map_or(left, |io| io.min(left))))
+    }
+}
+
+// where the exchange fails, to tell if it's safe to retry
+#[derive(Debug, PartialEq)]
+enum Stage {
+    Write,
+    NoResponse,
+    Read,
+}
+
+struct ExchangeError {
+    err: io::Error,
+    stage: Stage,
+}
+
+// write the request and read the response, return if the connection can be reused
+fn exchange(
+    conn: &mut Conn,
+    method: &str,
+    url: &Url,
+    headers: &[(String, String)],
+    body: &[u8],
+    timeouts: &Timeouts,
+) -> Result<(Response, bool), ExchangeError> {
+    let head = encode_head(method, url, headers, body).map_err(|err| ExchangeError {
+        err,
+        stage: Stage::Read,
+    })?;
+    let write = || -> io::Result<()> {
+        conn.set_timeout(timeouts.next()?)?;
+        let mut req = head;
+        req.extend_from_slice(body);
+        conn.write_all(&req)?;
+        conn.flush()
+    };
+    write().map_err(|err| ExchangeError {
+        err,
+        stage: Stage::Write,
+    })?;
+
+    let mut reader = RspReader::new(conn, timeouts);
+    let ret = read_response(&mut reader, method, url);
+    let received = reader.received;
+    let leftover = reader.pos < reader.buf.len();
+    match ret {
+        Ok((rsp, keep_alive)) => Ok((rsp, keep_alive && !leftover)),
+        Err(err) => Err(ExchangeError {
+            err,
+            stage: if received == 0 {
+                Stage::NoResponse
+            } else {
+                Stage::Read
+            },
+        }),
+    }
+}
+
+fn encode_head(
+    method: &str,
+    url: &Url,
+    headers: &[(String, String)],
+    body: &[u8],
+) -> io::Result<Vec<u8>> {
+    let mut head = format!(
+        "{} {} HTTP/1.1\r\nHost: {}\r\n",
+        method,
+        url.path,
+        url.origin.authority()
+    );
+    let mut has_agent = false;
+    for (k, v) in headers {
+        if k.contains(['\r', '\n', ':']) || v.contains(['\r', '\n']) {
+            return Err(invalid_input("invalid header"));
+        }
+        if ["host", "content-length", "transfer-encoding"]
+            .iter()
+            .any(|h| k.eq_ignore_ascii_case(h))
+        {
+            continue;
+        }
+        has_agent |= k.eq_ignore_ascii_case("user-agent");
+        head.push_str(&format!("{}: {}\r\n", k, v));
+    }
+    if !has_agent {
+        head.push_str(concat!(
+            "User-Agent: mco/",
+            env!("CARGO_PKG_VERSION"),
+            "\r\n"
+        ));
+    }
+    if !body.is_empty() || matches!(method, "POST" | "PUT" | "PATCH") {
+        head.push_str(&format!("Content-Length: {}\r\n", body.len()));
+    }
+    head.push_str("\r\n");
+    Ok(head.into_bytes())
+}
+
+// buffered reader of the response, applies the timeouts before each read
+struct RspReader<'a> {
+    conn: &'a mut Conn,
+    timeouts: &'a Timeouts,
+    buf: Vec<u8>,
+    pos: usize,
+    // total bytes received
+    received: usize,
+}
+
+impl<'a> RspReader<'a> {
+    fn new(conn: &'a mut Conn, timeouts: &'a Timeouts) -> Self {
+        RspReader {
+            conn,
+            timeouts,
+            buf: Vec::new(),
+            pos: 0,
+            received: 0,
+        }
+    }
+
+    // read more data into the buffer, return 0 on EOF
+    fn fill(&mut self) -> io::Result<usize> {
+        if self.pos == self.buf.len() {
+            self.buf.clear();
+            self.pos = 0;
+        }
+        self.conn.set_timeout(self.timeouts.next()?)?;
+        let len = self.buf.len();
+        self.buf.resize(len + 8 * 1024, 0);
+        let ret = self.conn.read(&mut self.buf[len..]);
+        let n = *ret.as_ref().unwrap_or(&0);
+        self.buf.truncate(len + n);
+        self.received += n;
+        ret
+    }
+
+    // read a line without the line ending
+    fn read_line(&mut self, limit: &mut usize) -> io::Result<String> {
+        loop {
+            if let Some(i) = self.buf[self.pos..].iter().position(|b| *b == b'\n') {
+                let line = &self.buf[self.pos..self.pos + i];
+                let line = line.strip_suffix(b"\r").unwrap_or(line);
+                let line = String::from_utf8_lossy(line).into_owned();
+                self.pos += i + 1;
+                return Ok(line);
+            }
+            if self.buf.len() - self.pos > *limit {
+                return Err(invalid_data("http response header is too large"));
+            }
+            let before = self.buf.len() - self.pos;
+            if self.fill()? == 0 {
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+            *limit = limit.saturating_sub(self.buf.len() - self.pos - before);
+        }
+    }
+
+    fn read_exact(&mut self, mut n: usize, out: &mut Vec<u8>) -> io::Result<()> {
+        while n > 0 {
+            if self.pos == self.buf.len() && self.fill()? == 0 {
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+            let len = n.min(self.buf.len() - self.pos);
+            out.extend_from_slice(&self.buf[self.pos..self.pos + len]);
+            self.pos += len;
+            n -= len;
+        }
+        Ok(())
+    }
+
+    fn read_to_end(&mut self, out: &mut Vec<u8>) -> io::Result<()> {
+        loop {
+            out.extend_from_slice(&self.buf[self.pos..]);
+            self.pos = self.buf.len();
+            if self.fill()? == 0 {
+                return Ok(());
+            }
+        }
+    }
+}
+
+fn read_headers(reader: &mut RspReader, limit: &mut usize) -> io::Result<Vec<(String, String)>> {
+    let mut headers = Vec::new();
+    loop {
+        let line = reader.read_line(limit)?;
+        if line.is_empty() {
+            return Ok(headers);
+        }
+        let (k, v) = line
+            .split_once(':')
+            .ok_or_else(|| invalid_data("invalid http header"))?;
+        headers.push((k.trim().to_owned(), v.trim().to_owned()));
+    }
+}
+
+fn has_token(headers: &[(String, String)], name: &str, token: &str) -> bool {
+    headers
+        .iter()
+        .filter(|(k, _)| k.eq_ignore_ascii_case(name))
+        .flat_map(|(_, v)| v.split(','))
+        .any(|t| t.trim().eq_ignore_ascii_case(token))
+}
+
+fn read_response(reader: &mut RspReader, method: &str, url: &Url) -> io::Result<(Response, bool)> {
+    let mut limit = MAX_HEADER_SIZE;
+    // skip the informational responses, e.g. `100 Continue`
+    let (version, status, reason, mut headers) = loop {
+        let line = reader.read_line(&mut limit)?;
+        let mut parts = line.splitn(3, ' ');
+        let version = match parts.next() {
+            Some("HTTP/1.1") => 1,
+            Some("HTTP/1.0") => 0,
+            _ => return Err(invalid_data("invalid http status line")),
+        };
+        let status: u16 = parts
+            .next()
+            .and_then(|s| s.parse().ok())
+            .ok_or_else(|| invalid_data("invalid http status code"))?;
+        let reason = parts.next().unwrap_or_default().to_owned();
+        let headers = read_headers(reader, &mut limit)?;
+        if status / 100 != 1 || status == 101 {
+            break (version, status, reason, headers);
+        }
+    };
+
+    let mut body = Vec::new();
+    let mut delimited = true;
+    if method == "HEAD" || status / 100 == 1 || status == 204 || status == 304 {
+        // no body
+    } else if has_token(&headers, "transfer-encoding", "chunked") {
+        loop {
+            let line = reader.read_line(&mut limit)?;
+            let size = line.split(';').next().unwrap_or_default().trim();
+            let size =
+                usize::from_str_radix(size, 16).map_err(|_| invalid_data("invalid chunk size"))?;
+            if size == 0 {
+                let trailers = read_headers(reader, &mut limit)?;
+                headers.extend(trailers);
+                break;
+            }
+            reader.read_exact(size, &mut body)?;
+            if !reader.read_line(&mut limit)?.is_empty() {
+                return Err(invalid_data("invalid chunk end"));
+            }
+            limit = MAX_HEADER_SIZE;
+        }
+    } else if let Some((_, len)) = headers
+        .iter()
+        .find(|(k, _)| k.eq_ignore_ascii_case("content-length"))
+    {
+        let len = len
+            .parse()
+            .map_err(|_| invalid_data("invalid content length"))?;
+        reader.read_exact(len, &mut body)?;
+    } else {
+        // the body ends with the connection
+        delimited = false;
+        reader.read_to_end(&mut body)?;
+    }
+
+    let keep_alive = match version {
+        1 => !has_token(&headers, "connection", "close"),
+        _ => has_token(&headers, "connection", "keep-alive"),
+    };
+    let rsp = Response {
+        status,
+        reason,
+        headers,
+        body,
+        url: url.to_string(),
+    };
+    Ok((rsp, keep_alive && delimited && status != 101))
+}
+
+// decode the body by the `Content-Encoding`, the unknown encodings are kept
+#[cfg(feature = "compression")]
+fn decompress(mut rsp: Response) -> io::Result<Response> {
+    use flate2::read::{DeflateDecoder, MultiGzDecoder, ZlibDecoder};
+
+    let encoding = match rsp.header("content-encoding") {
+        Some(encoding) if !rsp.body.is_empty() => encoding.trim().to_ascii_lowercase(),
+        _ => return Ok(rsp),
+    };
+    let data = &rsp.body[..];
+    let mut body = Vec::new();
+    match encoding.as_str() {
+        "gzip" | "x-gzip" => MultiGzDecoder::new(data).read_to_end(&mut body)?,
+        // `deflate` should be zlib wrapped, but some servers send the raw stream
+        "deflate"
+            if data.len() >= 2
+                && data[0] & 0x0f == 8
+                && u16::from_be_bytes([data[0], data[1]]) % 31 == 0 =>
+        {
+            ZlibDecoder::new(data).read_to_end(&mut body)?
+        }
+        "deflate" => DeflateDecoder::new(data).read_to_end(&mut body)?,
+        _ => return Ok(rsp),
+    };
+    rsp.body = body;
+    rsp.headers.retain(|(k, _)| {
+        !k.eq_ignore_ascii_case("content-encoding") && !k.eq_ignore_ascii_case("content-length")
+    });
+    Ok(rsp)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Url;
+
+    #[test]
+    fn url_parse_and_join() {
+        let url = Url::parse("http://Example.com:8080/a/b?x=1#frag").unwrap();
+        assert_eq!(url.origin.host, "example.com");
+        assert_eq!(url.origin.port, 8080);
+        assert_eq!(url.path, "/a/b?x=1");
+        assert_eq!(url.to_string(), "http://example.com:8080/a/b?x=1");
+
+        let url = Url::parse("https://[::1]?q").unwrap();
+        assert_eq!(url.origin.host, "::1");
+        assert_eq!(url.origin.port, 443);
+        assert_eq!(url.to_string(), "https://[::1]/?q");
+
+        let url = Url::parse("http://h/a/b").unwrap();
+        assert_eq!(url.join("c").unwrap().path, "/a/c");
+        assert_eq!(url.join("/c").unwrap().path, "/c");
+        assert_eq!(url.join("//o/c").unwrap().to_string(), "http://o/c");
+        assert!(Url::parse("ftp://h/").is_err());
+        assert!(Url::parse("http://u@h/").is_err());
+    }
+
+    #[cfg(feature = "compression")]
+    #[test]
+    fn decompress_body() {
+        use super::{decompress, Response};
+        use flate2::write::{DeflateEncoder, GzEncoder, ZlibEncoder};
+        use flate2::Compression;
+        use std::io::Write;
+
+        fn rsp(encoding: &str, body: Vec<u8>) -> Response {
+            Response {
+                status: 200,
+                reason: "OK".to_owned(),
+                headers: vec![
+                    ("Content-Encoding".to_owned(), encoding.to_owned()),
+                    ("Content-Length".to_owned(), body.len().to_string()),
+                ],
+                body,
+                url: String::new(),
+            }
+        }
+
+        let data = b"hello hello hello hello";
+        let mut gz = GzEncoder::new(Vec::new(), Compression::default());
+        gz.write_all(data).unwrap();
+        let mut zlib = ZlibEncoder::new(Vec::new(), Compression::default());
+        zlib.write_all(data).unwrap();
+        let mut raw = DeflateEncoder::new(Vec::new(), Compression::default());
+        raw.write_all(data).unwrap();
+
+        for (encoding, body) in [
+            ("gzip", gz.finish().unwrap()),
+            ("deflate", zlib.finish().unwrap()),
+            ("Deflate", raw.finish().unwrap()),
+        ] {
+            let rsp = decompress(rsp(encoding, body)).unwrap();
+            assert_eq!(rsp.body(), data);
+            assert!(rsp.headers().is_empty());
+        }
+
+        let rsp = decompress(rsp("br", b"xx".to_vec())).unwrap();
+        assert_eq!(rsp.body(), b"xx");
+        assert_eq!(rsp.headers().len(), 2);
+    }
+}
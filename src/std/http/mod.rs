@@ -0,0 +1,5 @@
+//! HTTP/1.1 over the coroutine sockets
+
+pub mod client;
+
+pub use self::client::{HttpClient, Request, Response};
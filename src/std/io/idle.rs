@@ -0,0 +1,182 @@
+use crate::io::{SetReadTimeout, SetWriteTimeout};
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::time::{Duration, Instant};
+
+/// stream that can bound a single read or write call by a timeout,
+/// this is what `IdleTimeout` needs from the wrapped stream
+///
+/// it's implemented for all the streams with the read and write timeouts
+pub trait IdleStream: Read + Write + SetReadTimeout + SetWriteTimeout {}
+
+impl<T: Read + Write + SetReadTimeout + SetWriteTimeout> IdleStream for T {}
+
+/// IdleTimeout wraps a stream and fails any read or write with `TimedOut`
+/// once no byte has been transferred in either direction for `dur`.
+/// the idle timer is reset on every successful read or write
+///
+/// the wrapper drives the per call timeout of the inner stream,
+/// so the read/write timeout of the inner stream is overwritten
+pub struct IdleTimeout<T> {
+    inner: T,
+    idle: Duration,
+    last_active: Instant,
+    on_timeout: Option<Box<dyn FnMut() + Send>>,
+}
+
+impl<T: IdleStream> IdleTimeout<T> {
+    pub fn new(inner: T, dur: Duration) -> Self {
+        IdleTimeout {
+            inner,
+            idle: dur,
+            last_active: Instant::now(),
+            on_timeout: None,
+        }
+    }
+
+    /// set a callback that is invoked each time the idle timeout fires
+    pub fn on_timeout<F>(mut self, f: F) -> Self
+    where
+        F: FnMut() + Send + 'static,
+    {
+        self.on_timeout = Some(Box::new(f));
+        self
+    }
+
+    /// the idle duration
+    pub fn idle_timeout(&self) -> Duration {
+        self.idle
+    }
+
+    /// change the idle duration, the timer is not reset
+    pub fn set_idle_timeout(&mut self, dur: Duration) {
+        self.idle = dur;
+    }
+
+    /// reset the idle timer as if some bytes were just transferred
+    pub fn reset(&mut self) {
+        self.last_active = Instant::now();
+    }
+
+    pub fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+
+    /// convert back to the inner stream, the inner read/write timeout are cleared
+    pub fn into_inner(self) -> T {
+        let _ = self.inner.set_read_timeout(None);
+        let _ = self.inner.set_write_timeout(None);
+        self.inner
+    }
+
+    // remaining time before the stream is considered idle
+    fn remain(&mut self) -> io::Result<Duration> {
+        let elapsed = self.last_active.elapsed();
+        if elapsed >= self.idle {
+            return Err(self.timed_out());
+        }
+        Ok(self.idle - elapsed)
+    }
+
+    fn timed_out(&mut self) -> io::Error {
+        if let Some(f) = self.on_timeout.as_mut() {
+            f();
+        }
+        io::Error::new(io::ErrorKind::TimedOut, "idle timeout")
+    }
+
+    fn check<R>(&mut self, r: io::Result<R>, progress: bool) -> io::Result<R> {
+        match r {
+            Ok(v) => {
+                if progress {
+                    self.last_active = Instant::now();
+                }
+                Ok(v)
+            }
+            Err(e) => match e.kind() {
+                // thread context sockets report a timeout as WouldBlock
+                io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock
+                    if self.last_active.elapsed() >= self.idle =>
+                {
+                    Err(self.timed_out())
+                }
+                _ => Err(e),
+            },
+        }
+    }
+}
+
+impl<T: IdleStream> Read for IdleTimeout<T> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let remain = self.remain()?;
+        self.inner.set_read_timeout(Some(remain))?;
+        let r = self.inner.read(buf);
+        let progress = matches!(r, Ok(n) if n > 0);
+        self.check(r, progress)
+    }
+}
+
+impl<T: IdleStream> Write for IdleTimeout<T> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let remain = self.remain()?;
+        self.inner.set_write_timeout(Some(remain))?;
+        let r = self.inner.write(buf);
+        let progress = matches!(r, Ok(n) if n > 0);
+        self.check(r, progress)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let remain = self.remain()?;
+        self.inner.set_write_timeout(Some(remain))?;
+        let r = self.inner.flush();
+        self.check(r, false)
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for IdleTimeout<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("IdleTimeout")
+            .field("inner", &self.inner)
+            .field("idle", &self.idle)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::IdleTimeout;
+    use crate::net::{TcpListener, TcpStream};
+    use std::io::{ErrorKind, Read, Write};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[test]
+    fn idle_read_timeout() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let client = std::thread::spawn(move || {
+            let mut s = std::net::TcpStream::connect(addr).unwrap();
+            s.write_all(b"hello").unwrap();
+            std::thread::sleep(Duration::from_millis(500));
+        });
+        let (s, _) = listener.accept().unwrap();
+        let fired = Arc::new(AtomicUsize::new(0));
+        let f = fired.clone();
+        let mut s = IdleTimeout::new(s, Duration::from_millis(100)).on_timeout(move || {
+            f.fetch_add(1, Ordering::SeqCst);
+        });
+        let mut buf = [0u8; 5];
+        s.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"hello");
+        let e = s.read(&mut buf).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::TimedOut);
+        assert_eq!(fired.load(Ordering::SeqCst), 1);
+        let _: TcpStream = s.into_inner();
+        client.join().unwrap();
+    }
+}
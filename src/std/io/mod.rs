@@ -1,7 +1,9 @@
+pub mod idle;
 pub mod io;
 pub mod stream;
 pub mod stream_chan;
 
+pub use idle::*;
 pub use io::*;
 pub use stream::*;
 pub use stream_chan::*;
@@ -7,6 +7,9 @@ pub mod io;
 #[macro_use]
 pub mod defer;
 pub mod blocking;
+pub mod context;
+pub mod fs;
+pub mod http;
 pub mod lazy;
 pub mod pool;
 pub mod time;
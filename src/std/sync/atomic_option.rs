@@ -1,9 +1,19 @@
 use crossbeam_utils::atomic::AtomicCell;
+use once_cell::race::OnceBox;
 use std::borrow::Borrow;
 use std::fmt::{Debug, Formatter};
+use std::time::{Duration, Instant};
 
+use super::Notify;
+
+/// an atomic one-slot cell
+///
+/// it can be used as a mailbox between coroutines, `take_timeout` parks
+/// the caller until a value is stored by `store` or `swap`
 pub struct AtomicOption<T> {
     inner: AtomicCell<Option<T>>,
+    // created by the first waiter, so the cell stays cheap when nobody waits
+    notify: OnceBox<Notify>,
 }
 
 impl<T: std::fmt::Debug> Debug for AtomicOption<T> {
@@ -26,23 +36,67 @@ impl<T> AtomicOption<T> {
     pub fn none() -> AtomicOption<T> {
         AtomicOption {
             inner: AtomicCell::new(None),
+            notify: OnceBox::new(),
         }
     }
 
     pub fn some(t: T) -> AtomicOption<T> {
         AtomicOption {
             inner: AtomicCell::new(Some(t)),
+            notify: OnceBox::new(),
         }
     }
 
     #[inline]
     pub fn store(&self, t: T) {
-        self.inner.store(Some(t))
+        self.inner.store(Some(t));
+        self.wakeup();
     }
 
+    /// store the value and return the old one
     #[inline]
     pub fn swap(&self, t: T) -> Option<T> {
-        self.inner.swap(Some(t))
+        let old = self.inner.swap(Some(t));
+        self.wakeup();
+        old
+    }
+
+    /// take the value only if it equals to `current`
+    #[inline]
+    pub fn compare_take(&self, current: T) -> Option<T>
+    where
+        T: Copy + Eq,
+    {
+        self.inner.compare_exchange(Some(current), None).ok().flatten()
+    }
+
+    /// take the value, wait until a value is stored if it's none
+    ///
+    /// return `None` if timeout happened. in coroutine context only the
+    /// coroutine is parked
+    pub fn take_timeout(&self, dur: Duration) -> Option<T> {
+        let deadline = Instant::now() + dur;
+        loop {
+            if let Some(v) = self.take() {
+                return Some(v);
+            }
+            let notify = self.notify.get_or_init(|| Box::new(Notify::new()));
+            // a store may happen before the notify is created
+            if let Some(v) = self.take() {
+                return Some(v);
+            }
+            let now = Instant::now();
+            if now >= deadline || !notify.notified_timeout(deadline - now) {
+                return self.take();
+            }
+        }
+    }
+
+    #[inline]
+    fn wakeup(&self) {
+        if let Some(notify) = self.notify.get() {
+            notify.notify_one();
+        }
     }
 
     #[inline]
@@ -80,3 +134,34 @@ impl<T> Drop for AtomicOption<T> {
         self.inner.store(None)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn compare_take() {
+        let v = AtomicOption::some(1);
+        assert_eq!(v.compare_take(2), None);
+        assert!(v.is_some());
+        assert_eq!(v.compare_take(1), Some(1));
+        assert!(v.is_none());
+        assert_eq!(v.swap(3), None);
+        assert_eq!(v.swap(4), Some(3));
+    }
+
+    #[test]
+    fn take_timeout() {
+        let v = Arc::new(AtomicOption::none());
+        assert_eq!(v.take_timeout(Duration::from_millis(10)), None);
+        let v1 = v.clone();
+        let h = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(10));
+            v1.store(String::from("hello"));
+        });
+        assert_eq!(v.take_timeout(Duration::from_secs(10)).unwrap(), "hello");
+        h.join().unwrap();
+    }
+}
@@ -0,0 +1,391 @@
+use std::borrow::Borrow;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::hash::Hash;
+use std::sync::{Arc, Weak};
+use std::time::{Duration, Instant};
+
+use crate::scheduler::get_scheduler;
+use parking_lot::Mutex;
+
+// the max interval that the sweeper checks the expired entries
+const SWEEP_INTERVAL: Duration = Duration::from_secs(1);
+
+/// A concurrent cache with LRU eviction and optional per-entry TTL
+///
+/// when the cache is full the least recently used entry is evicted. an
+/// entry with a TTL is invisible after it expires, and is removed by a
+/// sweep that the scheduler timer runs at the next expiration, no sweep is
+/// pending while there is no entry with a TTL.
+///
+/// the values are cloned out of the cache, wrap them in an `Arc` if the
+/// clone is expensive.
+///
+/// # Examples
+///
+/// ```rust
+/// use std::time::Duration;
+/// use mco::std::sync::Cache;
+///
+/// let cache = Cache::new(2);
+/// cache.insert(1, "a");
+/// cache.insert(2, "b");
+/// // 1 is the most recently used now
+/// assert_eq!(cache.get(&1), Some("a"));
+/// cache.insert(3, "c");
+/// assert_eq!(cache.get(&2), None);
+///
+/// cache.insert_with_ttl(4, "d", Duration::from_secs(60));
+/// assert_eq!(cache.get(&4), Some("d"));
+/// ```
+pub struct Cache<K, V> {
+    inner: Arc<Mutex<CacheInner<K, V>>>,
+    // the TTL applied by `insert`
+    ttl: Option<Duration>,
+}
+
+struct Entry<V> {
+    value: V,
+    // the position in the LRU order
+    tick: u64,
+    expire: Option<Instant>,
+}
+
+struct CacheInner<K, V> {
+    capacity: usize,
+    map: HashMap<K, Entry<V>>,
+    // tick -> key, the first one is the least recently used
+    lru: BTreeMap<u64, K>,
+    // (expire time, tick) -> key, the first one expires first
+    expires: BTreeMap<(Instant, u64), K>,
+    next_tick: u64,
+    // whether a sweep is pending in the timer
+    sweeping: bool,
+}
+
+impl<K: Eq + Hash + Clone, V> CacheInner<K, V> {
+    fn next_tick(&mut self) -> u64 {
+        self.next_tick += 1;
+        self.next_tick
+    }
+
+    fn unlink(&mut self, entry: &Entry<V>) {
+        self.lru.remove(&entry.tick);
+        if let Some(expire) = entry.expire {
+            self.expires.remove(&(expire, entry.tick));
+        }
+    }
+
+    fn remove<Q: ?Sized + Hash + Eq>(&mut self, k: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+    {
+        let entry = self.map.remove(k)?;
+        self.unlink(&entry);
+        Some(entry.value)
+    }
+
+    // return the live entry and mark it as the most recently used
+    fn touch<Q: ?Sized + Hash + Eq>(&mut self, k: &Q, now: Instant) -> Option<&V>
+    where
+        K: Borrow<Q>,
+    {
+        let (expire, tick) = match self.map.get(k) {
+            Some(e) => (e.expire, e.tick),
+            None => return None,
+        };
+        if expire.is_some_and(|e| e <= now) {
+            self.remove(k);
+            return None;
+        }
+        let new_tick = self.next_tick();
+        let key = self.lru.remove(&tick).expect("lru list corrupted");
+        if let Some(expire) = expire {
+            self.expires.remove(&(expire, tick));
+            self.expires.insert((expire, new_tick), key.clone());
+        }
+        self.lru.insert(new_tick, key);
+        let entry = self.map.get_mut(k).unwrap();
+        entry.tick = new_tick;
+        Some(&entry.value)
+    }
+
+    fn insert(&mut self, k: K, v: V, expire: Option<Instant>) -> Option<V> {
+        let old = self.remove(&k);
+        if self.map.len() >= self.capacity {
+            if let Some((_, lru)) = self.lru.pop_first() {
+                let entry = self.map.remove(&lru).unwrap();
+                if let Some(expire) = entry.expire {
+                    self.expires.remove(&(expire, entry.tick));
+                }
+            }
+        }
+        let tick = self.next_tick();
+        self.lru.insert(tick, k.clone());
+        if let Some(expire) = expire {
+            self.expires.insert((expire, tick), k.clone());
+        }
+        self.map.insert(
+            k,
+            Entry {
+                value: v,
+                tick,
+                expire,
+            },
+        );
+        old
+    }
+
+    // remove all the expired entries, return the number of them
+    fn purge(&mut self, now: Instant) -> usize {
+        let mut n = 0;
+        while let Some((&(expire, _), _)) = self.expires.iter().next() {
+            if expire > now {
+                break;
+            }
+            let (_, k) = self.expires.pop_first().unwrap();
+            self.remove(&k);
+            n += 1;
+        }
+        n
+    }
+
+    fn next_expire(&self) -> Option<Instant> {
+        self.expires.keys().next().map(|&(expire, _)| expire)
+    }
+}
+
+impl<K, V> Cache<K, V>
+where
+    K: Eq + Hash + Clone + Send + 'static,
+    V: Send + 'static,
+{
+    /// create a cache that holds at most `capacity` entries
+    ///
+    /// # Panics
+    ///
+    /// panics if the `capacity` is zero
+    pub fn new(capacity: usize) -> Self {
+        assert!(capacity > 0, "cache capacity must be positive");
+        Cache {
+            inner: Arc::new(Mutex::new(CacheInner {
+                capacity,
+                map: HashMap::new(),
+                lru: BTreeMap::new(),
+                expires: BTreeMap::new(),
+                next_tick: 0,
+                sweeping: false,
+            })),
+            ttl: None,
+        }
+    }
+
+    /// create a cache that applies `ttl` to the entries added by `insert`
+    pub fn with_ttl(capacity: usize, ttl: Duration) -> Self {
+        let mut cache = Self::new(capacity);
+        cache.ttl = Some(ttl);
+        cache
+    }
+
+    /// the max number of the entries
+    pub fn capacity(&self) -> usize {
+        self.inner.lock().capacity
+    }
+
+    /// the number of the entries, including the expired ones not removed yet
+    pub fn len(&self) -> usize {
+        self.inner.lock().map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// return a clone of the value and mark the entry as the most recently used
+    pub fn get<Q: ?Sized + Hash + Eq>(&self, k: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        V: Clone,
+    {
+        self.inner.lock().touch(k, Instant::now()).cloned()
+    }
+
+    pub fn contains_key<Q: ?Sized + Hash + Eq>(&self, k: &Q) -> bool
+    where
+        K: Borrow<Q>,
+    {
+        let now = Instant::now();
+        let inner = self.inner.lock();
+        match inner.map.get(k) {
+            Some(e) => e.expire.map_or(true, |e| e > now),
+            None => false,
+        }
+    }
+
+    /// insert an entry with the default TTL of the cache, return the old value
+    ///
+    /// the least recently used entry is evicted if the cache is full
+    pub fn insert(&self, k: K, v: V) -> Option<V> {
+        match self.ttl {
+            Some(ttl) => self.insert_with_ttl(k, v, ttl),
+            None => self.inner.lock().insert(k, v, None),
+        }
+    }
+
+    /// insert an entry that expires after `ttl`, return the old value
+    pub fn insert_with_ttl(&self, k: K, v: V, ttl: Duration) -> Option<V> {
+        let mut inner = self.inner.lock();
+        let old = inner.insert(k, v, Some(Instant::now() + ttl));
+        self.start_sweeper(&mut inner, ttl);
+        old
+    }
+
+    /// return the value of the key, or insert the one returned by `f` if absent
+    ///
+    /// `f` is called with the cache locked, keep it cheap
+    pub fn get_or_insert_with<F: FnOnce() -> V>(&self, k: K, f: F) -> V
+    where
+        V: Clone,
+    {
+        let now = Instant::now();
+        let mut inner = self.inner.lock();
+        if let Some(v) = inner.touch(&k, now) {
+            return v.clone();
+        }
+        let v = f();
+        inner.insert(k, v.clone(), self.ttl.map(|ttl| now + ttl));
+        if let Some(ttl) = self.ttl {
+            self.start_sweeper(&mut inner, ttl);
+        }
+        v
+    }
+
+    pub fn remove<Q: ?Sized + Hash + Eq>(&self, k: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+    {
+        self.inner.lock().remove(k)
+    }
+
+    pub fn clear(&self) {
+        let mut inner = self.inner.lock();
+        inner.map.clear();
+        inner.lru.clear();
+        inner.expires.clear();
+    }
+
+    /// remove all the expired entries now, return the number of them
+    pub fn purge_expired(&self) -> usize {
+        self.inner.lock().purge(Instant::now())
+    }
+
+    // schedule the sweep that removes the expired entries, if not pending yet
+    fn start_sweeper(&self, inner: &mut CacheInner<K, V>, ttl: Duration) {
+        if !inner.sweeping {
+            inner.sweeping = true;
+            sweep_after(
+                Arc::downgrade(&self.inner),
+                std::cmp::min(ttl, SWEEP_INTERVAL),
+            );
+        }
+    }
+}
+
+fn sweep_after<K, V>(inner: Weak<Mutex<CacheInner<K, V>>>, dur: Duration)
+where
+    K: Eq + Hash + Clone + Send + 'static,
+    V: Send + 'static,
+{
+    get_scheduler().add_callback_timer(dur, Box::new(move || sweep(inner)));
+}
+
+// runs in the timer thread, stops when the cache is dropped or has no TTL entry
+fn sweep<K, V>(weak: Weak<Mutex<CacheInner<K, V>>>)
+where
+    K: Eq + Hash + Clone + Send + 'static,
+    V: Send + 'static,
+{
+    let inner = match weak.upgrade() {
+        Some(inner) => inner,
+        None => return,
+    };
+    let mut inner = inner.lock();
+    let now = Instant::now();
+    inner.purge(now);
+    match inner.next_expire() {
+        Some(expire) => sweep_after(weak, std::cmp::min(expire - now, SWEEP_INTERVAL)),
+        None => inner.sweeping = false,
+    }
+}
+
+impl<K, V> fmt::Debug for Cache<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let inner = self.inner.lock();
+        write!(
+            f,
+            "Cache {{ len: {}, capacity: {} }}",
+            inner.map.len(),
+            inner.capacity
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lru_eviction() {
+        let cache = Cache::new(3);
+        for i in 0..3 {
+            cache.insert(i, i * 10);
+        }
+        assert_eq!(cache.get(&0), Some(0));
+        cache.insert(3, 30);
+        // 1 is the least recently used
+        assert!(!cache.contains_key(&1));
+        assert_eq!(cache.insert(0, 1), Some(0));
+        cache.insert(4, 40);
+        assert_eq!(cache.get(&2), None);
+        assert_eq!(cache.len(), 3);
+        assert_eq!(cache.remove(&3), Some(30));
+        assert_eq!(cache.get_or_insert_with(5, || 50), 50);
+        assert_eq!(cache.get_or_insert_with(5, || unreachable!()), 50);
+        cache.clear();
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn ttl_expire() {
+        let cache = Cache::new(10);
+        cache.insert_with_ttl(1, "a", Duration::from_millis(10));
+        cache.insert_with_ttl(2, "b", Duration::from_secs(60));
+        cache.insert(3, "c");
+        assert_eq!(cache.get(&1), Some("a"));
+        std::thread::sleep(Duration::from_millis(20));
+        assert_eq!(cache.get(&1), None);
+        assert!(!cache.contains_key(&1));
+        cache.insert_with_ttl(4, "d", Duration::from_millis(1));
+        std::thread::sleep(Duration::from_millis(5));
+        // removed by either the sweeper or the purge
+        cache.purge_expired();
+        assert!(!cache.contains_key(&4));
+        assert_eq!(cache.get(&2), Some("b"));
+        assert_eq!(cache.get(&3), Some("c"));
+        assert_eq!(cache.len(), 2);
+    }
+
+    #[test]
+    fn ttl_sweep() {
+        let cache = Cache::with_ttl(10, Duration::from_millis(10));
+        cache.insert(1, "a");
+        cache.insert_with_ttl(2, "b", Duration::from_millis(20));
+        // removed by the timer without reading the cache
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !cache.is_empty() && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert!(cache.is_empty());
+        assert!(!cache.inner.lock().sweeping);
+    }
+}
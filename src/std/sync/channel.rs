@@ -6,13 +6,15 @@
 //! would not see that the same data any more
 
 use std::fmt;
-use std::sync::atomic::{AtomicUsize, Ordering};
-use std::sync::mpsc::{RecvError, RecvTimeoutError, SendError, TryRecvError};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{TryRecvError, TrySendError};
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use super::Semphore;
+use crate::coroutine_impl::{current_cancel_data, is_coroutine};
 use crate::std::queue::seg_queue::SegQueue;
+use crate::yield_now::consume_budget;
 
 /// Create an unbounded channel. if If you want to limit the number of messages, use bounded channel_buf()
 pub fn channel<T>() -> (Sender<T>, Receiver<T>) {
@@ -30,6 +32,9 @@ pub fn unbounded<T>() -> (Sender<T>, Receiver<T>) {
 }
 
 /// Create a bounded channel
+///
+/// a zero `buf` creates a rendezvous channel like the unbuffered channel of go,
+/// `send` blocks until a receiver is ready to take the message
 pub fn bounded<T>(buf: usize) -> (Sender<T>, Receiver<T>) {
     let a = Arc::new(MPMCBuffer::new_buffer(buf));
     (Sender::new(a.clone()), Receiver::new(a))
@@ -47,6 +52,10 @@ pub fn bounded<T>(buf: usize) -> (Sender<T>, Receiver<T>) {
 ///   let (sender, receiver) = chan!();
 ///   sender.send(1);
 ///   let rv=receiver.recv();
+///   //rendezvous, the send blocks until the recv is ready
+///   let (sender, receiver) = chan!(0);
+///   let h = mco::co!(move || sender.send(1));
+///   let rv=receiver.recv();
 ///
 /// ```
 #[macro_export]
@@ -62,6 +71,174 @@ macro_rules! chan {
     };
 }
 
+/// the error returned by `Sender::send`, the message is returned back
+#[derive(PartialEq, Eq, Clone, Copy)]
+pub enum SendError<T> {
+    /// all the receivers are dropped or the channel is closed
+    Disconnected(T),
+    /// the wait is interrupted by the cancellation token or the context of the coroutine
+    Canceled(T),
+}
+
+impl<T> SendError<T> {
+    /// unwrap the message that is not sent
+    pub fn into_inner(self) -> T {
+        match self {
+            SendError::Disconnected(t) | SendError::Canceled(t) => t,
+        }
+    }
+
+    pub fn is_disconnected(&self) -> bool {
+        matches!(self, SendError::Disconnected(_))
+    }
+
+    pub fn is_canceled(&self) -> bool {
+        matches!(self, SendError::Canceled(_))
+    }
+}
+
+impl<T> fmt::Debug for SendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SendError::Disconnected(_) => "Disconnected(..)".fmt(f),
+            SendError::Canceled(_) => "Canceled(..)".fmt(f),
+        }
+    }
+}
+
+impl<T> fmt::Display for SendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SendError::Disconnected(_) => "sending on a disconnected channel".fmt(f),
+            SendError::Canceled(_) => "send operation canceled".fmt(f),
+        }
+    }
+}
+
+impl<T> std::error::Error for SendError<T> {}
+
+/// the error returned by `Sender::send_timeout`, the message is returned back
+#[derive(PartialEq, Eq, Clone, Copy)]
+pub enum SendTimeoutError<T> {
+    /// the message could not be sent before the timeout
+    Timeout(T),
+    /// all the receivers are dropped or the channel is closed
+    Disconnected(T),
+    /// the wait is interrupted by the cancellation token or the context of the coroutine
+    Canceled(T),
+}
+
+impl<T> SendTimeoutError<T> {
+    /// unwrap the message that is not sent
+    pub fn into_inner(self) -> T {
+        match self {
+            SendTimeoutError::Timeout(t)
+            | SendTimeoutError::Disconnected(t)
+            | SendTimeoutError::Canceled(t) => t,
+        }
+    }
+
+    pub fn is_timeout(&self) -> bool {
+        matches!(self, SendTimeoutError::Timeout(_))
+    }
+
+    pub fn is_disconnected(&self) -> bool {
+        matches!(self, SendTimeoutError::Disconnected(_))
+    }
+
+    pub fn is_canceled(&self) -> bool {
+        matches!(self, SendTimeoutError::Canceled(_))
+    }
+}
+
+impl<T> fmt::Debug for SendTimeoutError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SendTimeoutError::Timeout(_) => "Timeout(..)".fmt(f),
+            SendTimeoutError::Disconnected(_) => "Disconnected(..)".fmt(f),
+            SendTimeoutError::Canceled(_) => "Canceled(..)".fmt(f),
+        }
+    }
+}
+
+impl<T> fmt::Display for SendTimeoutError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SendTimeoutError::Timeout(_) => "timed out waiting on send operation".fmt(f),
+            SendTimeoutError::Disconnected(_) => "sending on a disconnected channel".fmt(f),
+            SendTimeoutError::Canceled(_) => "send operation canceled".fmt(f),
+        }
+    }
+}
+
+impl<T> std::error::Error for SendTimeoutError<T> {}
+
+impl<T> From<SendError<T>> for SendTimeoutError<T> {
+    fn from(err: SendError<T>) -> Self {
+        match err {
+            SendError::Disconnected(t) => SendTimeoutError::Disconnected(t),
+            SendError::Canceled(t) => SendTimeoutError::Canceled(t),
+        }
+    }
+}
+
+/// the error returned by `Receiver::recv`
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum RecvError {
+    /// the channel is empty, and it's closed or all the senders are dropped
+    Disconnected,
+    /// the wait is interrupted by the cancellation token or the context of the coroutine
+    Canceled,
+}
+
+impl fmt::Display for RecvError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RecvError::Disconnected => "receiving on a closed channel".fmt(f),
+            RecvError::Canceled => "receive operation canceled".fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for RecvError {}
+
+/// the error returned by `Receiver::recv_timeout`
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum RecvTimeoutError {
+    /// no message is received before the timeout
+    Timeout,
+    /// the channel is empty, and it's closed or all the senders are dropped
+    Disconnected,
+    /// the wait is interrupted by the cancellation token or the context of the coroutine
+    Canceled,
+}
+
+impl fmt::Display for RecvTimeoutError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RecvTimeoutError::Timeout => "timed out waiting on receive operation".fmt(f),
+            RecvTimeoutError::Disconnected => "receiving on a closed channel".fmt(f),
+            RecvTimeoutError::Canceled => "receive operation canceled".fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for RecvTimeoutError {}
+
+impl From<RecvError> for RecvTimeoutError {
+    fn from(err: RecvError) -> Self {
+        match err {
+            RecvError::Disconnected => RecvTimeoutError::Disconnected,
+            RecvError::Canceled => RecvTimeoutError::Canceled,
+        }
+    }
+}
+
+// the failed wait of the current coroutine is interrupted by the cancellation token
+fn interrupted() -> bool {
+    is_coroutine() && current_cancel_data().is_interrupted()
+}
+
 /// /////////////////////////////////////////////////////////////////////////////
 /// MPMCBuffer
 /// /////////////////////////////////////////////////////////////////////////////
@@ -77,10 +254,53 @@ struct MPMCBuffer<T> {
     sender_num: AtomicUsize,
     // The number of receiver
     receiver_num: AtomicUsize,
+    // for the zero capacity channel, the number of messages that are sent
+    // to the receivers which gave up waiting, see `Ticket`
+    orphan: AtomicUsize,
+    // closed explicitly by `Sender::close`
+    closed: AtomicBool,
+    // the max number of the buffered messages
+    high_water_mark: AtomicUsize,
+}
+
+// a zero capacity channel has no buffer slot, a receiver posts a ticket to
+// `wake_sender` before waiting so that exactly one sender can hand off a
+// message to it. when the receiver gives up waiting the ticket is retracted,
+// if it's already taken by a sender the message is recorded as an orphan
+// that the next receiver picks up without posting a new ticket
+struct Ticket<'a, T> {
+    buf: &'a MPMCBuffer<T>,
+    // true if the ticket is posted by us, false if we claimed an orphan
+    posted: bool,
+}
+
+impl<T> Ticket<'_, T> {
+    fn new(buf: &MPMCBuffer<T>) -> Ticket<'_, T> {
+        let posted = !buf.claim_orphan();
+        if posted {
+            buf.wake_sender.post();
+        }
+        Ticket { buf, posted }
+    }
+
+    // the message is received
+    fn done(self) {
+        std::mem::forget(self);
+    }
+}
+
+impl<T> Drop for Ticket<'_, T> {
+    fn drop(&mut self) {
+        if !self.posted || !self.buf.wake_sender.try_wait() {
+            self.buf.orphan.fetch_add(1, Ordering::SeqCst);
+        }
+    }
 }
 
 impl<T> MPMCBuffer<T> {
     /// have buffer channel. If the buffered message exceeds the limit, the sender blocks until the message is consumed
+    ///
+    /// a zero `buffer` makes a rendezvous channel, the sender blocks until a receiver is ready
     pub fn new_buffer(buffer: usize) -> MPMCBuffer<T> {
         MPMCBuffer {
             buffer: SegQueue::new(),
@@ -89,59 +309,134 @@ impl<T> MPMCBuffer<T> {
             buffer_limit: buffer,
             sender_num: AtomicUsize::new(1),
             receiver_num: AtomicUsize::new(1),
+            orphan: AtomicUsize::new(0),
+            closed: AtomicBool::new(false),
+            high_water_mark: AtomicUsize::new(0),
+        }
+    }
+
+    #[inline]
+    fn is_rendezvous(&self) -> bool {
+        self.buffer_limit == 0
+    }
+
+    fn claim_orphan(&self) -> bool {
+        let mut n = self.orphan.load(Ordering::SeqCst);
+        while n > 0 {
+            match self
+                .orphan
+                .compare_exchange(n, n - 1, Ordering::SeqCst, Ordering::SeqCst)
+            {
+                Ok(_) => return true,
+                Err(x) => n = x,
+            }
         }
+        false
     }
 
     /// send one message. If the length limit is exceeded or chan closed, wait for the message to be consumed
-    pub fn send(&self, t: T) -> Result<(), SendError<T>> {
-        if self.receiver_num.load(Ordering::Acquire) == 0 {
-            return Err(SendError(t));
+    pub fn send(&self, t: T, dur: Option<Duration>) -> Result<(), SendTimeoutError<T>> {
+        consume_budget();
+        if self.is_send_closed() {
+            return Err(SendTimeoutError::Disconnected(t));
         }
-        loop {
-            if self.buffer.len() >= self.buffer_limit {
-                self.wake_sender.wait();
-            } else {
-                break;
+        let deadline = dur.map(|d| Instant::now() + d);
+        // the send observes the cancellation token of the coroutine
+        let _g = is_coroutine().then(|| current_cancel_data().interruptible());
+        // return false if timeout or interrupted
+        let wait = || match deadline {
+            None => self.wake_sender.wait_timeout_impl(None),
+            Some(deadline) => {
+                let now = Instant::now();
+                now < deadline && self.wake_sender.wait_timeout(deadline - now)
             }
+        };
+        let fail = |t| match interrupted() {
+            true => SendTimeoutError::Canceled(t),
+            false => SendTimeoutError::Timeout(t),
+        };
+        if self.is_rendezvous() {
+            // wait for a receiver that is ready
+            if !wait() {
+                return Err(fail(t));
+            }
+        } else {
+            while self.buffer.len() >= self.buffer_limit {
+                if !wait() {
+                    return Err(fail(t));
+                }
+                if self.is_send_closed() {
+                    break;
+                }
+            }
+        }
+        // woken up by the last receiver dropped
+        if self.is_send_closed() {
+            return Err(SendTimeoutError::Disconnected(t));
         }
         self.buffer.push(t);
         self.wake_recv.post();
+        self.update_high_water_mark();
         Ok(())
     }
 
     /// try send one message.If the length limit is exceeded or chan closed, return a error
-    pub fn try_send(&self, t: T) -> Result<(), SendError<T>> {
-        if self.receiver_num.load(Ordering::Acquire) == 0 {
-            return Err(SendError(t));
+    pub fn try_send(&self, t: T) -> Result<(), TrySendError<T>> {
+        if self.is_send_closed() {
+            return Err(TrySendError::Disconnected(t));
         }
-        if self.buffer.len() >= self.buffer_limit {
-            return Err(SendError(t));
+        if self.is_rendezvous() {
+            if !self.wake_sender.try_wait() {
+                return Err(TrySendError::Full(t));
+            }
+        } else if self.buffer.len() >= self.buffer_limit {
+            return Err(TrySendError::Full(t));
         }
         self.buffer.push(t);
         self.wake_recv.post();
+        self.update_high_water_mark();
         Ok(())
     }
 
     /// wake one sender
+    ///
+    /// for the zero capacity channel a message is taken without a ticket,
+    /// post a ticket for the receiver that the message is sent to
     #[inline]
     fn wake_sender(&self) {
-        self.wake_sender.post();
+        if !self.is_rendezvous() || !self.claim_orphan() {
+            self.wake_sender.post();
+        }
     }
 
     /// received a message. If the message is empty, a wait is entered, and an error is returned if the channel is closed
     /// If you want to try to receive a message, use try_recv
     pub fn recv(&self, dur: Option<Duration>) -> Result<T, RecvTimeoutError> {
+        consume_budget();
+        if self.is_rendezvous() {
+            return self.recv_rendezvous(dur);
+        }
         match self.try_recv() {
             Ok(data) => return Ok(data),
             Err(TryRecvError::Empty) => {}
             Err(TryRecvError::Disconnected) => return Err(RecvTimeoutError::Disconnected),
         }
 
+        // the recv observes the cancellation token of the coroutine
+        let _g = is_coroutine().then(|| current_cancel_data().interruptible());
         match dur {
-            None => self.wake_recv.wait(),
+            // only the cancellation token can break the wait
+            None => {
+                if !self.wake_recv.wait_timeout_impl(None) {
+                    return Err(RecvTimeoutError::Canceled);
+                }
+            }
             Some(t) => {
                 if !self.wake_recv.wait_timeout(t) {
-                    return Err(RecvTimeoutError::Timeout);
+                    return Err(match interrupted() {
+                        true => RecvTimeoutError::Canceled,
+                        false => RecvTimeoutError::Timeout,
+                    });
                 }
             }
         }
@@ -151,18 +446,50 @@ impl<T> MPMCBuffer<T> {
                 self.wake_sender();
                 Ok(data)
             }
-            None => match self.sender_num.load(Ordering::Acquire) {
-                0 => Err(RecvTimeoutError::Disconnected),
-                _n => unreachable!("mpmc recv found no data"),
-            },
+            None if self.is_recv_closed() => {
+                // pass the wakeup to the other receivers
+                self.wake_recv.post();
+                Err(RecvTimeoutError::Disconnected)
+            }
+            None => unreachable!("mpmc recv found no data"),
+        }
+    }
+
+    // the receiver of the zero capacity channel, which must tell the senders
+    // that it's ready by posting a ticket before waiting
+    fn recv_rendezvous(&self, dur: Option<Duration>) -> Result<T, RecvTimeoutError> {
+        if self.is_recv_closed() {
+            return self.try_recv().map_err(|_| RecvTimeoutError::Disconnected);
+        }
+        let ticket = Ticket::new(self);
+        let _g = is_coroutine().then(|| current_cancel_data().interruptible());
+        if !self.wake_recv.wait_timeout_impl(dur) {
+            return Err(match interrupted() {
+                true => RecvTimeoutError::Canceled,
+                false => RecvTimeoutError::Timeout,
+            });
+        }
+
+        match self.buffer.pop() {
+            Some(data) => {
+                ticket.done();
+                Ok(data)
+            }
+            None if self.is_recv_closed() => {
+                // pass the wakeup to the other receivers
+                self.wake_recv.post();
+                Err(RecvTimeoutError::Disconnected)
+            }
+            None => unreachable!("mpmc recv found no data"),
         }
     }
 
     pub fn try_recv(&self) -> Result<T, TryRecvError> {
         if !self.wake_recv.try_wait() {
-            return match self.sender_num.load(Ordering::Acquire) {
-                0 => Err(TryRecvError::Disconnected),
-                _ => Err(TryRecvError::Empty),
+            return if self.is_recv_closed() {
+                Err(TryRecvError::Disconnected)
+            } else {
+                Err(TryRecvError::Empty)
             };
         }
 
@@ -171,10 +498,34 @@ impl<T> MPMCBuffer<T> {
                 self.wake_sender();
                 Ok(data)
             }
-            None => match self.sender_num.load(Ordering::Acquire) {
-                0 => Err(TryRecvError::Disconnected),
-                _ => unreachable!("mpmc try_recv found no data"),
-            },
+            None if self.is_recv_closed() => {
+                self.wake_recv.post();
+                Err(TryRecvError::Disconnected)
+            }
+            None => unreachable!("mpmc try_recv found no data"),
+        }
+    }
+
+    // no more messages can be sent
+    fn is_send_closed(&self) -> bool {
+        self.closed.load(Ordering::Acquire) || self.receiver_num.load(Ordering::Acquire) == 0
+    }
+
+    // no more messages would be sent, the buffered ones are still received
+    fn is_recv_closed(&self) -> bool {
+        self.closed.load(Ordering::Acquire) || self.sender_num.load(Ordering::Acquire) == 0
+    }
+
+    /// close the channel, the waiting senders and receivers are woken up
+    pub fn close(&self) {
+        if self.closed.swap(true, Ordering::AcqRel) {
+            return;
+        }
+        while self.wake_recv.get_value() == 0 {
+            self.wake_recv.post();
+        }
+        while self.wake_sender.get_value() == 0 {
+            self.wake_sender.post();
         }
     }
 
@@ -205,6 +556,10 @@ impl<T> MPMCBuffer<T> {
             1 => {
                 // there is no receiver any more, clear the data
                 while self.buffer.pop().is_some() {}
+                // and tell all the waited senders to come back
+                while self.wake_sender.get_value() == 0 {
+                    self.wake_sender.post();
+                }
             }
             n if n > 1 => {}
             n => panic!("bad number of recv_ports left {}", n),
@@ -216,6 +571,32 @@ impl<T> MPMCBuffer<T> {
         self.buffer.len()
     }
 
+    /// return the buffer limit, `None` for the unbounded channel
+    pub fn capacity(&self) -> Option<usize> {
+        match self.buffer_limit {
+            usize::MAX => None,
+            n => Some(n),
+        }
+    }
+
+    #[inline]
+    fn update_high_water_mark(&self) {
+        // the pending permits of the receivers is the cheap queue length
+        let len = self.wake_recv.get_value();
+        if len > self.high_water_mark.load(Ordering::Relaxed) {
+            self.high_water_mark.fetch_max(len, Ordering::Relaxed);
+        }
+    }
+
+    pub fn high_water_mark(&self) -> usize {
+        self.high_water_mark.load(Ordering::Relaxed)
+    }
+
+    pub fn reset_high_water_mark(&self) -> usize {
+        self.high_water_mark
+            .swap(self.wake_recv.get_value(), Ordering::Relaxed)
+    }
+
     pub fn sender_num(&self) -> usize {
         self.sender_num.load(Ordering::SeqCst)
     }
@@ -251,6 +632,42 @@ impl<T> Receiver<T> {
     pub fn receiver_num(&self) -> usize {
         self.inner.receiver_num()
     }
+    /// number of the buffered messages, the same as `remain`
+    pub fn len(&self) -> usize {
+        self.inner.remain()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// return the buffer limit, `None` for the unbounded channel
+    pub fn capacity(&self) -> Option<usize> {
+        self.inner.capacity()
+    }
+
+    /// Number of channel senders, the same as `sender_num`
+    pub fn sender_count(&self) -> usize {
+        self.inner.sender_num()
+    }
+
+    /// Number of channel receivers, the same as `receiver_num`
+    pub fn receiver_count(&self) -> usize {
+        self.inner.receiver_num()
+    }
+
+    /// the max number of the buffered messages since the channel is created
+    /// or the last `reset_high_water_mark`
+    pub fn high_water_mark(&self) -> usize {
+        self.inner.high_water_mark()
+    }
+
+    /// reset the high water mark to the current length, return the old one
+    ///
+    /// call it each time the metrics are exported to get the peak of the period
+    pub fn reset_high_water_mark(&self) -> usize {
+        self.inner.reset_high_water_mark()
+    }
 }
 
 unsafe impl<T: Send> Send for Receiver<T> {}
@@ -264,6 +681,12 @@ pub struct TryIter<'a, T: 'a> {
     inner: &'a Receiver<T>,
 }
 
+pub struct Drain<'a, T: 'a> {
+    inner: &'a Receiver<T>,
+    // the number of the messages left to take
+    remain: usize,
+}
+
 pub struct IntoIter<T> {
     inner: Receiver<T>,
 }
@@ -287,6 +710,42 @@ impl<T> Sender<T> {
     pub fn receiver_num(&self) -> usize {
         self.inner.receiver_num()
     }
+    /// number of the buffered messages, the same as `remain`
+    pub fn len(&self) -> usize {
+        self.inner.remain()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// return the buffer limit, `None` for the unbounded channel
+    pub fn capacity(&self) -> Option<usize> {
+        self.inner.capacity()
+    }
+
+    /// Number of channel senders, the same as `sender_num`
+    pub fn sender_count(&self) -> usize {
+        self.inner.sender_num()
+    }
+
+    /// Number of channel receivers, the same as `receiver_num`
+    pub fn receiver_count(&self) -> usize {
+        self.inner.receiver_num()
+    }
+
+    /// the max number of the buffered messages since the channel is created
+    /// or the last `reset_high_water_mark`
+    pub fn high_water_mark(&self) -> usize {
+        self.inner.high_water_mark()
+    }
+
+    /// reset the high water mark to the current length, return the old one
+    ///
+    /// call it each time the metrics are exported to get the peak of the period
+    pub fn reset_high_water_mark(&self) -> usize {
+        self.inner.reset_high_water_mark()
+    }
 }
 
 /// /////////////////////////////////////////////////////////////////////////////
@@ -299,15 +758,41 @@ impl<T> Sender<T> {
     }
 
     /// send one message. If the length limit is exceeded or chan closed, wait for the message to be consumed
+    ///
+    /// in a coroutine the wait returns `SendError::Canceled` once the
+    /// cancellation token or the context of the coroutine is done
     pub fn send(&self, t: T) -> Result<(), SendError<T>> {
-        self.inner.send(t)
+        self.inner.send(t, None).map_err(|e| match e {
+            SendTimeoutError::Canceled(t) => SendError::Canceled(t),
+            e => SendError::Disconnected(e.into_inner()),
+        })
     }
 
-    /// try send one message.If the length limit is exceeded or chan closed, return a error
-    pub fn try_send(&self, t: T) -> Result<(), SendError<T>> {
+    /// same as `send` except that with an extra timeout value,
+    /// the message is returned back if it's not sent in time
+    pub fn send_timeout(&self, t: T, timeout: Duration) -> Result<(), SendTimeoutError<T>> {
+        self.inner.send(t, Some(timeout))
+    }
+
+    /// try send one message. If the length limit is exceeded or chan closed,
+    /// return the message back in the error
+    pub fn try_send(&self, t: T) -> Result<(), TrySendError<T>> {
         self.inner.try_send(t)
     }
 
+    /// close the channel like the `close` of go, independent of the other senders
+    ///
+    /// the following sends fail, the receivers still get the buffered messages
+    /// and then get an error, so that `for msg in rx` terminates
+    pub fn close(&self) {
+        self.inner.close()
+    }
+
+    /// return true if the channel is closed or all the receivers are dropped
+    pub fn is_closed(&self) -> bool {
+        self.inner.is_send_closed()
+    }
+
     /// return how many elements in the queue that are not consumed by receivers
     pub fn pressure(&self) -> usize {
         self.inner.wake_recv.get_value()
@@ -346,12 +831,22 @@ impl<T> Receiver<T> {
         self.inner.try_recv()
     }
 
+    /// return true if the channel is closed or all the senders are dropped,
+    /// the buffered messages can still be received
+    pub fn is_closed(&self) -> bool {
+        self.inner.is_recv_closed()
+    }
+
     /// received a message. If the message is empty, a wait is entered, and an error is returned if the channel is closed
     /// If you want to try to receive a message, use try_recv
+    ///
+    /// in a coroutine the wait returns `RecvError::Canceled` once the
+    /// cancellation token or the context of the coroutine is done
     pub fn recv(&self) -> Result<T, RecvError> {
         match self.inner.recv(None) {
             Err(RecvTimeoutError::Timeout) => unreachable!("mpmc recv timeout"),
-            data => data.map_err(|_| RecvError),
+            Err(RecvTimeoutError::Canceled) => Err(RecvError::Canceled),
+            data => data.map_err(|_| RecvError::Disconnected),
         }
     }
 
@@ -359,13 +854,26 @@ impl<T> Receiver<T> {
         self.inner.recv(Some(timeout))
     }
 
-    pub fn iter(&self) -> Iter<T> {
+    /// return an iterator that blocks on each message,
+    /// it ends when the channel is closed or all the senders are dropped
+    pub fn iter(&self) -> Iter<'_, T> {
         Iter { inner: self }
     }
 
-    pub fn try_iter(&self) -> TryIter<T> {
+    /// return an iterator that takes the messages without blocking,
+    /// it ends when there is no message available
+    pub fn try_iter(&self) -> TryIter<'_, T> {
         TryIter { inner: self }
     }
+
+    /// return an iterator that takes the messages that are buffered now
+    /// without blocking, the messages sent after the call are left
+    pub fn drain(&self) -> Drain<'_, T> {
+        Drain {
+            inner: self,
+            remain: self.remain(),
+        }
+    }
 }
 
 impl<'a, T> Iterator for Iter<'a, T> {
@@ -384,6 +892,22 @@ impl<'a, T> Iterator for TryIter<'a, T> {
     }
 }
 
+impl<'a, T> Iterator for Drain<'a, T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        if self.remain == 0 {
+            return None;
+        }
+        self.remain -= 1;
+        self.inner.try_recv().ok()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.remain))
+    }
+}
+
 impl<'a, T> IntoIterator for &'a Receiver<T> {
     type Item = T;
     type IntoIter = Iter<'a, T>;
@@ -434,7 +958,7 @@ mod tests {
     use crate::coroutine::sleep;
     use crate::std::sync::WaitGroup;
     use std::env;
-    use std::sync::mpsc::{RecvTimeoutError, TryRecvError};
+    use std::sync::mpsc::TryRecvError;
     use std::thread;
     use std::time::Duration;
 
@@ -915,6 +1439,7 @@ mod tests {
                 }
                 Err(RecvTimeoutError::Timeout) => continue,
                 Err(RecvTimeoutError::Disconnected) => break,
+                Err(RecvTimeoutError::Canceled) => unreachable!(),
             }
         }
 
@@ -959,6 +1484,7 @@ mod tests {
                 }
                 Err(RecvTimeoutError::Timeout) => continue,
                 Err(RecvTimeoutError::Disconnected) => break,
+                Err(RecvTimeoutError::Canceled) => unreachable!(),
             }
         }
 
@@ -1197,4 +1723,199 @@ mod tests {
         }
         assert_eq!(rx1.try_recv().is_err(), true);
     }
+
+    #[test]
+    fn rendezvous() {
+        let (tx, rx) = chan!(0);
+        // no receiver is ready
+        assert_eq!(tx.try_send(1), Err(TrySendError::Full(1)));
+        assert_eq!(
+            rx.recv_timeout(Duration::from_millis(10)),
+            Err(RecvTimeoutError::Timeout)
+        );
+        // the ticket of the timed out receiver is retracted
+        assert_eq!(
+            tx.send_timeout(1, Duration::from_millis(10)),
+            Err(SendTimeoutError::Timeout(1))
+        );
+
+        let sent = Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let sent1 = sent.clone();
+        let h = thread::spawn(move || {
+            tx.send(1).unwrap();
+            sent1.store(true, Ordering::SeqCst);
+            tx
+        });
+        thread::sleep(Duration::from_millis(50));
+        // the sender is blocked until the receiver is ready
+        assert!(!sent.load(Ordering::SeqCst));
+        assert_eq!(rx.recv(), Ok(1));
+        let tx = h.join().unwrap();
+        assert!(sent.load(Ordering::SeqCst));
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Empty));
+
+        drop(tx);
+        assert_eq!(rx.recv(), Err(RecvError::Disconnected));
+    }
+
+    #[test]
+    fn rendezvous_threads() {
+        let (tx, rx) = bounded::<usize>(0);
+        let senders: Vec<_> = (0..4)
+            .map(|i| {
+                let tx = tx.clone();
+                thread::spawn(move || {
+                    for j in 0..100 {
+                        tx.send(i * 100 + j).unwrap();
+                    }
+                })
+            })
+            .collect();
+        drop(tx);
+        let receivers: Vec<_> = (0..3)
+            .map(|_| {
+                let rx = rx.clone();
+                thread::spawn(move || {
+                    let mut sum = 0;
+                    while let Ok(v) = rx
+                        .recv_timeout(Duration::from_millis(1))
+                        .or_else(|e| match e {
+                            RecvTimeoutError::Timeout => rx.recv().map_err(|_| e),
+                            e => Err(e),
+                        })
+                    {
+                        sum += v;
+                    }
+                    sum
+                })
+            })
+            .collect();
+        for h in senders {
+            h.join().unwrap();
+        }
+        let sum: usize = receivers.into_iter().map(|h| h.join().unwrap()).sum();
+        assert_eq!(sum, (0..400).sum());
+    }
+
+    #[test]
+    fn rendezvous_receiver_gone() {
+        let (tx, rx) = bounded::<i32>(0);
+        let h = thread::spawn(move || tx.send(1));
+        thread::sleep(Duration::from_millis(10));
+        drop(rx);
+        assert_eq!(h.join().unwrap(), Err(SendError::Disconnected(1)));
+    }
+
+    #[test]
+    fn send_timeout() {
+        let (tx, rx) = bounded::<i32>(1);
+        tx.send_timeout(1, Duration::from_millis(10)).unwrap();
+        assert_eq!(tx.try_send(2), Err(TrySendError::Full(2)));
+        let err = tx.send_timeout(2, Duration::from_millis(10)).unwrap_err();
+        assert!(err.is_timeout());
+        assert_eq!(err.into_inner(), 2);
+
+        let h = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(20));
+            assert_eq!(rx.recv(), Ok(1));
+            rx
+        });
+        tx.send_timeout(2, Duration::from_secs(10)).unwrap();
+        let rx = h.join().unwrap();
+        drop(rx);
+        assert_eq!(tx.try_send(3), Err(TrySendError::Disconnected(3)));
+        assert_eq!(
+            tx.send_timeout(3, Duration::from_millis(10)),
+            Err(SendTimeoutError::Disconnected(3))
+        );
+    }
+
+    #[test]
+    fn close() {
+        let (tx, rx) = bounded::<i32>(4);
+        let tx2 = tx.clone();
+        tx.send(1).unwrap();
+        tx.send(2).unwrap();
+        tx.close();
+        assert!(tx2.is_closed());
+        assert!(rx.is_closed());
+        assert_eq!(tx2.send(3), Err(SendError::Disconnected(3)));
+        assert_eq!(tx2.try_send(3), Err(TrySendError::Disconnected(3)));
+        // the buffered messages are drained first
+        assert_eq!(rx.iter().collect::<Vec<_>>(), vec![1, 2]);
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Disconnected));
+        assert_eq!(rx.recv(), Err(RecvError::Disconnected));
+    }
+
+    #[test]
+    fn close_wakeup() {
+        let (tx, rx) = bounded::<i32>(0);
+        let receivers: Vec<_> = (0..3)
+            .map(|_| {
+                let rx = rx.clone();
+                thread::spawn(move || rx.into_iter().count())
+            })
+            .collect();
+        thread::sleep(Duration::from_millis(10));
+        tx.send(1).unwrap();
+        tx.close();
+        let n: usize = receivers.into_iter().map(|h| h.join().unwrap()).sum();
+        assert_eq!(n, 1);
+
+        // the blocked sender is woken up as well
+        let (tx, _rx) = bounded::<i32>(1);
+        tx.send(1).unwrap();
+        let tx2 = tx.clone();
+        let h = thread::spawn(move || tx2.send(2));
+        thread::sleep(Duration::from_millis(10));
+        tx.close();
+        assert_eq!(h.join().unwrap(), Err(SendError::Disconnected(2)));
+    }
+
+    #[test]
+    fn drain() {
+        let (tx, rx) = channel::<i32>();
+        for i in 0..3 {
+            tx.send(i).unwrap();
+        }
+        let mut drain = rx.drain();
+        assert_eq!(drain.next(), Some(0));
+        // sent after the drain is created
+        tx.send(3).unwrap();
+        assert_eq!(drain.collect::<Vec<_>>(), vec![1, 2]);
+        assert_eq!(rx.try_iter().collect::<Vec<_>>(), vec![3]);
+        assert_eq!(rx.drain().next(), None);
+
+        let h = thread::spawn(move || {
+            for i in 0..3 {
+                tx.send(i).unwrap();
+                thread::sleep(Duration::from_millis(1));
+            }
+        });
+        assert_eq!(rx.iter().sum::<i32>(), 3);
+        h.join().unwrap();
+    }
+
+    #[test]
+    fn metrics() {
+        let (tx, rx) = bounded::<i32>(8);
+        let rx2 = rx.clone();
+        assert_eq!(tx.capacity(), Some(8));
+        assert_eq!(channel::<i32>().0.capacity(), None);
+        assert_eq!(tx.sender_count(), 1);
+        assert_eq!(rx.receiver_count(), 2);
+        assert!(rx.is_empty());
+
+        for i in 0..5 {
+            tx.send(i).unwrap();
+        }
+        assert_eq!(rx.len(), 5);
+        rx2.recv().unwrap();
+        rx2.recv().unwrap();
+        tx.try_send(5).unwrap();
+        assert_eq!(tx.len(), 4);
+        assert_eq!(rx.high_water_mark(), 5);
+        assert_eq!(rx.reset_high_water_mark(), 5);
+        assert_eq!(tx.high_water_mark(), 4);
+    }
 }
@@ -2,7 +2,7 @@
 //! please ref the doc from std::sync::condvar
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::{Arc, LockResult, PoisonError};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use crate::cancel::trigger_cancel_panic;
 use crate::park::ParkError;
@@ -28,6 +28,8 @@ pub struct Condvar {
     to_wake: SegQueue<Arc<SyncBlocker>>,
     // used to verify the same mutex instance
     mutex: AtomicUsize,
+    // number of the waiters that are not returned yet
+    waiters: AtomicUsize,
 }
 
 impl Condvar {
@@ -35,6 +37,7 @@ impl Condvar {
         Condvar {
             to_wake: SegQueue::new(),
             mutex: AtomicUsize::new(0),
+            waiters: AtomicUsize::new(0),
         }
     }
 
@@ -52,6 +55,7 @@ impl Condvar {
         if let Some(c) = cancel.as_ref() {
             c.disable_cancel();
         }
+        self.waiters.fetch_add(1, Ordering::Relaxed);
         self.to_wake.push(cur.clone());
         // unlock the mutex to let other continue
         mutex::unlock_mutex(lock);
@@ -61,6 +65,7 @@ impl Condvar {
 
         // wait until coming back
         let ret = cur.park(dur);
+        self.waiters.fetch_sub(1, Ordering::Relaxed);
         // disable cancel panic
         if let Some(c) = cancel.as_ref() {
             c.disable_cancel();
@@ -148,6 +153,59 @@ impl Condvar {
         }
     }
 
+    /// block until the `condition` returns false, the spurious wakeups are
+    /// handled inside, the `condition` is checked with the lock held
+    pub fn wait_while<'a, T, F>(
+        &self,
+        mut guard: MutexGuard<'a, T>,
+        mut condition: F,
+    ) -> LockResult<MutexGuard<'a, T>>
+    where
+        F: FnMut(&mut T) -> bool,
+    {
+        while condition(&mut *guard) {
+            guard = self.wait(guard)?;
+        }
+        Ok(guard)
+    }
+
+    /// same as `wait_while` but gives up after `dur`, the returned result
+    /// is timed out if the `condition` still holds
+    pub fn wait_timeout_while<'a, T, F>(
+        &self,
+        mut guard: MutexGuard<'a, T>,
+        dur: Duration,
+        mut condition: F,
+    ) -> LockResult<(MutexGuard<'a, T>, WaitTimeoutResult)>
+    where
+        F: FnMut(&mut T) -> bool,
+    {
+        let deadline = Instant::now() + dur;
+        loop {
+            if !condition(&mut *guard) {
+                return Ok((guard, WaitTimeoutResult(false)));
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return Ok((guard, WaitTimeoutResult(true)));
+            }
+            guard = match self.wait_timeout(guard, deadline - now) {
+                Ok((guard, _)) => guard,
+                Err(e) => {
+                    let (guard, timeout) = e.into_inner();
+                    return Err(PoisonError::new((guard, timeout)));
+                }
+            };
+        }
+    }
+
+    /// number of the threads and coroutines that are waiting on the condvar
+    ///
+    /// it's only a snapshot for debugging, the value may change at any time
+    pub fn waiters(&self) -> usize {
+        self.waiters.load(Ordering::Relaxed)
+    }
+
     pub fn notify_one(&self) -> Result<(), ParkError> {
         // NOTICE: the following code would not drop the lock!
         let w = self.to_wake.pop();
@@ -286,6 +344,35 @@ mod tests {
         drop(g);
     }
 
+    #[test]
+    fn wait_while() {
+        let pair = Arc::new((Mutex::new(0), Condvar::new()));
+        let pair2 = pair.clone();
+        let _t = thread::spawn(move || {
+            let (lock, cvar) = &*pair2;
+            for _ in 0..3 {
+                while cvar.waiters() == 0 {
+                    thread::sleep(Duration::from_millis(1));
+                }
+                *lock.lock().unwrap() += 1;
+                cvar.notify_one();
+            }
+        });
+        let (lock, cvar) = &*pair;
+        let g = cvar.wait_while(lock.lock().unwrap(), |n| *n < 3).unwrap();
+        assert_eq!(*g, 3);
+        assert_eq!(cvar.waiters(), 0);
+
+        let (g, res) = cvar
+            .wait_timeout_while(g, Duration::from_millis(10), |n| *n < 4)
+            .unwrap();
+        assert!(res.timed_out());
+        let (_g, res) = cvar
+            .wait_timeout_while(g, Duration::from_millis(10), |n| *n < 3)
+            .unwrap();
+        assert!(!res.timed_out());
+    }
+
     #[test]
     #[should_panic]
     fn two_mutexes() {
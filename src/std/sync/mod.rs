@@ -1,12 +1,17 @@
 #[macro_use]
 mod atomic_option;
+mod barrier;
 mod blocking;
+mod cache;
 mod condvar;
 mod mutex;
+mod notify;
 mod once;
 mod poison;
+mod reentrant_mutex;
 mod rwlock;
 mod semphore;
+mod sharded_hash_map;
 mod sync_array_queue;
 mod sync_btree_map;
 mod sync_flag;
@@ -20,15 +25,21 @@ pub(crate) mod atomic_dur;
 pub(crate) mod delay_drop;
 #[macro_use]
 pub mod channel;
+pub mod watch;
 
 pub use self::atomic_option::*;
+pub use self::barrier::*;
 pub use self::blocking::*;
+pub use self::cache::*;
 pub use self::channel::*;
 pub use self::condvar::*;
 pub use self::mutex::*;
+pub use self::notify::*;
 pub use self::once::*;
+pub use self::reentrant_mutex::*;
 pub use self::rwlock::*;
 pub use self::semphore::*;
+pub use self::sharded_hash_map::*;
 pub use self::sync_array_queue::*;
 pub use self::sync_btree_map::*;
 pub use self::sync_flag::*;
@@ -36,3 +47,4 @@ pub use self::sync_hash_map::*;
 pub use self::sync_queue::*;
 pub use self::sync_vec::*;
 pub use self::wait_group::*;
+pub use crate::std::lazy::sync::{Lazy, OnceCell};
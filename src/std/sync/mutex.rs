@@ -7,12 +7,15 @@ use std::ops::{Deref, DerefMut};
 use std::panic::{RefUnwindSafe, UnwindSafe};
 use std::sync::atomic::{fence, AtomicUsize, Ordering};
 use std::sync::Arc;
-use std::sync::{LockResult, TryLockError, TryLockResult};
+use std::sync::{LockResult, PoisonError, TryLockError, TryLockResult};
+use std::time::Duration;
 
 use super::blocking::SyncBlocker;
 use super::poison;
 use crate::cancel::trigger_cancel_panic;
+use crate::coroutine_impl::{current_cancel_data, is_coroutine};
 use crate::park::ParkError;
+use crate::yield_now::consume_budget;
 
 pub struct Mutex<T: ?Sized> {
     // the waiting blocker list
@@ -57,13 +60,89 @@ impl<T> Mutex<T> {
     }
 }
 
+/// the error returned by `Mutex::lock_interruptible`
+pub enum LockError<G> {
+    /// the lock is acquired but the mutex is poisoned
+    Poisoned(PoisonError<G>),
+    /// the lock is not acquired in time
+    Timeout,
+    /// the wait is interrupted by the cancellation token or the context of the coroutine
+    Canceled,
+}
+
+impl<G> From<PoisonError<G>> for LockError<G> {
+    fn from(err: PoisonError<G>) -> Self {
+        LockError::Poisoned(err)
+    }
+}
+
+impl<G> fmt::Debug for LockError<G> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LockError::Poisoned(_) => "Poisoned(..)".fmt(f),
+            LockError::Timeout => "Timeout".fmt(f),
+            LockError::Canceled => "Canceled".fmt(f),
+        }
+    }
+}
+
+impl<G> fmt::Display for LockError<G> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LockError::Poisoned(e) => e.fmt(f),
+            LockError::Timeout => "timed out waiting on the lock".fmt(f),
+            LockError::Canceled => "lock operation canceled".fmt(f),
+        }
+    }
+}
+
+impl<G> std::error::Error for LockError<G> {}
+
 impl<T: ?Sized> Mutex<T> {
     pub fn lock(&self) -> LockResult<MutexGuard<T>> {
+        consume_budget();
+        match self.lock_impl(None, false) {
+            Ok(g) => Ok(g),
+            Err(LockError::Poisoned(e)) => Err(e),
+            Err(_) => unreachable!("mutex timeout"),
+        }
+    }
+
+    /// same as `lock` except that with an extra timeout value
+    ///
+    /// return `Err(TryLockError::WouldBlock)` if the lock is not acquired in time
+    pub fn lock_timeout(&self, dur: Duration) -> TryLockResult<MutexGuard<T>> {
+        consume_budget();
+        match self.lock_impl(Some(dur), false) {
+            Ok(g) => Ok(g),
+            Err(LockError::Poisoned(e)) => Err(TryLockError::Poisoned(e)),
+            Err(_) => Err(TryLockError::WouldBlock),
+        }
+    }
+
+    /// same as `lock` except that the wait observes the cancellation token
+    /// and the context of the coroutine, with an optional timeout value
+    ///
+    /// return `Err(LockError::Canceled)` once the token or the context is
+    /// done, and `Err(LockError::Timeout)` if the lock is not acquired in time
+    pub fn lock_interruptible(
+        &self,
+        dur: Option<Duration>,
+    ) -> Result<MutexGuard<'_, T>, LockError<MutexGuard<'_, T>>> {
+        consume_budget();
+        self.lock_impl(dur, true)
+    }
+
+    fn lock_impl(
+        &self,
+        dur: Option<Duration>,
+        interruptible: bool,
+    ) -> Result<MutexGuard<'_, T>, LockError<MutexGuard<'_, T>>> {
         // try lock first
         match self.try_lock() {
             Ok(g) => return Ok(g),
             Err(TryLockError::WouldBlock) => {}
-            Err(TryLockError::Poisoned(e)) => return Err(e),
+            Err(TryLockError::Poisoned(e)) => return Err(LockError::Poisoned(e)),
         }
 
         let cur = SyncBlocker::current();
@@ -77,15 +156,37 @@ impl<T: ?Sized> Mutex<T> {
                 .map(|w| self.unpark_one(&w))
                 .expect("got null blocker!");
         }
+        let guard =
+            (interruptible && is_coroutine()).then(|| current_cancel_data().interruptible());
         loop {
-            match cur.park(None) {
+            match cur.park(dur) {
                 Ok(_) => {
                     break;
                 }
-                Err(ParkError::Timeout) => unreachable!("mutex timeout"),
-                Err(ParkError::Canceled) => {
-                    let b_ignore = if crate::coroutine_impl::is_coroutine() {
-                        let cancel = crate::coroutine_impl::current_cancel_data();
+                // woken up by the timeout or the cancellation token
+                Err(err)
+                    if err == ParkError::Timeout
+                        || (guard.is_some() && !current_cancel_data().is_canceled()) =>
+                {
+                    // the lock may be handed over to us just now
+                    if cur.is_unparked() {
+                        break;
+                    }
+                    // register, the unlocker would release the lock for us
+                    cur.set_release();
+                    // re-check unpark status
+                    if cur.is_unparked() && cur.take_release() {
+                        break;
+                    }
+                    return Err(match err {
+                        ParkError::Timeout => LockError::Timeout,
+                        ParkError::Canceled => LockError::Canceled,
+                    });
+                }
+                Err(_) => {
+                    // canceled by `Coroutine::cancel`
+                    let b_ignore = if is_coroutine() {
+                        let cancel = current_cancel_data();
                         cancel.is_disabled()
                     } else {
                         false
@@ -118,7 +219,7 @@ impl<T: ?Sized> Mutex<T> {
             }
         }
 
-        MutexGuard::new(self)
+        Ok(MutexGuard::new(self)?)
     }
 
     pub fn try_lock(&self) -> TryLockResult<MutexGuard<T>> {
@@ -237,6 +338,15 @@ pub fn unlock_mutex<T: ?Sized>(lock: &Mutex<T>) {
     lock.unlock();
 }
 
+// lock the mutex without charging the yield budget, used by the rwlock
+// that charges once per read operation
+pub fn lock_uncharged<T: ?Sized>(
+    lock: &Mutex<T>,
+    dur: Option<Duration>,
+) -> Result<MutexGuard<'_, T>, LockError<MutexGuard<'_, T>>> {
+    lock.lock_impl(dur, false)
+}
+
 pub fn guard_lock<'a, T: ?Sized>(guard: &MutexGuard<'a, T>) -> &'a Mutex<T> {
     guard.__lock
 }
@@ -265,6 +375,22 @@ mod tests {
     #[derive(Eq, PartialEq, Debug)]
     struct NonCopy(i32);
 
+    #[test]
+    fn lock_timeout() {
+        let m = Arc::new(Mutex::new(0));
+        let g = m.lock().unwrap();
+        let m2 = m.clone();
+        let t = thread::spawn(move || match m2.lock_timeout(Duration::from_millis(10)) {
+            Err(TryLockError::WouldBlock) => {}
+            _ => panic!("lock_timeout should time out"),
+        });
+        t.join().unwrap();
+        drop(g);
+        // the timed out waiter doesn't hold the lock
+        *m.lock_timeout(Duration::from_millis(10)).unwrap() += 1;
+        assert_eq!(*m.lock().unwrap(), 1);
+    }
+
     #[test]
     fn smoke() {
         let m = Mutex::new(());
@@ -0,0 +1,187 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::blocking::SyncBlocker;
+use crate::cancel::trigger_cancel_panic;
+use crate::park::ParkError;
+use parking_lot::Mutex;
+
+/// Notify primitive
+///
+/// Notify allows a thread or coroutine to wake up the ones that wait on it,
+/// without the need of a mutex like `Condvar`.
+///
+/// `notify_one` stores a permit if there is no waiter, so the next `notified`
+/// call returns immediately, a notification that happens before the wait is
+/// not lost. `notify_waiters` wakes up all the current waiters and stores nothing.
+///
+/// # Examples
+///
+/// ```rust
+/// use std::sync::Arc;
+/// use mco::std::sync::Notify;
+///
+/// let notify = Arc::new(Notify::new());
+/// let notify2 = notify.clone();
+///
+/// let h = mco::co!(move || {
+///     notify2.notified();
+///     println!("received notification");
+/// });
+///
+/// notify.notify_one();
+/// h.join().unwrap();
+/// ```
+pub struct Notify {
+    state: Mutex<NotifyState>,
+}
+
+struct NotifyState {
+    // a notify_one happens without waiters
+    permit: bool,
+    waiters: VecDeque<Arc<SyncBlocker>>,
+}
+
+impl Default for Notify {
+    fn default() -> Self {
+        Notify {
+            state: Mutex::new(NotifyState {
+                permit: false,
+                waiters: VecDeque::new(),
+            }),
+        }
+    }
+}
+
+impl Notify {
+    /// create a Notify without the permit
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// wake up one waiter, or store a permit for the next `notified` call
+    /// if there is no waiter
+    pub fn notify_one(&self) {
+        let waiter = {
+            let mut state = self.state.lock();
+            match state.waiters.pop_front() {
+                Some(w) => w,
+                None => {
+                    state.permit = true;
+                    return;
+                }
+            }
+        };
+        let _ = waiter.unpark();
+    }
+
+    /// wake up all the current waiters, the permit is not changed
+    pub fn notify_waiters(&self) {
+        let waiters = std::mem::take(&mut self.state.lock().waiters);
+        for w in waiters {
+            let _ = w.unpark();
+        }
+    }
+
+    /// wait until notified, consume the permit if there is one
+    pub fn notified(&self) {
+        self.notified_impl(None);
+    }
+
+    /// same as `notified` except that with an extra timeout value
+    /// return false if timeout happened
+    pub fn notified_timeout(&self, dur: Duration) -> bool {
+        self.notified_impl(Some(dur))
+    }
+
+    // return false if timeout
+    fn notified_impl(&self, dur: Option<Duration>) -> bool {
+        let cur = {
+            let mut state = self.state.lock();
+            if state.permit {
+                state.permit = false;
+                return true;
+            }
+            let cur = SyncBlocker::current();
+            state.waiters.push_back(cur.clone());
+            cur
+        };
+
+        match cur.park(dur) {
+            Ok(_) => true,
+            Err(err) => {
+                let removed = {
+                    let mut state = self.state.lock();
+                    let len = state.waiters.len();
+                    state.waiters.retain(|w| !Arc::ptr_eq(w, &cur));
+                    state.waiters.len() != len
+                };
+                if err == ParkError::Canceled {
+                    if !removed {
+                        // pass the notification to the others
+                        self.notify_one();
+                    }
+                    // now we can safely go with the cancel panic
+                    trigger_cancel_panic();
+                }
+                // the waiter is already taken by a notifier
+                !removed
+            }
+        }
+    }
+}
+
+impl fmt::Debug for Notify {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let state = self.state.lock();
+        f.debug_struct("Notify")
+            .field("permit", &state.permit)
+            .field("waiters", &state.waiters.len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc::channel;
+    use std::thread;
+
+    #[test]
+    fn notify_before_wait() {
+        let notify = Notify::new();
+        notify.notify_one();
+        notify.notified();
+        assert!(!notify.notified_timeout(Duration::from_millis(10)));
+    }
+
+    #[test]
+    fn notify_waiters() {
+        let notify = Arc::new(Notify::new());
+        let (tx, rx) = channel();
+        for _ in 0..4 {
+            let notify = notify.clone();
+            let tx = tx.clone();
+            thread::spawn(move || {
+                tx.send(()).unwrap();
+                notify.notified();
+                tx.send(()).unwrap();
+            });
+        }
+        for _ in 0..4 {
+            rx.recv().unwrap();
+        }
+        // wait all the threads get parked
+        while notify.state.lock().waiters.len() < 4 {
+            thread::sleep(Duration::from_millis(1));
+        }
+        notify.notify_waiters();
+        for _ in 0..4 {
+            rx.recv().unwrap();
+        }
+        // no permit is stored
+        assert!(!notify.notified_timeout(Duration::from_millis(10)));
+    }
+}
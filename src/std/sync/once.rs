@@ -1,17 +1,30 @@
-use std::sync::atomic::{AtomicU32, Ordering};
+use std::panic::{self, AssertUnwindSafe};
+
+use crate::std::lazy::sync::OnceCell;
+
 /// Once is an object that will perform exactly one action.
 ///
+/// the callers that come while the action is running are parked until it's
+/// done, a coroutine caller only suspends the coroutine, not the worker thread.
+///
 /// A Once must not be copied after first use.
 pub struct Once {
-    done: AtomicU32,
+    done: OnceCell<()>,
+}
+
+impl Default for Once {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Once {
-    pub fn new() -> Self {
+    pub const fn new() -> Self {
         Self {
-            done: Default::default(),
+            done: OnceCell::new(),
         }
     }
+
     /// Do calls the function f if and only if Do is being called for the
     /// first time for this instance of Once. In other words, given
     /// 	var once Once
@@ -33,7 +46,7 @@ impl Once {
     where
         F: FnMut(),
     {
-        if self.done.load(Ordering::SeqCst) == 0 {
+        if self.done.get().is_none() {
             self.do_slow(f);
         }
     }
@@ -42,11 +55,22 @@ impl Once {
     where
         F: FnMut(),
     {
-        if self.done.load(Ordering::SeqCst) == 0 {
-            self.done.store(1, Ordering::SeqCst);
-            f();
+        let mut panic = None;
+        self.done.get_or_init(|| {
+            // the panic still completes the once
+            if let Err(e) = panic::catch_unwind(AssertUnwindSafe(&mut f)) {
+                panic = Some(e);
+            }
+        });
+        if let Some(e) = panic {
+            panic::resume_unwind(e);
         }
     }
+
+    /// return true if the action is done
+    pub fn is_completed(&self) -> bool {
+        self.done.get().is_some()
+    }
 }
 
 #[cfg(test)]
@@ -0,0 +1,239 @@
+use std::cell::Cell;
+use std::fmt;
+use std::marker::PhantomData;
+use std::ops::Deref;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use super::Semphore;
+use crate::coroutine_impl::{current, is_coroutine};
+
+// the owner id of the current context, the coroutine id in coroutine
+// context, or a per thread id with the highest bit set in thread context
+fn owner_id() -> u64 {
+    static NEXT_THREAD_ID: AtomicU64 = AtomicU64::new(1);
+    thread_local! {
+        static THREAD_ID: u64 = NEXT_THREAD_ID.fetch_add(1, Ordering::Relaxed) | (1 << 63);
+    }
+
+    if is_coroutine() {
+        current().id()
+    } else {
+        THREAD_ID.with(|id| *id)
+    }
+}
+
+/// A reentrant mutual exclusion
+///
+/// the coroutine (or thread) that holds the lock can lock it again without
+/// deadlock, the lock is released when all the guards are dropped. only the
+/// shared `&T` access is provided, use a `RefCell` inside for mutation.
+/// a waiting coroutine is parked instead of blocking the worker thread.
+///
+/// # Examples
+///
+/// ```rust
+/// use mco::std::sync::ReentrantMutex;
+///
+/// let lock = ReentrantMutex::new(0);
+/// let a = lock.lock();
+/// let b = lock.lock();
+/// assert_eq!(*a + *b, 0);
+/// ```
+pub struct ReentrantMutex<T: ?Sized> {
+    sem: Semphore,
+    // the id of the holder, 0 means not locked
+    owner: AtomicU64,
+    // only accessed by the holder
+    count: Cell<usize>,
+    data: T,
+}
+
+unsafe impl<T: ?Sized + Send> Send for ReentrantMutex<T> {}
+unsafe impl<T: ?Sized + Send> Sync for ReentrantMutex<T> {}
+
+/// the guard of a `ReentrantMutex`, the lock is released when
+/// the last guard of the holder is dropped
+#[must_use]
+pub struct ReentrantMutexGuard<'a, T: ?Sized + 'a> {
+    lock: &'a ReentrantMutex<T>,
+    // the guard must stay with the holder
+    _marker: PhantomData<*const ()>,
+}
+
+impl<T> ReentrantMutex<T> {
+    pub fn new(t: T) -> ReentrantMutex<T> {
+        ReentrantMutex {
+            sem: Semphore::new(1),
+            owner: AtomicU64::new(0),
+            count: Cell::new(0),
+            data: t,
+        }
+    }
+
+    pub fn into_inner(self) -> T {
+        self.data
+    }
+}
+
+impl<T: ?Sized> ReentrantMutex<T> {
+    /// acquire the lock, return immediately if the current context
+    /// already holds it
+    pub fn lock(&self) -> ReentrantMutexGuard<'_, T> {
+        let id = owner_id();
+        if !self.reenter(id) {
+            self.sem.wait();
+            self.acquired(id);
+        }
+        self.guard()
+    }
+
+    /// try to acquire the lock without blocking
+    pub fn try_lock(&self) -> Option<ReentrantMutexGuard<'_, T>> {
+        let id = owner_id();
+        if !self.reenter(id) {
+            if !self.sem.try_wait() {
+                return None;
+            }
+            self.acquired(id);
+        }
+        Some(self.guard())
+    }
+
+    /// acquire the lock, return `None` if timeout happened
+    pub fn lock_timeout(&self, dur: Duration) -> Option<ReentrantMutexGuard<'_, T>> {
+        let id = owner_id();
+        if !self.reenter(id) {
+            if !self.sem.wait_timeout(dur) {
+                return None;
+            }
+            self.acquired(id);
+        }
+        Some(self.guard())
+    }
+
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.data
+    }
+
+    fn reenter(&self, id: u64) -> bool {
+        if self.owner.load(Ordering::Relaxed) == id {
+            let count = self.count.get().checked_add(1);
+            self.count
+                .set(count.expect("lock count overflow in reentrant mutex"));
+            return true;
+        }
+        false
+    }
+
+    fn acquired(&self, id: u64) {
+        self.owner.store(id, Ordering::Relaxed);
+        self.count.set(1);
+    }
+
+    fn guard(&self) -> ReentrantMutexGuard<'_, T> {
+        ReentrantMutexGuard {
+            lock: self,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T: Default> Default for ReentrantMutex<T> {
+    fn default() -> ReentrantMutex<T> {
+        ReentrantMutex::new(Default::default())
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for ReentrantMutex<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.try_lock() {
+            Some(guard) => write!(f, "ReentrantMutex {{ data: {:?} }}", &*guard),
+            None => write!(f, "ReentrantMutex {{ <locked> }}"),
+        }
+    }
+}
+
+impl<T: ?Sized> Deref for ReentrantMutexGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.lock.data
+    }
+}
+
+impl<T: ?Sized> Drop for ReentrantMutexGuard<'_, T> {
+    fn drop(&mut self) {
+        let count = self.lock.count.get() - 1;
+        self.lock.count.set(count);
+        if count == 0 {
+            self.lock.owner.store(0, Ordering::Relaxed);
+            self.lock.sem.post();
+        }
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for ReentrantMutexGuard<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn smoke() {
+        let m = ReentrantMutex::new(RefCell::new(0));
+        {
+            let a = m.lock();
+            {
+                let b = m.lock();
+                *b.borrow_mut() += 1;
+                let c = m.try_lock().unwrap();
+                *c.borrow_mut() += 1;
+            }
+            assert_eq!(*a.borrow(), 2);
+        }
+        assert_eq!(*m.into_inner().borrow(), 2);
+    }
+
+    #[test]
+    fn other_thread_blocked() {
+        let m = Arc::new(ReentrantMutex::new(()));
+        let g = m.lock();
+        let m2 = m.clone();
+        let h = thread::spawn(move || {
+            assert!(m2.try_lock().is_none());
+            assert!(m2.lock_timeout(Duration::from_millis(10)).is_none());
+        });
+        h.join().unwrap();
+        let g1 = m.lock();
+        drop(g);
+        let m2 = m.clone();
+        let h = thread::spawn(move || m2.try_lock().is_none());
+        assert!(h.join().unwrap());
+        drop(g1);
+        let m2 = m.clone();
+        let h = thread::spawn(move || m2.try_lock().is_some());
+        assert!(h.join().unwrap());
+    }
+
+    #[test]
+    fn reenter_in_coroutine() {
+        let m = Arc::new(ReentrantMutex::new(RefCell::new(Vec::new())));
+        let m1 = m.clone();
+        let h = co!(move || {
+            let a = m1.lock();
+            a.borrow_mut().push(1);
+            let b = m1.lock();
+            b.borrow_mut().push(2);
+        });
+        h.join().unwrap();
+        assert_eq!(*m.lock().borrow(), vec![1, 2]);
+    }
+}
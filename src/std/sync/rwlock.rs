@@ -8,12 +8,14 @@ use std::panic::{RefUnwindSafe, UnwindSafe};
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::sync::{LockResult, PoisonError, TryLockError, TryLockResult};
+use std::time::{Duration, Instant};
 
 use super::blocking::SyncBlocker;
-use super::mutex::{self, Mutex};
+use super::mutex::{self, LockError, Mutex};
 use super::poison;
 use crate::cancel::trigger_cancel_panic;
 use crate::park::ParkError;
+use crate::yield_now::consume_budget;
 
 /// A reader-writer lock
 ///
@@ -70,12 +72,10 @@ impl<T> RwLock<T> {
 
 impl<T: ?Sized> RwLock<T> {
     // global mutex lock without return a guard
-    fn lock(&self) -> Result<(), ParkError> {
-        // try lock first
-        match self.try_lock() {
-            Ok(_) => return Ok(()),
-            Err(TryLockError::WouldBlock) => {}
-            Err(TryLockError::Poisoned(_)) => return Err(ParkError::Timeout),
+    fn lock(&self, dur: Option<Duration>) -> Result<(), ParkError> {
+        // try lock first, the poison is reported by the guard
+        if self.try_lock().is_ok() {
+            return Ok(());
         }
 
         let cur = SyncBlocker::current();
@@ -89,9 +89,21 @@ impl<T: ?Sized> RwLock<T> {
                 .map(|w| self.unpark_one(&w))
                 .expect("got null blocker!");
         }
-        match cur.park(None) {
+        match cur.park(dur) {
             Ok(_) => Ok(()),
-            Err(ParkError::Timeout) => unreachable!("rwlock timeout"),
+            Err(ParkError::Timeout) => {
+                // the lock may be handed over to us just now
+                if cur.is_unparked() {
+                    return Ok(());
+                }
+                // register, the unlocker would release the lock for us
+                cur.set_release();
+                // re-check unpark status
+                if cur.is_unparked() && cur.take_release() {
+                    return Ok(());
+                }
+                Err(ParkError::Timeout)
+            }
             Err(ParkError::Canceled) => {
                 // check the unpark status
                 if cur.is_unparked() {
@@ -148,9 +160,13 @@ impl<T: ?Sized> RwLock<T> {
     }
 
     pub fn read(&self) -> LockResult<RwLockReadGuard<T>> {
-        let mut r = self.rlock.lock().expect("rwlock read");
+        consume_budget();
+        let mut r = match mutex::lock_uncharged(&self.rlock, None) {
+            Ok(r) => r,
+            Err(_) => panic!("rwlock read"),
+        };
         if *r == 0 {
-            if let Err(ParkError::Canceled) = self.lock() {
+            if let Err(ParkError::Canceled) = self.lock(None) {
                 // don't set the poison flag
                 ::std::mem::forget(r);
                 // release the mutex to let other run
@@ -164,6 +180,36 @@ impl<T: ?Sized> RwLock<T> {
         RwLockReadGuard::new(self)
     }
 
+    /// same as `read` except that with an extra timeout value
+    ///
+    /// return `Err(TryLockError::WouldBlock)` if the lock is not acquired in time
+    pub fn read_timeout(&self, dur: Duration) -> TryLockResult<RwLockReadGuard<T>> {
+        consume_budget();
+        let deadline = Instant::now() + dur;
+        let mut r = match mutex::lock_uncharged(&self.rlock, Some(dur)) {
+            Ok(r) => r,
+            Err(LockError::Poisoned(_)) => panic!("rwlock read"),
+            Err(_) => return Err(TryLockError::WouldBlock),
+        };
+        if *r == 0 {
+            let left = deadline.saturating_duration_since(Instant::now());
+            match self.lock(Some(left)) {
+                Ok(_) => {}
+                Err(ParkError::Timeout) => return Err(TryLockError::WouldBlock),
+                Err(ParkError::Canceled) => {
+                    // don't set the poison flag
+                    ::std::mem::forget(r);
+                    // release the mutex to let other run
+                    mutex::unlock_mutex(&self.rlock);
+                    // now we can safely go with the cancel panic
+                    trigger_cancel_panic();
+                }
+            }
+        }
+        *r += 1;
+        Ok(RwLockReadGuard::new(self)?)
+    }
+
     pub fn try_read(&self) -> TryLockResult<RwLockReadGuard<T>> {
         let mut r = match self.rlock.try_lock() {
             Ok(r) => r,
@@ -188,7 +234,10 @@ impl<T: ?Sized> RwLock<T> {
     }
 
     fn read_unlock(&self) {
-        let mut r = self.rlock.lock().expect("rwlock read_unlock");
+        let mut r = match mutex::lock_uncharged(&self.rlock, None) {
+            Ok(r) => r,
+            Err(_) => panic!("rwlock read_unlock"),
+        };
         *r -= 1;
         if *r == 0 {
             self.unlock();
@@ -196,13 +245,28 @@ impl<T: ?Sized> RwLock<T> {
     }
 
     pub fn write(&self) -> LockResult<RwLockWriteGuard<T>> {
-        if let Err(ParkError::Canceled) = self.lock() {
+        consume_budget();
+        if let Err(ParkError::Canceled) = self.lock(None) {
             // now we can safely go with the cancel panic
             trigger_cancel_panic();
         }
         RwLockWriteGuard::new(self)
     }
 
+    /// same as `write` except that with an extra timeout value
+    ///
+    /// return `Err(TryLockError::WouldBlock)` if the lock is not acquired in time
+    pub fn write_timeout(&self, dur: Duration) -> TryLockResult<RwLockWriteGuard<T>> {
+        consume_budget();
+        match self.lock(Some(dur)) {
+            Ok(_) => {}
+            Err(ParkError::Timeout) => return Err(TryLockError::WouldBlock),
+            // now we can safely go with the cancel panic
+            Err(ParkError::Canceled) => trigger_cancel_panic(),
+        }
+        Ok(RwLockWriteGuard::new(self)?)
+    }
+
     pub fn try_write(&self) -> TryLockResult<RwLockWriteGuard<T>> {
         if let Err(TryLockError::WouldBlock) = self.try_lock() {
             return Err(TryLockError::WouldBlock);
@@ -334,6 +398,25 @@ mod tests {
     #[derive(Eq, PartialEq, Debug)]
     struct NonCopy(i32);
 
+    #[test]
+    fn lock_timeout() {
+        use std::time::Duration;
+
+        let l = Arc::new(RwLock::new(0));
+        let w = l.write().unwrap();
+        let l2 = l.clone();
+        let t = thread::spawn(move || {
+            let dur = Duration::from_millis(10);
+            assert!(matches!(l2.read_timeout(dur), Err(TryLockError::WouldBlock)));
+            assert!(matches!(l2.write_timeout(dur), Err(TryLockError::WouldBlock)));
+        });
+        t.join().unwrap();
+        drop(w);
+        // the timed out waiters don't hold the lock
+        *l.write_timeout(Duration::from_millis(10)).unwrap() += 1;
+        assert_eq!(*l.read_timeout(Duration::from_millis(10)).unwrap(), 1);
+    }
+
     #[test]
     fn smoke() {
         let l = RwLock::new(());
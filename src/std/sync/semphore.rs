@@ -1,10 +1,12 @@
 use std::fmt;
 use std::sync::atomic::{AtomicIsize, Ordering};
-use std::sync::Arc;
+use std::sync::{Arc, PoisonError};
 use std::time::Duration;
 
 use super::blocking::SyncBlocker;
+use super::Mutex;
 use crate::cancel::trigger_cancel_panic;
+use crate::coroutine_impl::current_cancel_data;
 use crate::park::ParkError;
 use crate::std::queue::seg_queue::SegQueue as WaitList;
 
@@ -45,8 +47,14 @@ pub struct Semphore {
     cnt: AtomicIsize,
     // the waiting blocker list, must be mpmc
     to_wake: WaitList<Arc<SyncBlocker>>,
+    // serialize the `acquire_many` calls so that they don't deadlock
+    // by holding part of the resources each
+    many: Mutex<()>,
 }
 
+/// the correctly spelled alias of `Semphore`
+pub type Semaphore = Semphore;
+
 impl Semphore {
     /// create a semphore with the initial value
     pub fn new(init: usize) -> Self {
@@ -54,6 +62,7 @@ impl Semphore {
         Semphore {
             to_wake: WaitList::new(),
             cnt: AtomicIsize::new(init as isize),
+            many: Mutex::new(()),
         }
     }
 
@@ -70,8 +79,8 @@ impl Semphore {
             .expect("got null blocker!");
     }
 
-    // return false if timeout
-    fn wait_timeout_impl(&self, dur: Option<Duration>) -> bool {
+    // return false if timeout or woken up by the cancellation token
+    pub(crate) fn wait_timeout_impl(&self, dur: Option<Duration>) -> bool {
         // try wait first
         if self.try_wait() {
             return true;
@@ -101,7 +110,8 @@ impl Semphore {
                 }
 
                 // now we can safely go with the cancel panic
-                if err == ParkError::Canceled {
+                // unless it's woken up by the cancellation token
+                if err == ParkError::Canceled && current_cancel_data().is_canceled() {
                     trigger_cancel_panic();
                 }
                 false
@@ -152,6 +162,133 @@ impl Semphore {
         }
     }
 
+    /// increment the semphore value by `n`
+    /// and would wakeup up to `n` threads/coroutines that are calling `wait`
+    pub fn post_many(&self, n: usize) {
+        if n == 0 {
+            return;
+        }
+        let n = n as isize;
+        let cnt = self.cnt.fetch_add(n, Ordering::SeqCst);
+        assert!(cnt.checked_add(n).is_some());
+
+        // each negative count is a registered waiter
+        if cnt < 0 {
+            for _ in 0..std::cmp::min(n, -cnt) {
+                self.wakeup_one();
+            }
+        }
+    }
+
+    // wait for one resource for a permit, the permit can't be returned
+    // when interrupted by the cancellation token, so trigger the cancel panic
+    fn wait_permit(&self) {
+        if !self.wait_timeout_impl(None) {
+            trigger_cancel_panic();
+        }
+    }
+
+    /// acquire one resource, it's released when the returned permit is dropped
+    ///
+    /// # Panics
+    ///
+    /// triggers the cancel panic if the coroutine or its context is canceled
+    pub fn acquire(&self) -> SemaphorePermit<'_> {
+        self.wait_permit();
+        SemaphorePermit { sem: self, n: 1 }
+    }
+
+    /// acquire one resource without blocking, return `None` if would block
+    pub fn try_acquire(&self) -> Option<SemaphorePermit<'_>> {
+        if self.try_wait() {
+            Some(SemaphorePermit { sem: self, n: 1 })
+        } else {
+            None
+        }
+    }
+
+    /// acquire one resource, return `None` if timeout happened
+    pub fn acquire_timeout(&self, dur: Duration) -> Option<SemaphorePermit<'_>> {
+        if self.wait_timeout(dur) {
+            Some(SemaphorePermit { sem: self, n: 1 })
+        } else {
+            None
+        }
+    }
+
+    /// acquire `n` resources at once
+    ///
+    /// the resources that are already taken are released if the coroutine
+    /// or its context is canceled while waiting for the rest
+    ///
+    /// # Panics
+    ///
+    /// triggers the cancel panic if the coroutine or its context is canceled
+    pub fn acquire_many(&self, n: usize) -> SemaphorePermit<'_> {
+        if self.try_wait_many(n) {
+            return SemaphorePermit { sem: self, n };
+        }
+        // the lock is poisoned by the cancel panic
+        let _many = self.many.lock().unwrap_or_else(PoisonError::into_inner);
+        // the partial permit is dropped by the cancel panic
+        let mut permit = SemaphorePermit { sem: self, n: 0 };
+        while permit.n < n {
+            self.wait_permit();
+            permit.n += 1;
+        }
+        permit
+    }
+
+    /// acquire `n` resources without blocking, return `None` if would block
+    pub fn try_acquire_many(&self, n: usize) -> Option<SemaphorePermit<'_>> {
+        if self.try_wait_many(n) {
+            Some(SemaphorePermit { sem: self, n })
+        } else {
+            None
+        }
+    }
+
+    /// acquire one resource that is owned by the permit
+    ///
+    /// # Panics
+    ///
+    /// triggers the cancel panic if the coroutine or its context is canceled
+    pub fn acquire_owned(self: &Arc<Self>) -> OwnedSemaphorePermit {
+        self.wait_permit();
+        OwnedSemaphorePermit {
+            sem: self.clone(),
+            n: 1,
+        }
+    }
+
+    /// same as `acquire_owned` but return `None` if would block
+    pub fn try_acquire_owned(self: &Arc<Self>) -> Option<OwnedSemaphorePermit> {
+        if self.try_wait() {
+            Some(OwnedSemaphorePermit {
+                sem: self.clone(),
+                n: 1,
+            })
+        } else {
+            None
+        }
+    }
+
+    // take `n` resources if they are all available
+    fn try_wait_many(&self, n: usize) -> bool {
+        let n = n as isize;
+        let mut cnt = self.cnt.load(Ordering::SeqCst);
+        while cnt >= n {
+            match self
+                .cnt
+                .compare_exchange(cnt, cnt - n, Ordering::SeqCst, Ordering::SeqCst)
+            {
+                Ok(_) => return true,
+                Err(x) => cnt = x,
+            }
+        }
+        false
+    }
+
     /// return the current semphore value
     pub fn get_value(&self) -> usize {
         let cnt = self.cnt.load(Ordering::SeqCst);
@@ -169,6 +306,68 @@ impl fmt::Debug for Semphore {
     }
 }
 
+/// the resources acquired from a semaphore, released when dropped
+#[must_use]
+pub struct SemaphorePermit<'a> {
+    sem: &'a Semphore,
+    n: usize,
+}
+
+impl SemaphorePermit<'_> {
+    /// the number of the resources held by the permit
+    pub fn num_permits(&self) -> usize {
+        self.n
+    }
+
+    /// drop the permit without releasing the resources
+    pub fn forget(mut self) {
+        self.n = 0;
+    }
+}
+
+impl Drop for SemaphorePermit<'_> {
+    fn drop(&mut self) {
+        self.sem.post_many(self.n);
+    }
+}
+
+impl fmt::Debug for SemaphorePermit<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "SemaphorePermit {{ n: {} }}", self.n)
+    }
+}
+
+/// the owned version of `SemaphorePermit`, which can be moved into a coroutine
+#[must_use]
+pub struct OwnedSemaphorePermit {
+    sem: Arc<Semphore>,
+    n: usize,
+}
+
+impl OwnedSemaphorePermit {
+    /// the number of the resources held by the permit
+    pub fn num_permits(&self) -> usize {
+        self.n
+    }
+
+    /// drop the permit without releasing the resources
+    pub fn forget(mut self) {
+        self.n = 0;
+    }
+}
+
+impl Drop for OwnedSemaphorePermit {
+    fn drop(&mut self) {
+        self.sem.post_many(self.n);
+    }
+}
+
+impl fmt::Debug for OwnedSemaphorePermit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "OwnedSemaphorePermit {{ n: {} }}", self.n)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     #![feature(test)]
@@ -180,6 +379,75 @@ mod tests {
     use std::thread;
     use std::time::Duration;
 
+    #[test]
+    fn permits() {
+        let sem = Arc::new(Semaphore::new(3));
+        let p = sem.acquire_many(2);
+        assert_eq!(p.num_permits(), 2);
+        assert!(sem.try_acquire_many(2).is_none());
+        let p1 = sem.try_acquire().unwrap();
+        assert!(sem.acquire_timeout(Duration::from_millis(10)).is_none());
+        drop(p);
+        assert_eq!(sem.get_value(), 2);
+        p1.forget();
+        assert_eq!(sem.get_value(), 2);
+
+        let owned = sem.acquire_owned();
+        let sem2 = sem.clone();
+        thread::spawn(move || {
+            // released in another thread
+            drop(owned);
+            drop(sem2);
+        })
+        .join()
+        .unwrap();
+        assert_eq!(sem.get_value(), 2);
+    }
+
+    #[test]
+    fn acquire_many_wait() {
+        let sem = Arc::new(Semaphore::new(0));
+        let sem2 = sem.clone();
+        thread::spawn(move || {
+            for _ in 0..3 {
+                thread::sleep(Duration::from_millis(1));
+                sem2.post();
+            }
+        });
+        let p = sem.acquire_many(3);
+        assert_eq!(sem.get_value(), 0);
+        drop(p);
+        assert_eq!(sem.get_value(), 3);
+    }
+
+    #[test]
+    fn acquire_many_canceled() {
+        let sem = Arc::new(Semaphore::new(0));
+        let sem2 = sem.clone();
+        let h = co!(move || {
+            let _p = sem2.acquire_many(3);
+            unreachable!("acquired");
+        });
+        // the coroutine takes one of the three
+        sem.post();
+        thread::sleep(Duration::from_millis(50));
+        assert_eq!(sem.get_value(), 0);
+        unsafe { h.coroutine().cancel() };
+        h.join().unwrap_err();
+        // the partial progress is released
+        assert_eq!(sem.get_value(), 1);
+
+        // not blocked by the canceled one
+        let sem2 = sem.clone();
+        thread::spawn(move || {
+            thread::sleep(Duration::from_millis(10));
+            sem2.post_many(2);
+        });
+        let p = sem.acquire_many(3);
+        assert_eq!(p.num_permits(), 3);
+        assert_eq!(sem.get_value(), 0);
+    }
+
     #[test]
     fn sanity_1() {
         let sem = Arc::new(Semphore::new(0));
@@ -0,0 +1,563 @@
+use crate::std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+use serde::ser::SerializeMap;
+use serde::{Deserializer, Serialize, Serializer};
+use std::borrow::Borrow;
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap as Map;
+use std::fmt::{Debug, Formatter};
+use std::hash::{BuildHasher, Hash};
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, PoisonError};
+
+/// a concurrent hash map split into shards by the key hash
+///
+/// each shard is a `HashMap` behind its own `RwLock`, the loads take the read
+/// lock of the key's shard and the stores and deletes its write lock, so the
+/// writers of different shards don't contend with each other.
+///
+/// The values are shared by `Arc`, so a value returned by `get` stays valid
+/// after it's replaced or removed, and no shard is locked while it's in use.
+/// The iteration copies each shard's entries under its read lock.
+///
+/// unlike `SyncHashMap`, the reads are not lock free, use this map when the
+/// writes are frequent and spread over many keys.
+pub struct ShardedHashMap<K: Eq + Hash + Clone, V> {
+    shards: Box<[Shard<K, V>]>,
+    hasher: RandomState,
+}
+
+type Shard<K, V> = RwLock<Map<K, Arc<V>>>;
+
+// the map is always left consistent, so it's fine to ignore the poison
+fn read<K, V>(shard: &RwLock<Map<K, V>>) -> RwLockReadGuard<'_, Map<K, V>> {
+    shard.read().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn write<K, V>(shard: &RwLock<Map<K, V>>) -> RwLockWriteGuard<'_, Map<K, V>> {
+    shard.write().unwrap_or_else(PoisonError::into_inner)
+}
+
+impl<K, V> ShardedHashMap<K, V>
+where
+    K: Eq + Hash + Clone,
+{
+    pub fn new_arc() -> Arc<Self> {
+        Arc::new(Self::new())
+    }
+
+    pub fn new() -> Self {
+        Self::with_capacity(0)
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self::with_capacity_and_shards(capacity, default_shards())
+    }
+
+    /// create a map with `shards` shards, it's rounded up to a power of two
+    pub fn with_shards(shards: usize) -> Self {
+        Self::with_capacity_and_shards(0, shards)
+    }
+
+    pub fn with_capacity_and_shards(capacity: usize, shards: usize) -> Self {
+        let shards = shards.max(1).next_power_of_two();
+        let per_shard = capacity.div_ceil(shards);
+        Self {
+            shards: (0..shards)
+                .map(|_| RwLock::new(Map::with_capacity(per_shard)))
+                .collect(),
+            hasher: RandomState::new(),
+        }
+    }
+
+    /// the number of the shards
+    pub fn shards(&self) -> usize {
+        self.shards.len()
+    }
+
+    fn shard<Q: ?Sized + Hash>(&self, k: &Q) -> &Shard<K, V> {
+        let idx = self.hasher.hash_one(k) as usize & (self.shards.len() - 1);
+        &self.shards[idx]
+    }
+
+    pub fn insert(&self, k: K, v: V) -> Option<Arc<V>> {
+        write(self.shard(&k)).insert(k, Arc::new(v))
+    }
+
+    pub fn remove<Q>(&self, k: &Q) -> Option<Arc<V>>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        write(self.shard(k)).remove(k)
+    }
+
+    pub fn len(&self) -> usize {
+        self.shards.iter().map(|s| read(s).len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.shards.iter().all(|s| read(s).is_empty())
+    }
+
+    pub fn clear(&self) {
+        for shard in self.shards.iter() {
+            write(shard).clear();
+        }
+    }
+
+    pub fn shrink_to_fit(&self) {
+        for shard in self.shards.iter() {
+            write(shard).shrink_to_fit();
+        }
+    }
+
+    pub fn from(map: Map<K, V>) -> Self {
+        let s = Self::with_capacity(map.len());
+        for (k, v) in map {
+            s.insert(k, v);
+        }
+        s
+    }
+
+    /// Returns the value corresponding to the key.
+    ///
+    /// The key may be any borrowed form of the map's key type, but
+    /// [`Hash`] and [`Eq`] on the borrowed form *must* match those for
+    /// the key type.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mco::std::sync::ShardedHashMap;
+    ///
+    /// let map = ShardedHashMap::new();
+    /// map.insert(1, "a");
+    /// assert_eq!(*map.get(&1).unwrap(), "a");
+    /// assert_eq!(map.get(&2).is_none(), true);
+    /// ```
+    pub fn get<Q>(&self, k: &Q) -> Option<Arc<V>>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        read(self.shard(k)).get(k).cloned()
+    }
+
+    /// lock the shard of the key and return the value for modification
+    ///
+    /// the value is cloned if it's shared by a `get` or an iteration,
+    /// the shard is write locked until the returned ref is dropped
+    pub fn get_mut<Q>(&self, k: &Q) -> Option<ShardedHashMapRefMut<'_, K, V>>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        let g = write(self.shard(k));
+        let key = g.get_key_value(k)?.0.clone();
+        Some(ShardedHashMapRefMut { g, key })
+    }
+
+    /// return the value of the key, or insert the one returned by `f` if absent
+    ///
+    /// the check and the insertion are done under the shard write lock, `f` is
+    /// called at most once and only for the absent key. a present key is found
+    /// with the read lock
+    pub fn get_or_insert_with<F: FnOnce() -> V>(&self, k: K, f: F) -> Arc<V> {
+        if let Some(v) = self.get(&k) {
+            return v;
+        }
+        write(self.shard(&k))
+            .entry(k)
+            .or_insert_with(|| Arc::new(f()))
+            .clone()
+    }
+
+    /// lock the shard of the key for an in-place read-modify-write
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mco::std::sync::ShardedHashMap;
+    ///
+    /// let map = ShardedHashMap::new();
+    /// for w in ["a", "b", "a"] {
+    ///     map.entry(w).and_modify(|n| *n += 1).or_insert(1);
+    /// }
+    /// assert_eq!(*map.get("a").unwrap(), 2);
+    /// assert_eq!(*map.get("b").unwrap(), 1);
+    /// ```
+    pub fn entry(&self, k: K) -> ShardedHashMapEntry<'_, K, V> {
+        ShardedHashMapEntry {
+            g: write(self.shard(&k)),
+            key: k,
+        }
+    }
+
+    /// iterate over the entries
+    ///
+    /// the entries of a shard are copied under its read lock when the iteration
+    /// reaches it, so no lock is held between the calls of `next`
+    pub fn iter(&self) -> IterSharded<'_, K, V> {
+        IterSharded {
+            shards: self.shards.iter(),
+            inner: Vec::new().into_iter(),
+        }
+    }
+}
+
+impl<K: Eq + Hash + Clone, V> Default for ShardedHashMap<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn default_shards() -> usize {
+    (num_cpus::get() * 4).next_power_of_two()
+}
+
+/// a view into an entry of `ShardedHashMap`, the shard is locked until it's dropped
+pub struct ShardedHashMapEntry<'a, K, V> {
+    g: RwLockWriteGuard<'a, Map<K, Arc<V>>>,
+    key: K,
+}
+
+impl<'a, K: Eq + Hash + Clone, V: Clone> ShardedHashMapEntry<'a, K, V> {
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    /// modify the value in place if the entry is present
+    pub fn and_modify<F: FnOnce(&mut V)>(mut self, f: F) -> Self {
+        if let Some(v) = self.g.get_mut(&self.key) {
+            f(Arc::make_mut(v));
+        }
+        self
+    }
+
+    /// insert the value returned by `f` if the entry is absent,
+    /// return the value with the shard still locked
+    pub fn or_insert_with<F: FnOnce() -> V>(self, f: F) -> ShardedHashMapRefMut<'a, K, V> {
+        let ShardedHashMapEntry { mut g, key } = self;
+        g.entry(key.clone()).or_insert_with(|| Arc::new(f()));
+        ShardedHashMapRefMut { g, key }
+    }
+
+    pub fn or_insert(self, v: V) -> ShardedHashMapRefMut<'a, K, V> {
+        self.or_insert_with(|| v)
+    }
+
+    pub fn or_default(self) -> ShardedHashMapRefMut<'a, K, V>
+    where
+        V: Default,
+    {
+        self.or_insert_with(V::default)
+    }
+}
+
+/// a value of `ShardedHashMap`, its shard is write locked until it's dropped
+pub struct ShardedHashMapRefMut<'a, K, V> {
+    g: RwLockWriteGuard<'a, Map<K, Arc<V>>>,
+    // always present in the locked shard
+    key: K,
+}
+
+impl<K: Eq + Hash, V> Deref for ShardedHashMapRefMut<'_, K, V> {
+    type Target = V;
+
+    fn deref(&self) -> &Self::Target {
+        &self.g[&self.key]
+    }
+}
+
+impl<K: Eq + Hash, V: Clone> DerefMut for ShardedHashMapRefMut<'_, K, V> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        Arc::make_mut(self.g.get_mut(&self.key).unwrap())
+    }
+}
+
+impl<K: Eq + Hash, V: Debug> Debug for ShardedHashMapRefMut<'_, K, V> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        self.deref().fmt(f)
+    }
+}
+
+impl<K: Eq + Hash, V: PartialEq> PartialEq for ShardedHashMapRefMut<'_, K, V> {
+    fn eq(&self, other: &Self) -> bool {
+        self.deref().eq(other.deref())
+    }
+}
+
+impl<K: Eq + Hash, V: Eq> Eq for ShardedHashMapRefMut<'_, K, V> {}
+
+/// the iterator returned by `ShardedHashMap::iter`
+pub struct IterSharded<'a, K, V> {
+    shards: std::slice::Iter<'a, Shard<K, V>>,
+    // the copied entries of the current shard
+    inner: std::vec::IntoIter<(K, Arc<V>)>,
+}
+
+impl<K: Clone, V> Iterator for IterSharded<'_, K, V> {
+    type Item = (K, Arc<V>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(e) = self.inner.next() {
+                return Some(e);
+            }
+            let g = read(self.shards.next()?);
+            let entries: Vec<_> = g.iter().map(|(k, v)| (k.clone(), v.clone())).collect();
+            self.inner = entries.into_iter();
+        }
+    }
+}
+
+/// the owned iterator returned by `ShardedHashMap::into_iter`
+pub struct IntoIterSharded<K, V> {
+    inner: std::iter::Flatten<std::vec::IntoIter<Map<K, Arc<V>>>>,
+}
+
+impl<K, V> Iterator for IntoIterSharded<K, V> {
+    type Item = (K, Arc<V>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next()
+    }
+}
+
+impl<'a, K: Eq + Hash + Clone, V> IntoIterator for &'a ShardedHashMap<K, V> {
+    type Item = (K, Arc<V>);
+    type IntoIter = IterSharded<'a, K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<K: Eq + Hash + Clone, V> IntoIterator for ShardedHashMap<K, V> {
+    type Item = (K, Arc<V>);
+    type IntoIter = IntoIterSharded<K, V>;
+
+    /// consume the map and iterate over the owned entries
+    fn into_iter(self) -> Self::IntoIter {
+        let maps: Vec<_> = self
+            .shards
+            .into_vec()
+            .into_iter()
+            .map(|s| s.into_inner().unwrap_or_else(PoisonError::into_inner))
+            .collect();
+        IntoIterSharded {
+            inner: maps.into_iter().flatten(),
+        }
+    }
+}
+
+impl<K: Eq + Hash + Clone, V> From<Map<K, V>> for ShardedHashMap<K, V> {
+    fn from(arg: Map<K, V>) -> Self {
+        Self::from(arg)
+    }
+}
+
+impl<K, V> serde::Serialize for ShardedHashMap<K, V>
+where
+    K: Eq + Hash + Clone + Serialize,
+    V: Serialize,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut m = serializer.serialize_map(Some(self.len()))?;
+        for (k, v) in self.iter() {
+            m.serialize_entry(&k, &*v)?;
+        }
+        m.end()
+    }
+}
+
+impl<'de, K, V> serde::Deserialize<'de> for ShardedHashMap<K, V>
+where
+    K: Eq + Hash + Clone + serde::Deserialize<'de>,
+    V: serde::Deserialize<'de>,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let m = Map::deserialize(deserializer)?;
+        Ok(Self::from(m))
+    }
+}
+
+impl<K, V> Debug for ShardedHashMap<K, V>
+where
+    K: Eq + Hash + Clone + Debug,
+    V: Debug,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let mut m = f.debug_map();
+        for (k, v) in self.iter() {
+            m.entry(&k, &v);
+        }
+        m.finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::std::sync::ShardedHashMap;
+    use crate::std::sync::WaitGroup;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[test]
+    pub fn test_debug() {
+        let m: ShardedHashMap<i32, i32> = ShardedHashMap::new();
+        m.insert(1, 1);
+        assert_eq!(format!("{:?}", m), "{1: 1}");
+    }
+
+    #[test]
+    pub fn test_insert_get() {
+        let m = ShardedHashMap::<String, String>::new();
+        assert!(m.insert("/".to_string(), "1".to_string()).is_none());
+        m.insert("/js".to_string(), "2".to_string());
+        assert_eq!(*m.insert("/".to_string(), "3".to_string()).unwrap(), "1");
+        assert_eq!(*m.get("/").unwrap(), "3");
+        assert_eq!(*m.get("/js").unwrap(), "2");
+        assert!(m.get("/fn").is_none());
+        assert_eq!(m.len(), 2);
+    }
+
+    #[test]
+    pub fn test_remove_while_reading() {
+        let m = ShardedHashMap::<i32, String>::with_shards(1);
+        m.insert(1, "a".to_string());
+        let v = m.get(&1).unwrap();
+        // no lock is held by the value
+        assert_eq!(*m.remove(&1).unwrap(), "a");
+        m.insert(1, "b".to_string());
+        assert_eq!(*v, "a");
+        assert_eq!(*m.get(&1).unwrap(), "b");
+        m.clear();
+        assert!(m.is_empty());
+    }
+
+    #[test]
+    pub fn test_shards() {
+        let m = ShardedHashMap::<i32, String>::with_shards(3);
+        assert_eq!(m.shards(), 4);
+        for i in 0..100 {
+            m.insert(i, i.to_string());
+        }
+        assert_eq!(m.len(), 100);
+        assert!(m.shards.iter().all(|s| !s.read().unwrap().is_empty()));
+        let mut keys = Vec::new();
+        for (k, _) in m.iter() {
+            // the shards are not locked while iterating
+            m.entry(k).and_modify(|v| v.push('!'));
+            keys.push(k);
+        }
+        keys.sort();
+        assert_eq!(keys, (0..100).collect::<Vec<_>>());
+        assert_eq!(*m.get(&7).unwrap(), "7!");
+        let mut all: Vec<(i32, String)> = m.into_iter().map(|(k, v)| (k, (*v).clone())).collect();
+        all.sort();
+        assert_eq!(all.len(), 100);
+        assert_eq!(all[99], (99, "99!".to_string()));
+    }
+
+    #[test]
+    pub fn test_get_mut() {
+        let m = ShardedHashMap::<i32, i32>::new();
+        m.insert(1, 2);
+        let old = m.get(&1).unwrap();
+        *m.get_mut(&1).unwrap() += 1;
+        assert!(m.get_mut(&2).is_none());
+        assert_eq!(*old, 2);
+        assert_eq!(*m.get(&1).unwrap(), 3);
+    }
+
+    #[test]
+    pub fn test_entry() {
+        let m = ShardedHashMap::<i32, i32>::new();
+        *m.entry(1).or_insert(1) += 10;
+        m.entry(1).and_modify(|v| *v += 1).or_insert(0);
+        m.entry(2).and_modify(|v| *v += 1).or_default();
+        assert_eq!(*m.get(&1).unwrap(), 12);
+        assert_eq!(*m.get(&2).unwrap(), 0);
+
+        assert_eq!(*m.get_or_insert_with(3, || 3), 3);
+        assert_eq!(*m.get_or_insert_with(3, || unreachable!()), 3);
+        assert_eq!(m.len(), 3);
+    }
+
+    #[test]
+    pub fn test_entry_counter() {
+        let m = Arc::new(ShardedHashMap::<i32, i32>::with_shards(2));
+        let handles: Vec<_> = (0..8)
+            .map(|_| {
+                let m = m.clone();
+                std::thread::spawn(move || {
+                    for i in 0..1000 {
+                        *m.entry(i % 10).or_insert(0) += 1;
+                    }
+                })
+            })
+            .collect();
+        for h in handles {
+            h.join().unwrap();
+        }
+        for i in 0..10 {
+            assert_eq!(*m.get(&i).unwrap(), 800);
+        }
+    }
+
+    #[test]
+    pub fn test_get_or_insert_with_locked() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        let m = Arc::new(ShardedHashMap::<i32, i32>::with_shards(1));
+        let calls = Arc::new(AtomicUsize::new(0));
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let (m, calls) = (m.clone(), calls.clone());
+                std::thread::spawn(move || {
+                    let v = m.get_or_insert_with(1, || {
+                        calls.fetch_add(1, Ordering::SeqCst);
+                        std::thread::sleep(Duration::from_millis(20));
+                        1
+                    });
+                    *v
+                })
+            })
+            .collect();
+        for h in handles {
+            assert_eq!(h.join().unwrap(), 1);
+        }
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        // readers wait for the modification to finish
+        let e = m.entry(1).and_modify(|v| *v = 2);
+        let m2 = m.clone();
+        let h = std::thread::spawn(move || *m2.get(&1).unwrap());
+        std::thread::sleep(Duration::from_millis(20));
+        drop(e);
+        assert_eq!(h.join().unwrap(), 2);
+    }
+
+    #[test]
+    pub fn test_smoke() {
+        let wg = WaitGroup::new();
+        let m = Arc::new(ShardedHashMap::<i32, i32>::new());
+        for i in 0..1000 {
+            let wg = wg.clone();
+            let m = m.clone();
+            co!(move || {
+                m.insert(i, i);
+                assert_eq!(*m.get(&i).unwrap(), i);
+                drop(wg);
+            });
+        }
+        wg.wait();
+        assert_eq!(m.len(), 1000);
+    }
+}
@@ -1,12 +1,13 @@
+use std::collections::VecDeque;
 use std::fmt;
-use std::sync::atomic::{AtomicIsize, Ordering};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
 
 use super::blocking::SyncBlocker;
 use crate::cancel::trigger_cancel_panic;
 use crate::park::ParkError;
-use crate::std::queue::seg_queue::SegQueue;
+use parking_lot::Mutex;
 
 /// SyncFlag primitive
 ///
@@ -19,7 +20,11 @@ use crate::std::queue::seg_queue::SegQueue;
 /// When the SyncFalg is true, any thread or coroutine wait on it would
 /// return immediately.
 ///
-/// after the SyncFlag becomes true, it will never becomes false again.
+/// a manual-reset SyncFlag, which is created by `new`, stays true until
+/// `reset` is called. an auto-reset SyncFlag, which is created by
+/// `new_auto_reset`, releases only one waiter for each `fire` and becomes
+/// false again, if there is no waiter the next `wait` consumes the fire,
+/// like the windows event objects.
 ///
 /// # Examples
 ///
@@ -43,80 +48,86 @@ use crate::std::queue::seg_queue::SegQueue;
 /// flag.wait();
 /// ```
 pub struct SyncFlag {
-    // track how many resources available for the SyncFlag
-    // if it's negative means how many threads are waiting for
-    cnt: AtomicIsize,
-    // the waiting blocker list, must be mpmc
-    to_wake: SegQueue<Arc<SyncBlocker>>,
+    // only changed with the `waiters` lock held, read without it
+    fired: AtomicBool,
+    // the waiting blocker list
+    waiters: Mutex<VecDeque<Arc<SyncBlocker>>>,
+    auto_reset: bool,
 }
 
 impl Default for SyncFlag {
     fn default() -> Self {
         SyncFlag {
-            to_wake: SegQueue::new(),
-            cnt: AtomicIsize::new(0),
+            fired: AtomicBool::new(false),
+            waiters: Mutex::new(VecDeque::new()),
+            auto_reset: false,
         }
     }
 }
 
 impl SyncFlag {
-    /// create a SyncFlag with the initial value
+    /// create a manual-reset SyncFlag with the false value
     pub fn new() -> Self {
         Default::default()
     }
 
-    #[inline]
-    fn wakeup_all(&self) -> Result<(), ParkError> {
-        while let Some(w) = self.to_wake.pop() {
-            w.unpark()?;
-            if w.take_release() {
-                self.fire();
-            }
+    /// create an auto-reset SyncFlag with the false value
+    pub fn new_auto_reset() -> Self {
+        SyncFlag {
+            auto_reset: true,
+            ..Default::default()
         }
-        Ok(())
+    }
+
+    /// return true if the SyncFlag is auto-reset
+    pub fn is_auto_reset(&self) -> bool {
+        self.auto_reset
     }
 
     // return false if timeout
     fn wait_timeout_impl(&self, dur: Option<Duration>) -> bool {
-        // try wait first
-        if self.is_fired() {
+        // a manual-reset flag is not consumed by the wait
+        if !self.auto_reset && self.is_fired() {
             return true;
         }
-
-        let cur = SyncBlocker::current();
-        // register blocker first
-        self.to_wake.push(cur.clone());
-        // dec the cnt, if it's positive, unpark one waiter
-        if self.cnt.fetch_sub(1, Ordering::SeqCst) > 0 {
-            let _ = self.wakeup_all();
-        }
+        let cur = {
+            let mut waiters = self.waiters.lock();
+            if self.fired.load(Ordering::Acquire) {
+                if self.auto_reset {
+                    self.fired.store(false, Ordering::Release);
+                }
+                return true;
+            }
+            let cur = SyncBlocker::current();
+            waiters.push_back(cur.clone());
+            cur
+        };
 
         match cur.park(dur) {
             Ok(_) => true,
             Err(err) => {
-                // check the unpark status
-                if cur.is_unparked() {
-                    self.fire();
-                } else {
-                    // register
-                    cur.set_release();
-                    // re-check unpark status
-                    if cur.is_unparked() && cur.take_release() {
+                let removed = {
+                    let mut waiters = self.waiters.lock();
+                    let len = waiters.len();
+                    waiters.retain(|w| !Arc::ptr_eq(w, &cur));
+                    waiters.len() != len
+                };
+                if err == ParkError::Canceled {
+                    if !removed && self.auto_reset {
+                        // pass the fire to the others
                         self.fire();
                     }
-                }
-
-                // now we can safely go with the cancel panic
-                if err == ParkError::Canceled {
+                    // now we can safely go with the cancel panic
                     trigger_cancel_panic();
                 }
-                false
+                // the waiter is already released by a fire
+                !removed
             }
         }
     }
 
     /// wait for a SyncFlag
-    /// if the SyncFlag value is bigger than zero the function returns immediately
+    /// if the SyncFlag value is true the function returns immediately
     /// otherwise it would block the until a `fire` is executed
     pub fn wait(&self) {
         self.wait_timeout_impl(None);
@@ -130,17 +141,39 @@ impl SyncFlag {
 
     /// set the SyncFlag to true
     /// and would wakeup all threads/coroutines that are calling `wait`
+    ///
+    /// for an auto-reset SyncFlag only one waiter is waked up, the value
+    /// is set to true only if there is no waiter
     pub fn fire(&self) {
-        self.cnt.store(std::isize::MAX, Ordering::SeqCst);
+        let waiters = {
+            let mut waiters = self.waiters.lock();
+            if self.auto_reset {
+                match waiters.pop_front() {
+                    Some(w) => vec![w].into(),
+                    None => {
+                        self.fired.store(true, Ordering::Release);
+                        return;
+                    }
+                }
+            } else {
+                self.fired.store(true, Ordering::Release);
+                std::mem::take(&mut *waiters)
+            }
+        };
+        for w in waiters {
+            let _ = w.unpark();
+        }
+    }
 
-        // try to wakeup all waiters
-        let _ = self.wakeup_all();
+    /// set the SyncFlag back to false, the later `wait` would block again
+    pub fn reset(&self) {
+        let _waiters = self.waiters.lock();
+        self.fired.store(false, Ordering::Release);
     }
 
     /// return the current SyncFlag value
     pub fn is_fired(&self) -> bool {
-        let cnt = self.cnt.load(Ordering::SeqCst);
-        cnt > 0
+        self.fired.load(Ordering::Acquire)
     }
 }
 
@@ -174,6 +207,44 @@ mod tests {
         flag.wait();
     }
 
+    #[test]
+    fn reset() {
+        let flag = SyncFlag::new();
+        flag.fire();
+        assert!(flag.wait_timeout(Duration::from_millis(10)));
+        assert!(flag.is_fired());
+        flag.reset();
+        assert!(!flag.wait_timeout(Duration::from_millis(10)));
+        flag.fire();
+        flag.wait();
+    }
+
+    #[test]
+    fn auto_reset() {
+        let flag = Arc::new(SyncFlag::new_auto_reset());
+        // the fire is consumed by the first wait
+        flag.fire();
+        assert!(flag.wait_timeout(Duration::from_millis(10)));
+        assert!(!flag.wait_timeout(Duration::from_millis(10)));
+
+        // each fire releases only one waiter, no matter it's parked or not
+        let (tx, rx) = std::sync::mpsc::channel();
+        for _ in 0..2 {
+            let flag = flag.clone();
+            let tx = tx.clone();
+            thread::spawn(move || {
+                flag.wait();
+                tx.send(()).unwrap();
+            });
+        }
+        flag.fire();
+        rx.recv().unwrap();
+        assert!(rx.recv_timeout(Duration::from_millis(20)).is_err());
+        flag.fire();
+        rx.recv().unwrap();
+        assert!(!flag.is_fired());
+    }
+
     #[test]
     fn test_syncflag_canceled() {
         use crate::sleep::sleep;
@@ -4,6 +4,7 @@
 use crate::std::sync::{Condvar, Mutex};
 use std::fmt;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 /// Enables threads to synchronize the beginning or end of some computation.
 ///
@@ -56,6 +57,24 @@ use std::sync::Arc;
 /// wg.wait();
 /// ```
 ///
+/// Go style `add` / `done` are supported as well, each `add(n)` must be
+/// paired with `n` calls of `done`
+///
+/// ```
+/// use mco::std::sync::WaitGroup;
+///
+/// let wg = WaitGroup::new();
+/// wg.add(4);
+/// for _ in 0..4 {
+///     let wg = wg.clone();
+///     mco::co!(move || {
+///         // Do some work.
+///         wg.done();
+///     });
+/// }
+/// wg.wait();
+/// ```
+///
 /// [`Barrier`]: std::sync::Barrier
 pub struct WaitGroup {
     inner: Arc<Inner>,
@@ -64,7 +83,20 @@ pub struct WaitGroup {
 /// Inner state of a `WaitGroup`.
 struct Inner {
     cvar: Condvar,
-    count: Mutex<usize>,
+    count: Mutex<Count>,
+}
+
+// the live references and the pending `add` counts are tracked apart, so
+// an unpaired `done` can't release a reference that is dropped later
+struct Count {
+    refs: usize,
+    added: usize,
+}
+
+impl Count {
+    fn total(&self) -> usize {
+        self.refs + self.added
+    }
 }
 
 impl Default for WaitGroup {
@@ -72,7 +104,7 @@ impl Default for WaitGroup {
         Self {
             inner: Arc::new(Inner {
                 cvar: Condvar::new(),
-                count: Mutex::new(1),
+                count: Mutex::new(Count { refs: 1, added: 0 }),
             }),
         }
     }
@@ -114,7 +146,7 @@ impl WaitGroup {
     /// wg.wait();
     /// ```
     pub fn wait(self) {
-        if *self.inner.count.lock().unwrap() == 1 {
+        if self.inner.count.lock().unwrap().total() == 1 {
             return;
         }
 
@@ -122,27 +154,74 @@ impl WaitGroup {
         drop(self);
 
         let mut count = inner.count.lock().unwrap();
-        while *count > 0 {
+        while count.total() > 0 {
             count = inner.cvar.wait(count).unwrap();
         }
     }
+
+    /// Same as `wait`, but gives up after `dur`
+    ///
+    /// return false if timeout happened
+    pub fn wait_timeout(self, dur: Duration) -> bool {
+        if self.inner.count.lock().unwrap().total() == 1 {
+            return true;
+        }
+
+        let deadline = Instant::now() + dur;
+        let inner = self.inner.clone();
+        drop(self);
+
+        let mut count = inner.count.lock().unwrap();
+        while count.total() > 0 {
+            let now = Instant::now();
+            if now >= deadline {
+                return false;
+            }
+            count = inner.cvar.wait_timeout(count, deadline - now).unwrap().0;
+        }
+        true
+    }
+
+    /// Adds `n` to the counter, each of them must be released by `done`
+    pub fn add(&self, n: usize) {
+        self.inner.count.lock().unwrap().added += n;
+    }
+
+    /// Decrements the counter by one, pairs with `add`
+    ///
+    /// a call without a pending `add` is ignored, debug builds panic on it
+    pub fn done(&self) {
+        let paired = self.inner.sub(|c| {
+            let paired = c.added > 0;
+            c.added = c.added.saturating_sub(1);
+            paired
+        });
+        // the lock is released, so the panic doesn't poison it
+        debug_assert!(paired, "WaitGroup::done called more times than add");
+    }
 }
 
-impl Drop for WaitGroup {
-    fn drop(&mut self) {
-        let mut count = self.inner.count.lock().unwrap();
-        *count -= 1;
+impl Inner {
+    fn sub<R>(&self, f: impl FnOnce(&mut Count) -> R) -> R {
+        let mut count = self.count.lock().unwrap();
+        let r = f(&mut count);
 
-        if *count == 0 {
-            let _ = self.inner.cvar.notify_all();
+        if count.total() == 0 {
+            let _ = self.cvar.notify_all();
         }
+        r
+    }
+}
+
+impl Drop for WaitGroup {
+    fn drop(&mut self) {
+        self.inner.sub(|c| c.refs -= 1);
     }
 }
 
 impl Clone for WaitGroup {
     fn clone(&self) -> WaitGroup {
-        let mut count = self.inner.count.lock().unwrap();
-        *count += 1;
+        self.inner.count.lock().unwrap().refs += 1;
 
         WaitGroup {
             inner: self.inner.clone(),
@@ -152,7 +231,50 @@ impl Clone for WaitGroup {
 
 impl fmt::Debug for WaitGroup {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let count: &usize = &*self.inner.count.lock().unwrap();
-        f.debug_struct("WaitGroup").field("count", count).finish()
+        let count = self.inner.count.lock().unwrap().total();
+        f.debug_struct("WaitGroup").field("count", &count).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn add_done() {
+        let wg = WaitGroup::new();
+        wg.add(3);
+        for _ in 0..3 {
+            let wg = wg.clone();
+            thread::spawn(move || {
+                thread::sleep(Duration::from_millis(5));
+                wg.done();
+            });
+        }
+        wg.wait();
+    }
+
+    #[test]
+    fn wait_timeout() {
+        let wg = WaitGroup::new();
+        wg.add(1);
+        assert!(!wg.clone().wait_timeout(Duration::from_millis(10)));
+        let wg1 = wg.clone();
+        thread::spawn(move || wg1.done());
+        assert!(wg.wait_timeout(Duration::from_secs(10)));
+    }
+
+    #[test]
+    fn extra_done() {
+        let wg = WaitGroup::new();
+        wg.add(1);
+        wg.done();
+        let r = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| wg.done()));
+        assert_eq!(r.is_err(), cfg!(debug_assertions));
+        // the reference is still counted, dropping it doesn't panic
+        let wg1 = wg.clone();
+        drop(wg1);
+        wg.wait();
     }
 }
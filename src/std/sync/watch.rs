@@ -0,0 +1,364 @@
+//! single-producer, multi-consumer channel that only keeps the latest value
+//!
+//! the receivers don't queue the values, they can `borrow` the latest one
+//! at any time and `changed` parks the caller until a new value is sent.
+//! it's the way to propagate config or state to a lot of coroutines.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use mco::std::sync::watch;
+//!
+//! let (tx, mut rx) = watch::channel("init");
+//! let h = mco::co!(move || {
+//!     rx.changed().unwrap();
+//!     assert_eq!(*rx.borrow(), "ready");
+//! });
+//! tx.send("ready").unwrap();
+//! h.join().unwrap();
+//! ```
+
+use std::fmt;
+use std::ops::Deref;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{RecvError, RecvTimeoutError, SendError};
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::blocking::SyncBlocker;
+use crate::cancel::trigger_cancel_panic;
+use crate::park::ParkError;
+use parking_lot::{Mutex, RwLock, RwLockReadGuard};
+
+struct Shared<T> {
+    value: RwLock<T>,
+    state: Mutex<State>,
+    receiver_num: AtomicUsize,
+}
+
+struct State {
+    // increased by each send
+    version: usize,
+    closed: bool,
+    waiters: Vec<Arc<SyncBlocker>>,
+}
+
+impl<T> Shared<T> {
+    // bump the version or close the channel, return the waiters to wake up
+    //
+    // a new value must be bumped before the value lock is released, so that
+    // the readers that see the new value see the new version as well
+    fn bump(&self, close: bool) -> Vec<Arc<SyncBlocker>> {
+        let mut state = self.state.lock();
+        if close {
+            state.closed = true;
+        } else {
+            state.version += 1;
+        }
+        std::mem::take(&mut state.waiters)
+    }
+
+    // bump the version and wake up all the waiters
+    fn notify(&self, close: bool) {
+        for w in self.bump(close) {
+            let _ = w.unpark();
+        }
+    }
+
+    fn version(&self) -> usize {
+        self.state.lock().version
+    }
+}
+
+/// create a watch channel with the initial value
+///
+/// the initial value is treated as seen by the returned receiver
+pub fn channel<T>(init: T) -> (Sender<T>, Receiver<T>) {
+    let shared = Arc::new(Shared {
+        value: RwLock::new(init),
+        state: Mutex::new(State {
+            version: 0,
+            closed: false,
+            waiters: Vec::new(),
+        }),
+        receiver_num: AtomicUsize::new(1),
+    });
+    let rx = Receiver {
+        shared: shared.clone(),
+        seen: 0,
+    };
+    (Sender { shared }, rx)
+}
+
+/// a reference to the value in the channel
+///
+/// the sender is blocked while it's alive, don't hold it across a blocking call
+pub struct Ref<'a, T> {
+    inner: RwLockReadGuard<'a, T>,
+}
+
+impl<T> Deref for Ref<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.inner
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for Ref<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+/// the sending half of a watch channel
+pub struct Sender<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T> Sender<T> {
+    /// publish a new value, return the value back if there is no receiver
+    pub fn send(&self, value: T) -> Result<(), SendError<T>> {
+        if self.receiver_count() == 0 {
+            return Err(SendError(value));
+        }
+        self.send_replace(value);
+        Ok(())
+    }
+
+    /// publish a new value even if there is no receiver, return the old one
+    pub fn send_replace(&self, value: T) -> T {
+        let mut old = value;
+        self.send_modify(|v| std::mem::swap(v, &mut old));
+        old
+    }
+
+    /// modify the value in place and notify the receivers
+    pub fn send_modify<F: FnOnce(&mut T)>(&self, f: F) {
+        let mut value = self.shared.value.write();
+        f(&mut value);
+        let waiters = self.shared.bump(false);
+        drop(value);
+        for w in waiters {
+            let _ = w.unpark();
+        }
+    }
+
+    /// borrow the latest value
+    pub fn borrow(&self) -> Ref<'_, T> {
+        Ref {
+            inner: self.shared.value.read(),
+        }
+    }
+
+    /// create a new receiver, the current value is treated as seen
+    pub fn subscribe(&self) -> Receiver<T> {
+        self.shared.receiver_num.fetch_add(1, Ordering::SeqCst);
+        Receiver {
+            shared: self.shared.clone(),
+            seen: self.shared.version(),
+        }
+    }
+
+    pub fn receiver_count(&self) -> usize {
+        self.shared.receiver_num.load(Ordering::SeqCst)
+    }
+
+    /// return true if all the receivers are dropped
+    pub fn is_closed(&self) -> bool {
+        self.receiver_count() == 0
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        self.shared.notify(true);
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for Sender<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Sender")
+            .field("value", &*self.borrow())
+            .finish()
+    }
+}
+
+/// the receiving half of a watch channel
+pub struct Receiver<T> {
+    shared: Arc<Shared<T>>,
+    // the version that is already seen
+    seen: usize,
+}
+
+impl<T> Receiver<T> {
+    /// borrow the latest value, the value is not marked as seen
+    pub fn borrow(&self) -> Ref<'_, T> {
+        Ref {
+            inner: self.shared.value.read(),
+        }
+    }
+
+    /// borrow the latest value and mark it as seen
+    pub fn borrow_and_update(&mut self) -> Ref<'_, T> {
+        let inner = self.shared.value.read();
+        // the sender bumps the version before releasing the value lock
+        self.seen = self.shared.version();
+        Ref { inner }
+    }
+
+    /// return true if there is a value that is not seen yet
+    ///
+    /// return an error if the sender is dropped
+    pub fn has_changed(&self) -> Result<bool, RecvError> {
+        let state = self.shared.state.lock();
+        if state.version != self.seen {
+            return Ok(true);
+        }
+        if state.closed {
+            return Err(RecvError);
+        }
+        Ok(false)
+    }
+
+    /// wait until a new value is sent and mark it as seen
+    ///
+    /// return an error if the sender is dropped. in coroutine context only
+    /// the coroutine is parked
+    pub fn changed(&mut self) -> Result<(), RecvError> {
+        self.changed_impl(None).map_err(|_| RecvError)
+    }
+
+    /// same as `changed` except that with an extra timeout value
+    pub fn changed_timeout(&mut self, dur: Duration) -> Result<(), RecvTimeoutError> {
+        self.changed_impl(Some(dur))
+    }
+
+    fn changed_impl(&mut self, dur: Option<Duration>) -> Result<(), RecvTimeoutError> {
+        loop {
+            let cur = {
+                let mut state = self.shared.state.lock();
+                if state.version != self.seen {
+                    self.seen = state.version;
+                    return Ok(());
+                }
+                if state.closed {
+                    return Err(RecvTimeoutError::Disconnected);
+                }
+                let cur = SyncBlocker::current();
+                state.waiters.push(cur.clone());
+                cur
+            };
+
+            if let Err(err) = cur.park(dur) {
+                self.shared
+                    .state
+                    .lock()
+                    .waiters
+                    .retain(|w| !Arc::ptr_eq(w, &cur));
+                if err == ParkError::Canceled {
+                    trigger_cancel_panic();
+                }
+                // a new value may be sent right before the timeout
+                return match self.has_changed() {
+                    Ok(true) => {
+                        self.seen = self.shared.version();
+                        Ok(())
+                    }
+                    Ok(false) => Err(RecvTimeoutError::Timeout),
+                    Err(_) => Err(RecvTimeoutError::Disconnected),
+                };
+            }
+        }
+    }
+}
+
+impl<T> Clone for Receiver<T> {
+    fn clone(&self) -> Self {
+        self.shared.receiver_num.fetch_add(1, Ordering::SeqCst);
+        Receiver {
+            shared: self.shared.clone(),
+            seen: self.seen,
+        }
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        self.shared.receiver_num.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for Receiver<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Receiver")
+            .field("value", &*self.borrow())
+            .field("seen", &self.seen)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn latest_value() {
+        let (tx, mut rx) = channel(0);
+        assert_eq!(rx.has_changed(), Ok(false));
+        tx.send(1).unwrap();
+        tx.send(2).unwrap();
+        assert_eq!(rx.has_changed(), Ok(true));
+        // only the latest value is seen
+        rx.changed().unwrap();
+        assert_eq!(*rx.borrow(), 2);
+        assert_eq!(
+            rx.changed_timeout(Duration::from_millis(10)),
+            Err(RecvTimeoutError::Timeout)
+        );
+
+        let rx2 = tx.subscribe();
+        assert_eq!(tx.receiver_count(), 2);
+        assert_eq!(rx2.has_changed(), Ok(false));
+        tx.send_modify(|v| *v += 1);
+        assert_eq!(*rx2.borrow(), 3);
+
+        let mut rx3 = rx2.clone();
+        tx.send(4).unwrap();
+        assert_eq!(*rx3.borrow_and_update(), 4);
+        assert_eq!(rx3.has_changed(), Ok(false));
+
+        drop(tx);
+        assert!(rx.changed().is_ok());
+        assert_eq!(rx.changed(), Err(RecvError));
+        assert_eq!(
+            rx.changed_timeout(Duration::from_millis(10)),
+            Err(RecvTimeoutError::Disconnected)
+        );
+    }
+
+    #[test]
+    fn changed_wakeup() {
+        let (tx, rx) = channel(String::new());
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let mut rx = rx.clone();
+                thread::spawn(move || {
+                    rx.changed().unwrap();
+                    rx.borrow_and_update().clone()
+                })
+            })
+            .collect();
+        while tx.shared.state.lock().waiters.len() < 4 {
+            thread::sleep(Duration::from_millis(1));
+        }
+        tx.send("ready".to_owned()).unwrap();
+        for h in handles {
+            assert_eq!(h.join().unwrap(), "ready");
+        }
+        drop(rx);
+        assert!(tx.is_closed());
+        assert!(tx.send(String::new()).is_err());
+    }
+}
@@ -0,0 +1,229 @@
+use crate::std::sync::{Condvar, Mutex};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::time::{Duration, Instant};
+
+/// The key of an item in the [`DelayQueue`], used to reset or remove it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Key(u64);
+
+struct State<T> {
+    // the items ordered by the deadline, the key breaks the ties
+    items: BTreeMap<(Instant, u64), T>,
+    deadlines: HashMap<u64, Instant>,
+    next_key: u64,
+}
+
+impl<T> State<T> {
+    fn first_deadline(&self) -> Option<Instant> {
+        self.items.keys().next().map(|(deadline, _)| *deadline)
+    }
+}
+
+/// A queue of items that are yielded once their delay has elapsed
+///
+/// one coroutine can serve a lot of timeouts, e.g. the retries or the
+/// session expiry, instead of one sleeping coroutine per item.
+/// `poll_expired` parks the coroutine until the next item matures, the
+/// queue can be shared by the coroutines and threads
+/// for example:
+/// ```
+///         use mco::std::time::DelayQueue;
+///         use std::time::Duration;
+///
+///         let queue = DelayQueue::new();
+///         let key = queue.insert("session-1", Duration::from_secs(60));
+///         queue.insert("retry-1", Duration::from_millis(10));
+///         // the session is still alive, expire it later
+///         queue.reset(&key, Duration::from_secs(120));
+///         let (_, item) = queue.poll_expired();
+///         assert_eq!(item, "retry-1");
+/// ```
+pub struct DelayQueue<T> {
+    state: Mutex<State<T>>,
+    cond: Condvar,
+}
+
+impl<T> DelayQueue<T> {
+    pub fn new() -> Self {
+        DelayQueue {
+            state: Mutex::new(State {
+                items: BTreeMap::new(),
+                deadlines: HashMap::new(),
+                next_key: 0,
+            }),
+            cond: Condvar::new(),
+        }
+    }
+
+    /// insert an item that expires after `delay`
+    pub fn insert(&self, value: T, delay: Duration) -> Key {
+        self.insert_at(value, Instant::now() + delay)
+    }
+
+    /// insert an item that expires at the `deadline`
+    pub fn insert_at(&self, value: T, deadline: Instant) -> Key {
+        let mut state = self.state.lock().unwrap();
+        let key = state.next_key;
+        state.next_key += 1;
+        let first = state.first_deadline();
+        state.items.insert((deadline, key), value);
+        state.deadlines.insert(key, deadline);
+        drop(state);
+        self.notify_if_earlier(first, deadline);
+        Key(key)
+    }
+
+    /// remove the item, returns `None` if it's expired or removed already
+    pub fn remove(&self, key: &Key) -> Option<T> {
+        let mut state = self.state.lock().unwrap();
+        let deadline = state.deadlines.remove(&key.0)?;
+        state.items.remove(&(deadline, key.0))
+    }
+
+    /// set the item to expire after `delay` from now
+    ///
+    /// returns false if the item is expired or removed already
+    pub fn reset(&self, key: &Key, delay: Duration) -> bool {
+        self.reset_at(key, Instant::now() + delay)
+    }
+
+    /// set the item to expire at the `deadline`
+    ///
+    /// returns false if the item is expired or removed already
+    pub fn reset_at(&self, key: &Key, deadline: Instant) -> bool {
+        let mut state = self.state.lock().unwrap();
+        let old = match state.deadlines.get_mut(&key.0) {
+            Some(old) => std::mem::replace(old, deadline),
+            None => return false,
+        };
+        let first = state.first_deadline();
+        let value = state.items.remove(&(old, key.0)).unwrap();
+        state.items.insert((deadline, key.0), value);
+        drop(state);
+        self.notify_if_earlier(first, deadline);
+        true
+    }
+
+    /// the deadline of the item if it's still in the queue
+    pub fn deadline(&self, key: &Key) -> Option<Instant> {
+        self.state.lock().unwrap().deadlines.get(&key.0).copied()
+    }
+
+    /// wait for the next expired item
+    ///
+    /// parks the coroutine until the earliest item matures, an empty queue
+    /// waits for the items to be inserted
+    pub fn poll_expired(&self) -> (Key, T) {
+        let mut state = self.state.lock().unwrap();
+        loop {
+            if let Some(item) = Self::pop_expired(&mut state) {
+                return item;
+            }
+            state = match state.first_deadline() {
+                Some(deadline) => {
+                    let dur = deadline.saturating_duration_since(Instant::now());
+                    self.cond.wait_timeout(state, dur).unwrap().0
+                }
+                None => self.cond.wait(state).unwrap(),
+            };
+        }
+    }
+
+    /// get an expired item without waiting
+    pub fn try_poll_expired(&self) -> Option<(Key, T)> {
+        Self::pop_expired(&mut self.state.lock().unwrap())
+    }
+
+    /// the number of the items in the queue
+    pub fn len(&self) -> usize {
+        self.state.lock().unwrap().items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// remove all the items
+    pub fn clear(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.items.clear();
+        state.deadlines.clear();
+    }
+
+    fn pop_expired(state: &mut State<T>) -> Option<(Key, T)> {
+        let entry = state.items.first_entry()?;
+        if entry.key().0 > Instant::now() {
+            return None;
+        }
+        let key = entry.key().1;
+        let value = entry.remove();
+        state.deadlines.remove(&key);
+        Some((Key(key), value))
+    }
+
+    // the waiters sleep until the first deadline, wake them up to recompute
+    fn notify_if_earlier(&self, first: Option<Instant>, deadline: Instant) {
+        if first.map_or(true, |first| deadline < first) {
+            let _ = self.cond.notify_all();
+        }
+    }
+}
+
+impl<T> Default for DelayQueue<T> {
+    fn default() -> Self {
+        DelayQueue::new()
+    }
+}
+
+impl<T> fmt::Debug for DelayQueue<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("DelayQueue")
+            .field("len", &self.len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::DelayQueue;
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_delay_queue() {
+        let queue = DelayQueue::new();
+        let a = queue.insert("a", Duration::from_millis(30));
+        let b = queue.insert("b", Duration::from_millis(10));
+        let c = queue.insert("c", Duration::from_millis(20));
+        assert_eq!(queue.len(), 3);
+        assert!(queue.try_poll_expired().is_none());
+
+        assert_eq!(queue.remove(&c), Some("c"));
+        assert_eq!(queue.remove(&c), None);
+        assert!(queue.reset(&a, Duration::from_millis(5)));
+
+        assert_eq!(queue.poll_expired(), (a, "a"));
+        assert_eq!(queue.poll_expired(), (b, "b"));
+        assert!(queue.is_empty());
+        assert!(!queue.reset(&a, Duration::from_millis(5)));
+    }
+
+    #[test]
+    fn test_delay_queue_wait_insert() {
+        let queue = Arc::new(DelayQueue::new());
+        let q = queue.clone();
+        let start = Instant::now();
+        let h = thread::spawn(move || {
+            q.insert(1, Duration::from_secs(60));
+            thread::sleep(Duration::from_millis(20));
+            // an earlier item wakes up the waiter
+            q.insert(2, Duration::from_millis(10));
+        });
+        assert_eq!(queue.poll_expired().1, 2);
+        assert!(start.elapsed() < Duration::from_secs(10));
+        assert_eq!(queue.len(), 1);
+        h.join().unwrap();
+    }
+}
@@ -1,8 +1,13 @@
+pub mod delay_queue;
 pub mod format;
 pub mod sys;
 pub mod tick;
 pub mod time;
+pub mod timer;
 
+pub use self::delay_queue::*;
 pub use self::format::*;
 pub use self::tick::*;
 pub use self::time::*;
+pub use self::timer::*;
+pub use crate::sleep::{sleep, sleep_until};
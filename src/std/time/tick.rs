@@ -1,14 +1,17 @@
-use crate::coroutine::sleep;
 use crate::std::errors::Result;
 use crate::std::sync::channel::Receiver;
-use crate::std::sync::Mutex;
+use crate::std::sync::{Condvar, Mutex};
 use crate::std::time::time::Time;
-use std::ops::Deref;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 /// A Ticker holds a channel that delivers ``ticks'' of a clock
 /// at intervals.
+///
+/// the ticks are scheduled at `start + n * period`, so a slow tick doesn't
+/// delay the following ones, the ticks missed by a late ticker are skipped
+/// instead of sent in a burst. the ticker is stopped when dropped.
 /// for example:
 /// ```
 ///         use mco::coroutine::sleep;
@@ -29,8 +32,12 @@ use std::time::Duration;
 ///
 /// ```
 pub struct Ticker {
-    pub d: Arc<Mutex<Duration>>,
-    pub recv: Receiver<Time>,
+    d: Arc<Mutex<Duration>>,
+    recv: Receiver<Time>,
+    // wakes the ticker coroutine on stop, reset and drop
+    cond: Arc<Condvar>,
+    // set when the ticker is dropped, the ticker coroutine exits
+    dropped: Arc<AtomicBool>,
 }
 
 impl Ticker {
@@ -40,27 +47,81 @@ impl Ticker {
 
     pub fn new(d: Duration) -> Self {
         let d = Arc::new(Mutex::new(d));
+        let cond = Arc::new(Condvar::new());
+        let dropped = Arc::new(AtomicBool::new(false));
         let (s, r) = chan!();
-        let ticker = Self { d: d, recv: r };
-        let d = ticker.d.clone();
-        let tick = move || loop {
-            match d.lock() {
-                Ok(d) => {
-                    if d.is_zero() {
-                        break;
-                    }
-                    sleep(d.deref().clone());
-                    let _ = s.send(Time::now());
+        let ticker = Self {
+            d: d.clone(),
+            recv: r,
+            cond: cond.clone(),
+            dropped: dropped.clone(),
+        };
+        let tick = move || {
+            let mut guard = match d.lock() {
+                Ok(guard) => guard,
+                Err(_) => return,
+            };
+            let mut next = Instant::now() + *guard;
+            loop {
+                if dropped.load(Ordering::Acquire) {
+                    break;
                 }
-                Err(_) => {
+                if guard.is_zero() {
+                    // stopped, wait for `reset`
+                    guard = match cond.wait(guard) {
+                        Ok(guard) => guard,
+                        Err(_) => break,
+                    };
+                    next = Instant::now() + *guard;
+                    continue;
+                }
+                let now = Instant::now();
+                if now < next {
+                    guard = match cond.wait_timeout(guard, next - now) {
+                        // woken up by `reset`, start a new period
+                        Ok((guard, r)) if !r.timed_out() => {
+                            next = Instant::now() + *guard;
+                            guard
+                        }
+                        Ok((guard, _)) => guard,
+                        Err(_) => break,
+                    };
+                    continue;
+                }
+                if s.send(Time::now()).is_err() {
                     break;
                 }
+                // skip the missed ticks instead of firing them in a burst
+                let period = *guard;
+                next += period;
+                if next <= now {
+                    let missed = (now - next).as_nanos() / period.as_nanos() + 1;
+                    next += period * missed as u32;
+                }
             }
         };
         co!(tick);
         ticker
     }
 
+    /// the current period of the ticker, zero if it's stopped
+    pub fn period(&self) -> Duration {
+        match self.d.lock() {
+            Ok(d) => *d,
+            Err(e) => **e.get_ref(),
+        }
+    }
+
+    /// the channel that receives the ticks
+    pub fn receiver(&self) -> &Receiver<Time> {
+        &self.recv
+    }
+
+    /// wait for the next tick, a stopped ticker waits until it's reset
+    pub fn tick(&self) -> Option<Time> {
+        self.recv.recv().ok()
+    }
+
     /// Stop turns off a ticker. After Stop, no more ticks will be sent.
     /// Stop does not close the channel, to prevent a concurrent goroutine
     /// reading from the channel from seeing an erroneous "tick".
@@ -68,6 +129,7 @@ impl Ticker {
         match self.d.lock() {
             Ok(mut d) => {
                 *d = Duration::from_secs(0);
+                let _ = self.cond.notify_one();
                 Ok(())
             }
             Err(e) => Err(err!("lock fail: {}", e)),
@@ -80,6 +142,7 @@ impl Ticker {
         match self.d.lock() {
             Ok(mut dur) => {
                 *dur = d;
+                let _ = self.cond.notify_one();
                 Ok(())
             }
             Err(e) => Err(err!("lock fail: {}", e)),
@@ -87,6 +150,15 @@ impl Ticker {
     }
 }
 
+impl Drop for Ticker {
+    fn drop(&mut self) {
+        self.dropped.store(true, Ordering::Release);
+        // take the lock so that the ticker coroutine can't miss the wakeup
+        let _guard = self.d.lock();
+        let _ = self.cond.notify_one();
+    }
+}
+
 impl Iterator for Ticker {
     type Item = Time;
 
@@ -114,7 +186,7 @@ mod test {
     use crate::sleep::sleep;
     use crate::std::time::tick::Ticker;
     use std::sync::Arc;
-    use std::time::Duration;
+    use std::time::{Duration, Instant};
 
     //test --package mco --lib std::time::tick::test::test_tick -- --exact --nocapture
     #[test]
@@ -129,4 +201,28 @@ mod test {
         sleep(Duration::from_secs(3));
         t.stop();
     }
+
+    #[test]
+    fn test_tick_no_drift() {
+        let period = Duration::from_millis(50);
+        let start = Instant::now();
+        let t = Ticker::new(period);
+        sleep(Duration::from_millis(500));
+        t.stop().unwrap();
+        let elapsed = start.elapsed();
+        let ticks = std::iter::from_fn(|| t.receiver().try_recv().ok()).count();
+        // the ticks are never early and the missed ones are not sent in a burst
+        let max = (elapsed.as_millis() / period.as_millis()) as usize;
+        assert!(
+            ticks >= 1 && ticks <= max,
+            "{} ticks in {:?}",
+            ticks,
+            elapsed
+        );
+
+        // the stopped ticker can be reset
+        assert_eq!(t.period(), Duration::from_secs(0));
+        t.reset(period).unwrap();
+        assert!(t.tick().is_some());
+    }
 }
@@ -0,0 +1,269 @@
+use crate::scheduler::{get_scheduler, CallbackHandle};
+use crate::std::sync::channel::Receiver;
+use crate::timeout_list::{self, START_TIME};
+use parking_lot::Mutex;
+use std::fmt;
+use std::ops::Deref;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+// the timers of this module run the callbacks in the timer thread of the
+// scheduler, the callbacks must be short and never block
+fn add_timer<F: FnOnce() + Send + 'static>(dur: Duration, f: F) -> CallbackHandle {
+    get_scheduler().add_callback_timer(dur, Box::new(f))
+}
+
+/// returns a channel that receives the current instant after `dur`
+///
+/// like Go's `time.After`, it's handy as the timeout arm of `select!`.
+/// no coroutine is spawned for the wait, the timer only holds the sender,
+/// and the pending timer is removed once the returned channel is dropped
+/// for example:
+/// ```
+///         use mco::{chan, select};
+///         use mco::std::time::after;
+///         use std::time::Duration;
+///
+///         let (_s, r) = chan!(i32, 1);
+///         select! {
+///             v = r.recv() => println!("recv {:?}", v),
+///             _ = after(Duration::from_millis(100)).recv() => println!("timeout"),
+///         };
+/// ```
+pub fn after(dur: Duration) -> After {
+    let (s, rx) = chan!(1);
+    let handle = add_timer(dur, move || {
+        let _ = s.try_send(Instant::now());
+    });
+    After {
+        rx,
+        handle: Some(handle),
+    }
+}
+
+/// the channel returned by `after`, derefs to the receiver
+pub struct After {
+    rx: Receiver<Instant>,
+    handle: Option<CallbackHandle>,
+}
+
+impl Deref for After {
+    type Target = Receiver<Instant>;
+
+    fn deref(&self) -> &Receiver<Instant> {
+        &self.rx
+    }
+}
+
+impl Drop for After {
+    fn drop(&mut self) {
+        // drops the sender held by the timer as well
+        if let Some(h) = self.handle.take() {
+            h.remove();
+        }
+    }
+}
+
+impl fmt::Debug for After {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.pad("After { .. }")
+    }
+}
+
+struct TimerState {
+    handle: Option<CallbackHandle>,
+    // bumped by each reset, a stale expiration is ignored
+    gen: u64,
+}
+
+/// A cancellable timer that runs a callback in the timer thread
+///
+/// the timer can be reset and cancelled, e.g. one timer per connection for
+/// the idle timeout, instead of a sleeping coroutine. the callback must be
+/// short and never block, send to a channel to do the real work in a
+/// coroutine. the timer is cancelled when dropped
+/// for example:
+/// ```
+///         use mco::std::time::Timer;
+///         use std::time::Duration;
+///
+///         let (timer, r) = Timer::channel(Duration::from_millis(100));
+///         // the connection is active, push the timeout back
+///         assert!(timer.reset(Duration::from_millis(50)));
+///         r.recv().unwrap();
+///         assert!(!timer.cancel());
+/// ```
+pub struct Timer {
+    f: Arc<dyn Fn() + Send + Sync>,
+    state: Arc<Mutex<TimerState>>,
+}
+
+impl Timer {
+    /// create a timer that calls `f` after `dur`
+    pub fn new<F>(dur: Duration, f: F) -> Self
+    where
+        F: Fn() + Send + Sync + 'static,
+    {
+        let timer = Timer {
+            f: Arc::new(f),
+            state: Arc::new(Mutex::new(TimerState {
+                handle: None,
+                gen: 0,
+            })),
+        };
+        timer.reset(dur);
+        timer
+    }
+
+    /// create a timer that sends the current instant to the channel after `dur`
+    ///
+    /// the channel buffers one value, a reset timer that fires again before
+    /// the value is received is dropped
+    pub fn channel(dur: Duration) -> (Self, Receiver<Instant>) {
+        let (s, r) = chan!(1);
+        let timer = Timer::new(dur, move || {
+            let _ = s.try_send(Instant::now());
+        });
+        (timer, r)
+    }
+
+    /// stop the timer, returns false if it already expired or was cancelled
+    pub fn cancel(&self) -> bool {
+        let mut state = self.state.lock();
+        state.gen += 1;
+        Self::remove(&mut state)
+    }
+
+    /// expire the timer after `dur` from now, whether it's pending or not
+    ///
+    /// returns true if the pending timer is replaced
+    pub fn reset(&self, dur: Duration) -> bool {
+        let mut state = self.state.lock();
+        state.gen += 1;
+        let pending = Self::remove(&mut state);
+        let gen = state.gen;
+        let (f, weak) = (self.f.clone(), Arc::downgrade(&self.state));
+        let cb = move || {
+            let state = match weak.upgrade() {
+                Some(state) => state,
+                None => return,
+            };
+            let mut state = state.lock();
+            if state.gen != gen {
+                return;
+            }
+            state.handle = None;
+            drop(state);
+            f();
+        };
+        state.handle = Some(add_timer(dur, cb));
+        pending
+    }
+
+    /// whether the timer is waiting to expire
+    pub fn is_pending(&self) -> bool {
+        self.state.lock().handle.is_some()
+    }
+
+    fn remove(state: &mut TimerState) -> bool {
+        match state.handle.take() {
+            Some(h) => h.remove().is_some(),
+            None => false,
+        }
+    }
+}
+
+impl Drop for Timer {
+    fn drop(&mut self) {
+        self.cancel();
+    }
+}
+
+impl fmt::Debug for Timer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Timer")
+            .field("pending", &self.is_pending())
+            .finish()
+    }
+}
+
+/// returns the cached current instant
+///
+/// the value is refreshed by the timer thread of the scheduler, so it's
+/// cheaper than `Instant::now` but falls behind it for the resolution set by
+/// `Config::set_coarse_clock_resolution` at most. it's good for the
+/// timestamps and the timeouts on the hot paths
+pub fn now_coarse() -> Instant {
+    // make sure the timer thread is running
+    get_scheduler();
+    *START_TIME + timeout_list::ns_to_dur(timeout_list::now_coarse())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{after, now_coarse, Timer};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_after() {
+        let start = Instant::now();
+        let r = after(Duration::from_millis(50));
+        let fired = r.recv().unwrap();
+        assert!(fired >= start + Duration::from_millis(50));
+        // the sender is dropped after firing
+        assert!(r.recv().is_err());
+
+        // dropping the channel early removes the timer
+        drop(after(Duration::from_millis(10)));
+        let r = after(Duration::from_millis(20));
+        assert!(r.recv_timeout(Duration::from_secs(1)).is_ok());
+    }
+
+    #[test]
+    fn test_now_coarse() {
+        let t = now_coarse();
+        assert!(t <= Instant::now());
+        std::thread::sleep(Duration::from_millis(20));
+        let t1 = now_coarse();
+        assert!(t1 > t);
+        assert!(t1 <= Instant::now());
+    }
+
+    #[test]
+    fn test_timer() {
+        let count = Arc::new(AtomicUsize::new(0));
+        let c = count.clone();
+        let timer = Timer::new(Duration::from_secs(60), move || {
+            c.fetch_add(1, Ordering::SeqCst);
+        });
+        assert!(timer.is_pending());
+        assert!(timer.cancel());
+        assert!(!timer.cancel());
+        assert!(!timer.is_pending());
+
+        // the reset timer fires only once
+        assert!(!timer.reset(Duration::from_secs(60)));
+        assert!(timer.reset(Duration::from_millis(10)));
+        std::thread::sleep(Duration::from_millis(200));
+        assert_eq!(count.load(Ordering::SeqCst), 1);
+        assert!(!timer.is_pending());
+        assert!(!timer.cancel());
+
+        // a cancelled timer never fires
+        assert!(!timer.reset(Duration::from_millis(10)));
+        drop(timer);
+        std::thread::sleep(Duration::from_millis(100));
+        assert_eq!(count.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_timer_channel() {
+        let start = Instant::now();
+        let (timer, r) = Timer::channel(Duration::from_millis(50));
+        assert!(r.recv().unwrap() >= start + Duration::from_millis(50));
+        timer.reset(Duration::from_millis(10));
+        assert!(r.recv_timeout(Duration::from_secs(1)).is_ok());
+    }
+}
@@ -1,14 +1,10 @@
 use std::cmp;
-use std::collections::{BinaryHeap, HashMap};
-use std::mem;
-use std::sync::atomic::{AtomicUsize, Ordering};
-use std::sync::{Arc, RwLock};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
 use std::thread;
 use std::time::{Duration, Instant};
 
-use crate::std::queue::mpsc_list_v1::Entry;
-use crate::std::queue::mpsc_list_v1::Queue as TimeoutQueue;
-use crate::std::queue::seg_queue::SegQueue as mpsc;
+use crate::timer_wheel::TimerWheel;
 use crossbeam::atomic::AtomicCell;
 use once_cell::sync::Lazy;
 use parking_lot::Mutex;
@@ -16,10 +12,8 @@ use parking_lot::Mutex;
 const NANOS_PER_MILLI: u64 = 1_000_000;
 const NANOS_PER_SEC: u64 = 1_000_000_000;
 
-const HASH_CAP: usize = 1024;
-
 #[inline]
-fn dur_to_ns(dur: Duration) -> u64 {
+pub fn dur_to_ns(dur: Duration) -> u64 {
     // Note that a duration is a (u64, u32) (seconds, nanoseconds) pair
     dur.as_secs()
         .saturating_mul(NANOS_PER_SEC)
@@ -46,95 +40,85 @@ pub fn now() -> u64 {
     START_TIME.elapsed().as_nanos() as u64
 }
 
-// timeout event data
-pub struct TimeoutData<T> {
-    time: u64,
-    // the wall clock in ns that the timer expires
-    pub data: T, // the data associate with the timeout event
+// convert the instant to the clock used by `now`, saturates at zero
+#[inline]
+pub fn instant_to_ns(t: Instant) -> u64 {
+    t.saturating_duration_since(*START_TIME).as_nanos() as u64
 }
 
-// timeout handler which can be removed/cancelled
-pub type TimeoutHandle<T> = Entry<TimeoutData<T>>;
+// the cached clock in ns, refreshed by the scheduler timer thread
+static COARSE_NOW: AtomicU64 = AtomicU64::new(0);
 
-struct TimeoutQueueWrapper<T> {
-    inner: TimeoutQueue<TimeoutData<T>>,
-    in_use: AtomicUsize,
+// get the cached clock, it falls behind `now` for the coarse clock resolution at most
+#[inline]
+pub fn now_coarse() -> u64 {
+    COARSE_NOW.load(Ordering::Relaxed)
 }
 
-impl<T> TimeoutQueueWrapper<T> {
-    fn new() -> Self {
-        TimeoutQueueWrapper {
-            inner: TimeoutQueue::new(),
-            in_use: AtomicUsize::new(0),
-        }
-    }
+// refresh the cached clock, return the current clock
+#[inline]
+pub fn refresh_coarse() -> u64 {
+    let now = now();
+    COARSE_NOW.fetch_max(now, Ordering::Relaxed);
+    now
 }
 
-type IntervalList<T> = Arc<TimeoutQueueWrapper<T>>;
-
-// this is the data type that used by the binary heap to get the latest timer
-struct IntervalEntry<T> {
-    time: u64,
-    // the head timeout value in the list, should be latest
-    list: IntervalList<T>,
-    // point to the interval list
-    interval: u64,
+// the deadline after `dur` by the cached clock
+// the resolution is added so that the timer never expires early
+#[inline]
+pub fn coarse_deadline(dur: Duration) -> u64 {
+    let lag = dur_to_ns(crate::config().get_coarse_clock_resolution());
+    now_coarse().saturating_add(lag).saturating_add(dur_to_ns(dur))
 }
 
-impl<T> IntervalEntry<T> {
-    // trigger the timeout event with the supplying function
-    // return next expire time
-    pub fn pop_timeout<F>(&self, now: u64, f: &F) -> Option<u64>
-    where
-        F: Fn(T),
-    {
-        let p = |v: &TimeoutData<T>| v.time <= now;
-        while let Some(timeout) = self.list.inner.pop_if(&p) {
-            f(timeout.data);
-        }
-        self.list.inner.peek().map(|t| t.time)
-    }
+// timeout event data
+pub struct TimeoutData<T> {
+    pub data: T, // the data associate with the timeout event
 }
 
-impl<T> PartialEq for IntervalEntry<T> {
-    fn eq(&self, other: &Self) -> bool {
-        self.time == other.time
-    }
-}
+type Wheel<T> = Arc<Mutex<TimerWheel<TimeoutData<T>>>>;
 
-impl<T> Eq for IntervalEntry<T> {}
+// timeout handler which can be removed/cancelled
+pub struct TimeoutHandle<T> {
+    token: u64,
+    wheel: Wheel<T>,
+}
 
-impl<T> PartialOrd for IntervalEntry<T> {
-    fn partial_cmp(&self, other: &Self) -> Option<cmp::Ordering> {
-        Some(self.cmp(other))
+impl<T> TimeoutHandle<T> {
+    /// get the internal data mut ref, do nothing if the timer is expired
+    /// # Safety
+    ///
+    /// the data may be shared with the timeout handler, e.g. the raw pointers
+    #[inline]
+    pub unsafe fn with_mut_data<F>(&self, f: F)
+    where
+        F: FnOnce(&mut TimeoutData<T>),
+    {
+        if let Some(data) = self.wheel.lock().get_mut(self.token) {
+            f(data);
+        }
     }
-}
 
-impl<T> cmp::Ord for IntervalEntry<T> {
-    fn cmp(&self, other: &Self) -> cmp::Ordering {
-        other.time.cmp(&self.time)
+    /// remove the timer, return the data if it's not expired yet
+    #[inline]
+    pub fn remove(self) -> Option<T> {
+        self.wheel.lock().remove(self.token).map(|t| t.data)
     }
 }
 
 // the timeout list data structure
+//
+// a hierarchical timer wheel protected by a lock, the insert and remove are
+// O(1) no matter how many timers are pending. the timers are added in any
+// thread and expired in the thread that calls `schedule_timer`
 pub struct TimeOutList<T> {
-    // interval based hash map, protected by rw lock
-    interval_map: RwLock<HashMap<u64, IntervalList<T>>>,
-    // a priority queue, each element is the head of a mpsc queue
-    timer_bh: Mutex<BinaryHeap<IntervalEntry<T>>>,
+    wheel: Wheel<T>,
 }
 
 impl<T> TimeOutList<T> {
     pub fn new() -> Self {
         TimeOutList {
-            interval_map: RwLock::new(HashMap::with_capacity(HASH_CAP)),
-            timer_bh: Mutex::new(BinaryHeap::new()),
-        }
-    }
-
-    fn install_timer_bh(&self, entry: IntervalEntry<T>) {
-        if entry.list.in_use.fetch_add(1, Ordering::AcqRel) == 0 {
-            self.timer_bh.lock().push(entry);
+            wheel: Arc::new(Mutex::new(TimerWheel::new())),
         }
     }
 
@@ -142,63 +126,25 @@ impl<T> TimeOutList<T> {
     // this can be called in any thread
     // return true if we need to recall next expire
     pub fn add_timer(&self, dur: Duration, data: T) -> (TimeoutHandle<T>, bool) {
-        let interval = dur_to_ns(dur);
-        let time = now() + interval; // TODO: deal with overflow?
-                                     //println!("add timer = {:?}", time);
-
-        let timeout = TimeoutData { time, data };
+        self.add_timer_at(now().saturating_add(dur_to_ns(dur)), data)
+    }
 
-        let interval_list = {
-            // use the read lock protect
-            let interval_map_r = self.interval_map.read().unwrap();
-            (*interval_map_r).get(&interval).cloned()
-            // drop the read lock here
+    // same as `add_timer` but the timer expires at the absolute time in ns
+    pub fn add_timer_at(&self, deadline: u64, data: T) -> (TimeoutHandle<T>, bool) {
+        let mut wheel = self.wheel.lock();
+        // compare the absolute expire time, no need to read the clock
+        let next = wheel.next_expire(0);
+        let token = wheel.insert_at(deadline, TimeoutData { data });
+        // the new timer expires before the one that the thread is waiting for
+        let is_head = match next {
+            Some(next) => wheel.next_expire(0).is_some_and(|t| t < next),
+            None => true,
         };
-
-        if let Some(interval_list) = interval_list {
-            let (handle, is_head) = interval_list.inner.push(timeout);
-            if is_head {
-                // install the interval list to the binary heap
-                self.install_timer_bh(IntervalEntry {
-                    time,
-                    interval,
-                    list: interval_list,
-                });
-            }
-            return (handle, is_head);
-        }
-
-        // if the interval list is not there, get the write locker to install the list
-        // use the write lock protect
-        let mut interval_map_w = self.interval_map.write().unwrap();
-        // recheck the interval list in case other thread may install it
-        if let Some(interval_list) = (*interval_map_w).get(&interval) {
-            let (handle, is_head) = interval_list.inner.push(timeout);
-            if is_head {
-                // this rarely happens
-                self.install_timer_bh(IntervalEntry {
-                    time,
-                    interval,
-                    list: interval_list.clone(),
-                });
-            }
-            return (handle, is_head);
-        }
-
-        let interval_list = Arc::new(TimeoutQueueWrapper::<T>::new());
-        let ret = interval_list.inner.push(timeout).0;
-        (*interval_map_w).insert(interval, interval_list.clone());
-        // drop the write lock here
-        mem::drop(interval_map_w);
-
-        // install the new interval list to the binary heap
-        self.install_timer_bh(IntervalEntry {
-            time,
-            interval,
-            list: interval_list,
-        });
-
-        (ret, true)
+        let handle = TimeoutHandle {
+            token,
+            wheel: self.wheel.clone(),
+        };
+        (handle, is_head)
     }
 
     // schedule in the timer thread
@@ -207,55 +153,18 @@ impl<T> TimeOutList<T> {
     // return the time in ns for the next expiration
     pub fn schedule_timer<F: Fn(T)>(&self, now: u64, f: &F) -> Option<u64> {
         loop {
-            // first peek the BH to see if there is any timeout event
-            let mut entry = {
-                let mut timer_bh = self.timer_bh.lock();
-                match timer_bh.peek() {
-                    // the latest timeout event not happened yet
-                    Some(entry) => {
-                        if entry.time > now {
-                            return Some(entry.time - now);
-                        } else {
-                            // find out one entry
-                        }
-                    }
-                    None => return None,
-                }
-                let entry = timer_bh.pop().unwrap();
-                entry.list.in_use.store(0, Ordering::Release);
-                entry
+            let mut expired = Vec::new();
+            let next = {
+                let mut wheel = self.wheel.lock();
+                wheel.poll(now, |t| expired.push(t));
+                wheel.next_expire(now)
             };
-
-            // consume all the timeout event
-            // the binary heap can be modified here
-            // during running the timeout handler
-            match entry.pop_timeout(now, f) {
-                Some(time) => {
-                    if entry.list.in_use.fetch_add(1, Ordering::AcqRel) == 0 {
-                        // re-push the entry
-                        entry.time = time;
-                        self.timer_bh.lock().push(entry);
-                    }
-                }
-
-                None => {
-                    // if the interval list is empty, need to delete it
-                    let mut interval_map_w = self.interval_map.write().unwrap();
-                    // recheck if the interval list is empty, other thread may append data to it
-                    if entry.list.inner.is_empty() {
-                        // if the len of the hash map is big enough just leave the queue there
-                        if (*interval_map_w).len() > HASH_CAP {
-                            // the list is really empty now, we can safely remove it
-                            (*interval_map_w).remove(&entry.interval);
-                        }
-                    } else if entry.list.in_use.fetch_add(1, Ordering::AcqRel) == 0 {
-                        // release the w lock first, we don't need it any more
-                        mem::drop(interval_map_w);
-                        // the list is push some data by other thread
-                        entry.time = entry.list.inner.peek().unwrap().time;
-                        self.timer_bh.lock().push(entry);
-                    }
-                }
+            if expired.is_empty() {
+                return next;
+            }
+            // run the handlers without the lock, they may add new timers
+            for t in expired {
+                f(t.data);
             }
         }
     }
@@ -263,8 +172,6 @@ impl<T> TimeOutList<T> {
 
 pub struct TimerThread<T> {
     timer_list: TimeOutList<T>,
-    // collect the remove request
-    remove_list: mpsc<TimeoutHandle<T>>,
     // the timer thread wakeup handler
     wakeup: AtomicCell<Option<thread::Thread>>,
 }
@@ -273,14 +180,13 @@ impl<T> TimerThread<T> {
     pub fn new() -> Self {
         TimerThread {
             timer_list: TimeOutList::new(),
-            remove_list: mpsc::new(),
             wakeup: AtomicCell::new(None),
         }
     }
 
     pub fn add_timer(&self, dur: Duration, data: T) -> TimeoutHandle<T> {
         let (h, is_recal) = self.timer_list.add_timer(dur, data);
-        // wake up the timer thread if it's a new queue
+        // wake up the timer thread if it's the first timer to expire
         if is_recal {
             if let Some(t) = self.wakeup.take() {
                 t.unpark();
@@ -290,32 +196,33 @@ impl<T> TimerThread<T> {
     }
 
     pub fn del_timer(&self, handle: TimeoutHandle<T>) {
-        self.remove_list.push(handle);
-        if let Some(t) = self.wakeup.take() {
-            t.unpark();
-        }
+        // the thread would find nothing when waking up, no need to wake it now
+        handle.remove();
     }
 
-    // the timer thread function
-    pub fn run<F: Fn(T)>(&self, f: &F) {
+    // the timer thread function, `tick` is called at least every `interval`
+    pub fn run_with_tick<F: Fn(T), G: FnMut()>(&self, f: &F, interval: Option<Duration>, mut tick: G) {
         let current_thread = thread::current();
+        let interval = interval.map(dur_to_ns);
+        let mut next_tick = interval.map(|i| now() + i);
         loop {
-            while let Some(h) = self.remove_list.pop() {
-                h.remove();
+            if let (Some(i), Some(t)) = (interval, next_tick) {
+                let now = now();
+                if now >= t {
+                    tick();
+                    next_tick = Some(now + i);
+                }
             }
+
             // we must register the thread handle first
             // or there will be no signal to wakeup the timer thread
             self.wakeup.swap(Some(current_thread.clone()));
 
-            if !self.remove_list.is_empty() {
-                if let Some(t) = self.wakeup.take() {
-                    t.unpark();
-                }
-            }
-
-            match self.timer_list.schedule_timer(now(), f) {
-                Some(time) => thread::park_timeout(ns_to_dur(time)),
-                None => thread::park(),
+            let next_tick = next_tick.map(|t| t.saturating_sub(now()));
+            match (self.timer_list.schedule_timer(now(), f), next_tick) {
+                (Some(time), Some(t)) => thread::park_timeout(ns_to_dur(cmp::min(time, t))),
+                (Some(time), None) | (None, Some(time)) => thread::park_timeout(ns_to_dur(time)),
+                (None, None) => thread::park(),
             }
         }
     }
@@ -335,7 +242,7 @@ mod tests {
         let f = |data: usize| {
             println!("timeout data:{:?}", data);
         };
-        thread::spawn(move || t.run(&f));
+        thread::spawn(move || t.run_with_tick(&f, None, || {}));
         let t1 = timer.clone();
         thread::spawn(move || {
             t1.add_timer(Duration::from_millis(1000), 50);
@@ -351,4 +258,19 @@ mod tests {
 
         thread::sleep(Duration::from_millis(1500));
     }
+
+    #[test]
+    fn test_timer_tick() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        let timer = Arc::new(TimerThread::<usize>::new());
+        let ticks = Arc::new(AtomicUsize::new(0));
+        let (t, c) = (timer.clone(), ticks.clone());
+        thread::spawn(move || {
+            t.run_with_tick(&|_| {}, Some(Duration::from_millis(10)), || {
+                c.fetch_add(1, Ordering::Relaxed);
+            })
+        });
+        thread::sleep(Duration::from_millis(100));
+        assert!(ticks.load(Ordering::Relaxed) >= 5);
+    }
 }
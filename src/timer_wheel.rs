@@ -0,0 +1,284 @@
+//! hierarchical timer wheel
+//!
+//! each io worker owns a wheel that is only accessed in the worker thread,
+//! the timeout list wraps one in a lock for the timers added in any thread.
+//! the wheel has `LEVELS` levels of `SLOTS` slots, one tick is 1ms
+use std::cmp;
+use std::collections::HashMap;
+use std::mem;
+
+use crate::timeout_list::now;
+
+const SLOT_BITS: usize = 6;
+const SLOTS: usize = 1 << SLOT_BITS;
+const LEVELS: usize = 6;
+const NANOS_PER_TICK: u64 = 1_000_000;
+// the max ticks that a timer is linked ahead, about two years
+// a longer timer is linked at the max ticks and relinked when that slot expires
+const MAX_TICKS: u64 = (SLOTS as u64 - 1) << (SLOT_BITS * (LEVELS - 1));
+
+struct Level {
+    // bit map of the none empty slots
+    occupied: u64,
+    slots: Vec<Vec<u64>>,
+}
+
+impl Level {
+    fn new() -> Self {
+        Level {
+            occupied: 0,
+            slots: (0..SLOTS).map(|_| Vec::new()).collect(),
+        }
+    }
+}
+
+pub struct TimerWheel<T> {
+    levels: Vec<Level>,
+    // token -> (the tick that the timer expires, data)
+    entries: HashMap<u64, (u64, T)>,
+    next_token: u64,
+    // the tick that the wheel has processed
+    elapsed: u64,
+    // the removed tokens that are still left in the slots
+    stale: usize,
+}
+
+impl<T> TimerWheel<T> {
+    pub fn new() -> Self {
+        TimerWheel {
+            levels: (0..LEVELS).map(|_| Level::new()).collect(),
+            entries: HashMap::new(),
+            next_token: 0,
+            elapsed: now() / NANOS_PER_TICK,
+            stale: 0,
+        }
+    }
+
+    /// number of the pending timers
+    #[allow(dead_code)]
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    // add a timer that expires at the absolute time in ns, see `now`
+    // return the token that can be used to remove it
+    pub fn insert_at(&mut self, deadline: u64, data: T) -> u64 {
+        // round up, a timer never expires early
+        let when = deadline.div_ceil(NANOS_PER_TICK);
+        let when = cmp::max(when, self.elapsed);
+        let token = self.next_token;
+        self.next_token += 1;
+        self.entries.insert(token, (when, data));
+        self.link(token, when);
+        token
+    }
+
+    // the data of a pending timer
+    pub fn get_mut(&mut self, token: u64) -> Option<&mut T> {
+        self.entries.get_mut(&token).map(|(_, data)| data)
+    }
+
+    // remove a timer, return the data if it's not expired yet
+    pub fn remove(&mut self, token: u64) -> Option<T> {
+        // the token left in the slot is skipped when the slot expires
+        let (_, data) = self.entries.remove(&token)?;
+        self.stale += 1;
+        // the slots are compacted once they hold more removed tokens than
+        // pending ones, so the cost is amortized to the removes
+        if self.stale > SLOTS && self.stale > self.entries.len() {
+            self.compact();
+        }
+        Some(data)
+    }
+
+    // remove all the pending timers, return (token, deadline in ns, data)
+    pub fn drain(&mut self) -> Vec<(u64, u64, T)> {
+        for lv in self.levels.iter_mut() {
+            lv.occupied = 0;
+            lv.slots.iter_mut().for_each(Vec::clear);
+        }
+        self.stale = 0;
+        self.entries
+            .drain()
+            .map(|(token, (when, data))| (token, when * NANOS_PER_TICK, data))
+            .collect()
+    }
+
+    // drop the removed tokens from the slots
+    fn compact(&mut self) {
+        let entries = &self.entries;
+        for lv in self.levels.iter_mut() {
+            for (slot, tokens) in lv.slots.iter_mut().enumerate() {
+                tokens.retain(|t| entries.contains_key(t));
+                if tokens.is_empty() {
+                    lv.occupied &= !(1 << slot);
+                }
+            }
+        }
+        self.stale = 0;
+    }
+
+    // expire all the timers that are due at `now`, `now` is in ns
+    pub fn poll<F: FnMut(T)>(&mut self, now: u64, mut f: F) {
+        let now = now / NANOS_PER_TICK;
+        while let Some((level, slot, deadline)) = self.next_expiration() {
+            if deadline > now {
+                break;
+            }
+            self.elapsed = cmp::max(self.elapsed, deadline);
+            let lv = &mut self.levels[level];
+            lv.occupied &= !(1 << slot);
+            let tokens = mem::take(&mut lv.slots[slot]);
+            for token in tokens {
+                let when = match self.entries.get(&token) {
+                    Some(&(when, _)) => when,
+                    // already removed
+                    None => {
+                        self.stale = self.stale.saturating_sub(1);
+                        continue;
+                    }
+                };
+                if when <= now {
+                    let (_, data) = self.entries.remove(&token).unwrap();
+                    f(data);
+                } else {
+                    // cascade to a lower level
+                    self.link(token, when);
+                }
+            }
+        }
+        self.elapsed = cmp::max(self.elapsed, now);
+    }
+
+    // the time in ns before the next slot expires, `now` is in ns
+    pub fn next_expire(&self, now: u64) -> Option<u64> {
+        self.next_expiration()
+            .map(|(_, _, deadline)| (deadline * NANOS_PER_TICK).saturating_sub(now))
+    }
+
+    fn link(&mut self, token: u64, when: u64) {
+        let when = cmp::min(when, self.elapsed + MAX_TICKS);
+        // the level is decided by the highest bit that differs from the elapsed tick
+        let masked = (self.elapsed ^ when) | (SLOTS as u64 - 1);
+        let significant = 63 - masked.leading_zeros() as usize;
+        let level = cmp::min(significant / SLOT_BITS, LEVELS - 1);
+        let slot = ((when >> (level * SLOT_BITS)) as usize) & (SLOTS - 1);
+        let lv = &mut self.levels[level];
+        lv.slots[slot].push(token);
+        lv.occupied |= 1 << slot;
+    }
+
+    // find out the slot that expires first, return (level, slot, deadline tick)
+    fn next_expiration(&self) -> Option<(usize, usize, u64)> {
+        let mut ret: Option<(usize, usize, u64)> = None;
+        for (level, lv) in self.levels.iter().enumerate() {
+            if lv.occupied == 0 {
+                continue;
+            }
+            let slot_range = 1u64 << (level * SLOT_BITS);
+            let level_range = slot_range << SLOT_BITS;
+            let pos = ((self.elapsed >> (level * SLOT_BITS)) as usize) & (SLOTS - 1);
+            let rotated = lv.occupied.rotate_right(pos as u32);
+            let slot = (rotated.trailing_zeros() as usize + pos) & (SLOTS - 1);
+            let mut deadline = (self.elapsed & !(level_range - 1)) + slot as u64 * slot_range;
+            if slot < pos {
+                deadline += level_range;
+            }
+            match ret {
+                Some((_, _, d)) if d <= deadline => {}
+                _ => ret = Some((level, slot, deadline)),
+            }
+        }
+        ret
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_timer_wheel() {
+        let mut wheel = TimerWheel::new();
+        let start = now();
+        // one tick is 1ms
+        let ms = |n: u64| start + n * NANOS_PER_TICK;
+        wheel.insert_at(ms(10), 10);
+        wheel.insert_at(ms(100), 100);
+        let t = wheel.insert_at(ms(50), 50);
+        wheel.insert_at(ms(10_000), 10_000);
+        assert_eq!(wheel.remove(t), Some(50));
+        assert_eq!(wheel.len(), 3);
+
+        let mut fired = Vec::new();
+        wheel.poll(start, |v| fired.push(v));
+        assert!(fired.is_empty());
+        // rounded up to the next tick
+        assert!(wheel.next_expire(start).unwrap() <= 11 * NANOS_PER_TICK);
+
+        wheel.poll(start + 200 * NANOS_PER_TICK, |v| fired.push(v));
+        assert_eq!(fired, vec![10, 100]);
+
+        wheel.poll(start + 20_000 * NANOS_PER_TICK, |v| fired.push(v));
+        assert_eq!(fired, vec![10, 100, 10_000]);
+        assert!(wheel.is_empty());
+
+        // the deadline in the past expires on the next poll
+        wheel.insert_at(start, 1);
+        wheel.insert_at(start + 30_000 * NANOS_PER_TICK, 2);
+        wheel.poll(start + 20_000 * NANOS_PER_TICK, |v| fired.push(v));
+        assert_eq!(fired, vec![10, 100, 10_000, 1]);
+        wheel.poll(start + 30_001 * NANOS_PER_TICK, |v| fired.push(v));
+        assert_eq!(fired, vec![10, 100, 10_000, 1, 2]);
+
+        // the drained timers keep their deadline, rounded up to the tick
+        let t = wheel.insert_at(ms(40_000), 3);
+        let drained = wheel.drain();
+        assert_eq!(drained.len(), 1);
+        let (token, deadline, data) = drained[0];
+        assert_eq!((token, data), (t, 3));
+        assert!(deadline >= ms(40_000) && deadline < ms(40_001));
+        assert!(wheel.is_empty());
+        assert!(wheel.next_expire(start).is_none());
+    }
+
+    #[test]
+    fn long_timer_is_relinked() {
+        let mut wheel = TimerWheel::new();
+        let start = now();
+        let tick = |n: u64| start + n * NANOS_PER_TICK;
+        wheel.insert_at(tick(MAX_TICKS * 2), 1);
+        let mut fired = Vec::new();
+        wheel.poll(tick(MAX_TICKS + 1), |v| fired.push(v));
+        assert!(fired.is_empty());
+        assert_eq!(wheel.len(), 1);
+        wheel.poll(tick(MAX_TICKS * 2 + 1), |v| fired.push(v));
+        assert_eq!(fired, vec![1]);
+    }
+
+    #[test]
+    fn removed_tokens_are_compacted() {
+        let mut wheel = TimerWheel::new();
+        let start = now();
+        let tokens: Vec<_> = (0..1000)
+            .map(|i| wheel.insert_at(start + 1_000 * NANOS_PER_TICK, i))
+            .collect();
+        let keep = wheel.insert_at(start + 10 * NANOS_PER_TICK, 1);
+        for t in tokens {
+            assert!(wheel.remove(t).is_some());
+        }
+        let left: usize = wheel
+            .levels
+            .iter()
+            .flat_map(|lv| lv.slots.iter())
+            .map(Vec::len)
+            .sum();
+        assert!(left <= SLOTS + 1, "{} tokens left", left);
+        assert!(wheel.next_expire(start).unwrap() <= 11 * NANOS_PER_TICK);
+        assert_eq!(wheel.remove(keep), Some(1));
+    }
+}
@@ -1,16 +1,26 @@
+use std::cell::Cell;
 use std::thread;
 
+use crate::config::config;
 use crate::coroutine_impl::{current_cancel_data, is_coroutine};
 use crate::coroutine_impl::{CoroutineImpl, EventResult, EventSource, EventSubscriber};
+use crate::registry::State;
 use crate::scheduler::get_scheduler;
 use mco_gen::{co_get_yield, co_set_para, co_yield_with};
 
+// the remaining yield budget of the running coroutine, 0 means unlimited
+thread_local! {static BUDGET: Cell<usize> = const { Cell::new(0) };}
+
 struct Yield {}
 
 impl EventSource for Yield {
     fn subscribe(&mut self, co: CoroutineImpl) {
-        // just re-push the coroutine to the ready list
-        get_scheduler().schedule(co);
+        // just re-push the coroutine to the back of the ready list
+        get_scheduler().schedule_fifo(co);
+    }
+
+    fn state(&self) -> State {
+        State::Ready
     }
 }
 
@@ -24,13 +34,22 @@ pub fn yield_with<T: EventSource>(resource: &T) {
     // no need to get into kernel any more
     if cancel.is_canceled() {
         {
-            co_set_para(::std::io::Error::new(
-                ::std::io::ErrorKind::Other,
-                "Canceled",
-            ));
+            co_set_para(::std::io::Error::other("Canceled"));
             return resource.yield_back(cancel);
         }
     }
+    // the cancellation token is canceled, no need to wait
+    if cancel.is_interrupted() {
+        co_set_para(::std::io::Error::other("Canceled"));
+        return resource.yield_back(cancel);
+    }
+
+    #[cfg(nightly)]
+    if config().get_coroutine_backtrace() {
+        if let Some(l) = crate::local::get_co_local_data() {
+            unsafe { l.as_ref() }.get_co().capture_backtrace();
+        }
+    }
 
     let r = resource as &dyn EventSource as *const _ as *mut _;
     let es = EventSubscriber::new(r);
@@ -52,6 +71,32 @@ pub fn get_co_para() -> Option<EventResult> {
     co_get_yield::<EventResult>()
 }
 
+// refill the yield budget before a coroutine is resumed
+#[inline]
+pub(crate) fn reset_budget() {
+    BUDGET.with(|b| b.set(config().get_yield_budget()));
+}
+
+/// consume one unit of the yield budget of the current coroutine,
+/// yield once the budget is used up, see `Config::set_yield_budget`
+///
+/// it's called by the channel, lock and io operations, long running
+/// computations can call it as well
+#[inline]
+pub fn consume_budget() {
+    let left = BUDGET.with(|b| {
+        let left = b.get();
+        if left > 1 {
+            b.set(left - 1);
+        }
+        left
+    });
+    // the budget would be refilled when the coroutine is resumed
+    if left == 1 && is_coroutine() {
+        yield_now();
+    }
+}
+
 /// Suspends current execution, So that other coroutines can preempt. current may resume at some point in the future.
 /// It is safe to call this method on coroutines/threads
 #[inline]
@@ -0,0 +1,44 @@
+#[macro_use]
+extern crate mco;
+
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+use mco::coroutine::{Builder, SpawnError};
+
+#[test]
+fn spawn_when_queue_full() {
+    mco::config().set_workers(1).set_max_pending_coroutines(4);
+
+    // block the only worker so that nothing is taken from the queues
+    let (tx, rx) = mpsc::channel();
+    let blocker = co!(move || rx.recv().unwrap());
+    while mco::scheduler().pending() != 0 {
+        thread::sleep(Duration::from_millis(1));
+    }
+
+    let mut handles = Vec::new();
+    loop {
+        match Builder::new().try_spawn(|| 1) {
+            Ok(h) => handles.push(h),
+            Err(e) => {
+                assert_eq!(e, SpawnError::QueueFull);
+                break;
+            }
+        }
+    }
+    assert_eq!(handles.len(), 4);
+
+    // spawn waits until the queues drain
+    let spawner = thread::spawn(|| co!(|| 2).join().unwrap());
+    thread::sleep(Duration::from_millis(20));
+    assert_eq!(mco::scheduler().pending(), 4);
+
+    tx.send(()).unwrap();
+    blocker.join().unwrap();
+    for h in handles {
+        assert_eq!(h.join().unwrap(), 1);
+    }
+    assert_eq!(spawner.join().unwrap(), 2);
+}
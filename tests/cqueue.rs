@@ -229,3 +229,83 @@ fn cqueue_loop() {
 
     assert_eq!(result, 50);
 }
+
+#[test]
+fn cqueue_try_poll() {
+    cqueue::scope(|cqueue| {
+        cqueue_add_oneshot!(cqueue, 0, _ = coroutine::sleep(Duration::from_secs(10)) => {});
+        match cqueue.try_poll() {
+            Err(x) => assert_eq!(x, Timeout),
+            _ => unreachable!(),
+        }
+        cqueue_add_oneshot!(cqueue, 1, _ = () => {});
+        // let the select coroutine push its event
+        std::thread::sleep(Duration::from_millis(50));
+        match cqueue.try_poll() {
+            Ok(ev) => assert_eq!(ev.token, 1),
+            _ => unreachable!(),
+        }
+    });
+}
+
+#[test]
+fn select_default() {
+    use mco::std::sync::channel::channel;
+
+    let (tx, rx) = channel();
+    let mut hit = false;
+    let id = select!(
+        _ = rx.recv() => {},
+        default => hit = true,
+    );
+    assert_eq!(id, 1);
+    assert!(hit);
+
+    // a ready branch wins over the default one, in a coroutine
+    // the yield of the default branch lets the queued branches run
+    tx.send(1).unwrap();
+    let (id, hit) = co!(move || {
+        let mut hit = false;
+        let id = select!(
+            v = rx.recv() => assert_eq!(v, Ok(1)),
+            _ = coroutine::sleep(Duration::from_secs(10)) => {},
+            default => hit = true
+        );
+        (id, hit)
+    })
+    .join()
+    .unwrap();
+    assert_eq!(id, 0);
+    assert!(!hit);
+}
+
+#[test]
+fn select_random() {
+    use mco::std::sync::channel::channel;
+
+    let (tx1, rx1) = channel();
+    let (tx2, rx2) = channel();
+    let mut hits = [0; 2];
+    for _ in 0..100 {
+        tx1.send(()).unwrap();
+        tx2.send(()).unwrap();
+        let id = select!(
+            _ = rx1.recv() => {},
+            _ = rx2.recv() => {},
+        );
+        hits[id] += 1;
+        // drain the other one
+        let _ = rx1.try_recv();
+        let _ = rx2.try_recv();
+    }
+    assert!(hits[0] > 0 && hits[1] > 0, "{:?}", hits);
+
+    tx1.send(()).unwrap();
+    let id = select!(
+        biased;
+        Ok(_) = rx1.try_recv() => {},
+        _ = rx2.recv() => {},
+        default => {}
+    );
+    assert_eq!(id, 0);
+}
@@ -0,0 +1,71 @@
+use mco::co;
+use mco::std::http::{HttpClient, Request};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+
+// serve the requests of one connection, return the paths
+fn serve_one(listener: TcpListener) -> Vec<String> {
+    let (s, _) = listener.accept().unwrap();
+    let mut reader = BufReader::new(s.try_clone().unwrap());
+    let mut s = s;
+    let mut paths = Vec::new();
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).unwrap() == 0 {
+            return paths;
+        }
+        let path = line.split(' ').nth(1).unwrap().to_owned();
+        let mut len = 0;
+        loop {
+            let mut header = String::new();
+            reader.read_line(&mut header).unwrap();
+            if header == "\r\n" {
+                break;
+            }
+            if let Some(v) = header.to_ascii_lowercase().strip_prefix("content-length:") {
+                len = v.trim().parse().unwrap();
+            }
+        }
+        let mut body = vec![0; len];
+        reader.read_exact(&mut body).unwrap();
+
+        let rsp = match path.as_str() {
+            "/redirect" => {
+                "HTTP/1.1 302 Found\r\nLocation: /chunked\r\nContent-Length: 0\r\n\r\n".to_owned()
+            }
+            "/chunked" => "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n\
+                 5\r\nhello\r\n6;ext=1\r\n world\r\n0\r\nX-Trailer: t\r\n\r\n"
+                .to_owned(),
+            _ => format!(
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n{}",
+                body.len(),
+                String::from_utf8(body).unwrap()
+            ),
+        };
+        s.write_all(rsp.as_bytes()).unwrap();
+        paths.push(path);
+    }
+}
+
+#[test]
+fn http_client_keep_alive() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = std::thread::spawn(move || serve_one(listener));
+
+    let h = co!(move || {
+        let client = HttpClient::new();
+        let rsp = client.get(&format!("http://{}/redirect", addr)).unwrap();
+        assert_eq!(rsp.status(), 200);
+        assert_eq!(rsp.text(), "hello world");
+        assert_eq!(rsp.header("x-trailer"), Some("t"));
+        assert!(rsp.url().ends_with("/chunked"));
+
+        let req = Request::post(&format!("http://{}/echo", addr)).body("ping");
+        let rsp = client.request(req).unwrap();
+        assert_eq!(rsp.body(), b"ping");
+    });
+    h.join().unwrap();
+    // all the requests are on the same connection, closed with the client
+    assert_eq!(server.join().unwrap(), ["/redirect", "/chunked", "/echo"]);
+}
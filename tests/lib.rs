@@ -190,6 +190,175 @@ fn scoped_coroutine() {
     assert_eq!(array[2], 4);
 }
 
+#[test]
+fn scope_cancel() {
+    let start = Instant::now();
+    let ret: Result<(), &str> = coroutine::scope_cancel(|scope| unsafe {
+        scope.spawn(|| {
+            // canceled by the failed sibling
+            coroutine::sleep(Duration::from_secs(10));
+            assert!(coroutine::current().cancel_token().is_canceled());
+            Ok(())
+        });
+        scope.spawn(|| Err("failed"));
+    });
+    assert_eq!(ret, Err("failed"));
+    assert!(start.elapsed() < Duration::from_secs(5));
+}
+
+#[test]
+fn scope_cancel_detach() {
+    let j = co!(|| {
+        let ret: Result<_, ()> = coroutine::scope_cancel(|scope| scope.token().clone());
+        let token = ret.unwrap();
+        // the escaped scope token no longer follows the coroutine token
+        coroutine::current().cancel_token().cancel();
+        token.is_canceled()
+    });
+    assert!(!j.join().unwrap());
+}
+
+#[test]
+fn stack_usage() {
+    mco::config().set_stack_stats(true);
+    let j = co!(|| {
+        let buf = [1u8; 1024];
+        coroutine::yield_now();
+        let (used, total) = coroutine::current().stack_usage();
+        assert!(used >= buf.len());
+        assert!(used < total);
+    });
+    let r = j.join();
+    mco::config().set_stack_stats(false);
+    r.unwrap();
+}
+
+#[test]
+fn stack_usage_deep_call() {
+    #[inline(never)]
+    fn deep(n: usize) -> u8 {
+        let buf = std::hint::black_box([n as u8; 256]);
+        if n == 0 {
+            return buf[0];
+        }
+        deep(n - 1).wrapping_add(buf[255])
+    }
+
+    mco::config().set_stack_stats(true);
+    let j = co!(coroutine::Builder::new().stack_size(0x8000), || {
+        // the frames are gone before the coroutine is suspended
+        deep(32);
+        coroutine::yield_now();
+        let (used, total) = coroutine::current().stack_usage();
+        assert!(used >= 32 * 256, "used={}", used);
+        assert!(used < total);
+    });
+    let r = j.join();
+    mco::config().set_stack_stats(false);
+    r.unwrap();
+}
+
+#[test]
+fn coroutine_id() {
+    let j = co!(|| {
+        let co = coroutine::current();
+        assert_eq!(co.parent_id(), None);
+        co.id()
+    });
+    let id = j.coroutine().id();
+    assert_eq!(j.join().unwrap(), id);
+}
+
+#[test]
+fn panic_handler() {
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::Arc;
+
+    let panicked = Arc::new(AtomicU64::new(0));
+    let p = panicked.clone();
+    coroutine::set_panic_handler(move |co, payload| {
+        if payload.downcast_ref::<&str>() == Some(&"panic_handler") {
+            p.store(co.id(), Ordering::Relaxed);
+        }
+    });
+    let j = co!(|| panic!("panic_handler"));
+    let id = j.coroutine().id();
+    assert!(j.join().is_err());
+    coroutine::take_panic_handler();
+    assert_eq!(panicked.load(Ordering::Relaxed), id);
+}
+
+#[test]
+fn scoped_fork() {
+    let mut array = [1, 2, 3];
+    let sum = coroutine::scope(|scope| {
+        let (a, b) = array.split_at_mut(1);
+        scope.fork(move || a[0] += 1);
+        let h = scope.fork(move || {
+            b[0] += 1;
+            b[0] + b[1]
+        });
+        h.join()
+    });
+
+    assert_eq!(sum, 6);
+    assert_eq!(array, [2, 3, 3]);
+}
+
+#[test]
+fn yield_budget() {
+    use mco::std::sync::{Mutex, RwLock};
+
+    // restore the global config even if the test fails
+    struct ResetBudget;
+    impl Drop for ResetBudget {
+        fn drop(&mut self) {
+            mco::config().set_yield_budget(0);
+        }
+    }
+
+    let _reset = ResetBudget;
+    mco::config().set_yield_budget(4);
+    let j = co!(|| {
+        let lock = Mutex::new(0);
+        let rwlock = RwLock::new(0);
+        for _ in 0..100 {
+            *lock.lock().unwrap() += 1;
+            let v = *rwlock.read().unwrap();
+            *rwlock.write().unwrap() = v + 1;
+            coroutine::consume_budget();
+        }
+        let v = *lock.lock().unwrap() + *rwlock.read().unwrap();
+        v
+    });
+    assert_eq!(j.join().unwrap(), 200);
+}
+
+#[test]
+fn spawn_detached() {
+    let (tx, rx) = mco::chan!();
+    co_detached!(move || tx.send(10).unwrap());
+    assert_eq!(rx.recv().unwrap(), 10);
+}
+
+#[test]
+fn spawn_pinned() {
+    let j = co!(|| {
+        let worker = thread::current().id();
+        // the closure doesn't need to be Send
+        let rc = std::rc::Rc::new(10);
+        let h = coroutine::spawn_pinned(move || {
+            for _ in 0..10 {
+                assert_eq!(thread::current().id(), worker);
+                coroutine::sleep(Duration::from_millis(1));
+            }
+            *rc
+        });
+        h.join().unwrap()
+    });
+    assert_eq!(j.join().unwrap(), 10);
+}
+
 #[test]
 #[allow(unused_assignments)]
 fn unpark() {
@@ -259,6 +428,22 @@ fn test_sleep() {
     });
 }
 
+#[test]
+fn test_sleep_until() {
+    let deadline = Instant::now() + Duration::from_millis(200);
+    let h = co!(move || {
+        // each step only waits for what is left
+        for _ in 0..3 {
+            coroutine::sleep_until(deadline);
+        }
+        assert!(Instant::now() >= deadline);
+        coroutine::sleep_until(Instant::now() - Duration::from_secs(1));
+    });
+    h.join().unwrap();
+    assert!(Instant::now() >= deadline);
+    assert!(deadline.elapsed() < Duration::from_millis(150));
+}
+
 #[test]
 fn join_macro() {
     use mco::std::sync::channel::channel;
@@ -319,3 +504,130 @@ fn co_with_macro() {
         assert_eq!(stack_size, 10240);
     }
 }
+
+#[test]
+fn dynamic_workers() {
+    let s = mco::scheduler();
+    let base = s.workers();
+    assert!(s.max_workers() >= base);
+    let added = s.add_workers(2);
+    assert_eq!(s.workers(), base + added);
+
+    let j = co!(|| 42);
+    assert_eq!(j.join().unwrap(), 42);
+
+    assert_eq!(s.remove_workers(added), added);
+    assert_eq!(s.workers(), base);
+}
+
+#[test]
+fn spawn_blocking() {
+    let j = co!(|| {
+        let h = blocking!(|| {
+            thread::sleep(Duration::from_millis(50));
+            7
+        });
+        h.join().unwrap()
+    });
+    assert_eq!(j.join().unwrap(), 7);
+
+    let h = coroutine::spawn_blocking(|| panic!("panic inside blocking task"));
+    assert!(h.join().is_err());
+}
+
+#[test]
+fn scheduler_stats() {
+    let s = mco::scheduler();
+    co!(coroutine::yield_now).join().unwrap();
+    let stats = s.stats();
+    assert_eq!(stats.workers.len(), s.workers());
+    assert!(stats.workers.iter().map(|w| w.tasks).sum::<u64>() > 0);
+}
+
+#[test]
+fn lifo_ping_pong() {
+    use mco::std::sync::channel::channel;
+
+    let (tx1, rx1) = channel();
+    let (tx2, rx2) = channel();
+    let j = co!(move || {
+        for i in 0..1000 {
+            tx1.send(i).unwrap();
+            assert_eq!(rx2.recv().unwrap(), i);
+        }
+    });
+    for _ in 0..1000 {
+        let v = rx1.recv().unwrap();
+        tx2.send(v).unwrap();
+    }
+    j.join().unwrap();
+}
+
+#[test]
+fn coroutine_priority() {
+    use mco::coroutine::{Builder, Priority};
+
+    let j = Builder::new().priority(Priority::High).spawn(|| {
+        coroutine::yield_now();
+        coroutine::current().priority()
+    });
+    assert_eq!(j.join().unwrap(), Priority::High);
+}
+
+#[test]
+fn try_spawn() {
+    use mco::coroutine::Builder;
+
+    // the queues are unbounded by default
+    assert_eq!(mco::config().get_max_pending_coroutines(), 0);
+    let j = Builder::new().try_spawn(|| 42).unwrap();
+    assert_eq!(j.join().unwrap(), 42);
+    assert_eq!(
+        mco::coroutine::SpawnError::QueueFull.to_string(),
+        "too many pending coroutines"
+    );
+}
+
+#[test]
+fn spawn_on_unknown_worker() {
+    use mco::coroutine::{Builder, SpawnError};
+
+    let r = Builder::new().spawn_on(usize::MAX, || ());
+    assert_eq!(r.unwrap_err(), SpawnError::UnknownWorker(usize::MAX));
+}
+
+#[test]
+fn cancel_token() {
+    use mco::coroutine::CancellationToken;
+
+    let h = co!(|| {
+        let token = coroutine::current().cancel_token();
+        let now = Instant::now();
+        // the sleep returns early once the token is canceled
+        coroutine::sleep(Duration::from_secs(10));
+        assert!(now.elapsed() < Duration::from_secs(5));
+        assert!(token.check().is_err());
+    });
+    thread::sleep(Duration::from_millis(50));
+    h.coroutine().cancel_token().cancel();
+    h.join().unwrap();
+
+    let parent = CancellationToken::new();
+    let child = parent.child_token();
+    assert!(!child.is_canceled());
+    parent.cancel();
+    assert!(child.is_canceled());
+}
+
+#[test]
+fn cancel_token_recv() {
+    use mco::std::sync::RecvError;
+
+    let (tx, rx) = chan!(i32, 1);
+    let h = co!(move || rx.recv());
+    thread::sleep(Duration::from_millis(50));
+    h.coroutine().cancel_token().cancel();
+    // the channel is still connected
+    assert_eq!(h.join().unwrap(), Err(RecvError::Canceled));
+    drop(tx);
+}
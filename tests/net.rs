@@ -0,0 +1,560 @@
+use mco::co;
+use mco::net::UdpSocket;
+use std::io::ErrorKind;
+use std::time::Duration;
+
+#[test]
+fn udp_peek_and_connect() {
+    let h = co!(|| {
+        let a = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let b = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let a_addr = a.local_addr().unwrap();
+        let b_addr = b.local_addr().unwrap();
+
+        let mut buf = [0u8; 16];
+        b.set_read_timeout(Some(Duration::from_millis(10))).unwrap();
+        let err = b.peek_from(&mut buf).unwrap_err();
+        assert!(matches!(
+            err.kind(),
+            ErrorKind::TimedOut | ErrorKind::WouldBlock
+        ));
+
+        a.send_to(b"hello", b_addr).unwrap();
+        // the datagram is still there after peek
+        assert_eq!(b.peek_from(&mut buf).unwrap(), (5, a_addr));
+        assert_eq!(b.recv_from(&mut buf).unwrap(), (5, a_addr));
+        assert_eq!(&buf[..5], b"hello");
+
+        a.connect(b_addr).unwrap();
+        b.connect(a_addr).unwrap();
+        assert_eq!(a.peer_addr().unwrap(), b_addr);
+        b.send(b"world").unwrap();
+        assert_eq!(a.peek(&mut buf).unwrap(), 5);
+        assert_eq!(a.recv(&mut buf).unwrap(), 5);
+        assert_eq!(&buf[..5], b"world");
+    });
+    h.join().unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn unix_socketpair() {
+    use mco::net::unix::socketpair;
+    use std::io::{Read, Write};
+
+    let (mut a, mut b) = socketpair().unwrap();
+    let h = co!(move || {
+        let mut buf = [0u8; 4];
+        b.read_exact(&mut buf).unwrap();
+        b.write_all(&buf).unwrap();
+    });
+    a.write_all(b"ping").unwrap();
+    let mut buf = [0u8; 4];
+    a.read_exact(&mut buf).unwrap();
+    assert_eq!(&buf, b"ping");
+    h.join().unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn unix_datagram_pair() {
+    use mco::net::unix::UnixDatagram;
+
+    let (a, b) = UnixDatagram::pair().unwrap();
+    let h = co!(move || {
+        let mut buf = [0u8; 8];
+        let n = b.recv(&mut buf).unwrap();
+        b.send(&buf[..n]).unwrap();
+    });
+    a.send(b"pong").unwrap();
+    let mut buf = [0u8; 4];
+    assert_eq!(a.recv(&mut buf).unwrap(), 4);
+    assert_eq!(&buf, b"pong");
+    h.join().unwrap();
+}
+
+// the connects to a listener with a full backlog are pending
+fn full_backlog() -> (socket2::Socket, Vec<std::net::TcpStream>) {
+    use socket2::{Domain, Socket, Type};
+    use std::net::SocketAddr;
+
+    let listener = Socket::new(Domain::IPV4, Type::STREAM, None).unwrap();
+    let addr: SocketAddr = "127.0.0.1:0".parse().unwrap();
+    listener.bind(&addr.into()).unwrap();
+    listener.listen(0).unwrap();
+    let addr = listener.local_addr().unwrap().as_socket().unwrap();
+    let mut streams = Vec::new();
+    while let Ok(s) = std::net::TcpStream::connect_timeout(&addr, Duration::from_millis(100)) {
+        streams.push(s);
+    }
+    (listener, streams)
+}
+
+#[test]
+fn tcp_connect_timeout() {
+    use mco::net::TcpStream;
+
+    let (listener, _streams) = full_backlog();
+    let addr = listener.local_addr().unwrap().as_socket().unwrap();
+    let h = co!(move || {
+        let now = std::time::Instant::now();
+        let err = TcpStream::connect_timeout(&addr, Duration::from_millis(100)).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::TimedOut);
+        assert!(now.elapsed() < Duration::from_secs(1));
+    });
+    h.join().unwrap();
+}
+
+#[test]
+fn tcp_connect_cancel_token() {
+    use mco::net::TcpStream;
+
+    let (listener, _streams) = full_backlog();
+    let addr = listener.local_addr().unwrap().as_socket().unwrap();
+    let h = co!(move || TcpStream::connect(addr).map(|_| ()));
+    std::thread::sleep(Duration::from_millis(50));
+    h.coroutine().cancel_token().cancel();
+    assert_eq!(h.join().unwrap().unwrap_err().to_string(), "Canceled");
+}
+
+#[test]
+fn udp_recv_from_cancel_token() {
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let h = co!(move || {
+        let mut buf = [0u8; 16];
+        socket.recv_from(&mut buf).map(|_| ())
+    });
+    std::thread::sleep(Duration::from_millis(50));
+    h.coroutine().cancel_token().cancel();
+    assert_eq!(h.join().unwrap().unwrap_err().to_string(), "Canceled");
+}
+
+#[test]
+fn tcp_vectored() {
+    use mco::net::TcpListener;
+    use std::io::{IoSlice, IoSliceMut, Read, Write};
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let h = co!(move || {
+        let (mut s, _) = listener.accept().unwrap();
+        let (mut head, mut body) = ([0u8; 2], [0u8; 3]);
+        let mut bufs = [IoSliceMut::new(&mut head), IoSliceMut::new(&mut body)];
+        // the small segment on loopback arrives at once
+        assert_eq!(s.read_vectored(&mut bufs).unwrap(), 5);
+        let bufs = [IoSlice::new(&body), IoSlice::new(&head)];
+        assert_eq!(s.write_vectored(&bufs).unwrap(), 5);
+    });
+
+    let mut s = std::net::TcpStream::connect(addr).unwrap();
+    s.write_all(b"hello").unwrap();
+    let mut buf = Vec::new();
+    s.read_to_end(&mut buf).unwrap();
+    assert_eq!(buf, b"llohe");
+    h.join().unwrap();
+}
+
+#[test]
+fn udp_vectored() {
+    use std::io::{IoSlice, IoSliceMut};
+
+    let h = co!(|| {
+        let a = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let b = UdpSocket::bind("127.0.0.1:0").unwrap();
+        a.connect(b.local_addr().unwrap()).unwrap();
+        b.connect(a.local_addr().unwrap()).unwrap();
+
+        let bufs = [IoSlice::new(b"he"), IoSlice::new(b"llo")];
+        assert_eq!(a.send_vectored(&bufs).unwrap(), 5);
+        let (mut head, mut body) = ([0u8; 3], [0u8; 8]);
+        let mut bufs = [IoSliceMut::new(&mut head), IoSliceMut::new(&mut body)];
+        assert_eq!(b.recv_vectored(&mut bufs).unwrap(), 5);
+        assert_eq!(&head, b"hel");
+        assert_eq!(&body[..2], b"lo");
+    });
+    h.join().unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn unix_vectored() {
+    use mco::net::unix::socketpair;
+    use std::io::{IoSlice, IoSliceMut, Read, Write};
+
+    let (mut a, mut b) = socketpair().unwrap();
+    let h = co!(move || {
+        let (mut head, mut body) = ([0u8; 1], [0u8; 3]);
+        let mut bufs = [IoSliceMut::new(&mut head), IoSliceMut::new(&mut body)];
+        assert_eq!(b.read_vectored(&mut bufs).unwrap(), 4);
+        assert_eq!(&head, b"p");
+        assert_eq!(&body, b"ing");
+    });
+    let bufs = [IoSlice::new(b"pi"), IoSlice::new(b"ng")];
+    assert_eq!(a.write_vectored(&bufs).unwrap(), 4);
+    h.join().unwrap();
+}
+
+#[test]
+fn tcp_peek() {
+    use mco::net::TcpListener;
+    use std::io::{Read, Write};
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let h = co!(move || {
+        let (mut s, _) = listener.accept().unwrap();
+        // parked until the data comes
+        let mut buf = [0u8; 5];
+        assert_eq!(s.peek(&mut buf).unwrap(), 5);
+        assert_eq!(&buf, b"hello");
+        let mut buf = [0u8; 5];
+        s.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"hello");
+    });
+
+    let mut s = std::net::TcpStream::connect(addr).unwrap();
+    std::thread::sleep(Duration::from_millis(50));
+    s.write_all(b"hello").unwrap();
+    h.join().unwrap();
+}
+
+// reuse_port is unix only
+#[cfg(unix)]
+#[test]
+fn socket_options() {
+    use mco::net::{TcpKeepalive, TcpListenOptions, TcpListener, TcpStream};
+
+    let opts = TcpListenOptions::new().backlog(16);
+    let listener = TcpListener::bind_with("127.0.0.1:0", &opts).unwrap();
+    let addr = listener.local_addr().unwrap();
+    // SO_REUSEPORT lets the second listener share the address
+    let _shared = TcpListener::bind_with(addr, &opts).unwrap();
+    let opts = TcpListenOptions::new().reuse_port(false);
+    assert!(TcpListener::bind_with(addr, &opts).is_err());
+    listener.set_ttl(32).unwrap();
+    assert_eq!(listener.ttl().unwrap(), 32);
+
+    let s = TcpStream::new(std::net::TcpStream::connect(addr).unwrap()).unwrap();
+    s.set_nodelay(true).unwrap();
+    assert!(s.nodelay().unwrap());
+    let params = TcpKeepalive::new().with_time(Duration::from_secs(30));
+    s.set_keepalive(Some(&params)).unwrap();
+    assert!(s.keepalive().unwrap());
+    s.set_keepalive(None).unwrap();
+    assert!(!s.keepalive().unwrap());
+    s.set_linger(Some(Duration::from_secs(1))).unwrap();
+    assert_eq!(s.linger().unwrap(), Some(Duration::from_secs(1)));
+    s.set_recv_buffer_size(64 * 1024).unwrap();
+    assert!(s.recv_buffer_size().unwrap() >= 64 * 1024);
+
+    let udp = UdpSocket::bind("127.0.0.1:0").unwrap();
+    udp.set_send_buffer_size(64 * 1024).unwrap();
+    assert!(udp.send_buffer_size().unwrap() >= 64 * 1024);
+}
+
+#[cfg(unix)]
+#[test]
+fn tcp_bind_sharded() {
+    use mco::net::TcpListener;
+    use std::io::{Read, Write};
+
+    let (tx, rx) = std::sync::mpsc::channel();
+    let tx = std::sync::Mutex::new(tx);
+    let shards = TcpListener::bind_sharded("127.0.0.1:0", move |listener| {
+        tx.lock().unwrap().send(listener.local_addr().unwrap()).unwrap();
+        // each shard serves one connection, then leaves the reuseport group
+        let (mut s, _) = listener.accept().unwrap();
+        s.write_all(b"ok").unwrap();
+    })
+    .unwrap();
+    assert_eq!(shards.len(), mco::config().get_workers());
+    let addrs: Vec<_> = rx.iter().take(shards.len()).collect();
+    assert!(addrs.windows(2).all(|w| w[0] == w[1]));
+
+    let mut served = 0;
+    while served < shards.len() {
+        // the connection queued on a shard that just closed is reset
+        let mut s = std::net::TcpStream::connect(addrs[0]).unwrap();
+        let mut buf = Vec::new();
+        if s.read_to_end(&mut buf).is_ok() && buf == b"ok" {
+            served += 1;
+        }
+    }
+    for h in shards {
+        h.join().unwrap();
+    }
+}
+
+#[test]
+fn tcp_half_close() {
+    use mco::net::TcpStream;
+    use std::io::{Read, Write};
+    use std::net::Shutdown;
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let h = co!(move || {
+        let mut s = TcpStream::connect(addr).unwrap();
+        s.write_all(b"ping").unwrap();
+        s.shutdown(Shutdown::Write).unwrap();
+        // the response tail is still readable
+        let mut rsp = Vec::new();
+        s.read_to_end(&mut rsp).unwrap();
+        rsp
+    });
+
+    let (mut s, _) = listener.accept().unwrap();
+    let mut req = Vec::new();
+    s.read_to_end(&mut req).unwrap();
+    assert_eq!(req, b"ping");
+    s.write_all(b"pong").unwrap();
+    drop(s);
+    assert_eq!(h.join().unwrap(), b"pong");
+}
+
+#[cfg(unix)]
+#[test]
+fn tcp_shutdown_wakes_reader() {
+    use mco::net::TcpStream;
+    use std::io::Read;
+    use std::net::Shutdown;
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let s = TcpStream::connect(addr).unwrap();
+    let _peer = listener.accept().unwrap();
+    let reader = s.try_clone().unwrap();
+    let h = co!(move || {
+        let mut reader = reader;
+        let mut buf = [0u8; 4];
+        reader.read(&mut buf).unwrap()
+    });
+    std::thread::sleep(Duration::from_millis(50));
+    s.shutdown(Shutdown::Read).unwrap();
+    assert_eq!(h.join().unwrap(), 0);
+}
+
+#[cfg(unix)]
+#[test]
+fn poll_fd_readable() {
+    use mco::io::PollFd;
+    use std::io::{Read, Write};
+    use std::os::unix::net::UnixStream;
+
+    let (a, mut b) = UnixStream::pair().unwrap();
+    a.set_nonblocking(true).unwrap();
+    let h = co!(move || {
+        let fd = PollFd::new(a).unwrap();
+        // parked until the peer writes
+        fd.readable().unwrap();
+        let mut buf = [0u8; 5];
+        let n = fd.read_with(|mut s| s.read(&mut buf)).unwrap();
+        buf[..n].to_vec()
+    });
+    std::thread::sleep(Duration::from_millis(50));
+    b.write_all(b"hello").unwrap();
+    assert_eq!(h.join().unwrap(), b"hello");
+}
+
+#[test]
+fn udp_multicast_options() {
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    let sock = UdpSocket::bind("0.0.0.0:0").unwrap();
+    sock.set_multicast_ttl_v4(4).unwrap();
+    assert_eq!(sock.multicast_ttl_v4().unwrap(), 4);
+    sock.set_multicast_if_v4(&Ipv4Addr::LOCALHOST).unwrap();
+    assert_eq!(sock.multicast_if_v4().unwrap(), Ipv4Addr::LOCALHOST);
+
+    if let Ok(sock) = UdpSocket::bind((Ipv6Addr::UNSPECIFIED, 0)) {
+        sock.set_multicast_hops_v6(4).unwrap();
+        assert_eq!(sock.multicast_hops_v6().unwrap(), 4);
+    }
+}
+
+#[test]
+fn tcp_copy_bidirectional() {
+    use mco::net::{copy_bidirectional, TcpStream};
+    use std::io::{Read, Write};
+    use std::net::{Shutdown, TcpListener};
+
+    let front = TcpListener::bind("127.0.0.1:0").unwrap();
+    let upstream = TcpListener::bind("127.0.0.1:0").unwrap();
+    let mut client = std::net::TcpStream::connect(front.local_addr().unwrap()).unwrap();
+    let a = TcpStream::new(front.accept().unwrap().0).unwrap();
+    let b = TcpStream::connect(upstream.local_addr().unwrap()).unwrap();
+    let (mut server, _) = upstream.accept().unwrap();
+
+    let server = std::thread::spawn(move || {
+        let mut req = Vec::new();
+        server.read_to_end(&mut req).unwrap();
+        req.reverse();
+        server.write_all(&req).unwrap();
+    });
+    let client = std::thread::spawn(move || {
+        client.write_all(b"hello").unwrap();
+        client.shutdown(Shutdown::Write).unwrap();
+        let mut rsp = Vec::new();
+        client.read_to_end(&mut rsp).unwrap();
+        rsp
+    });
+
+    assert_eq!(copy_bidirectional(&a, &b).unwrap(), (5, 5));
+    server.join().unwrap();
+    assert_eq!(client.join().unwrap(), b"olleh");
+}
+
+#[cfg(unix)]
+#[test]
+fn unix_copy_bidirectional() {
+    use mco::io::copy_bidirectional;
+    use mco::os::unix::net::UnixStream;
+    use std::io::{Read, Write};
+    use std::net::Shutdown;
+
+    let (a, mut client) = UnixStream::pair().unwrap();
+    let (b, mut server) = UnixStream::pair().unwrap();
+    let server = std::thread::spawn(move || {
+        let mut req = Vec::new();
+        server.read_to_end(&mut req).unwrap();
+        req.reverse();
+        server.write_all(&req).unwrap();
+    });
+    let client = std::thread::spawn(move || {
+        client.write_all(b"hello").unwrap();
+        client.shutdown(Shutdown::Write).unwrap();
+        let mut rsp = Vec::new();
+        client.read_to_end(&mut rsp).unwrap();
+        rsp
+    });
+
+    assert_eq!(copy_bidirectional(&a, &b).unwrap(), (5, 5));
+    server.join().unwrap();
+    assert_eq!(client.join().unwrap(), b"olleh");
+}
+
+#[test]
+fn socks5_connect() {
+    use mco::net::proxy::Socks5Connector;
+    use std::io::{Read, Write};
+
+    let proxy = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = proxy.local_addr().unwrap();
+    let h = co!(move || {
+        let connector = Socks5Connector::new(addr).auth("user", "pass");
+        let mut s = connector.connect("example.com", 80).unwrap();
+        s.write_all(b"ping").unwrap();
+        let mut buf = [0u8; 4];
+        s.read_exact(&mut buf).unwrap();
+        buf
+    });
+
+    // a fake proxy that tunnels to an echo server
+    let (mut s, _) = proxy.accept().unwrap();
+    let mut buf = [0u8; 4];
+    s.read_exact(&mut buf).unwrap();
+    assert_eq!(buf, [0x05, 0x02, 0x00, 0x02]);
+    s.write_all(&[0x05, 0x02]).unwrap();
+    let mut auth = [0u8; 11];
+    s.read_exact(&mut auth).unwrap();
+    assert_eq!(&auth, b"\x01\x04user\x04pass");
+    s.write_all(&[0x01, 0x00]).unwrap();
+    let mut req = [0u8; 18];
+    s.read_exact(&mut req).unwrap();
+    assert_eq!(&req, b"\x05\x01\x00\x03\x0bexample.com\x00\x50");
+    s.write_all(&[0x05, 0x00, 0x00, 0x01, 127, 0, 0, 1, 0x1f, 0x90])
+        .unwrap();
+    s.read_exact(&mut buf).unwrap();
+    s.write_all(&buf).unwrap();
+    assert_eq!(&h.join().unwrap(), b"ping");
+}
+
+#[test]
+fn http_connect() {
+    use mco::net::proxy::HttpConnectConnector;
+    use std::io::{BufRead, BufReader, Read, Write};
+
+    let proxy = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = proxy.local_addr().unwrap();
+    let h = co!(move || {
+        let connector = HttpConnectConnector::new(addr).auth("user", "pass");
+        let mut s = connector.connect("example.com", 443).unwrap();
+        let mut buf = [0u8; 5];
+        s.read_exact(&mut buf).unwrap();
+        let err = connector.connect("example.com", 443).unwrap_err();
+        (buf, err.kind())
+    });
+
+    let (s, _) = proxy.accept().unwrap();
+    let mut reader = BufReader::new(s);
+    let mut header = String::new();
+    while !header.ends_with("\r\n\r\n") {
+        reader.read_line(&mut header).unwrap();
+    }
+    assert!(header.starts_with("CONNECT example.com:443 HTTP/1.1\r\n"));
+    assert!(header.contains("Proxy-Authorization: Basic dXNlcjpwYXNz\r\n"));
+    // the tunnel data right after the response is not lost
+    let mut s = reader.into_inner();
+    s.write_all(b"HTTP/1.1 200 Connection established\r\n\r\nhello")
+        .unwrap();
+
+    let (mut s, _) = proxy.accept().unwrap();
+    s.write_all(b"HTTP/1.1 407 Proxy Authentication Required\r\n\r\n")
+        .unwrap();
+    let (buf, err) = h.join().unwrap();
+    assert_eq!(&buf, b"hello");
+    assert_eq!(err, ErrorKind::PermissionDenied);
+}
+
+#[test]
+fn tcp_std_conversion() {
+    use mco::net::{TcpListener, TcpStream};
+    use std::io::{Read, Write};
+
+    let listener = TcpListener::from_std(std::net::TcpListener::bind("127.0.0.1:0").unwrap())
+        .unwrap()
+        .into_std()
+        .unwrap();
+    let addr = listener.local_addr().unwrap();
+    let s = std::net::TcpStream::connect(addr).unwrap();
+    let (mut peer, _) = listener.accept().unwrap();
+
+    let dur = Some(Duration::from_millis(500));
+    s.set_read_timeout(dur).unwrap();
+    let s = TcpStream::from_std(s).unwrap();
+    assert_eq!(s.read_timeout().unwrap(), dur);
+
+    // back to a blocking std stream
+    let mut s = s.into_std().unwrap();
+    assert_eq!(s.read_timeout().unwrap(), dur);
+    peer.write_all(b"hello").unwrap();
+    let mut buf = [0u8; 5];
+    s.read_exact(&mut buf).unwrap();
+    assert_eq!(&buf, b"hello");
+}
+
+#[test]
+fn tcp_accept_timeout() {
+    use mco::net::{TcpListener, TcpStream};
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    // out of coroutine context
+    let err = listener
+        .accept_timeout(Duration::from_millis(10))
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::TimedOut);
+    let _s = std::net::TcpStream::connect(addr).unwrap();
+    listener.accept_timeout(Duration::from_secs(5)).unwrap();
+
+    let h = co!(move || {
+        let err = listener
+            .accept_timeout(Duration::from_millis(10))
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::TimedOut);
+        listener.accept_timeout(Duration::from_secs(5)).map(|_| ())
+    });
+    std::thread::sleep(Duration::from_millis(100));
+    let _s = TcpStream::connect(addr).unwrap();
+    h.join().unwrap().unwrap();
+}
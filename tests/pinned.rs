@@ -0,0 +1,37 @@
+#[macro_use]
+extern crate mco;
+
+use std::thread;
+use std::time::Duration;
+
+use mco::coroutine;
+use mco::SchedulePolicy;
+
+// the workers never take coroutines from the global queue
+struct NoSteal;
+
+impl SchedulePolicy for NoSteal {
+    fn steal_global(&self, _worker: usize) -> bool {
+        false
+    }
+}
+
+#[test]
+fn spawn_pinned_without_steal() {
+    mco::config().set_schedule_policy(NoSteal);
+    let j = co!(|| {
+        let worker = thread::current().id();
+        let h = coroutine::spawn_pinned(move || {
+            for _ in 0..10 {
+                assert_eq!(thread::current().id(), worker);
+                coroutine::sleep(Duration::from_millis(1));
+            }
+            // woken up from another thread
+            let (tx, rx) = mco::chan!();
+            thread::spawn(move || tx.send(1).unwrap());
+            rx.recv().unwrap()
+        });
+        h.join().unwrap()
+    });
+    assert_eq!(j.join().unwrap(), 1);
+}
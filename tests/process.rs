@@ -0,0 +1,95 @@
+use mco::co;
+use std::io::{Read, Write};
+use std::time::Duration;
+
+#[test]
+fn os_pipe() {
+    let (mut reader, mut writer) = mco::os::pipe().unwrap();
+    let h = co!(move || {
+        // parked until the writer is closed
+        let mut buf = String::new();
+        reader.read_to_string(&mut buf).unwrap();
+        buf
+    });
+    std::thread::sleep(Duration::from_millis(50));
+    writer.write_all(b"hello").unwrap();
+    drop(writer);
+    assert_eq!(h.join().unwrap(), "hello");
+}
+
+#[cfg(unix)]
+#[test]
+fn process_output() {
+    use mco::process::{Command, Stdio};
+
+    let h = co!(|| {
+        let mut child = Command::new("cat")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+        child.stdin.take().unwrap().write_all(b"hello").unwrap();
+        let output = child.wait_with_output().unwrap();
+        assert!(output.status.success());
+        output.stdout
+    });
+    assert_eq!(h.join().unwrap(), b"hello");
+}
+
+#[cfg(unix)]
+#[test]
+fn process_output_null_stdin() {
+    use mco::process::Command;
+
+    // the stdin is not inherited, `cat` sees the eof at once
+    let h = co!(|| Command::new("cat").output());
+    let output = h.join().unwrap().unwrap();
+    assert!(output.status.success());
+    assert!(output.stdout.is_empty());
+}
+
+#[cfg(unix)]
+#[test]
+fn process_wait() {
+    use mco::process::Command;
+
+    let h = co!(|| Command::new("sh")
+        .args(["-c", "sleep 0.1; exit 3"])
+        .status());
+    assert_eq!(h.join().unwrap().unwrap().code(), Some(3));
+}
+
+#[cfg(unix)]
+#[test]
+fn signal_recv() {
+    use mco::os::{signal, SignalKind};
+
+    let mut stream = signal(SignalKind::user_defined1()).unwrap();
+    let h = co!(move || {
+        // parked until the signal is delivered
+        stream.recv().unwrap();
+        stream.kind()
+    });
+    std::thread::sleep(Duration::from_millis(50));
+    unsafe { libc::kill(libc::getpid(), libc::SIGUSR1) };
+    assert_eq!(h.join().unwrap(), SignalKind::user_defined1());
+}
+
+#[cfg(unix)]
+#[test]
+fn signal_recv_threads() {
+    use mco::os::{signal, SignalKind};
+
+    // every waiter is woken up by the same delivery
+    let hs: Vec<_> = (0..2)
+        .map(|_| {
+            let mut stream = signal(SignalKind::user_defined2()).unwrap();
+            std::thread::spawn(move || stream.recv().unwrap())
+        })
+        .collect();
+    std::thread::sleep(Duration::from_millis(50));
+    unsafe { libc::kill(libc::getpid(), libc::SIGUSR2) };
+    for h in hs {
+        h.join().unwrap();
+    }
+}
@@ -0,0 +1,108 @@
+#![cfg(feature = "tls")]
+
+use mco::co;
+use mco::net::tls::rustls::pki_types::{CertificateDer, PrivatePkcs8KeyDer};
+use mco::net::tls::{rustls, TlsAcceptor, TlsConnector};
+use mco::net::TcpListener;
+use std::io::{self, Read, Write};
+use std::net::{self, TcpStream};
+use std::sync::Arc;
+use std::thread;
+
+fn configs() -> (TlsConnector, TlsAcceptor) {
+    let ca = CertificateDer::from(&include_bytes!("certs/ca.der")[..]);
+    let cert = CertificateDer::from(&include_bytes!("certs/localhost.der")[..]);
+    let key = PrivatePkcs8KeyDer::from(&include_bytes!("certs/localhost.key.der")[..]);
+
+    let mut roots = rustls::RootCertStore::empty();
+    roots.add(ca).unwrap();
+    let client = rustls::ClientConfig::builder()
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    let server = rustls::ServerConfig::builder()
+        .with_no_client_auth()
+        .with_single_cert(vec![cert], key.into())
+        .unwrap();
+    (
+        TlsConnector::new(Arc::new(client)),
+        TlsAcceptor::new(Arc::new(server)),
+    )
+}
+
+#[test]
+fn tls_echo() {
+    let (connector, acceptor) = configs();
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let server = co!(move || {
+        let (tcp, _) = listener.accept().unwrap();
+        let mut stream = acceptor.accept(tcp).unwrap();
+        let mut buf = [0u8; 5];
+        stream.read_exact(&mut buf).unwrap();
+        stream.write_all(&buf).unwrap();
+        stream.shutdown().unwrap();
+
+        // the client rejects the certificate
+        let (tcp, _) = listener.accept().unwrap();
+        assert!(acceptor.accept(tcp).is_err());
+    });
+
+    // the connector works on the blocking std stream as well
+    let tcp = TcpStream::connect(addr).unwrap();
+    let mut stream = connector.connect("localhost", tcp).unwrap();
+    stream.write_all(b"hello").unwrap();
+    let mut rsp = Vec::new();
+    stream.read_to_end(&mut rsp).unwrap();
+    assert_eq!(rsp, b"hello");
+
+    // the certificate is only issued for localhost
+    let tcp = TcpStream::connect(addr).unwrap();
+    assert!(connector.connect("example.com", tcp).is_err());
+    server.join().unwrap();
+}
+
+// a stream whose writes fail once it is broken
+struct Breakable {
+    tcp: TcpStream,
+    broken: bool,
+}
+
+impl Read for Breakable {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.tcp.read(buf)
+    }
+}
+
+impl Write for Breakable {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.broken {
+            return Err(io::ErrorKind::BrokenPipe.into());
+        }
+        self.tcp.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.tcp.flush()
+    }
+}
+
+#[test]
+fn tls_write_error() {
+    let (connector, acceptor) = configs();
+    let listener = net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = thread::spawn(move || {
+        let (tcp, _) = listener.accept().unwrap();
+        acceptor.accept(tcp).unwrap()
+    });
+
+    let tcp = TcpStream::connect(addr).unwrap();
+    let sock = Breakable { tcp, broken: false };
+    let mut stream = connector.connect("localhost", sock).unwrap();
+    let _server = server.join().unwrap();
+
+    stream.get_mut().broken = true;
+    let err = stream.write(b"hello").unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::BrokenPipe);
+}
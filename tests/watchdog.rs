@@ -0,0 +1,37 @@
+#[macro_use]
+extern crate mco;
+
+use std::thread;
+use std::time::{Duration, Instant};
+
+use mco::coroutine::{self, Builder};
+
+#[test]
+fn sleep_on_blocked_worker() {
+    mco::config()
+        .set_workers(1)
+        .set_worker_block_timeout(Duration::from_millis(50));
+
+    // the timer of the sleep is in the wheel of worker 0
+    let start = Instant::now();
+    let sleeper = Builder::new()
+        .spawn_on(0, move || {
+            coroutine::sleep(Duration::from_millis(100));
+            start.elapsed()
+        })
+        .unwrap();
+    thread::sleep(Duration::from_millis(20));
+
+    // block worker 0, the queued coroutine makes the watchdog see it
+    let blocker = Builder::new()
+        .spawn_on(0, || thread::sleep(Duration::from_secs(3)))
+        .unwrap();
+    thread::sleep(Duration::from_millis(20));
+    let queued = co!(|| ());
+
+    // the sleep fires on the timer thread before the worker comes back
+    let slept = sleeper.join().unwrap();
+    assert!(slept < Duration::from_secs(2), "{:?}", slept);
+    queued.join().unwrap();
+    blocker.join().unwrap();
+}